@@ -3,6 +3,8 @@ import { storage } from '@/lib/storage'
 import type {
   CredentialsStatusResponse,
   BalanceResponse,
+  BalancesResponse,
+  RecentErrorsResponse,
   SuccessResponse,
   SetDisabledRequest,
   SetPriorityRequest,
@@ -29,7 +31,9 @@ api.interceptors.request.use((config) => {
 
 // 获取所有凭据状态
 export async function getCredentials(): Promise<CredentialsStatusResponse> {
-  const { data } = await api.get<CredentialsStatusResponse>('/credentials')
+  const { data } = await api.get<CredentialsStatusResponse>('/credentials', {
+    params: { per_page: 500 },
+  })
   return data
 }
 
@@ -71,6 +75,22 @@ export async function getCredentialBalance(id: number): Promise<BalanceResponse>
   return data
 }
 
+// 并发获取所有凭据余额
+export async function getAllBalances(concurrency?: number): Promise<BalancesResponse> {
+  const { data } = await api.get<BalancesResponse>('/balances', {
+    params: concurrency ? { concurrency } : undefined,
+  })
+  return data
+}
+
+// 获取最近失败的请求（最新的在前）
+export async function getRecentErrors(limit?: number): Promise<RecentErrorsResponse> {
+  const { data } = await api.get<RecentErrorsResponse>('/recent-errors', {
+    params: limit ? { limit } : undefined,
+  })
+  return data
+}
+
 // 添加新凭据
 export async function addCredential(
   req: AddCredentialRequest
@@ -182,6 +182,12 @@ export function CredentialCard({ credential, onViewBalance }: CredentialCardProp
                 {credential.failureCount}
               </span>
             </div>
+            <div>
+              <span className="text-muted-foreground">健康分：</span>
+              <span className={credential.healthScore < 0.6 ? 'text-red-500 font-medium' : 'font-medium'}>
+                {credential.healthScore.toFixed(2)}
+              </span>
+            </div>
             <div>
               <span className="text-muted-foreground">认证方式：</span>
               <span className="font-medium">{credential.authMethod || '未知'}</span>
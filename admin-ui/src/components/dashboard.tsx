@@ -149,7 +149,7 @@ export function Dashboard({ onLogout }: DashboardProps) {
               添加凭据
             </Button>
           </div>
-          {data?.credentials.length === 0 ? (
+          {data?.items.length === 0 ? (
             <Card>
               <CardContent className="py-8 text-center text-muted-foreground">
                 暂无凭据
@@ -157,7 +157,7 @@ export function Dashboard({ onLogout }: DashboardProps) {
             </Card>
           ) : (
             <div className="grid gap-4 md:grid-cols-2 lg:grid-cols-3">
-              {data?.credentials.map((credential) => (
+              {data?.items.map((credential) => (
                 <CredentialCard
                   key={credential.id}
                   credential={credential}
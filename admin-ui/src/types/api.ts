@@ -3,7 +3,9 @@ export interface CredentialsStatusResponse {
   total: number
   available: number
   currentId: number
-  credentials: CredentialStatusItem[]
+  page: number
+  perPage: number
+  items: CredentialStatusItem[]
 }
 
 // 单个凭据状态
@@ -16,6 +18,8 @@ export interface CredentialStatusItem {
   expiresAt: string | null
   authMethod: string | null
   hasProfileArn: boolean
+  healthScore: number
+  lastUsedAt: string | null
 }
 
 // 余额响应
@@ -27,6 +31,39 @@ export interface BalanceResponse {
   remaining: number
   usagePercentage: number
   nextResetAt: number | null
+  credits: number
+  limit: number
+  resetAt: string | null
+}
+
+// 单个凭据的批量余额查询结果
+export interface BalanceResult {
+  id: number
+  success: boolean
+  balance?: BalanceResponse
+  error?: string
+}
+
+// 批量余额查询响应
+export interface BalancesResponse {
+  total: number
+  failed: number
+  balances: BalanceResult[]
+}
+
+// 最近失败的请求
+export interface RecentError {
+  timestamp: string
+  code: string
+  status?: number
+  credentialId?: number
+  message: string
+}
+
+// 最近错误响应
+export interface RecentErrorsResponse {
+  capacity: number
+  errors: RecentError[]
 }
 
 // 成功响应
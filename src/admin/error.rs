@@ -20,6 +20,12 @@ pub enum AdminServiceError {
 
     /// 凭据无效（验证失败）
     InvalidCredential(String),
+
+    /// 请求参数无效
+    InvalidRequest(String),
+
+    /// 操作与当前状态冲突（如存储后端只读）
+    Conflict(String),
 }
 
 impl fmt::Display for AdminServiceError {
@@ -31,6 +37,8 @@ impl fmt::Display for AdminServiceError {
             AdminServiceError::UpstreamError(msg) => write!(f, "上游服务错误: {}", msg),
             AdminServiceError::InternalError(msg) => write!(f, "内部错误: {}", msg),
             AdminServiceError::InvalidCredential(msg) => write!(f, "凭据无效: {}", msg),
+            AdminServiceError::InvalidRequest(msg) => write!(f, "请求无效: {}", msg),
+            AdminServiceError::Conflict(msg) => write!(f, "{}", msg),
         }
     }
 }
@@ -44,7 +52,10 @@ impl AdminServiceError {
             AdminServiceError::NotFound { .. } => StatusCode::NOT_FOUND,
             AdminServiceError::UpstreamError(_) => StatusCode::BAD_GATEWAY,
             AdminServiceError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            AdminServiceError::InvalidCredential(_) => StatusCode::BAD_REQUEST,
+            AdminServiceError::InvalidCredential(_) | AdminServiceError::InvalidRequest(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            AdminServiceError::Conflict(_) => StatusCode::CONFLICT,
         }
     }
 
@@ -56,9 +67,10 @@ impl AdminServiceError {
             AdminServiceError::InternalError(_) => {
                 AdminErrorResponse::internal_error(self.to_string())
             }
-            AdminServiceError::InvalidCredential(_) => {
+            AdminServiceError::InvalidCredential(_) | AdminServiceError::InvalidRequest(_) => {
                 AdminErrorResponse::invalid_request(self.to_string())
             }
+            AdminServiceError::Conflict(_) => AdminErrorResponse::conflict(self.to_string()),
         }
     }
 }
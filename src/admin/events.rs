@@ -0,0 +1,57 @@
+//! Admin API 事件推送（WebSocket）
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::kiro::credential_events::CredentialEvent;
+
+use super::service::AdminService;
+
+/// 统计快照推送间隔
+const STATS_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 向 WebSocket 客户端推送凭据事件，直到客户端断开
+///
+/// 连接建立后立即推送一次统计快照，此后每 `STATS_SNAPSHOT_INTERVAL` 推送一次；
+/// 客户端处理过慢时广播通道丢弃最旧的事件，并推送 `lagged` 事件告知丢弃数量
+pub async fn stream_events(
+    mut socket: WebSocket,
+    service: Arc<AdminService>,
+    mut events: broadcast::Receiver<CredentialEvent>,
+) {
+    let mut snapshot_ticker = tokio::time::interval(STATS_SNAPSHOT_INTERVAL);
+    snapshot_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let event = tokio::select! {
+            _ = snapshot_ticker.tick() => CredentialEvent::StatsSnapshot(service.stats_snapshot()),
+            received = events.recv() => match received {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Admin 事件订阅者处理过慢，已丢弃 {} 个事件", skipped);
+                    CredentialEvent::Lagged { skipped }
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                // 忽略客户端消息（ping/pong 由 axum 自动处理），断开或出错时结束
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+
+        let text = match serde_json::to_string(&event) {
+            Ok(text) => text,
+            Err(e) => {
+                tracing::warn!("序列化 Admin 事件失败: {}", e);
+                continue;
+            }
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            break;
+        }
+    }
+}
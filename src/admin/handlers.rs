@@ -2,22 +2,59 @@
 
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Path, Query, State, WebSocketUpgrade},
     response::IntoResponse,
 };
 
+use crate::kiro::model::credentials::CredentialsConfig;
+
 use super::{
+    error::AdminServiceError,
+    events::stream_events,
     middleware::AdminState,
-    types::{AddCredentialRequest, SetDisabledRequest, SetPriorityRequest, SuccessResponse},
+    types::{
+        AddCredentialRequest, BalancesQuery, CredentialFilter, ImportCredentialsQuery,
+        ListCredentialsQuery, RecentErrorsQuery, SelectDryRunRequest, SetDisabledRequest,
+        SetPriorityRequest, SuccessResponse, UpdateCredentialRequest,
+    },
 };
 
 /// GET /api/admin/credentials
-/// 获取所有凭据状态
-pub async fn get_all_credentials(State(state): State<AdminState>) -> impl IntoResponse {
-    let response = state.service.get_all_credentials();
+/// 分页获取凭据状态
+///
+/// 查询参数：`?page=&per_page=&status=enabled|disabled|breaker_open&sort=priority|last_used`
+pub async fn get_all_credentials(
+    State(state): State<AdminState>,
+    Query(query): Query<ListCredentialsQuery>,
+) -> impl IntoResponse {
+    let response = state.service.list_credentials(&query);
     Json(response)
 }
 
+/// GET /api/admin/events
+/// 升级为 WebSocket，推送凭据重新加载、禁用/启用、熔断器状态变化和定期统计快照
+pub async fn admin_events(
+    State(state): State<AdminState>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    // 升级前订阅，避免握手期间发生的事件丢失
+    let events = state.service.subscribe_events();
+    ws.on_upgrade(move |socket| stream_events(socket, state.service, events))
+}
+
+/// POST /api/admin/sync
+/// 立即从存储后端同步凭据（不等待定时同步）
+pub async fn sync_credentials(State(state): State<AdminState>) -> impl IntoResponse {
+    let Some(sync_manager) = &state.sync_manager else {
+        let e = AdminServiceError::Conflict("凭据同步未启用".to_string());
+        return (e.status_code(), Json(e.into_response())).into_response();
+    };
+    match state.service.sync_credentials(sync_manager).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/credentials/:id/disabled
 /// 设置凭据禁用状态
 pub async fn set_credential_disabled(
@@ -34,6 +71,30 @@ pub async fn set_credential_disabled(
     }
 }
 
+/// POST /api/admin/credentials/bulk-disable
+/// 批量禁用匹配筛选条件的凭据
+pub async fn bulk_disable_credentials(
+    State(state): State<AdminState>,
+    Json(filter): Json<CredentialFilter>,
+) -> impl IntoResponse {
+    match state.service.bulk_set_disabled(filter, true) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/credentials/bulk-enable
+/// 批量启用匹配筛选条件的凭据
+pub async fn bulk_enable_credentials(
+    State(state): State<AdminState>,
+    Json(filter): Json<CredentialFilter>,
+) -> impl IntoResponse {
+    match state.service.bulk_set_disabled(filter, false) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/credentials/:id/priority
 /// 设置凭据优先级
 pub async fn set_credential_priority(
@@ -51,6 +112,19 @@ pub async fn set_credential_priority(
     }
 }
 
+/// PATCH /api/admin/credentials/:id
+/// 一次更新凭据的优先级、禁用状态、权重和代理地址（未出现的字段保持不变）
+pub async fn update_credential(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+    Json(payload): Json<UpdateCredentialRequest>,
+) -> impl IntoResponse {
+    match state.service.update_credential(id, payload) {
+        Ok(_) => Json(SuccessResponse::new(format!("凭据 #{} 已更新", id))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// POST /api/admin/credentials/:id/reset
 /// 重置失败计数并重新启用
 pub async fn reset_failure_count(
@@ -67,6 +141,54 @@ pub async fn reset_failure_count(
     }
 }
 
+/// GET /api/admin/credentials/:id/stats
+/// 获取凭据自启动（或上次重置）以来的请求数、token 数和最近使用时间
+pub async fn get_credential_stats(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    match state.service.credential_stats(id) {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/credentials/:id/stats/reset
+/// 清零凭据的使用统计
+pub async fn reset_credential_stats(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    match state.service.reset_credential_stats(id) {
+        Ok(_) => {
+            Json(SuccessResponse::new(format!("凭据 #{} 使用统计已重置", id))).into_response()
+        }
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// POST /api/admin/pin/:id
+/// 固定使用指定凭据（忽略选择模式和优先级，不可用时返回 503）
+pub async fn pin_credential(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+) -> impl IntoResponse {
+    match state.service.pin_credential(id) {
+        Ok(_) => Json(SuccessResponse::new(format!("已固定使用凭据 #{}", id))).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
+/// DELETE /api/admin/pin
+/// 取消凭据固定，恢复正常凭据选择
+pub async fn unpin_credential(State(state): State<AdminState>) -> impl IntoResponse {
+    let message = match state.service.unpin_credential() {
+        Some(id) => format!("已取消固定凭据 #{}", id),
+        None => "当前未固定凭据".to_string(),
+    };
+    Json(SuccessResponse::new(message))
+}
+
 /// GET /api/admin/credentials/:id/balance
 /// 获取指定凭据的余额
 pub async fn get_credential_balance(
@@ -79,6 +201,39 @@ pub async fn get_credential_balance(
     }
 }
 
+/// GET /api/admin/balances
+/// 并发查询所有凭据的余额（`?concurrency=N`），单个凭据失败不影响其他凭据
+pub async fn get_all_balances(
+    State(state): State<AdminState>,
+    Query(query): Query<BalancesQuery>,
+) -> impl IntoResponse {
+    Json(state.service.get_all_balances(query.concurrency).await)
+}
+
+/// GET /api/admin/refresh-breaker
+/// 获取 Token 刷新熔断器状态
+pub async fn get_refresh_breaker(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(state.service.refresh_breaker_status())
+}
+
+/// GET /api/admin/recent-errors
+/// 获取最近失败的请求（`?limit=N`，最新的在前）
+pub async fn get_recent_errors(
+    State(state): State<AdminState>,
+    Query(query): Query<RecentErrorsQuery>,
+) -> impl IntoResponse {
+    Json(state.service.recent_errors(query.limit))
+}
+
+/// POST /api/admin/select-dry-run
+/// 模拟一次凭据选择，返回将被选中的凭据和各凭据的跳过原因
+pub async fn select_dry_run(
+    State(state): State<AdminState>,
+    Json(payload): Json<SelectDryRunRequest>,
+) -> impl IntoResponse {
+    Json(state.service.select_dry_run(payload))
+}
+
 /// POST /api/admin/credentials
 /// 添加新凭据
 pub async fn add_credential(
@@ -91,13 +246,26 @@ pub async fn add_credential(
     }
 }
 
+/// POST /api/admin/credentials/import?dedup_by=refresh_token|profile_arn|id
+/// 批量导入凭据（按指定字段去重）
+pub async fn import_credentials(
+    State(state): State<AdminState>,
+    Query(query): Query<ImportCredentialsQuery>,
+    Json(payload): Json<CredentialsConfig>,
+) -> impl IntoResponse {
+    match state.service.import_credentials(payload, query.dedup_by) {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
+    }
+}
+
 /// DELETE /api/admin/credentials/:id
 /// 删除凭据
 pub async fn delete_credential(
     State(state): State<AdminState>,
     Path(id): Path<u64>,
 ) -> impl IntoResponse {
-    match state.service.delete_credential(id) {
+    match state.service.delete_credential(id).await {
         Ok(_) => Json(SuccessResponse::new(format!("凭据 #{} 已删除", id))).into_response(),
         Err(e) => (e.status_code(), Json(e.into_response())).into_response(),
     }
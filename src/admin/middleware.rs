@@ -13,6 +13,8 @@ use axum::{
 use super::service::AdminService;
 use super::types::AdminErrorResponse;
 use crate::common::auth;
+use crate::common::body_limit;
+use crate::kiro::storage::CredentialSyncManager;
 
 /// Admin API 共享状态
 #[derive(Clone)]
@@ -21,6 +23,8 @@ pub struct AdminState {
     pub admin_api_key: String,
     /// Admin 服务
     pub service: Arc<AdminService>,
+    /// 凭据同步管理器（用于手动触发同步，未设置时 `/sync` 返回 409）
+    pub sync_manager: Option<Arc<CredentialSyncManager>>,
 }
 
 impl AdminState {
@@ -28,8 +32,15 @@ impl AdminState {
         Self {
             admin_api_key: admin_api_key.into(),
             service: Arc::new(service),
+            sync_manager: None,
         }
     }
+
+    /// 设置凭据同步管理器
+    pub fn with_sync_manager(mut self, sync_manager: Arc<CredentialSyncManager>) -> Self {
+        self.sync_manager = Some(sync_manager);
+        self
+    }
 }
 
 /// Admin API 认证中间件
@@ -48,3 +59,19 @@ pub async fn admin_auth_middleware(
         }
     }
 }
+
+/// Admin API 请求体大小限制中间件（`max_request_bytes`），超限时返回 413
+pub async fn admin_body_limit_middleware(
+    State(limit): State<usize>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    body_limit::limit_body(limit, request, next, |limit| {
+        let error = AdminErrorResponse::new(
+            "request_too_large",
+            format!("请求体超过上限 {} 字节", limit),
+        );
+        (StatusCode::PAYLOAD_TOO_LARGE, Json(error)).into_response()
+    })
+    .await
+}
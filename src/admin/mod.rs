@@ -8,6 +8,7 @@
 //! - 修改凭据优先级
 //! - 重置失败计数
 //! - 查询凭据余额
+//! - 通过 WebSocket 推送凭据事件
 //!
 //! # 使用
 //! ```ignore
@@ -17,6 +18,7 @@
 //! ```
 
 mod error;
+mod events;
 mod handlers;
 mod middleware;
 mod router;
@@ -1,47 +1,557 @@
 //! Admin API 路由配置
 
 use axum::{
-    Router, middleware,
+    Router,
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{delete, get, post},
 };
 
+use crate::common::json_format::json_format_middleware;
+
 use super::{
     handlers::{
-        add_credential, delete_credential, get_all_credentials, get_credential_balance,
-        reset_failure_count, set_credential_disabled, set_credential_priority,
+        add_credential, admin_events, bulk_disable_credentials, bulk_enable_credentials,
+        delete_credential, get_all_balances, get_all_credentials, get_credential_balance,
+        get_credential_stats, get_recent_errors, get_refresh_breaker, import_credentials,
+        pin_credential, reset_credential_stats, reset_failure_count, select_dry_run,
+        set_credential_disabled, set_credential_priority, sync_credentials, unpin_credential,
+        update_credential,
     },
-    middleware::{AdminState, admin_auth_middleware},
+    middleware::{AdminState, admin_auth_middleware, admin_body_limit_middleware},
 };
 
 /// 创建 Admin API 路由
 ///
 /// # 端点
-/// - `GET /credentials` - 获取所有凭据状态
-/// - `POST /credentials` - 添加新凭据
-/// - `DELETE /credentials/:id` - 删除凭据
+/// - `GET /credentials` - 分页获取凭据状态（`?page=&per_page=&status=&sort=priority|last_used`）
+/// - `POST /credentials` - 添加新凭据（未指定 `id` 时自动分配，添加后立即参与选择）
+/// - `POST /credentials/import` - 批量导入凭据（`?dedup_by=refresh_token|profile_arn|id`）
+/// - `POST /credentials/bulk-disable` - 按筛选条件（`{region?, ids?, tag?}`）批量禁用凭据
+/// - `POST /credentials/bulk-enable` - 按筛选条件（`{region?, ids?, tag?}`）批量启用凭据
+/// - `PATCH /credentials/:id` - 一次更新 `priority`、`disabled`、`weight`、`proxyUrl` 中的任意字段
+///   （未出现的字段不变，`weight`/`proxyUrl` 为 null 时清除；包含其他字段时返回 400）
+/// - `DELETE /credentials/:id` - 删除凭据（需先禁用；存储后端只读时返回 409）
 /// - `POST /credentials/:id/disabled` - 设置凭据禁用状态
 /// - `POST /credentials/:id/priority` - 设置凭据优先级
 /// - `POST /credentials/:id/reset` - 重置失败计数
 /// - `GET /credentials/:id/balance` - 获取凭据余额
+/// - `GET /credentials/:id/stats` - 获取凭据使用统计（请求数、token 数、最近使用时间）
+/// - `POST /credentials/:id/stats/reset` - 清零凭据使用统计
+/// - `GET /balances` - 并发获取所有凭据余额（`?concurrency=N`）
+/// - `POST /pin/:id` - 固定使用指定凭据（单账号调试）
+/// - `DELETE /pin` - 取消凭据固定
+/// - `GET /refresh-breaker` - 获取 Token 刷新熔断器状态
+/// - `GET /recent-errors` - 获取最近失败的请求（`?limit=N`）
+/// - `POST /select-dry-run` - 模拟凭据选择（`{model?, excludeCredentials?}`），不发起上游请求
+/// - `POST /sync` - 立即从存储后端同步凭据，返回是否有变更和同步后的凭据数量
+/// - `GET /events` - WebSocket 推送凭据事件（重新加载、禁用/启用、熔断器状态变化、统计快照）
 ///
 /// # 认证
 /// 需要 Admin API Key 认证，支持：
 /// - `x-api-key` header
 /// - `Authorization: Bearer <token>` header
 pub fn create_admin_router(state: AdminState) -> Router {
+    let pretty_json = state.service.config().pretty_json;
+    let max_request_bytes = state.service.config().max_request_bytes;
+
     Router::new()
         .route(
             "/credentials",
             get(get_all_credentials).post(add_credential),
         )
-        .route("/credentials/{id}", delete(delete_credential))
+        .route("/credentials/import", post(import_credentials))
+        .route("/credentials/bulk-disable", post(bulk_disable_credentials))
+        .route("/credentials/bulk-enable", post(bulk_enable_credentials))
+        .route(
+            "/credentials/{id}",
+            delete(delete_credential).patch(update_credential),
+        )
         .route("/credentials/{id}/disabled", post(set_credential_disabled))
         .route("/credentials/{id}/priority", post(set_credential_priority))
         .route("/credentials/{id}/reset", post(reset_failure_count))
         .route("/credentials/{id}/balance", get(get_credential_balance))
+        .route("/credentials/{id}/stats", get(get_credential_stats))
+        .route("/credentials/{id}/stats/reset", post(reset_credential_stats))
+        .route("/balances", get(get_all_balances))
+        .route("/pin", delete(unpin_credential))
+        .route("/pin/{id}", post(pin_credential))
+        .route("/refresh-breaker", get(get_refresh_breaker))
+        .route("/recent-errors", get(get_recent_errors))
+        .route("/select-dry-run", post(select_dry_run))
+        .route("/sync", post(sync_credentials))
+        .route("/events", get(admin_events))
+        .layer(middleware::from_fn_with_state(
+            pretty_json,
+            json_format_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            max_request_bytes,
+            admin_body_limit_middleware,
+        ))
+        .layer(DefaultBodyLimit::max(max_request_bytes))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             admin_auth_middleware,
         ))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::{Value, json};
+    use tokio_tungstenite::tungstenite::Message;
+
+    use super::*;
+    use crate::admin::AdminService;
+    use crate::kiro::model::credentials::KiroCredentials;
+    use crate::kiro::storage::{
+        CredentialChangeEvent, CredentialStorage, CredentialSyncManager, FileCredentialStorage,
+    };
+    use crate::kiro::token_manager::MultiTokenManager;
+    use crate::model::config::Config;
+
+    fn write_credentials(file: &tempfile::NamedTempFile, ids: &[u64]) {
+        let credentials: Vec<Value> = ids
+            .iter()
+            .map(|id| json!({"id": id, "refreshToken": format!("refresh-{}", id)}))
+            .collect();
+        std::fs::write(file.path(), serde_json::to_string(&credentials).unwrap()).unwrap();
+    }
+
+    async fn post_sync(client: &reqwest::Client, url: &str) -> (u16, Value) {
+        let response = client
+            .post(url)
+            .header("x-api-key", "admin-key")
+            .send()
+            .await
+            .unwrap();
+        (response.status().as_u16(), response.json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_sync_endpoint_reloads_changed_backing_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        write_credentials(&file, &[1]);
+        let storage = Arc::new(FileCredentialStorage::new(file.path(), true));
+        let credentials = vec![KiroCredentials {
+            id: Some(1),
+            refresh_token: Some("refresh-1".to_string()),
+            ..Default::default()
+        }];
+        let mut token_manager =
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap();
+        token_manager.set_storage(storage.clone());
+        let token_manager = Arc::new(token_manager);
+
+        // 定时同步禁用（间隔 0），只能手动触发
+        let sync_manager = Arc::new(CredentialSyncManager::new(storage, 0));
+        let tm_for_callback = token_manager.clone();
+        sync_manager.add_callback(Box::new(move |event| {
+            let CredentialChangeEvent::Reloaded(credentials) = event;
+            tm_for_callback.reload_credentials(credentials);
+        }));
+
+        let state = AdminState::new("admin-key", AdminService::new(token_manager.clone()))
+            .with_sync_manager(sync_manager);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().nest("/api/admin", create_admin_router(state));
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/api/admin/sync", addr);
+
+        // 首次同步建立基线，随后文件未变化时报告无变更
+        post_sync(&client, &url).await;
+        let (status, body) = post_sync(&client, &url).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["changed"], false);
+        assert_eq!(body["credentialCount"], 1);
+        assert!(body["nextAutoSyncInSecs"].is_null());
+
+        write_credentials(&file, &[1, 2, 3]);
+        let (status, body) = post_sync(&client, &url).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["changed"], true);
+        assert_eq!(body["credentialCount"], 3);
+        assert_eq!(token_manager.total_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_patch_credential_updates_only_given_fields() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let credentials = json!([
+            {"id": 1, "refreshToken": "refresh-1"},
+            {
+                "id": 2,
+                "refreshToken": "refresh-2",
+                "proxyUrl": "http://127.0.0.1:8080",
+                "proxyUsername": "user",
+                "proxyPassword": "pass"
+            }
+        ]);
+        std::fs::write(file.path(), credentials.to_string()).unwrap();
+        let credentials = FileCredentialStorage::new(file.path(), true)
+            .load_all()
+            .await
+            .unwrap();
+        let mut token_manager =
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap();
+        token_manager.set_storage(Arc::new(FileCredentialStorage::new(file.path(), true)));
+        let token_manager = Arc::new(token_manager);
+        token_manager.set_priority(2, 5).unwrap();
+        token_manager.flush_pending_writes().await;
+
+        let state = AdminState::new("admin-key", AdminService::new(token_manager.clone()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_admin_router(state)).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let patch = |id: u64, body: Value| {
+            let request = client
+                .patch(format!("http://{}/credentials/{}", addr, id))
+                .header("x-api-key", "admin-key")
+                .json(&body);
+            async move {
+                let response = request.send().await.unwrap();
+                (response.status().as_u16(), response.json::<Value>().await.unwrap())
+            }
+        };
+        let persisted = || {
+            let content = std::fs::read_to_string(file.path()).unwrap();
+            serde_json::from_str::<Vec<Value>>(&content)
+                .unwrap()
+                .into_iter()
+                .find(|c| c["id"] == 2)
+                .unwrap()
+        };
+        let entry = |id: u64| {
+            token_manager
+                .snapshot()
+                .entries
+                .into_iter()
+                .find(|e| e.id == id)
+                .unwrap()
+        };
+
+        let (status, _) = patch(
+            2,
+            json!({"priority": 0, "weight": 3, "proxyUrl": "socks5://127.0.0.1:1080"}),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(entry(2).priority, 0);
+        assert!(!entry(2).disabled);
+        token_manager.flush_pending_writes().await;
+        let stored = persisted();
+        // priority 为 0 时不写入文件
+        assert!(stored.get("priority").is_none_or(|p| p == 0));
+        assert_eq!(stored["weight"], 3);
+        assert_eq!(stored["proxyUrl"], "socks5://127.0.0.1:1080");
+        assert_eq!(stored["proxyUsername"], "user");
+
+        // 只修改禁用状态和清除代理，其余字段保持不变
+        let (status, _) = patch(2, json!({"disabled": true, "proxyUrl": null})).await;
+        assert_eq!(status, 200);
+        assert!(entry(2).disabled);
+        assert_eq!(entry(2).priority, 0);
+        token_manager.flush_pending_writes().await;
+        let stored = persisted();
+        assert_eq!(stored["weight"], 3);
+        assert!(stored.get("proxyUrl").is_none_or(Value::is_null));
+        // 清除代理地址时代理认证信息一并清除
+        assert!(stored.get("proxyUsername").is_none_or(Value::is_null));
+        assert!(stored.get("proxyPassword").is_none_or(Value::is_null));
+
+        let (status, _) = patch(9, json!({"priority": 1})).await;
+        assert_eq!(status, 404);
+        let (status, body) = patch(2, json!({})).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"]["type"], "invalid_request");
+        let (status, _) = patch(2, json!({"weight": 0})).await;
+        assert_eq!(status, 400);
+        let (status, _) = patch(2, json!({"proxyUrl": "ftp://proxy"})).await;
+        assert_eq!(status, 400);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_disable_filters_by_tag() {
+        let credential = |id: u64, tags: &[&str]| KiroCredentials {
+            id: Some(id),
+            refresh_token: Some(format!("refresh-{}", id)),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        };
+        let credentials = vec![
+            credential(1, &["team-a"]),
+            credential(2, &["team-b"]),
+            credential(3, &["team-a", "batch"]),
+        ];
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap(),
+        );
+        let state = AdminState::new("admin-key", AdminService::new(token_manager.clone()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_admin_router(state)).await.unwrap();
+        });
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/credentials/bulk-disable", addr))
+            .header("x-api-key", "admin-key")
+            .json(&json!({"tag": "team-a"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["affectedIds"], json!([1, 3]));
+
+        let disabled: Vec<u64> = token_manager
+            .snapshot()
+            .entries
+            .into_iter()
+            .filter(|e| e.disabled)
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(disabled, vec![1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_patch_credential_rejects_immutable_fields() {
+        let credentials = vec![KiroCredentials {
+            id: Some(1),
+            refresh_token: Some("refresh-1".to_string()),
+            ..Default::default()
+        }];
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap(),
+        );
+        let state = AdminState::new("admin-key", AdminService::new(token_manager.clone()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_admin_router(state)).await.unwrap();
+        });
+
+        let response = reqwest::Client::new()
+            .patch(format!("http://{}/credentials/1", addr))
+            .header("x-api-key", "admin-key")
+            .json(&json!({"priority": 3, "refreshToken": "other"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request");
+        assert!(
+            body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("refreshToken"),
+            "{}",
+            body
+        );
+
+        // 整个请求被拒绝，允许修改的字段也不生效
+        let snapshot = token_manager.snapshot();
+        assert_eq!(snapshot.entries[0].priority, 0);
+        assert_eq!(
+            token_manager.credentials().refresh_token.as_deref(),
+            Some("refresh-1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_over_body_limit_returns_413() {
+        let config = Config {
+            max_request_bytes: 256,
+            ..Config::default()
+        };
+        let token_manager = MultiTokenManager::new(config, vec![], None, None, false).unwrap();
+        let state = AdminState::new("admin-key", AdminService::new(Arc::new(token_manager)));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_admin_router(state)).await.unwrap();
+        });
+
+        let credentials: Vec<Value> = (0..20)
+            .map(|id| json!({"refreshToken": format!("refresh-{}", id)}))
+            .collect();
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/credentials/import", addr))
+            .header("x-api-key", "admin-key")
+            .json(&credentials)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 413);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "request_too_large");
+    }
+
+    #[tokio::test]
+    async fn test_sync_endpoint_without_sync_manager_returns_conflict() {
+        let token_manager =
+            MultiTokenManager::new(Config::default(), vec![], None, None, false).unwrap();
+        let state = AdminState::new("admin-key", AdminService::new(Arc::new(token_manager)));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_admin_router(state)).await.unwrap();
+        });
+
+        let (status, body) =
+            post_sync(&reqwest::Client::new(), &format!("http://{}/sync", addr)).await;
+        assert_eq!(status, 409);
+        assert_eq!(body["error"]["type"], "conflict");
+    }
+
+    #[tokio::test]
+    async fn test_credential_stats_count_requests_and_reset() {
+        let credentials = vec![KiroCredentials {
+            access_token: Some("token".to_string()),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        }];
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap(),
+        );
+        let state = AdminState::new("admin-key", AdminService::new(token_manager.clone()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_admin_router(state)).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let get_stats = |id: u64| {
+            let request = client
+                .get(format!("http://{}/credentials/{}/stats", addr, id))
+                .header("x-api-key", "admin-key");
+            async move {
+                let response = request.send().await.unwrap();
+                (response.status().as_u16(), response.json::<Value>().await.unwrap())
+            }
+        };
+
+        let (status, body) = get_stats(1).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["requests"], 0);
+        assert!(body["lastUsedAt"].is_null());
+
+        // 模拟三次请求：两次成功（其中一次重试前失败一次），一次失败
+        let requests = [(0, Some((100, 20))), (1, Some((50, 5))), (1, None)];
+        for (failures, tokens) in requests {
+            let ctx = token_manager.acquire_context().await.unwrap();
+            for _ in 0..failures {
+                token_manager.record_upstream_failure(ctx.id);
+            }
+            if let Some((input, output)) = tokens {
+                token_manager.report_success(ctx.id);
+                token_manager.record_request_tokens(ctx.id, input, output);
+            }
+        }
+
+        let (status, body) = get_stats(1).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["id"], 1);
+        assert_eq!(body["requests"], 4);
+        assert_eq!(body["successes"], 2);
+        assert_eq!(body["failures"], 2);
+        assert_eq!(body["selections"], 3);
+        assert_eq!(body["inputTokens"], 150);
+        assert_eq!(body["outputTokens"], 25);
+        assert!(body["lastUsedAt"].is_string());
+
+        let reset_url = format!("http://{}/credentials/1/stats/reset", addr);
+        let (status, _) = post_sync(&client, &reset_url).await;
+        assert_eq!(status, 200);
+        let (_, body) = get_stats(1).await;
+        assert_eq!(body["requests"], 0);
+        assert_eq!(body["inputTokens"], 0);
+
+        let (status, body) = get_stats(9).await;
+        assert_eq!(status, 404);
+        assert_eq!(body["error"]["type"], "not_found");
+    }
+
+    /// 读取 WebSocket 的下一条事件
+    async fn next_event<S>(socket: &mut S) -> Value
+    where
+        S: futures::Stream<Item = tokio_tungstenite::tungstenite::Result<Message>> + Unpin,
+    {
+        use futures::StreamExt;
+
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+            .await
+            .expect("等待事件超时")
+            .unwrap()
+            .unwrap();
+        let Message::Text(text) = message else {
+            panic!("非文本消息: {:?}", message);
+        };
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_events_websocket_pushes_disable_event() {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        let credentials = vec![
+            KiroCredentials {
+                access_token: Some("token".to_string()),
+                expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            };
+            2
+        ];
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap(),
+        );
+        let state = AdminState::new("admin-key", AdminService::new(token_manager));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_admin_router(state)).await.unwrap();
+        });
+        let ws_url = format!("ws://{}/events", addr);
+
+        // 未认证的握手被拒绝
+        assert!(tokio_tungstenite::connect_async(&ws_url).await.is_err());
+
+        let mut request = ws_url.into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("x-api-key", "admin-key".parse().unwrap());
+        let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+        // 连接后立即推送一次统计快照
+        let snapshot = next_event(&mut socket).await;
+        assert_eq!(snapshot["type"], "stats_snapshot");
+        assert_eq!(snapshot["total"], 2);
+        assert_eq!(snapshot["available"], 2);
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/credentials/2/disabled", addr))
+            .header("x-api-key", "admin-key")
+            .json(&json!({"disabled": true}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+
+        let event = next_event(&mut socket).await;
+        assert_eq!(
+            event,
+            json!({"type": "credential_disabled", "id": 2, "reason": "manual"})
+        );
+    }
+}
@@ -1,16 +1,42 @@
 //! Admin API 业务逻辑服务
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::kiro::model::credentials::KiroCredentials;
-use crate::kiro::token_manager::MultiTokenManager;
+use futures::stream::{self, StreamExt};
+use tokio::sync::broadcast;
+
+use crate::http_client::ProxyConfig;
+use crate::kiro::credential_events::{CredentialEvent, StatsSnapshot};
+use crate::kiro::model::credentials::{CredentialsConfig, KiroCredentials, mask_token};
+use crate::kiro::storage::{CredentialSyncManager, validate_batch, validate_credential};
+use crate::kiro::token_manager::{
+    AcquireOptions, BreakerState, CredentialStats, CredentialUpdate, DedupKey, MultiTokenManager,
+    RefreshBreakerStatus,
+};
+use crate::model::config::Config;
 
 use super::error::AdminServiceError;
 use super::types::{
-    AddCredentialRequest, AddCredentialResponse, BalanceResponse, CredentialStatusItem,
-    CredentialsStatusResponse,
+    AddCredentialRequest, AddCredentialResponse, BalanceResponse, BalanceResult,
+    BalancesResponse, BulkUpdateResponse, CredentialFilter, CredentialSort,
+    CredentialStatusFilter, CredentialStatusItem, CredentialsStatusResponse,
+    ImportCredentialsResponse, ListCredentialsQuery, RecentErrorsResponse, SelectDryRunRequest,
+    SelectDryRunResponse, SyncCredentialsResponse, UpdateCredentialRequest,
 };
 
+/// 批量查询余额时单个凭据的超时时间
+const BALANCE_FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 批量查询余额的最大并发数
+const MAX_BALANCE_FETCH_CONCURRENCY: usize = 64;
+
+/// 凭据列表默认每页条数
+const DEFAULT_CREDENTIALS_PER_PAGE: usize = 50;
+
+/// 凭据列表每页条数上限
+const MAX_CREDENTIALS_PER_PAGE: usize = 500;
+
 /// Admin 服务
 ///
 /// 封装所有 Admin API 的业务逻辑
@@ -23,33 +49,142 @@ impl AdminService {
         Self { token_manager }
     }
 
-    /// 获取所有凭据状态
-    pub fn get_all_credentials(&self) -> CredentialsStatusResponse {
+    /// 获取应用配置
+    pub fn config(&self) -> Arc<Config> {
+        self.token_manager.config()
+    }
+
+    /// 立即从存储后端同步凭据
+    ///
+    /// 检测到变更时由同步管理器的回调热更新凭据，返回同步后的凭据数量
+    pub async fn sync_credentials(
+        &self,
+        sync_manager: &CredentialSyncManager,
+    ) -> Result<SyncCredentialsResponse, AdminServiceError> {
+        let outcome = sync_manager.sync_now().await;
+        if let Some(error) = outcome.error {
+            return Err(AdminServiceError::UpstreamError(format!(
+                "凭据同步失败: {}",
+                error
+            )));
+        }
+        Ok(SyncCredentialsResponse {
+            changed: outcome.changed,
+            credential_count: self.token_manager.total_count(),
+            next_auto_sync_in_secs: outcome.next_auto_sync_in.map(|d| d.as_secs()),
+        })
+    }
+
+    /// 按优先级排序的全部凭据 ID（不分页）
+    pub fn credential_ids(&self) -> Vec<u64> {
+        let mut entries = self.token_manager.snapshot().entries;
+        entries.sort_by_key(|e| e.priority);
+        entries.into_iter().map(|e| e.id).collect()
+    }
+
+    /// 分页列出凭据状态
+    ///
+    /// 先按 `status` 筛选、按 `sort` 排序，再取第 `page` 页；页码超出范围时返回空列表
+    pub fn list_credentials(&self, query: &ListCredentialsQuery) -> CredentialsStatusResponse {
         let snapshot = self.token_manager.snapshot();
+        let mut secrets = self.token_manager.credentials_by_id();
+        let reveal = self.token_manager.config().admin_reveal_secrets;
 
-        let mut credentials: Vec<CredentialStatusItem> = snapshot
+        let mut entries: Vec<_> = snapshot
             .entries
             .into_iter()
-            .map(|entry| CredentialStatusItem {
-                id: entry.id,
-                priority: entry.priority,
-                disabled: entry.disabled,
-                failure_count: entry.failure_count,
-                is_current: entry.id == snapshot.current_id,
-                expires_at: entry.expires_at,
-                auth_method: entry.auth_method,
-                has_profile_arn: entry.has_profile_arn,
+            .filter(|entry| match query.status {
+                None => true,
+                Some(CredentialStatusFilter::Enabled) => !entry.disabled,
+                Some(CredentialStatusFilter::Disabled) => entry.disabled,
+                Some(CredentialStatusFilter::BreakerOpen) => {
+                    entry.breaker_state == BreakerState::Open
+                }
             })
             .collect();
 
-        // 按优先级排序（数字越小优先级越高）
-        credentials.sort_by_key(|c| c.priority);
+        match query.sort {
+            // 按优先级排序（数字越小优先级越高）
+            CredentialSort::Priority => entries.sort_by_key(|e| e.priority),
+            CredentialSort::LastUsed => entries.sort_by(|a, b| {
+                b.last_used_at
+                    .cmp(&a.last_used_at)
+                    .then(a.priority.cmp(&b.priority))
+            }),
+        }
+
+        let total = entries.len();
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_CREDENTIALS_PER_PAGE)
+            .clamp(1, MAX_CREDENTIALS_PER_PAGE);
+
+        let items = entries
+            .into_iter()
+            .skip((page - 1).saturating_mul(per_page))
+            .take(per_page)
+            .map(|entry| {
+                let secret = secrets.remove(&entry.id).unwrap_or_default();
+                CredentialStatusItem {
+                    id: entry.id,
+                    priority: entry.priority,
+                    disabled: entry.disabled,
+                    failure_count: entry.failure_count,
+                    breaker_state: entry.breaker_state,
+                    breaker_remaining_secs: entry.breaker_remaining_secs,
+                    is_current: entry.id == snapshot.current_id,
+                    expires_at: entry.expires_at,
+                    auth_method: entry.auth_method,
+                    has_profile_arn: entry.has_profile_arn,
+                    health_score: entry.health_score,
+                    last_used_at: entry.last_used_at.map(|at| at.to_rfc3339()),
+                    access_token: display_secret(secret.access_token, reveal),
+                    refresh_token: display_secret(secret.refresh_token, reveal),
+                }
+            })
+            .collect();
 
         CredentialsStatusResponse {
-            total: snapshot.total,
+            total,
             available: snapshot.available,
             current_id: snapshot.current_id,
-            credentials,
+            pinned_id: snapshot.pinned_id,
+            page,
+            per_page,
+            items,
+        }
+    }
+
+    /// 获取 Token 刷新熔断器状态
+    pub fn refresh_breaker_status(&self) -> RefreshBreakerStatus {
+        self.token_manager.refresh_breaker_status()
+    }
+
+    /// 订阅凭据运行时事件
+    pub fn subscribe_events(&self) -> broadcast::Receiver<CredentialEvent> {
+        self.token_manager.subscribe_events()
+    }
+
+    /// 获取凭据统计快照
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        self.token_manager.stats_snapshot()
+    }
+
+    /// 获取最近失败的请求
+    pub fn recent_errors(&self, limit: Option<usize>) -> RecentErrorsResponse {
+        RecentErrorsResponse {
+            capacity: self.token_manager.recent_errors_capacity(),
+            errors: self.token_manager.recent_errors(limit),
+        }
+    }
+
+    /// 模拟一次凭据选择（不发起上游请求、不修改任何状态）
+    pub fn select_dry_run(&self, req: SelectDryRunRequest) -> SelectDryRunResponse {
+        let options = AcquireOptions::excluding(req.exclude_credentials);
+        SelectDryRunResponse {
+            model: req.model,
+            decision: self.token_manager.select_dry_run(&options),
         }
     }
 
@@ -70,6 +205,32 @@ impl AdminService {
         Ok(())
     }
 
+    /// 批量设置匹配筛选条件的凭据禁用状态
+    pub fn bulk_set_disabled(
+        &self,
+        filter: CredentialFilter,
+        disabled: bool,
+    ) -> Result<BulkUpdateResponse, AdminServiceError> {
+        if filter.is_empty() {
+            return Err(AdminServiceError::InvalidRequest(
+                "至少需要指定 region 或 ids 之一".to_string(),
+            ));
+        }
+
+        let default_region = self.token_manager.config().region.clone();
+        let affected_ids = self
+            .token_manager
+            .set_disabled_where(|cred| filter.matches(cred, &default_region), disabled)
+            .map_err(|e| AdminServiceError::InternalError(e.to_string()))?;
+
+        let action = if disabled { "禁用" } else { "启用" };
+        Ok(BulkUpdateResponse {
+            success: true,
+            message: format!("已{} {} 个凭据", action, affected_ids.len()),
+            affected_ids,
+        })
+    }
+
     /// 设置凭据优先级
     pub fn set_priority(&self, id: u64, priority: u32) -> Result<(), AdminServiceError> {
         self.token_manager
@@ -77,6 +238,49 @@ impl AdminService {
             .map_err(|e| self.classify_error(e, id))
     }
 
+    /// 一次更新凭据的优先级、禁用状态、权重和代理地址（未指定的字段保持不变）
+    ///
+    /// 请求中出现其他字段时整体拒绝，不做任何修改
+    pub fn update_credential(
+        &self,
+        id: u64,
+        request: UpdateCredentialRequest,
+    ) -> Result<(), AdminServiceError> {
+        if !request.other.is_empty() {
+            let fields: Vec<&str> = request.other.keys().map(String::as_str).collect();
+            return Err(AdminServiceError::InvalidRequest(format!(
+                "不支持修改字段: {}（仅支持 priority、disabled、weight、proxyUrl，\
+                 其他字段请通过添加或导入凭据替换）",
+                fields.join(", ")
+            )));
+        }
+        let update = CredentialUpdate {
+            priority: request.priority,
+            disabled: request.disabled,
+            weight: request.weight,
+            proxy_url: request.proxy_url,
+        };
+        if update == CredentialUpdate::default() {
+            return Err(AdminServiceError::InvalidRequest(
+                "至少需要指定 priority、disabled、weight、proxyUrl 之一".to_string(),
+            ));
+        }
+        if update.weight == Some(Some(0)) {
+            return Err(AdminServiceError::InvalidRequest(
+                "weight 必须大于 0".to_string(),
+            ));
+        }
+        if let Some(Some(url)) = &update.proxy_url {
+            ProxyConfig::new(url.as_str())
+                .scheme()
+                .map_err(|e| AdminServiceError::InvalidRequest(e.to_string()))?;
+        }
+
+        self.token_manager
+            .update_credential(id, update)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
     /// 重置失败计数并重新启用
     pub fn reset_and_enable(&self, id: u64) -> Result<(), AdminServiceError> {
         self.token_manager
@@ -84,6 +288,32 @@ impl AdminService {
             .map_err(|e| self.classify_error(e, id))
     }
 
+    /// 获取凭据的累计使用统计
+    pub fn credential_stats(&self, id: u64) -> Result<CredentialStats, AdminServiceError> {
+        self.token_manager
+            .credential_stats(id)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 清零凭据的累计使用统计
+    pub fn reset_credential_stats(&self, id: u64) -> Result<(), AdminServiceError> {
+        self.token_manager
+            .reset_credential_stats(id)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 固定使用指定凭据
+    pub fn pin_credential(&self, id: u64) -> Result<(), AdminServiceError> {
+        self.token_manager
+            .pin_credential(id)
+            .map_err(|e| self.classify_error(e, id))
+    }
+
+    /// 取消凭据固定，返回之前固定的凭据 ID
+    pub fn unpin_credential(&self) -> Option<u64> {
+        self.token_manager.unpin_credential()
+    }
+
     /// 获取凭据余额
     pub async fn get_balance(&self, id: u64) -> Result<BalanceResponse, AdminServiceError> {
         let usage = self
@@ -92,24 +322,35 @@ impl AdminService {
             .await
             .map_err(|e| self.classify_balance_error(e, id))?;
 
-        let current_usage = usage.current_usage();
-        let usage_limit = usage.usage_limit();
-        let remaining = (usage_limit - current_usage).max(0.0);
-        let usage_percentage = if usage_limit > 0.0 {
-            (current_usage / usage_limit * 100.0).min(100.0)
-        } else {
-            0.0
-        };
+        Ok(BalanceResponse::from_usage(id, &usage))
+    }
 
-        Ok(BalanceResponse {
-            id,
-            subscription_title: usage.subscription_title().map(|s| s.to_string()),
-            current_usage,
-            usage_limit,
-            remaining,
-            usage_percentage,
-            next_reset_at: usage.next_date_reset,
+    /// 并发查询所有凭据的余额
+    ///
+    /// 并发数默认取 `balance_fetch_concurrency`（限制在 1..=64），
+    /// 单个凭据查询失败或超时只记录在该凭据的结果中，不影响其他凭据
+    pub async fn get_all_balances(&self, concurrency: Option<usize>) -> BalancesResponse {
+        let concurrency = concurrency
+            .unwrap_or(self.config().balance_fetch_concurrency)
+            .clamp(1, MAX_BALANCE_FETCH_CONCURRENCY);
+        let ids = self
+            .token_manager
+            .snapshot()
+            .entries
+            .iter()
+            .map(|e| e.id)
+            .collect();
+
+        let balances = fetch_balances_concurrently(ids, concurrency, BALANCE_FETCH_TIMEOUT, |id| {
+            self.get_balance(id)
         })
+        .await;
+
+        BalancesResponse {
+            total: balances.len(),
+            failed: balances.iter().filter(|b| !b.success).count(),
+            balances,
+        }
     }
 
     /// 添加新凭据
@@ -119,18 +360,29 @@ impl AdminService {
     ) -> Result<AddCredentialResponse, AdminServiceError> {
         // 构建凭据对象
         let new_cred = KiroCredentials {
-            id: None,
-            access_token: None,
-            refresh_token: Some(req.refresh_token),
-            profile_arn: None,
-            expires_at: None,
+            id: req.id,
+            access_token: req.access_token,
+            refresh_token: req.refresh_token,
+            profile_arn: req.profile_arn,
+            expires_at: req.expires_at,
             auth_method: Some(req.auth_method),
             client_id: req.client_id,
             client_secret: req.client_secret,
             priority: req.priority,
             region: req.region,
             machine_id: req.machine_id,
+            monthly_token_limit: req.monthly_token_limit,
+            monthly_usage: None,
+            plan_cost: req.plan_cost,
+            quota_exhausted_until: None,
+            tags: Vec::new(),
+            proxy_url: req.proxy_url,
+            proxy_username: req.proxy_username,
+            proxy_password: req.proxy_password,
+            weight: req.weight,
         };
+        // 与存储后端批量保存使用相同的校验规则，避免无效凭据进入内存后导致回写失败
+        validate_credential(&new_cred).map_err(AdminServiceError::InvalidRequest)?;
 
         // 调用 token_manager 添加凭据
         let credential_id = self
@@ -146,10 +398,35 @@ impl AdminService {
         })
     }
 
+    /// 批量导入凭据（支持单对象或数组格式），按 `dedup_by` 去重
+    pub fn import_credentials(
+        &self,
+        credentials: CredentialsConfig,
+        dedup_by: DedupKey,
+    ) -> Result<ImportCredentialsResponse, AdminServiceError> {
+        let credentials = credentials.into_sorted_credentials_without_ids();
+        validate_batch(&credentials, false)
+            .map_err(|e| AdminServiceError::InvalidRequest(e.to_string()))?;
+        let summary = self
+            .token_manager
+            .import_credentials(credentials, dedup_by)
+            .map_err(|e| AdminServiceError::InternalError(e.to_string()))?;
+
+        Ok(ImportCredentialsResponse {
+            success: true,
+            message: format!(
+                "导入完成：新增 {}，替换 {}，跳过 {}",
+                summary.imported, summary.replaced, summary.skipped
+            ),
+            summary,
+        })
+    }
+
     /// 删除凭据
-    pub fn delete_credential(&self, id: u64) -> Result<(), AdminServiceError> {
+    pub async fn delete_credential(&self, id: u64) -> Result<(), AdminServiceError> {
         self.token_manager
             .delete_credential(id)
+            .await
             .map_err(|e| self.classify_delete_error(e, id))
     }
 
@@ -210,6 +487,8 @@ impl AdminService {
 
         if is_invalid_credential {
             AdminServiceError::InvalidCredential(msg)
+        } else if msg.contains("凭据 ID") {
+            AdminServiceError::InvalidRequest(msg)
         } else if msg.contains("error trying to connect")
             || msg.contains("connection")
             || msg.contains("timeout")
@@ -227,8 +506,550 @@ impl AdminService {
             AdminServiceError::NotFound { id }
         } else if msg.contains("只能删除已禁用的凭据") {
             AdminServiceError::InvalidCredential(msg)
+        } else if msg.contains("不支持删除凭据") {
+            AdminServiceError::Conflict(msg)
         } else {
             AdminServiceError::InternalError(msg)
         }
     }
 }
+
+/// 以有限并发查询多个凭据的余额，结果按凭据 ID 升序返回
+///
+/// 每个凭据的查询最多等待 `timeout`，超时记为失败
+async fn fetch_balances_concurrently<F, Fut>(
+    ids: Vec<u64>,
+    concurrency: usize,
+    timeout: Duration,
+    fetch: F,
+) -> Vec<BalanceResult>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<BalanceResponse, AdminServiceError>>,
+{
+    let mut results: Vec<BalanceResult> = stream::iter(ids)
+        .map(|id| {
+            let fetch = fetch(id);
+            async move {
+                let (balance, error) = match tokio::time::timeout(timeout, fetch).await {
+                    Ok(Ok(balance)) => (Some(balance), None),
+                    Ok(Err(e)) => (None, Some(e.to_string())),
+                    Err(_) => (None, Some(format!("查询超时（{} 秒）", timeout.as_secs_f32()))),
+                };
+                BalanceResult {
+                    id,
+                    success: balance.is_some(),
+                    balance,
+                    error,
+                }
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    results.sort_by_key(|r| r.id);
+    results
+}
+
+/// 凭据列表中敏感字段的展示值：默认脱敏，`reveal` 时返回完整值
+fn display_secret(value: Option<String>, reveal: bool) -> Option<String> {
+    value.map(|v| if reveal { v } else { mask_token(&v) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::storage::{CredentialStorage, FileCredentialStorage};
+
+    fn sample_balance(id: u64) -> BalanceResponse {
+        BalanceResponse {
+            id,
+            subscription_title: Some("KIRO PRO".to_string()),
+            current_usage: 10.0,
+            usage_limit: 100.0,
+            remaining: 90.0,
+            usage_percentage: 10.0,
+            next_reset_at: None,
+            credits: 90.0,
+            limit: 100.0,
+            reset_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_balance_fetch_returns_partial_results_without_waiting_for_slow_credential() {
+        let started = std::time::Instant::now();
+        let results = fetch_balances_concurrently(
+            vec![3, 1, 2, 4],
+            2,
+            Duration::from_millis(200),
+            |id| async move {
+                match id {
+                    1 | 4 => Ok(sample_balance(id)),
+                    2 => Err(AdminServiceError::UpstreamError("凭证已过期或无效".to_string())),
+                    _ => {
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                        Ok(sample_balance(id))
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(
+            results.iter().map(|r| r.id).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert!(results[0].success && results[3].success);
+        assert_eq!(results[0].balance.as_ref().unwrap().remaining, 90.0);
+        assert!(!results[1].success);
+        assert!(results[1].error.as_ref().unwrap().contains("凭证已过期或无效"));
+        assert!(!results[2].success);
+        assert!(results[2].error.as_ref().unwrap().contains("超时"));
+    }
+
+    #[tokio::test]
+    async fn test_balance_queries_by_auth_method_and_normalizes_response() {
+        use axum::extract::RawQuery;
+        use axum::http::HeaderMap;
+        use std::sync::Mutex;
+
+        // 按 host 请求头区分 region，分别返回 Social / IdC 两种响应结构
+        let seen: Arc<Mutex<Vec<(String, String)>>> = Default::default();
+        let recorded = seen.clone();
+        let app = axum::Router::new().route(
+            "/getUsageLimits",
+            axum::routing::get(move |headers: HeaderMap, RawQuery(query): RawQuery| {
+                let recorded = recorded.clone();
+                async move {
+                    let host = headers["host"].to_str().unwrap().to_string();
+                    recorded
+                        .lock()
+                        .unwrap()
+                        .push((host.clone(), query.unwrap_or_default()));
+                    if host == "q.eu-west-1.amazonaws.com" {
+                        // IdC：多条明细，重置时间位于明细中
+                        axum::Json(serde_json::json!({
+                            "subscriptionInfo": {"subscriptionTitle": "KIRO PRO"},
+                            "usageBreakdownList": [
+                                {"resourceType": "SPEC_REQUEST", "usageLimitWithPrecision": 10.0},
+                                {
+                                    "resourceType": "AGENTIC_REQUEST",
+                                    "currentUsageWithPrecision": 250.0,
+                                    "usageLimitWithPrecision": 1000.0,
+                                    "nextDateReset": 1767225600.0
+                                }
+                            ]
+                        }))
+                    } else {
+                        // Social：顶层重置时间，激活中的免费试用额度并入总额度
+                        axum::Json(serde_json::json!({
+                            "nextDateReset": 1767225600.0,
+                            "subscriptionInfo": {"subscriptionTitle": "KIRO FREE"},
+                            "usageBreakdownList": [{
+                                "currentUsageWithPrecision": 20.0,
+                                "usageLimitWithPrecision": 50.0,
+                                "freeTrialInfo": {
+                                    "currentUsageWithPrecision": 100.0,
+                                    "usageLimitWithPrecision": 500.0,
+                                    "freeTrialStatus": "ACTIVE"
+                                }
+                            }]
+                        }))
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let expires_at = (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+        let social = KiroCredentials {
+            auth_method: Some("social".to_string()),
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            profile_arn: Some("arn:aws:codewhisperer:us-east-1:1:profile/P".to_string()),
+            expires_at: Some(expires_at.clone()),
+            ..Default::default()
+        };
+        let idc = KiroCredentials {
+            auth_method: Some("idc".to_string()),
+            access_token: Some("token".to_string()),
+            refresh_token: Some("b".repeat(150)),
+            client_id: Some("client".to_string()),
+            client_secret: Some("secret".to_string()),
+            region: Some("eu-west-1".to_string()),
+            profile_arn: Some("arn:aws:codewhisperer:us-east-1:1:profile/P".to_string()),
+            expires_at: Some(expires_at),
+            ..Default::default()
+        };
+        let token_manager =
+            MultiTokenManager::new(Config::default(), vec![social, idc], None, None, false)
+                .unwrap()
+                .with_usage_limits_url(format!("http://{}", addr));
+        let service = AdminService::new(Arc::new(token_manager));
+
+        let balance = service.get_balance(1).await.unwrap();
+        assert_eq!(balance.subscription_title.as_deref(), Some("KIRO FREE"));
+        assert_eq!(balance.credits, 430.0);
+        assert_eq!(balance.limit, 550.0);
+        assert_eq!(
+            balance.reset_at.as_deref(),
+            Some("2026-01-01T00:00:00+00:00")
+        );
+
+        let balance = service.get_balance(2).await.unwrap();
+        assert_eq!(balance.subscription_title.as_deref(), Some("KIRO PRO"));
+        assert_eq!(balance.credits, 750.0);
+        assert_eq!(balance.limit, 1000.0);
+        assert_eq!(
+            balance.reset_at.as_deref(),
+            Some("2026-01-01T00:00:00+00:00")
+        );
+
+        // Social 按 config.region 查询并携带 profileArn；IdC 按凭据 region 查询且不携带
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0].0, "q.us-east-1.amazonaws.com");
+        assert!(seen[0].1.contains("profileArn="));
+        assert_eq!(seen[1].0, "q.eu-west-1.amazonaws.com");
+        assert!(!seen[1].1.contains("profileArn"));
+        assert!(seen[1].1.contains("resourceType=AGENTIC_REQUEST"));
+    }
+
+    fn valid_credential() -> KiroCredentials {
+        KiroCredentials {
+            access_token: Some("token".to_string()),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        }
+    }
+
+    /// 按 `POST /api/admin/credentials` 请求体构造添加请求（携带有效 accessToken，无需刷新）
+    fn add_request(extra: serde_json::Value) -> AddCredentialRequest {
+        let mut body = serde_json::json!({
+            "refreshToken": "r".repeat(120),
+            "accessToken": "added-token",
+            "expiresAt": (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339(),
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_credential_listing_masks_tokens_unless_revealed() {
+        let mut credentials = valid_credential();
+        credentials.access_token = Some("access-0123456789-abcd".to_string());
+        credentials.refresh_token = Some("refresh-0123456789-wxyz".to_string());
+
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), vec![credentials.clone()], None, None, false)
+                .unwrap(),
+        );
+        let listed =
+            AdminService::new(token_manager).list_credentials(&ListCredentialsQuery::default());
+        let item = &listed.items[0];
+        assert_eq!(item.access_token.as_deref(), Some("acce...abcd"));
+        assert_eq!(item.refresh_token.as_deref(), Some("refr...wxyz"));
+        let json = serde_json::to_string(&listed).unwrap();
+        assert!(!json.contains("0123456789"), "{}", json);
+
+        let config = Config {
+            admin_reveal_secrets: true,
+            ..Config::default()
+        };
+        let token_manager =
+            Arc::new(MultiTokenManager::new(config, vec![credentials], None, None, false).unwrap());
+        let listed =
+            AdminService::new(token_manager).list_credentials(&ListCredentialsQuery::default());
+        assert_eq!(
+            listed.items[0].refresh_token.as_deref(),
+            Some("refresh-0123456789-wxyz")
+        );
+    }
+
+    fn listed_ids(listed: &CredentialsStatusResponse) -> Vec<u64> {
+        listed.items.iter().map(|c| c.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_credential_listing_filters_by_status() {
+        let config = Config {
+            breaker_failure_threshold: 1,
+            breaker_cooldown_secs: 60,
+            ..Config::default()
+        };
+        let credentials = vec![valid_credential(); 4];
+        let token_manager =
+            Arc::new(MultiTokenManager::new(config, credentials, None, None, false).unwrap());
+        let service = AdminService::new(token_manager.clone());
+        service.set_disabled(2, true).unwrap();
+        token_manager.report_failure(3);
+
+        let list = |status| {
+            service.list_credentials(&ListCredentialsQuery {
+                status: Some(status),
+                ..Default::default()
+            })
+        };
+        let enabled = list(CredentialStatusFilter::Enabled);
+        assert_eq!(listed_ids(&enabled), vec![1, 3, 4]);
+        assert_eq!(enabled.total, 3);
+        assert_eq!(listed_ids(&list(CredentialStatusFilter::Disabled)), vec![2]);
+        assert_eq!(listed_ids(&list(CredentialStatusFilter::BreakerOpen)), vec![3]);
+
+        // 无参数时返回第一页全部凭据
+        let all = service.list_credentials(&ListCredentialsQuery::default());
+        assert_eq!((all.total, all.page, all.per_page), (4, 1, 50));
+        assert_eq!(listed_ids(&all), vec![1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_credential_listing_pages_and_sorts_by_last_used() {
+        let credentials = vec![valid_credential(); 3];
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap(),
+        );
+        let service = AdminService::new(token_manager.clone());
+        let page = |page, per_page| {
+            service.list_credentials(&ListCredentialsQuery {
+                page: Some(page),
+                per_page: Some(per_page),
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(listed_ids(&page(1, 2)), vec![1, 2]);
+        assert_eq!(listed_ids(&page(2, 2)), vec![3]);
+        // 页码超出范围返回空列表，total 仍为匹配总数
+        let beyond = page(5, 2);
+        assert!(beyond.items.is_empty());
+        assert_eq!((beyond.total, beyond.page), (3, 5));
+
+        token_manager
+            .acquire_context_with(&AcquireOptions::excluding([1, 2]))
+            .await
+            .unwrap();
+        let listed = service.list_credentials(&ListCredentialsQuery {
+            sort: CredentialSort::LastUsed,
+            ..Default::default()
+        });
+        // 最近使用的在前，未使用过的按优先级排在后面
+        assert_eq!(listed_ids(&listed), vec![3, 1, 2]);
+        assert!(listed.items[0].last_used_at.is_some());
+        assert!(listed.items[1].last_used_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_added_credential_is_listed_and_immediately_selectable() {
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), vec![valid_credential()], None, None, false)
+                .unwrap(),
+        );
+        let service = AdminService::new(token_manager.clone());
+
+        let added = service.add_credential(add_request(serde_json::json!({}))).await.unwrap();
+        assert_eq!(added.credential_id, 2);
+        let explicit = service
+            .add_credential(add_request(serde_json::json!({"id": 10, "priority": 3})))
+            .await
+            .unwrap();
+        assert_eq!(explicit.credential_id, 10);
+
+        let listed = service.list_credentials(&ListCredentialsQuery::default());
+        assert_eq!(listed.total, 3);
+        assert_eq!(
+            listed.items.iter().map(|c| c.id).collect::<Vec<_>>(),
+            vec![1, 2, 10]
+        );
+        assert_eq!(listed.items[2].priority, 3);
+
+        let ctx = token_manager
+            .acquire_context_with(&AcquireOptions::excluding([1]))
+            .await
+            .unwrap();
+        assert_eq!((ctx.id, ctx.token.as_str()), (2, "added-token"));
+    }
+
+    #[tokio::test]
+    async fn test_add_credential_rejects_missing_refresh_token_and_taken_id() {
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), vec![valid_credential()], None, None, false)
+                .unwrap(),
+        );
+        let service = AdminService::new(token_manager);
+
+        // 缺少 refreshToken 的请求体可以解析，由服务层返回 400（而不是 422）
+        let missing: AddCredentialRequest =
+            serde_json::from_value(serde_json::json!({"accessToken": "token"})).unwrap();
+        let err = service.add_credential(missing).await.unwrap_err();
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("缺少 refreshToken"));
+
+        let err = service
+            .add_credential(add_request(serde_json::json!({"id": 1})))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("凭据 ID 1 已存在"));
+        let listed = service.list_credentials(&ListCredentialsQuery::default());
+        assert_eq!(listed.total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_import_reject_invalid_credentials() {
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), vec![valid_credential()], None, None, false)
+                .unwrap(),
+        );
+        let service = AdminService::new(token_manager);
+
+        for (body, reason) in [
+            (serde_json::json!({"expiresAt": "tomorrow"}), "expiresAt"),
+            (serde_json::json!({"planCost": -1.0}), "planCost"),
+            (serde_json::json!({"weight": 0}), "weight"),
+        ] {
+            let err = service.add_credential(add_request(body)).await.unwrap_err();
+            assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+            assert!(err.to_string().contains(reason), "{}", err);
+        }
+
+        let batch: CredentialsConfig = serde_json::from_value(serde_json::json!([
+            {"refreshToken": "a".repeat(150)},
+            {"refreshToken": "b".repeat(150), "planCost": -5.0}
+        ]))
+        .unwrap();
+        let err = service
+            .import_credentials(batch, DedupKey::RefreshToken)
+            .unwrap_err();
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("planCost"), "{}", err);
+
+        // 整批拒绝，未写入任何凭据
+        let listed = service.list_credentials(&ListCredentialsQuery::default());
+        assert_eq!(listed.total, 1);
+    }
+
+    /// 以给定格式写入凭据文件并创建挂载文件存储的管理器
+    fn file_backed_service(
+        file: &tempfile::NamedTempFile,
+        is_multiple_format: bool,
+    ) -> (AdminService, Arc<FileCredentialStorage>) {
+        let credentials: Vec<KiroCredentials> = (1..=2)
+            .map(|id| KiroCredentials {
+                id: Some(id),
+                refresh_token: Some(format!("refresh-{}", id)),
+                ..valid_credential()
+            })
+            .collect();
+        std::fs::write(file.path(), serde_json::to_string(&credentials).unwrap()).unwrap();
+
+        let storage = Arc::new(FileCredentialStorage::new(file.path(), is_multiple_format));
+        let mut token_manager =
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap();
+        token_manager.set_storage(storage.clone());
+        (AdminService::new(Arc::new(token_manager)), storage)
+    }
+
+    #[tokio::test]
+    async fn test_delete_credential_removes_from_file_storage_without_breaking_in_flight_request()
+    {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let (service, storage) = file_backed_service(&file, true);
+
+        // 删除前已取得调用上下文的请求
+        let in_flight = service
+            .token_manager
+            .acquire_context_with(&AcquireOptions::excluding([1]))
+            .await
+            .unwrap();
+        assert_eq!(in_flight.id, 2);
+
+        service.set_disabled(2, true).unwrap();
+        service.delete_credential(2).await.unwrap();
+
+        let listed = service.list_credentials(&ListCredentialsQuery::default());
+        assert_eq!(listed.items.iter().map(|c| c.id).collect::<Vec<_>>(), vec![1]);
+        // 后台回写完成后文件中只剩凭据 1
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        loop {
+            let ids: Vec<_> = storage.load_all().await.unwrap().iter().map(|c| c.id).collect();
+            if ids == vec![Some(1)] {
+                break;
+            }
+            assert!(std::time::Instant::now() < deadline, "凭据文件未更新: {:?}", ids);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // 进行中的请求仍持有有效 Token，结束时的上报被忽略
+        assert_eq!(in_flight.token, "token");
+        service.token_manager.report_success(in_flight.id);
+        assert!(service.token_manager.report_failure(in_flight.id));
+        let listed = service.list_credentials(&ListCredentialsQuery::default());
+        assert_eq!(listed.total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_credential_on_read_only_storage_returns_conflict() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let (service, _storage) = file_backed_service(&file, false);
+
+        service.set_disabled(2, true).unwrap();
+        let err = service.delete_credential(2).await.unwrap_err();
+
+        assert_eq!(err.status_code(), axum::http::StatusCode::CONFLICT);
+        assert!(err.to_string().contains("不支持删除凭据"));
+        let listed = service.list_credentials(&ListCredentialsQuery::default());
+        assert_eq!(listed.total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_recent_errors_returns_failed_request_details() {
+        use crate::http_client::ProxyConfig;
+        use crate::kiro::error_code::KiroErrorCode;
+        use crate::kiro::provider::KiroProvider;
+
+        // 通过无法连接的代理让请求发送失败，截止时间内不会进入重试
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = ProxyConfig::new(format!("http://{}", listener.local_addr().unwrap()));
+        drop(listener);
+
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let token_manager = Arc::new(
+            MultiTokenManager::new(Config::default(), vec![credentials], Some(proxy), None, false)
+                .unwrap(),
+        );
+        let provider = KiroProvider::new(token_manager.clone());
+        let service = AdminService::new(token_manager);
+        assert!(service.recent_errors(None).errors.is_empty());
+
+        let options = AcquireOptions {
+            deadline: Some(tokio::time::Instant::now() + Duration::from_millis(100)),
+            ..Default::default()
+        };
+        assert!(provider.call_api("{}", &options).await.is_err());
+
+        let response = service.recent_errors(Some(10));
+        assert_eq!(response.capacity, 100);
+        assert_eq!(response.errors.len(), 1);
+        let error = &response.errors[0];
+        assert_eq!(error.code, KiroErrorCode::DeadlineExceeded);
+        assert_eq!(error.credential_id, Some(1));
+        assert_eq!(error.status, None);
+        assert!(!error.message.is_empty());
+        assert!(chrono::DateTime::parse_from_rfc3339(&error.timestamp).is_ok());
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["errors"][0]["code"], "deadline_exceeded");
+        assert_eq!(json["errors"][0]["credentialId"], 1);
+    }
+}
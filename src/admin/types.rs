@@ -1,21 +1,70 @@
 //! Admin API 类型定义
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::model::usage_limits::UsageLimitsResponse;
+use crate::kiro::recent_errors::RecentError;
+use crate::kiro::token_manager::{BreakerState, DedupKey, ImportSummary, SelectionDecision};
 
 // ============ 凭据状态 ============
 
-/// 所有凭据状态响应
+/// 凭据列表查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct ListCredentialsQuery {
+    /// 页码（从 1 开始，默认 1）
+    pub page: Option<usize>,
+    /// 每页条数（默认 50，最大 500）
+    pub per_page: Option<usize>,
+    /// 按状态筛选（未指定时返回全部）
+    pub status: Option<CredentialStatusFilter>,
+    /// 排序方式（默认按优先级）
+    #[serde(default)]
+    pub sort: CredentialSort,
+}
+
+/// 凭据列表的状态筛选
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialStatusFilter {
+    /// 未禁用
+    Enabled,
+    /// 已禁用
+    Disabled,
+    /// 熔断冷却中
+    BreakerOpen,
+}
+
+/// 凭据列表的排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialSort {
+    /// 按优先级（数字越小越靠前）
+    #[default]
+    Priority,
+    /// 按最近使用时间（最近使用的在前，从未使用的排在最后）
+    LastUsed,
+}
+
+/// 凭据列表响应（分页）
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CredentialsStatusResponse {
-    /// 凭据总数
+    /// 符合筛选条件的凭据数量
     pub total: usize,
-    /// 可用凭据数量（未禁用）
+    /// 可用凭据数量（未禁用，不受筛选影响）
     pub available: usize,
     /// 当前活跃凭据 ID
     pub current_id: u64,
-    /// 各凭据状态列表
-    pub credentials: Vec<CredentialStatusItem>,
+    /// 固定使用的凭据 ID（未固定时不返回）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_id: Option<u64>,
+    /// 当前页码
+    pub page: usize,
+    /// 每页条数
+    pub per_page: usize,
+    /// 当前页的凭据状态列表（页码超出范围时为空）
+    pub items: Vec<CredentialStatusItem>,
 }
 
 /// 单个凭据的状态信息
@@ -30,6 +79,10 @@ pub struct CredentialStatusItem {
     pub disabled: bool,
     /// 连续失败次数
     pub failure_count: u32,
+    /// 熔断状态（`closed` / `open` / `half_open`）
+    pub breaker_state: BreakerState,
+    /// 熔断剩余冷却时间（秒，仅 `open` 状态时有值）
+    pub breaker_remaining_secs: Option<u64>,
     /// 是否为当前活跃凭据
     pub is_current: bool,
     /// Token 过期时间（RFC3339 格式）
@@ -38,6 +91,16 @@ pub struct CredentialStatusItem {
     pub auth_method: Option<String>,
     /// 是否有 Profile ARN
     pub has_profile_arn: bool,
+    /// 健康分（0.0 ~ 1.0，由成功率、延迟和剩余月度额度计算，`autoReorder` 按此调整选择顺序）
+    pub health_score: f64,
+    /// 最近一次被选中的时间（RFC3339 格式，从未使用时为 null）
+    pub last_used_at: Option<String>,
+    /// 访问令牌（默认仅显示首尾各 4 个字符，`adminRevealSecrets` 开启时为完整值）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    /// 刷新令牌（默认仅显示首尾各 4 个字符，`adminRevealSecrets` 开启时为完整值）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
 }
 
 // ============ 操作请求 ============
@@ -50,6 +113,88 @@ pub struct SetDisabledRequest {
     pub disabled: bool,
 }
 
+/// 凭据选择模拟请求（部分请求参数）
+///
+/// 当前凭据选择与模型无关，`model` 仅用于回显；未知字段忽略
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectDryRunRequest {
+    /// 请求的模型
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 需要排除的凭据 ID（等价于 `x-kiro-exclude-credentials` 请求头）
+    #[serde(default)]
+    pub exclude_credentials: Vec<u64>,
+}
+
+/// 凭据选择模拟响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectDryRunResponse {
+    /// 请求的模型（回显）
+    pub model: Option<String>,
+    /// 选择结果
+    #[serde(flatten)]
+    pub decision: SelectionDecision,
+}
+
+/// 批量启用/禁用凭据的筛选条件（各条件之间为"且"关系，至少指定一项）
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CredentialFilter {
+    /// 凭据 region（未配置凭据级 region 时按全局 region 匹配）
+    pub region: Option<String>,
+    /// 凭据 ID 列表
+    pub ids: Option<Vec<u64>>,
+    /// 凭据标签（凭据的 `tags` 中包含该标签即匹配）
+    pub tag: Option<String>,
+}
+
+impl CredentialFilter {
+    /// 是否未指定任何条件
+    pub fn is_empty(&self) -> bool {
+        self.region.is_none() && self.ids.is_none() && self.tag.is_none()
+    }
+
+    /// 判断凭据是否匹配
+    pub fn matches(&self, credentials: &KiroCredentials, default_region: &str) -> bool {
+        let region_matches = self.region.as_deref().is_none_or(|region| {
+            credentials.region.as_deref().unwrap_or(default_region) == region
+        });
+        let id_matches = self
+            .ids
+            .as_ref()
+            .is_none_or(|ids| credentials.id.is_some_and(|id| ids.contains(&id)));
+        let tag_matches = self
+            .tag
+            .as_ref()
+            .is_none_or(|tag| credentials.tags.contains(tag));
+        region_matches && id_matches && tag_matches
+    }
+}
+
+/// 批量启用/禁用凭据响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkUpdateResponse {
+    pub success: bool,
+    pub message: String,
+    /// 受影响的凭据 ID
+    pub affected_ids: Vec<u64>,
+}
+
+/// 手动同步凭据响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncCredentialsResponse {
+    /// 是否检测到变更并重新加载
+    pub changed: bool,
+    /// 同步后的凭据数量
+    pub credential_count: usize,
+    /// 距离下次自动同步的秒数（定时同步禁用时为 None）
+    pub next_auto_sync_in_secs: Option<u64>,
+}
+
 /// 修改优先级请求
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -58,12 +203,55 @@ pub struct SetPriorityRequest {
     pub priority: u32,
 }
 
+/// 部分更新凭据请求（`PATCH /credentials/:id`），未出现的字段保持不变
+///
+/// `weight`、`proxyUrl` 为 null 时清除该字段；`refreshToken` 等其他字段不可通过此接口修改
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCredentialRequest {
+    /// 新优先级
+    pub priority: Option<u32>,
+    /// 禁用/启用
+    pub disabled: Option<bool>,
+    /// 选择权重（null 表示清除）
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub weight: Option<Option<u32>>,
+    /// 凭据级代理地址（null 表示清除，同时清除 proxyUsername/proxyPassword，回退到全局代理）
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub proxy_url: Option<Option<String>>,
+    /// 其余字段（不可修改，出现时拒绝请求）
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// 区分字段缺失（None）与显式 null（`Some(None)`）
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 /// 添加凭据请求
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AddCredentialRequest {
-    /// 刷新令牌（必填）
-    pub refresh_token: String,
+    /// 凭据 ID（可选，未指定时分配为当前最大 ID + 1）
+    pub id: Option<u64>,
+
+    /// 刷新令牌（必填，缺少时返回 400）
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+
+    /// 访问令牌（可选），与 `expires_at` 一同提供且仍在有效期内时直接使用，不再刷新验证
+    pub access_token: Option<String>,
+
+    /// 访问令牌过期时间（RFC3339 格式）
+    pub expires_at: Option<String>,
+
+    /// Profile ARN（可选）
+    pub profile_arn: Option<String>,
 
     /// 认证方式（可选，默认 social）
     #[serde(default = "default_auth_method")]
@@ -86,6 +274,24 @@ pub struct AddCredentialRequest {
     /// 凭据级 Machine ID（可选，64 位字符串）
     /// 未配置时回退到 config.json 的 machineId
     pub machine_id: Option<String>,
+
+    /// 每月 token 上限（可选）
+    pub monthly_token_limit: Option<u64>,
+
+    /// 套餐成本（可选，cheapest 选择模式下使用）
+    pub plan_cost: Option<f64>,
+
+    /// 凭据级代理地址（可选），未配置时使用全局代理
+    pub proxy_url: Option<String>,
+
+    /// 凭据级代理认证用户名
+    pub proxy_username: Option<String>,
+
+    /// 凭据级代理认证密码
+    pub proxy_password: Option<String>,
+
+    /// 选择权重（可选，weighted 选择策略下使用，默认 1）
+    pub weight: Option<u32>,
 }
 
 fn default_auth_method() -> String {
@@ -102,6 +308,27 @@ pub struct AddCredentialResponse {
     pub credential_id: u64,
 }
 
+// ============ 批量导入 ============
+
+/// 批量导入凭据查询参数
+#[derive(Debug, Deserialize)]
+pub struct ImportCredentialsQuery {
+    /// 去重字段：refresh_token（默认）、profile_arn 或 id
+    #[serde(default)]
+    pub dedup_by: DedupKey,
+}
+
+/// 批量导入凭据响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportCredentialsResponse {
+    pub success: bool,
+    pub message: String,
+    /// 导入统计
+    #[serde(flatten)]
+    pub summary: ImportSummary,
+}
+
 // ============ 余额查询 ============
 
 /// 余额查询响应
@@ -122,6 +349,97 @@ pub struct BalanceResponse {
     pub usage_percentage: f64,
     /// 下次重置时间（Unix 时间戳）
     pub next_reset_at: Option<f64>,
+    /// 剩余额度（归一化字段，与 `remaining` 相同）
+    pub credits: f64,
+    /// 总额度（归一化字段，与 `usage_limit` 相同）
+    pub limit: f64,
+    /// 下次重置时间（归一化字段，RFC3339）
+    pub reset_at: Option<String>,
+}
+
+impl BalanceResponse {
+    /// 从上游使用额度响应构建余额信息
+    pub fn from_usage(id: u64, usage: &UsageLimitsResponse) -> Self {
+        let current_usage = usage.current_usage();
+        let usage_limit = usage.usage_limit();
+        let remaining = (usage_limit - current_usage).max(0.0);
+        let usage_percentage = if usage_limit > 0.0 {
+            (current_usage / usage_limit * 100.0).min(100.0)
+        } else {
+            0.0
+        };
+
+        let next_reset_at = usage.next_reset();
+        let reset_at = next_reset_at
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts as i64, 0))
+            .map(|dt| dt.to_rfc3339());
+
+        Self {
+            id,
+            subscription_title: usage.subscription_title().map(|s| s.to_string()),
+            current_usage,
+            usage_limit,
+            remaining,
+            usage_percentage,
+            next_reset_at,
+            credits: remaining,
+            limit: usage_limit,
+            reset_at,
+        }
+    }
+}
+
+/// 批量余额查询参数
+#[derive(Debug, Deserialize)]
+pub struct BalancesQuery {
+    /// 并发查询数（未指定时使用 `balance_fetch_concurrency`）
+    pub concurrency: Option<usize>,
+}
+
+/// 单个凭据的余额查询结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceResult {
+    /// 凭据 ID
+    pub id: u64,
+    pub success: bool,
+    /// 余额信息（查询成功时返回）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<BalanceResponse>,
+    /// 错误信息（查询失败时返回）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 批量余额查询响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalancesResponse {
+    /// 查询的凭据数量
+    pub total: usize,
+    /// 查询失败的凭据数量
+    pub failed: usize,
+    /// 各凭据的查询结果（按凭据 ID 升序）
+    pub balances: Vec<BalanceResult>,
+}
+
+// ============ 最近错误 ============
+
+/// 最近错误查询参数
+#[derive(Debug, Deserialize)]
+pub struct RecentErrorsQuery {
+    /// 返回条数上限（未指定时返回全部）
+    pub limit: Option<usize>,
+}
+
+/// 最近错误响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentErrorsResponse {
+    /// 保留容量（`recent_errors_capacity`）
+    pub capacity: usize,
+    /// 最近的错误（最新的在前）
+    pub errors: Vec<RecentError>,
 }
 
 // ============ 通用响应 ============
@@ -177,6 +495,10 @@ impl AdminErrorResponse {
         Self::new("not_found", message)
     }
 
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new("conflict", message)
+    }
+
     pub fn api_error(message: impl Into<String>) -> Self {
         Self::new("api_error", message)
     }
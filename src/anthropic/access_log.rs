@@ -0,0 +1,225 @@
+//! 结构化访问日志
+//!
+//! 每个 `/v1/messages` 请求在响应结束（流式请求为流结束或客户端断开）时输出一条
+//! tracing 事件，字段包括模型、是否流式、状态码、上游延迟、token 用量和服务本次请求的凭据 ID，
+//! 便于 Loki/ELK 等按字段检索。启用 `log_credential_refs` 时以伪名标识代替凭据 ID。
+//! 可通过 `RUST_LOG=kiro_rs::anthropic::access_log=off` 关闭
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{body::Body, http::Request, middleware::Next, response::Response};
+use futures::StreamExt;
+use parking_lot::Mutex;
+
+use crate::kiro::credential_ref::credential_ref;
+use crate::kiro::provider::ServedBy;
+use crate::model::config::Config;
+
+/// 访问日志中由处理链路填充的字段
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessLogFields {
+    /// 客户端请求的模型
+    pub model: Option<String>,
+    /// 是否为流式请求
+    pub stream: Option<bool>,
+    /// 服务本次请求的凭据 ID（启用 `log_credential_refs` 时不记录）
+    pub credential_id: Option<u64>,
+    /// 服务本次请求的凭据伪名标识（仅启用 `log_credential_refs` 时记录）
+    pub credential_ref: Option<String>,
+    /// 上游响应延迟（发送请求到收到响应头，毫秒）
+    pub upstream_latency_ms: Option<u64>,
+    /// 输入 token 数
+    pub input_tokens: Option<i32>,
+    /// 输出 token 数
+    pub output_tokens: Option<i32>,
+}
+
+/// 单个请求的访问日志记录
+///
+/// 由 [`access_log_middleware`] 创建并放入请求扩展，处理链路通过
+/// `Extension<AccessLogRecord>` 取得后填充字段（克隆共享同一份记录）
+#[derive(Debug, Clone, Default)]
+pub struct AccessLogRecord(Arc<Mutex<AccessLogFields>>);
+
+impl AccessLogRecord {
+    /// 记录客户端请求的模型和是否流式
+    pub fn set_request(&self, model: &str, stream: bool) {
+        let mut fields = self.0.lock();
+        fields.model = Some(model.to_string());
+        fields.stream = Some(stream);
+    }
+
+    /// 记录服务本次请求的凭据和上游延迟
+    pub fn set_served(&self, served_by: &ServedBy, config: &Config) {
+        let mut fields = self.0.lock();
+        if config.log_credential_refs {
+            fields.credential_ref = Some(credential_ref(served_by.credential_id));
+        } else {
+            fields.credential_id = Some(served_by.credential_id);
+        }
+        fields.upstream_latency_ms = Some(served_by.upstream_latency.as_millis() as u64);
+    }
+
+    /// 记录 token 用量
+    pub fn set_usage(&self, input_tokens: i32, output_tokens: i32) {
+        let mut fields = self.0.lock();
+        fields.input_tokens = Some(input_tokens);
+        fields.output_tokens = Some(output_tokens);
+    }
+
+    /// 当前字段快照
+    pub fn fields(&self) -> AccessLogFields {
+        self.0.lock().clone()
+    }
+}
+
+/// 在响应体结束（或被丢弃）时输出访问日志
+struct AccessLogGuard {
+    record: AccessLogRecord,
+    method: String,
+    path: String,
+    status: u16,
+    started: Instant,
+}
+
+impl Drop for AccessLogGuard {
+    fn drop(&mut self) {
+        let fields = self.record.fields();
+        tracing::info!(
+            method = %self.method,
+            path = %self.path,
+            status = self.status,
+            model = fields.model.as_deref(),
+            stream = fields.stream,
+            credential_id = fields.credential_id,
+            credential_ref = fields.credential_ref.as_deref(),
+            upstream_latency_ms = fields.upstream_latency_ms,
+            duration_ms = self.started.elapsed().as_millis() as u64,
+            input_tokens = fields.input_tokens,
+            output_tokens = fields.output_tokens,
+            "access"
+        );
+    }
+}
+
+/// 访问日志中间件
+///
+/// 将 [`AccessLogRecord`] 放入请求扩展，响应体发送完毕后输出结构化访问日志；
+/// 流式响应的 token 用量在流结束时才确定，因此日志延迟到响应体结束时输出
+pub async fn access_log_middleware(mut request: Request<Body>, next: Next) -> Response {
+    let record = AccessLogRecord::default();
+    request.extensions_mut().insert(record.clone());
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+    let guard = AccessLogGuard {
+        record,
+        method,
+        path,
+        status: response.status().as_u16(),
+        started,
+    };
+
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &guard;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Extension, Json, Router, middleware, routing::post};
+    use serde_json::json;
+    use std::io::Write;
+    use std::sync::Mutex as StdMutex;
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<StdMutex<Vec<u8>>>);
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_access_log_event_carries_request_fields() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .without_time()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        // 单线程运行时：服务端任务与测试在同一线程，共用线程本地的 subscriber
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route(
+                "/v1/messages",
+                post(|Extension(record): Extension<AccessLogRecord>| async move {
+                    record.set_request("claude-sonnet-4", false);
+                    let served_by = ServedBy {
+                        credential_id: 7,
+                        region: "us-east-1".to_string(),
+                        upstream_latency: Duration::from_millis(42),
+                    };
+                    record.set_served(&served_by, &Config::default());
+                    record.set_usage(12, 34);
+                    Json(json!({"type": "message"}))
+                }),
+            )
+            .layer(middleware::from_fn(access_log_middleware));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/v1/messages", addr))
+            .json(&json!({"model": "claude-sonnet-4"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        response.bytes().await.unwrap();
+
+        // 日志在服务端丢弃响应体时输出，可能略晚于客户端读完响应
+        let mut logs = String::new();
+        for _ in 0..50 {
+            logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+            if logs.contains(" access ") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let line = logs.lines().find(|line| line.contains(" access ")).unwrap();
+        for field in [
+            "method=POST",
+            "path=/v1/messages",
+            "status=200",
+            "model=\"claude-sonnet-4\"",
+            "stream=false",
+            "credential_id=7",
+            "upstream_latency_ms=42",
+            "input_tokens=12",
+            "output_tokens=34",
+        ] {
+            assert!(line.contains(field), "缺少字段 {}: {}", field, line);
+        }
+        assert!(line.contains("duration_ms="));
+        assert!(!line.contains("credential_ref"));
+    }
+}
@@ -0,0 +1,356 @@
+//! 请求/响应归档
+//!
+//! 按 `config.archive` 的采样率将非流式 `/v1/messages` 请求与响应写入 JSONL 文件，
+//! 用于离线分析和回归测试。写入前按配置脱敏密钥和对话内容
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, Request, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures::{StreamExt, stream};
+use serde_json::{Value, json};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex as TokioMutex;
+
+use crate::model::config::ArchiveConfig;
+
+use super::error::{ApiError, ApiErrorKind};
+
+/// 归档时允许缓冲的最大响应体（请求体大小已由外层 `body_limit_middleware` 限制）
+const MAX_ARCHIVED_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// 脱敏后的占位文本
+const REDACTED: &str = "[REDACTED]";
+
+/// 视为密钥的请求头（小写）
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "cookie",
+];
+
+/// 视为密钥的 JSON 字段（忽略大小写、下划线和连字符）
+const SECRET_KEYS: &[&str] = &[
+    "apikey",
+    "xapikey",
+    "authorization",
+    "accesstoken",
+    "refreshtoken",
+    "clientsecret",
+    "password",
+    "secret",
+];
+
+/// 视为对话内容的 JSON 字段
+const CONTENT_KEYS: &[&str] = &["text", "thinking", "content", "system", "input"];
+
+/// 按采样率决定是否采样（rate <= 0 从不采样，rate >= 1 总是采样）
+pub fn sampled(rate: f64) -> bool {
+    rate >= 1.0 || (rate > 0.0 && fastrand::f64() < rate)
+}
+
+/// 请求/响应归档器（中间件状态）
+pub struct Archiver {
+    config: ArchiveConfig,
+    path: PathBuf,
+    /// 串行化文件追加，避免并发写入交错
+    write_lock: TokioMutex<()>,
+}
+
+impl Archiver {
+    pub fn new(config: ArchiveConfig) -> Self {
+        Self {
+            path: PathBuf::from(&config.path),
+            config,
+            write_lock: TokioMutex::new(()),
+        }
+    }
+
+    /// 构建一条归档记录（按配置脱敏）
+    fn build_entry(
+        &self,
+        request_headers: &HeaderMap,
+        mut request: Value,
+        status: u16,
+        mut response: Value,
+        duration_ms: u128,
+    ) -> Value {
+        let mut headers: serde_json::Map<String, Value> = request_headers
+            .iter()
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|v| (name.as_str().to_string(), Value::String(v.to_string())))
+            })
+            .collect();
+
+        if self.config.redact_secrets {
+            for (name, value) in headers.iter_mut() {
+                if SECRET_HEADERS.contains(&name.as_str()) {
+                    *value = Value::String(REDACTED.to_string());
+                }
+            }
+            redact_keys(&mut request, is_secret_key);
+            redact_keys(&mut response, is_secret_key);
+        }
+        if self.config.redact_content {
+            redact_keys(&mut request, |key| CONTENT_KEYS.contains(&key));
+            redact_keys(&mut response, |key| CONTENT_KEYS.contains(&key));
+        }
+
+        json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "durationMs": duration_ms,
+            "status": status,
+            "requestHeaders": headers,
+            "request": request,
+            "response": response,
+        })
+    }
+
+    /// 追加写入一条记录
+    async fn append(&self, entry: &Value) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let _guard = self.write_lock.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        // tokio 文件写入在后台线程完成，flush 后才保证已落盘
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// 字段名是否为密钥类字段
+fn is_secret_key(key: &str) -> bool {
+    let normalized: String = key
+        .chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect();
+    SECRET_KEYS.contains(&normalized.as_str())
+}
+
+/// 递归替换匹配字段的值为占位文本
+fn redact_keys(value: &mut Value, matches: impl Fn(&str) -> bool + Copy) {
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                if matches(key) {
+                    *item = Value::String(REDACTED.to_string());
+                } else {
+                    redact_keys(item, matches);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact_keys(item, matches);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 将请求/响应体解析为 JSON，非 JSON 时按字符串保存
+fn body_value(bytes: &[u8]) -> Value {
+    serde_json::from_slice(bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+/// 缓冲响应体用于归档
+///
+/// 不超过 `limit` 时返回完整响应体；超出上限或读取出错时不再缓冲，
+/// 返回由已读取部分和剩余数据流拼接的原始响应体（读取错误原样传给客户端）
+async fn buffer_body(body: Body, limit: usize) -> Result<Bytes, Body> {
+    let mut data = body.into_data_stream();
+    let mut chunks: Vec<Bytes> = Vec::new();
+    let mut size = 0;
+    while let Some(chunk) = data.next().await {
+        match chunk {
+            Ok(chunk) => {
+                size += chunk.len();
+                chunks.push(chunk);
+                if size > limit {
+                    tracing::warn!("响应体超过 {} 字节，跳过归档", limit);
+                    let head = stream::iter(chunks.into_iter().map(Ok));
+                    return Err(Body::from_stream(head.chain(data)));
+                }
+            }
+            Err(e) => {
+                tracing::warn!("读取响应体失败，跳过归档: {}", e);
+                let head = stream::iter(chunks.into_iter().map(Ok));
+                return Err(Body::from_stream(
+                    head.chain(stream::once(async { Err(e) })),
+                ));
+            }
+        }
+    }
+    Ok(chunks.concat().into())
+}
+
+/// 归档中间件
+///
+/// 未被采样或为流式请求时原样放行；非流式请求在响应完成后追加一条记录
+pub async fn archive_middleware(
+    State(archiver): State<Arc<Archiver>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if !sampled(archiver.config.sample_rate) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("读取请求体失败: {}", e);
+            return ApiError::new(ApiErrorKind::RequestTooLarge, "Request body too large")
+                .into_response();
+        }
+    };
+
+    let request_value = body_value(&bytes);
+    let is_stream = request_value
+        .get("stream")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let request_headers = parts.headers.clone();
+    let started = Instant::now();
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    if is_stream {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match buffer_body(body, MAX_ARCHIVED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(body) => return Response::from_parts(parts, body),
+    };
+
+    let entry = archiver.build_entry(
+        &request_headers,
+        request_value,
+        parts.status.as_u16(),
+        body_value(&bytes),
+        started.elapsed().as_millis(),
+    );
+    if let Err(e) = archiver.append(&entry).await {
+        tracing::warn!("写入归档文件失败: {}", e);
+    }
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, middleware, routing::post};
+
+    #[test]
+    fn test_sampled_bounds() {
+        assert!(sampled(1.0));
+        assert!(!sampled(0.0));
+        assert!(!sampled(-1.0));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_passed_through_without_archiving() {
+        let chunks = ["abc", "def", "ghi"].map(|chunk| Ok::<_, axum::Error>(Bytes::from(chunk)));
+        let body = Body::from_stream(stream::iter(chunks));
+
+        let body = buffer_body(body, 4).await.unwrap_err();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        assert_eq!(&bytes[..], b"abcdefghi");
+
+        let bytes = buffer_body(Body::from("small"), 8).await.unwrap();
+        assert_eq!(&bytes[..], b"small");
+    }
+
+    #[tokio::test]
+    async fn test_sampled_request_archived_with_secrets_scrubbed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.jsonl");
+        let archiver = Arc::new(Archiver::new(ArchiveConfig {
+            path: path.to_string_lossy().into_owned(),
+            sample_rate: 1.0,
+            redact_secrets: true,
+            redact_content: false,
+        }));
+
+        let app = Router::new()
+            .route(
+                "/v1/messages",
+                post(|| async {
+                    Json(json!({
+                        "type": "message",
+                        "content": [{"type": "text", "text": "hello back"}],
+                        "usage": {"input_tokens": 3, "output_tokens": 2}
+                    }))
+                }),
+            )
+            .layer(middleware::from_fn_with_state(archiver, archive_middleware));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/v1/messages", addr);
+        let response = client
+            .post(&url)
+            .header("x-api-key", "sk-client-secret")
+            .json(&json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 16,
+                "messages": [{"role": "user", "content": "hello"}],
+                "metadata": {"user_id": "u1", "api_key": "sk-body-secret"}
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["content"][0]["text"], "hello back");
+
+        // 流式请求不归档
+        client
+            .post(&url)
+            .json(&json!({"model": "claude-sonnet-4-5", "stream": true}))
+            .send()
+            .await
+            .unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 1);
+        assert!(!content.contains("sk-client-secret"));
+        assert!(!content.contains("sk-body-secret"));
+
+        let entry: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(entry["status"], 200);
+        assert_eq!(entry["requestHeaders"]["x-api-key"], REDACTED);
+        assert_eq!(entry["request"]["metadata"]["api_key"], REDACTED);
+        assert_eq!(entry["request"]["metadata"]["user_id"], "u1");
+        assert_eq!(entry["request"]["max_tokens"], 16);
+        assert_eq!(entry["response"]["content"][0]["text"], "hello back");
+    }
+}
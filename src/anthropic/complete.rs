@@ -0,0 +1,599 @@
+//! 旧版 Text Completions 端点（`POST /v1/complete`）
+//!
+//! 将 `\n\nHuman:` / `\n\nAssistant:` 格式的 prompt 转换为 Messages 请求，复用 `/v1/messages`
+//! 的处理链路调用 Kiro，再把响应（含流式 SSE）转换回 `{"completion", "stop_reason"}` 结构
+
+use std::collections::HashMap;
+
+use axum::{
+    Json as JsonExtractor,
+    body::Body,
+    extract::State,
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures::{StreamExt, stream};
+use serde_json::{Value, json};
+
+use crate::common::sse::{find_event_end, parse_event};
+use crate::kiro::error_code::KiroErrorCode;
+
+use super::error::ApiError;
+use super::handlers::post_messages;
+use super::middleware::AppState;
+use super::stream::SseEvent;
+use super::types::{CompleteRequest, CompletionResponse, Message, MessagesRequest, SystemMessage};
+
+/// 用户轮次标记
+const HUMAN_PROMPT: &str = "\n\nHuman:";
+/// 助手轮次标记
+const AI_PROMPT: &str = "\n\nAssistant:";
+
+/// 旧版模型的默认映射（客户端模型名 → 实际请求的模型名）
+const LEGACY_MODELS: &[(&str, &str)] = &[
+    ("claude-2", "claude-sonnet-4.5"),
+    ("claude-2.0", "claude-sonnet-4.5"),
+    ("claude-2.1", "claude-sonnet-4.5"),
+    ("claude-instant-1", "claude-haiku-4.5"),
+    ("claude-instant-1.2", "claude-haiku-4.5"),
+];
+
+/// POST /v1/complete
+///
+/// 将 prompt 转换为 Messages 请求后复用 `/v1/messages` 的完整处理链路
+/// （模型映射、限流、重试、凭据故障转移），错误响应保持 Anthropic 格式原样返回
+pub async fn post_complete(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    JsonExtractor(payload): JsonExtractor<CompleteRequest>,
+) -> Response {
+    tracing::info!(
+        model = %payload.model,
+        stream = %payload.stream,
+        "Received POST /v1/complete request"
+    );
+
+    let model = payload.model.clone();
+    let mut request = match to_messages_request(payload) {
+        Ok(request) => request,
+        Err(message) => {
+            tracing::warn!("prompt 转换失败: {}", message);
+            return ApiError::invalid_request(message).into_response();
+        }
+    };
+    // 旧版模型名改写为默认映射的模型，响应中仍返回客户端请求的模型名
+    if let Some(provider) = &state.kiro_provider
+        && let Some(target) = legacy_model(
+            &request.model,
+            &provider.token_manager().config().model_aliases,
+        )
+    {
+        tracing::debug!(model = %request.model, target = %target, "映射旧版模型");
+        request.model = target.to_string();
+    }
+    let is_stream = request.stream;
+
+    let response = post_messages(State(state), None, headers, JsonExtractor(request)).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    if is_stream {
+        let mut translator = CompletionTranslator::new(model);
+        let body = body.into_data_stream().flat_map(move |chunk| {
+            let items: Vec<_> = match chunk {
+                Ok(chunk) => translator.feed(&chunk).into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(items)
+        });
+        return Response::from_parts(parts, Body::from_stream(body));
+    }
+
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("读取响应体失败: {}", e);
+            return ApiError::from_code(
+                KiroErrorCode::InternalError,
+                format!("读取响应体失败: {}", e),
+            )
+            .into_response();
+        }
+    };
+    let message = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::error!("解析 Anthropic 响应失败: {}", e);
+            return ApiError::from_code(
+                KiroErrorCode::InternalError,
+                format!("解析响应失败: {}", e),
+            )
+            .into_response();
+        }
+    };
+    let completion = to_completion(&message, model);
+    let body = serde_json::to_vec(&completion).unwrap_or_default();
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// 将 Text Completions 请求转换为 Messages 请求
+fn to_messages_request(request: CompleteRequest) -> Result<MessagesRequest, String> {
+    let (system, messages) = prompt_to_messages(&request.prompt)?;
+    Ok(MessagesRequest {
+        model: request.model,
+        max_tokens: request.max_tokens_to_sample,
+        messages,
+        stream: request.stream,
+        system: (!system.is_empty()).then_some(system),
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: request.metadata,
+    })
+}
+
+/// 旧版模型默认映射的目标模型（`modelAliases` 中配置了同名别名时以配置为准，返回 `None`）
+fn legacy_model(model: &str, aliases: &HashMap<String, String>) -> Option<&'static str> {
+    if aliases.contains_key(model) {
+        return None;
+    }
+    LEGACY_MODELS
+        .iter()
+        .find(|(legacy, _)| *legacy == model)
+        .map(|(_, target)| *target)
+}
+
+/// 将 prompt 拆分为系统提示和对话消息
+///
+/// 第一个 `\n\nHuman:` 之前的文本作为系统提示；空轮次忽略，相邻的同角色轮次合并；
+/// 末尾的 `\n\nAssistant:` 为空时是生成起点，非空时作为预填充的 assistant 消息
+fn prompt_to_messages(prompt: &str) -> Result<(Vec<SystemMessage>, Vec<Message>), String> {
+    let Some((start, mut role, marker_len)) = next_turn(prompt) else {
+        return Err(format!("prompt 中缺少 {:?} 轮次", HUMAN_PROMPT));
+    };
+    if role != "user" {
+        return Err(format!("prompt 必须以 {:?} 轮次开始", HUMAN_PROMPT));
+    }
+
+    let system_text = prompt[..start].trim();
+    let system = if system_text.is_empty() {
+        Vec::new()
+    } else {
+        vec![SystemMessage {
+            text: system_text.to_string(),
+        }]
+    };
+
+    let mut turns: Vec<(&'static str, &str)> = Vec::new();
+    let mut rest = &prompt[start + marker_len..];
+    loop {
+        match next_turn(rest) {
+            Some((end, next_role, next_len)) => {
+                turns.push((role, &rest[..end]));
+                role = next_role;
+                rest = &rest[end + next_len..];
+            }
+            None => {
+                turns.push((role, rest));
+                break;
+            }
+        }
+    }
+
+    let mut messages: Vec<Message> = Vec::new();
+    for (role, text) in turns {
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match messages.last_mut() {
+            Some(last) if last.role == role => {
+                if let Value::String(content) = &mut last.content {
+                    content.push_str("\n\n");
+                    content.push_str(text);
+                }
+            }
+            _ => messages.push(Message {
+                role: role.to_string(),
+                content: Value::String(text.to_string()),
+            }),
+        }
+    }
+
+    if messages
+        .first()
+        .is_none_or(|message| message.role != "user")
+    {
+        return Err(format!("prompt 中缺少非空的 {:?} 轮次", HUMAN_PROMPT));
+    }
+    Ok((system, messages))
+}
+
+/// 查找下一个轮次标记，返回（位置, 角色, 标记长度）
+fn next_turn(text: &str) -> Option<(usize, &'static str, usize)> {
+    let human = text
+        .find(HUMAN_PROMPT)
+        .map(|pos| (pos, "user", HUMAN_PROMPT.len()));
+    let assistant = text
+        .find(AI_PROMPT)
+        .map(|pos| (pos, "assistant", AI_PROMPT.len()));
+    human
+        .into_iter()
+        .chain(assistant)
+        .min_by_key(|(pos, ..)| *pos)
+}
+
+/// 将 Anthropic stop_reason 映射为旧版 stop_reason
+fn map_stop_reason(stop_reason: Option<&str>) -> &'static str {
+    match stop_reason {
+        Some("max_tokens") | Some("model_context_window_exceeded") => "max_tokens",
+        _ => "stop_sequence",
+    }
+}
+
+/// 由 Anthropic 消息 ID 生成 completion ID（缺失时随机生成）
+fn completion_id(message_id: Option<&str>) -> String {
+    let suffix = message_id
+        .map(|id| id.trim_start_matches("msg_").to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+    format!("compl_{}", suffix)
+}
+
+/// 将非流式 Anthropic 消息转换为 Text Completions 响应（只保留文本内容块）
+fn to_completion(message: &Value, model: String) -> CompletionResponse {
+    let completion = message["content"]
+        .as_array()
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter(|block| block["type"] == "text")
+                .filter_map(|block| block["text"].as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    CompletionResponse {
+        response_type: "completion",
+        id: completion_id(message["id"].as_str()),
+        completion,
+        stop_reason: Some(map_stop_reason(message["stop_reason"].as_str())),
+        model,
+    }
+}
+
+/// 流式响应转换器
+///
+/// 文本增量转换为 `completion` 事件，`message_delta` 转换为携带 stop_reason 的空 `completion` 事件；
+/// `ping` 和 `error` 事件原样转发，`error` 之后结束流
+struct CompletionTranslator {
+    /// 响应 ID（收到 message_start 后由消息 ID 生成）
+    id: String,
+    /// 客户端请求的模型名称
+    model: String,
+    /// 未构成完整事件的剩余字节
+    buffer: Vec<u8>,
+    /// 是否已结束（收到 message_stop 或 error）
+    finished: bool,
+}
+
+impl CompletionTranslator {
+    fn new(model: String) -> Self {
+        Self {
+            id: completion_id(None),
+            model,
+            buffer: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// 输入一段 Anthropic SSE 字节，返回需要发送给客户端的 SSE 字节
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Bytes> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut output = Vec::new();
+        while let Some(end) = find_event_end(&self.buffer) {
+            let raw: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            if self.finished {
+                continue;
+            }
+            let Some((event, data)) = parse_event(&String::from_utf8_lossy(&raw)) else {
+                continue;
+            };
+            output.extend(self.translate(&event, data));
+        }
+        output
+    }
+
+    /// 转换单个 Anthropic 事件
+    fn translate(&mut self, event: &str, data: Value) -> Option<Bytes> {
+        match event {
+            "message_start" => {
+                self.id = completion_id(data["message"]["id"].as_str());
+                None
+            }
+            "content_block_delta" if data["delta"]["type"] == "text_delta" => {
+                let text = data["delta"]["text"].as_str().unwrap_or_default();
+                Some(self.completion(text.to_string(), None))
+            }
+            "message_delta" => {
+                let stop_reason = map_stop_reason(data["delta"]["stop_reason"].as_str());
+                Some(self.completion(String::new(), Some(stop_reason)))
+            }
+            "message_stop" => {
+                self.finished = true;
+                None
+            }
+            "error" => {
+                self.finished = true;
+                Some(Bytes::from(SseEvent::new("error", data).to_sse_string()))
+            }
+            "ping" => Some(Bytes::from(
+                SseEvent::new("ping", json!({ "type": "ping" })).to_sse_string(),
+            )),
+            // thinking、工具调用等内容不属于 Text Completions 响应结构，忽略
+            _ => None,
+        }
+    }
+
+    /// 构建一个 `completion` 事件
+    fn completion(&self, completion: String, stop_reason: Option<&'static str>) -> Bytes {
+        let data = CompletionResponse {
+            response_type: "completion",
+            id: self.id.clone(),
+            completion,
+            stop_reason,
+            model: self.model.clone(),
+        };
+        let data = serde_json::to_value(&data).unwrap_or_default();
+        Bytes::from(SseEvent::new("completion", data).to_sse_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anthropic_sse(events: &[(&str, Value)]) -> String {
+        events
+            .iter()
+            .map(|(event, data)| format!("event: {}\ndata: {}\n\n", event, data))
+            .collect()
+    }
+
+    fn contents(messages: &[Message]) -> Vec<(&str, &str)> {
+        messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_prompt_to_messages_splits_turns_and_system() {
+        let prompt =
+            "You are terse.\n\nHuman: Hi\n\nAssistant: Hello!\n\nHuman: Sum 1+1\n\nAssistant:";
+        let (system, messages) = prompt_to_messages(prompt).unwrap();
+
+        assert_eq!(system.len(), 1);
+        assert_eq!(system[0].text, "You are terse.");
+        assert_eq!(
+            contents(&messages),
+            vec![("user", "Hi"), ("assistant", "Hello!"), ("user", "Sum 1+1")]
+        );
+    }
+
+    #[test]
+    fn test_prompt_to_messages_keeps_prefill_and_merges_turns() {
+        let prompt = "\n\nHuman: a\n\nHuman: b\n\nAssistant:\n\nAssistant: {\"x\":";
+        let (system, messages) = prompt_to_messages(prompt).unwrap();
+
+        assert!(system.is_empty());
+        assert_eq!(
+            contents(&messages),
+            vec![("user", "a\n\nb"), ("assistant", "{\"x\":")]
+        );
+    }
+
+    #[test]
+    fn test_prompt_to_messages_rejects_invalid_prompts() {
+        assert!(prompt_to_messages("Hello there").is_err());
+        assert!(prompt_to_messages("\n\nAssistant: hi\n\nHuman: yo").is_err());
+        assert!(prompt_to_messages("\n\nHuman:   \n\nAssistant:").is_err());
+    }
+
+    #[test]
+    fn test_to_messages_request_maps_fields() {
+        let request: CompleteRequest = serde_json::from_value(json!({
+            "model": "claude-2.1",
+            "prompt": "\n\nHuman: Hi\n\nAssistant:",
+            "max_tokens_to_sample": 256,
+            "stream": true,
+            "temperature": 0.5
+        }))
+        .unwrap();
+        let request = to_messages_request(request).unwrap();
+
+        assert_eq!(request.model, "claude-2.1");
+        assert_eq!(request.max_tokens, Some(256));
+        assert!(request.stream);
+        assert!(request.system.is_none());
+        assert_eq!(request.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_legacy_model_defaults_and_alias_override() {
+        let aliases = HashMap::new();
+        assert_eq!(
+            legacy_model("claude-2.1", &aliases),
+            Some("claude-sonnet-4.5")
+        );
+        assert_eq!(
+            legacy_model("claude-instant-1", &aliases),
+            Some("claude-haiku-4.5")
+        );
+        assert_eq!(legacy_model("claude-sonnet-4-5", &aliases), None);
+
+        let aliases = HashMap::from([("claude-2".to_string(), "claude-opus-4.5".to_string())]);
+        assert_eq!(legacy_model("claude-2", &aliases), None);
+    }
+
+    #[tokio::test]
+    async fn test_post_complete_through_router() {
+        use crate::anthropic::create_router;
+        use crate::kiro::model::credentials::KiroCredentials;
+        use crate::kiro::provider::KiroProvider;
+        use crate::kiro::token_manager::MultiTokenManager;
+        use crate::model::config::Config;
+
+        // mock 上游：始终返回 503，由降级响应给出固定文本
+        let upstream = axum::Router::new().route(
+            "/generateAssistantResponse",
+            axum::routing::post(|| async {
+                (axum::http::StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_url = format!(
+            "http://{}/generateAssistantResponse",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move {
+            axum::serve(listener, upstream).await.unwrap();
+        });
+
+        let config = Config {
+            retry_base_delay_ms: 1,
+            enable_outage_fallback: true,
+            outage_fallback_message: Some("degraded".to_string()),
+            ..Default::default()
+        };
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(config, vec![credentials], None, None, false).unwrap();
+        let provider =
+            KiroProvider::new(std::sync::Arc::new(manager)).with_upstream_url(upstream_url);
+        let app = create_router(AppState::new("test-key").with_kiro_provider(provider));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let send = |body: Value| {
+            client
+                .post(format!("http://{}/v1/complete", addr))
+                .header("x-api-key", "test-key")
+                .json(&body)
+                .send()
+        };
+
+        // 旧版模型按默认映射调用上游，响应保留客户端请求的模型名
+        let response = send(json!({
+            "model": "claude-2.1",
+            "prompt": "\n\nHuman: Hi\n\nAssistant:",
+            "max_tokens_to_sample": 16
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["type"], "completion");
+        assert_eq!(body["completion"], "degraded");
+        assert_eq!(body["model"], "claude-2.1");
+        assert!(body["id"].as_str().unwrap().starts_with("compl_"));
+
+        // 无效 prompt 返回 Anthropic 格式的 400
+        let response = send(json!({"model": "claude-2.1", "prompt": "Hello"}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+    }
+
+    #[test]
+    fn test_to_completion_shapes_response() {
+        let message = json!({
+            "id": "msg_abc",
+            "type": "message",
+            "content": [
+                {"type": "thinking", "thinking": "..."},
+                {"type": "text", "text": "Hello"},
+                {"type": "text", "text": " world"}
+            ],
+            "stop_reason": "end_turn"
+        });
+        let completion = to_completion(&message, "claude-2.1".to_string());
+
+        assert_eq!(
+            serde_json::to_value(&completion).unwrap(),
+            json!({
+                "type": "completion",
+                "id": "compl_abc",
+                "completion": "Hello world",
+                "stop_reason": "stop_sequence",
+                "model": "claude-2.1"
+            })
+        );
+
+        let truncated = json!({"content": [], "stop_reason": "max_tokens"});
+        let completion = to_completion(&truncated, "claude-2.1".to_string());
+        assert_eq!(completion.stop_reason, Some("max_tokens"));
+    }
+
+    #[test]
+    fn test_translator_streams_completion_deltas() {
+        let sse = anthropic_sse(&[
+            ("message_start", json!({"type": "message_start", "message": {"id": "msg_9"}})),
+            ("content_block_delta", json!({"type": "content_block_delta", "index": 0,
+                "delta": {"type": "text_delta", "text": "你好"}})),
+            ("ping", json!({"type": "ping"})),
+            ("message_delta", json!({"type": "message_delta",
+                "delta": {"stop_reason": "end_turn"}})),
+            ("message_stop", json!({"type": "message_stop"})),
+        ]);
+
+        // 逐块输入，验证跨块拆分的事件和多字节字符
+        let mut translator = CompletionTranslator::new("claude-2.1".to_string());
+        let events: Vec<(String, Value)> = sse
+            .as_bytes()
+            .chunks(5)
+            .flat_map(|chunk| translator.feed(chunk))
+            .map(|bytes| parse_event(std::str::from_utf8(&bytes).unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].0, "completion");
+        assert_eq!(events[0].1["id"], "compl_9");
+        assert_eq!(events[0].1["completion"], "你好");
+        assert!(events[0].1["stop_reason"].is_null());
+        assert_eq!(events[1].0, "ping");
+        assert_eq!(events[2].1["completion"], "");
+        assert_eq!(events[2].1["stop_reason"], "stop_sequence");
+        assert!(translator.finished);
+    }
+
+    #[test]
+    fn test_translator_forwards_error_and_stops() {
+        let sse = anthropic_sse(&[
+            ("error", json!({"type": "error",
+                "error": {"type": "api_error", "message": "boom"}})),
+            ("message_stop", json!({"type": "message_stop"})),
+        ]);
+        let mut translator = CompletionTranslator::new("claude-2.1".to_string());
+        let output = translator.feed(sse.as_bytes());
+
+        assert_eq!(output.len(), 1);
+        let (event, data) = parse_event(std::str::from_utf8(&output[0]).unwrap()).unwrap();
+        assert_eq!(event, "error");
+        assert_eq!(data["error"]["message"], "boom");
+    }
+}
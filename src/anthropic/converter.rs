@@ -46,6 +46,8 @@ pub struct ConversionResult {
 pub enum ConversionError {
     UnsupportedModel(String),
     EmptyMessages,
+    /// `tool_choice` 指定的工具不在 `tools` 中
+    UnknownToolChoice(String),
 }
 
 impl std::fmt::Display for ConversionError {
@@ -53,6 +55,9 @@ impl std::fmt::Display for ConversionError {
         match self {
             ConversionError::UnsupportedModel(model) => write!(f, "模型不支持: {}", model),
             ConversionError::EmptyMessages => write!(f, "消息列表为空"),
+            ConversionError::UnknownToolChoice(name) => {
+                write!(f, "tool_choice 指定的工具不存在: {}", name)
+            }
         }
     }
 }
@@ -117,6 +122,12 @@ fn create_placeholder_tool(name: &str) -> Tool {
     }
 }
 
+/// 末尾为 assistant 预填充时发送的当前消息
+///
+/// Kiro 不支持预填充：预填充作为历史中的 assistant 消息，当前消息要求模型接着它继续输出
+const PREFILL_CONTINUE_PROMPT: &str =
+    "Continue your previous response exactly where it stopped, without repeating it.";
+
 /// 将 Anthropic 请求转换为 Kiro 请求
 pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, ConversionError> {
     // 1. 映射模型
@@ -141,12 +152,16 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
     // 4. 确定触发类型
     let chat_trigger_type = determine_chat_trigger_type(req);
 
-    // 5. 处理最后一条消息作为 current_message
+    // 5. 处理最后一条消息作为 current_message（assistant 预填充已在历史中，不作为用户输入发送）
     let last_message = req.messages.last().unwrap();
-    let (text_content, images, tool_results) = process_message_content(&last_message.content)?;
+    let (text_content, images, tool_results) = if last_message.role == "assistant" {
+        (PREFILL_CONTINUE_PROMPT.to_string(), Vec::new(), Vec::new())
+    } else {
+        process_message_content(&last_message.content)?
+    };
 
-    // 6. 转换工具定义
-    let mut tools = convert_tools(&req.tools);
+    // 6. 转换工具定义，并按 tool_choice 筛选
+    let mut tools = apply_tool_choice(convert_tools(&req.tools), req.tool_choice.as_ref())?;
 
     // 7. 构建历史消息（需要先构建，以便收集历史中使用的工具）
     let history = build_history(req, &model_id)?;
@@ -232,10 +247,12 @@ fn process_message_content(
                             }
                         }
                         "image" => {
-                            if let Some(source) = block.source {
-                                if let Some(format) = get_image_format(&source.media_type) {
-                                    images.push(KiroImage::from_base64(format, source.data));
-                                }
+                            // `url` 图片源已由 handler 改写为 base64，媒体类型也已校验
+                            let source = block.source.filter(|s| s.source_type == "base64");
+                            if let Some(source) = source
+                                && let Some(format) = get_image_format(&source.media_type)
+                            {
+                                images.push(KiroImage::from_base64(format, source.data));
                             }
                         }
                         "tool_result" => {
@@ -403,6 +420,36 @@ fn convert_tools(tools: &Option<Vec<super::types::Tool>>) -> Vec<Tool> {
         .collect()
 }
 
+/// 按 `tool_choice` 筛选转发给上游的工具定义
+///
+/// Kiro API 没有 tool_choice 参数，只能通过可用工具集合表达：
+/// `none` 不转发请求中的工具，`tool` 仅转发指定工具；`auto`、`any` 或未指定时全部转发
+/// （`any` 与 `tool` 无法强制上游必须调用工具）
+fn apply_tool_choice(
+    tools: Vec<Tool>,
+    tool_choice: Option<&serde_json::Value>,
+) -> Result<Vec<Tool>, ConversionError> {
+    let Some(choice) = tool_choice else {
+        return Ok(tools);
+    };
+
+    match choice.get("type").and_then(|t| t.as_str()) {
+        Some("none") => Ok(Vec::new()),
+        Some("tool") => {
+            let name = choice.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+            let selected: Vec<Tool> = tools
+                .into_iter()
+                .filter(|t| t.tool_specification.name == name)
+                .collect();
+            if selected.is_empty() {
+                return Err(ConversionError::UnknownToolChoice(name.to_string()));
+            }
+            Ok(selected)
+        }
+        _ => Ok(tools),
+    }
+}
+
 /// 检查是否为不支持的工具
 fn is_unsupported_tool(name: &str) -> bool {
     // matches!(name.to_lowercase().as_str(), "web_search" | "websearch")
@@ -677,7 +724,7 @@ mod tests {
         // 无工具时返回 MANUAL
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
-            max_tokens: 1024,
+            max_tokens: Some(1024),
             messages: vec![],
             stream: false,
             system: None,
@@ -745,7 +792,7 @@ mod tests {
         // 创建一个请求，历史中有工具使用，但 tools 列表为空
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
-            max_tokens: 1024,
+            max_tokens: Some(1024),
             messages: vec![
                 AnthropicMessage {
                     role: "user".to_string(),
@@ -790,6 +837,172 @@ mod tests {
         );
     }
 
+    /// 带工具定义、tool_choice、历史 tool_use 和当前 tool_result 的请求
+    fn tool_calling_request(tool_choice: Option<serde_json::Value>) -> MessagesRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "tools": [
+                {
+                    "name": "get_weather",
+                    "description": "Get the current weather",
+                    "input_schema": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"]
+                    }
+                },
+                {
+                    "name": "get_time",
+                    "description": "Get the current time",
+                    "input_schema": {"type": "object", "properties": {}}
+                }
+            ],
+            "tool_choice": tool_choice,
+            "messages": [
+                {"role": "user", "content": "What's the weather in Paris?"},
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "Let me check."},
+                    {
+                        "type": "tool_use",
+                        "id": "toolu_01",
+                        "name": "get_weather",
+                        "input": {"city": "Paris"}
+                    }
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_01", "content": "18°C, sunny"}
+                ]}
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn current_tool_names(result: &ConversionResult) -> Vec<String> {
+        result
+            .conversation_state
+            .current_message
+            .user_input_message
+            .user_input_message_context
+            .tools
+            .iter()
+            .map(|t| t.tool_specification.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_tool_calling_request_round_trip() {
+        let result = convert_request(&tool_calling_request(None)).unwrap();
+
+        // 工具定义原样转发
+        assert_eq!(current_tool_names(&result), vec!["get_weather", "get_time"]);
+
+        // 当前消息的 tool_result 转发给上游
+        let context = &result
+            .conversation_state
+            .current_message
+            .user_input_message
+            .user_input_message_context;
+        assert_eq!(context.tool_results.len(), 1);
+        assert_eq!(context.tool_results[0].tool_use_id, "toolu_01");
+        assert_eq!(context.tool_results[0].content[0]["text"], "18°C, sunny");
+
+        // 历史中的 tool_use 保留 id/name/input
+        let tool_use = result
+            .conversation_state
+            .history
+            .iter()
+            .find_map(|m| match m {
+                Message::Assistant(a) => a.assistant_response_message.tool_uses.clone(),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(tool_use[0].tool_use_id, "toolu_01");
+        assert_eq!(tool_use[0].name, "get_weather");
+        assert_eq!(tool_use[0].input, serde_json::json!({"city": "Paris"}));
+    }
+
+    #[test]
+    fn test_tool_choice_filters_forwarded_tools() {
+        let any = convert_request(&tool_calling_request(Some(serde_json::json!({"type": "any"}))));
+        assert_eq!(current_tool_names(&any.unwrap()), vec!["get_weather", "get_time"]);
+
+        let choice = serde_json::json!({"type": "tool", "name": "get_time"});
+        let result = convert_request(&tool_calling_request(Some(choice))).unwrap();
+        // 历史中使用的 get_weather 仍以占位符定义保留
+        assert_eq!(current_tool_names(&result), vec!["get_time", "get_weather"]);
+
+        let result =
+            convert_request(&tool_calling_request(Some(serde_json::json!({"type": "none"}))))
+                .unwrap();
+        assert_eq!(current_tool_names(&result), vec!["get_weather"]);
+        let context = &result
+            .conversation_state
+            .current_message
+            .user_input_message
+            .user_input_message_context;
+        assert_eq!(
+            context.tools[0].tool_specification.description,
+            "Tool used in conversation history"
+        );
+
+        let choice = serde_json::json!({"type": "tool", "name": "missing"});
+        assert!(matches!(
+            convert_request(&tool_calling_request(Some(choice))),
+            Err(ConversionError::UnknownToolChoice(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn test_base64_image_forwarded_to_current_message() {
+        let req: MessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": [
+                {"type": "image", "source": {
+                    "type": "base64",
+                    "media_type": "image/png",
+                    "data": "iVBORw0KGgo="
+                }},
+                {"type": "text", "text": "Describe this image"}
+            ]}]
+        }))
+        .unwrap();
+
+        let result = convert_request(&req).unwrap();
+        let message = &result.conversation_state.current_message.user_input_message;
+        assert_eq!(message.content, "Describe this image");
+        assert_eq!(message.images.len(), 1);
+        assert_eq!(message.images[0].format, "png");
+        assert_eq!(message.images[0].source.bytes, "iVBORw0KGgo=");
+    }
+
+    #[test]
+    fn test_trailing_assistant_prefill_kept_in_history() {
+        let req: MessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "messages": [
+                {"role": "user", "content": "Count to three"},
+                {"role": "assistant", "content": "One,"}
+            ]
+        }))
+        .unwrap();
+
+        let result = convert_request(&req).unwrap();
+        let state = &result.conversation_state;
+        assert_eq!(
+            state.current_message.user_input_message.content,
+            PREFILL_CONTINUE_PROMPT
+        );
+        match state.history.last() {
+            Some(Message::Assistant(assistant)) => {
+                assert_eq!(assistant.assistant_response_message.content, "One,");
+            }
+            other => panic!("unexpected last history message: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_extract_session_id_valid() {
         // 测试有效的 user_id 格式
@@ -824,7 +1037,7 @@ mod tests {
         // 测试带有 metadata 的请求，应该使用 session UUID 作为 conversationId
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
-            max_tokens: 1024,
+            max_tokens: Some(1024),
             messages: vec![AnthropicMessage {
                 role: "user".to_string(),
                 content: serde_json::json!("Hello"),
@@ -855,7 +1068,7 @@ mod tests {
         // 测试没有 metadata 的请求，应该生成新的 UUID
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
-            max_tokens: 1024,
+            max_tokens: Some(1024),
             messages: vec![AnthropicMessage {
                 role: "user".to_string(),
                 content: serde_json::json!("Hello"),
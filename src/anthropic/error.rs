@@ -0,0 +1,251 @@
+//! Anthropic 格式的错误响应
+//!
+//! 所有 Anthropic 端点的错误统一为 `{"type": "error", "error": {"type": ..., "message": ...}}`，
+//! HTTP 状态码由错误类型决定；携带结构化错误码时额外返回 `kiro_error_code` 字段
+
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+use crate::kiro::error_code::KiroErrorCode;
+
+use super::types::ErrorResponse;
+
+/// Anthropic 错误类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiErrorKind {
+    /// 请求格式或参数无效（400）
+    InvalidRequest,
+    /// API Key 无效（401）
+    Authentication,
+    /// 额度或预算用尽（402）
+    Billing,
+    /// 请求体过大（413）
+    RequestTooLarge,
+    /// 请求过于频繁（429）
+    RateLimit,
+    /// 服务内部错误（500）
+    Api,
+    /// 上游调用失败或响应异常（502）
+    Upstream,
+    /// 超过请求时限（504）
+    Timeout,
+    /// 暂无可用凭据或存储后端未就绪，稍后重试（503）
+    Overloaded,
+}
+
+impl ApiErrorKind {
+    /// 错误类型字符串（响应中 `error.type` 的值）
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiErrorKind::InvalidRequest => "invalid_request_error",
+            ApiErrorKind::Authentication => "authentication_error",
+            ApiErrorKind::Billing => "billing_error",
+            ApiErrorKind::RequestTooLarge => "request_too_large",
+            ApiErrorKind::RateLimit => "rate_limit_error",
+            ApiErrorKind::Api | ApiErrorKind::Upstream => "api_error",
+            ApiErrorKind::Timeout => "timeout_error",
+            ApiErrorKind::Overloaded => "overloaded_error",
+        }
+    }
+
+    /// 对应的 HTTP 状态码
+    pub fn status(self) -> StatusCode {
+        match self {
+            ApiErrorKind::InvalidRequest => StatusCode::BAD_REQUEST,
+            ApiErrorKind::Authentication => StatusCode::UNAUTHORIZED,
+            ApiErrorKind::Billing => StatusCode::PAYMENT_REQUIRED,
+            ApiErrorKind::RequestTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiErrorKind::RateLimit => StatusCode::TOO_MANY_REQUESTS,
+            ApiErrorKind::Api => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiErrorKind::Upstream => StatusCode::BAD_GATEWAY,
+            ApiErrorKind::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ApiErrorKind::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// 结构化错误码对应的错误类型
+    ///
+    /// 上游认证失败属于服务端凭据问题，与上游不可用、响应过大一样按 502 `api_error` 返回，
+    /// 避免客户端误以为自身 API Key 无效；
+    /// 上游拒绝请求（400 等 4xx）按 `invalid_request_error` 返回；
+    /// 无可用凭据或存储后端未就绪按 503 `overloaded_error` 返回，客户端可稍后重试
+    pub fn of(code: KiroErrorCode) -> Self {
+        match code {
+            KiroErrorCode::InvalidRequest
+            | KiroErrorCode::ModelUnsupported
+            | KiroErrorCode::UpstreamRejected => ApiErrorKind::InvalidRequest,
+            KiroErrorCode::RateLimited | KiroErrorCode::RateLimitedUpstream => {
+                ApiErrorKind::RateLimit
+            }
+            KiroErrorCode::CredentialBudgetExhausted | KiroErrorCode::UpstreamQuotaExhausted => {
+                ApiErrorKind::Billing
+            }
+            KiroErrorCode::NoCredentialsAvailable | KiroErrorCode::StorageUnavailable => {
+                ApiErrorKind::Overloaded
+            }
+            KiroErrorCode::DeadlineExceeded | KiroErrorCode::UpstreamTimeout => {
+                ApiErrorKind::Timeout
+            }
+            KiroErrorCode::UpstreamAuthFailed
+            | KiroErrorCode::UpstreamUnavailable
+            | KiroErrorCode::UpstreamResponseTooLarge => ApiErrorKind::Upstream,
+            KiroErrorCode::ProviderNotConfigured | KiroErrorCode::InternalError => {
+                ApiErrorKind::Api
+            }
+        }
+    }
+}
+
+/// Anthropic 格式的错误
+#[derive(Debug)]
+pub struct ApiError {
+    kind: ApiErrorKind,
+    message: String,
+    code: Option<KiroErrorCode>,
+    retry_after_secs: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(kind: ApiErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            code: None,
+            retry_after_secs: None,
+        }
+    }
+
+    /// 按结构化错误码确定错误类型，并附加该错误码
+    pub fn from_code(code: KiroErrorCode, message: impl Into<String>) -> Self {
+        Self::new(ApiErrorKind::of(code), message).with_code(code)
+    }
+
+    /// 请求格式或参数无效
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::from_code(KiroErrorCode::InvalidRequest, message)
+    }
+
+    /// 附加结构化错误码
+    pub fn with_code(mut self, code: KiroErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// 附加 `Retry-After` 响应头（秒）
+    pub fn with_retry_after(mut self, secs: u64) -> Self {
+        self.retry_after_secs = Some(secs);
+        self
+    }
+
+    /// 响应体
+    pub fn body(&self) -> ErrorResponse {
+        let body = ErrorResponse::new(self.kind.as_str(), self.message.clone());
+        match self.code {
+            Some(code) => body.with_code(code),
+            None => body,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.kind.status();
+        let body = Json(self.body());
+        match self.retry_after_secs {
+            Some(secs) => (status, [(header::RETRY_AFTER, secs.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    async fn parts_of(error: ApiError) -> (u16, Value) {
+        let response = error.into_response();
+        let status = response.status().as_u16();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_each_kind_has_anthropic_shape_and_status() {
+        let cases = [
+            (ApiErrorKind::InvalidRequest, 400, "invalid_request_error"),
+            (ApiErrorKind::Authentication, 401, "authentication_error"),
+            (ApiErrorKind::Billing, 402, "billing_error"),
+            (ApiErrorKind::RequestTooLarge, 413, "request_too_large"),
+            (ApiErrorKind::RateLimit, 429, "rate_limit_error"),
+            (ApiErrorKind::Api, 500, "api_error"),
+            (ApiErrorKind::Upstream, 502, "api_error"),
+            (ApiErrorKind::Timeout, 504, "timeout_error"),
+            (ApiErrorKind::Overloaded, 503, "overloaded_error"),
+        ];
+        for (kind, status, error_type) in cases {
+            let (actual_status, body) = parts_of(ApiError::new(kind, "boom")).await;
+            assert_eq!(actual_status, status, "{:?}", kind);
+            assert_eq!(
+                body,
+                json!({"type": "error", "error": {"type": error_type, "message": "boom"}})
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_code_and_retry_after_are_included() {
+        let response = ApiError::from_code(KiroErrorCode::RateLimited, "slow down")
+            .with_retry_after(3)
+            .into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+
+        let (_, body) = parts_of(ApiError::invalid_request("bad")).await;
+        assert_eq!(body["type"], "error");
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["kiro_error_code"], "invalid_request");
+    }
+
+    #[test]
+    fn test_upstream_codes_map_to_anthropic_kinds() {
+        let cases = [
+            (
+                KiroErrorCode::UpstreamRejected,
+                ApiErrorKind::InvalidRequest,
+            ),
+            (
+                KiroErrorCode::ModelUnsupported,
+                ApiErrorKind::InvalidRequest,
+            ),
+            (KiroErrorCode::RateLimitedUpstream, ApiErrorKind::RateLimit),
+            (KiroErrorCode::UpstreamQuotaExhausted, ApiErrorKind::Billing),
+            (
+                KiroErrorCode::CredentialBudgetExhausted,
+                ApiErrorKind::Billing,
+            ),
+            (
+                KiroErrorCode::NoCredentialsAvailable,
+                ApiErrorKind::Overloaded,
+            ),
+            (KiroErrorCode::StorageUnavailable, ApiErrorKind::Overloaded),
+            (KiroErrorCode::DeadlineExceeded, ApiErrorKind::Timeout),
+            (KiroErrorCode::UpstreamTimeout, ApiErrorKind::Timeout),
+            (KiroErrorCode::UpstreamAuthFailed, ApiErrorKind::Upstream),
+            (KiroErrorCode::UpstreamUnavailable, ApiErrorKind::Upstream),
+            (
+                KiroErrorCode::UpstreamResponseTooLarge,
+                ApiErrorKind::Upstream,
+            ),
+            (KiroErrorCode::InternalError, ApiErrorKind::Api),
+        ];
+        for (code, kind) in cases {
+            assert_eq!(ApiErrorKind::of(code), kind, "{}", code);
+        }
+    }
+}
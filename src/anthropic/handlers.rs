@@ -1,40 +1,67 @@
 //! Anthropic API Handler 函数
 
+use std::collections::HashMap;
 use std::convert::Infallible;
 
+use crate::common::rate_limit;
+use crate::kiro::credential_ref::credential_label;
+use crate::kiro::error_code::KiroErrorCode;
 use crate::kiro::model::events::Event;
 use crate::kiro::model::requests::kiro::KiroRequest;
 use crate::kiro::parser::decoder::EventStreamDecoder;
+use crate::kiro::provider::ServedBy;
+use crate::kiro::stream_timeout::{self, StreamTimeoutError, StreamTimeouts};
+use crate::kiro::token_manager::AcquireOptions;
+use crate::model::config::{Config, ErrorMessageOverride};
 use crate::token;
 use axum::{
     Json as JsonExtractor,
+    Extension,
     body::Body,
     extract::State,
-    http::{StatusCode, header},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     response::{IntoResponse, Json, Response},
 };
 use bytes::Bytes;
-use futures::{Stream, StreamExt, stream};
+use futures::{
+    Stream, StreamExt,
+    stream::{self, BoxStream},
+};
 use serde_json::json;
 use std::time::Duration;
-use tokio::time::interval;
+use tokio::time::{Instant, interval_at};
 use uuid::Uuid;
 
-use super::converter::{ConversionError, convert_request};
+use super::access_log::AccessLogRecord;
+use super::converter::{ConversionError, convert_request, map_model};
+use super::error::ApiError;
+use super::image;
 use super::middleware::AppState;
 use super::stream::{SseEvent, StreamContext};
-use super::types::{
-    CountTokensRequest, CountTokensResponse, ErrorResponse, MessagesRequest, Model, ModelsResponse,
-};
+use super::types::{CountTokensRequest, CountTokensResponse, MessagesRequest, Model, ModelsResponse};
 use super::websearch;
 
 /// GET /v1/models
 ///
-/// 返回可用的模型列表
-pub async fn get_models() -> impl IntoResponse {
+/// 返回可用的模型列表，配置的模型别名追加在内置模型之后
+pub async fn get_models(State(state): State<AppState>) -> impl IntoResponse {
     tracing::info!("Received GET /v1/models request");
 
-    let models = vec![
+    let mut models = builtin_models();
+    if let Some(provider) = &state.kiro_provider {
+        let aliases = alias_models(&provider.token_manager().config().model_aliases, &models);
+        models.extend(aliases);
+    }
+
+    Json(ModelsResponse {
+        object: "list".to_string(),
+        data: models,
+    })
+}
+
+/// 内置模型列表
+fn builtin_models() -> Vec<Model> {
+    vec![
         Model {
             id: "claude-sonnet-4-5-20250929".to_string(),
             object: "model".to_string(),
@@ -62,24 +89,69 @@ pub async fn get_models() -> impl IntoResponse {
             model_type: "chat".to_string(),
             max_tokens: 32000,
         },
-    ];
+    ]
+}
 
-    Json(ModelsResponse {
-        object: "list".to_string(),
-        data: models,
-    })
+/// 将模型别名展开为模型列表条目（按别名排序）
+///
+/// 别名目标映射到某个内置模型时沿用其元数据，否则使用默认值
+fn alias_models(aliases: &HashMap<String, String>, builtin: &[Model]) -> Vec<Model> {
+    let mut names: Vec<_> = aliases.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|alias| {
+            let target = &aliases[alias];
+            let base = map_model(target).and_then(|resolved| {
+                builtin
+                    .iter()
+                    .find(|model| map_model(&model.id).as_deref() == Some(resolved.as_str()))
+            });
+            Model {
+                id: alias.clone(),
+                object: "model".to_string(),
+                created: base.map_or(0, |model| model.created),
+                owned_by: base.map_or("anthropic", |model| &model.owned_by).to_string(),
+                display_name: format!("{} (alias of {})", alias, target),
+                model_type: "chat".to_string(),
+                max_tokens: base.map_or(32000, |model| model.max_tokens),
+            }
+        })
+        .collect()
 }
 
 /// POST /v1/messages
 ///
-/// 创建消息（对话）
+/// 创建消息（对话），按客户端模型和响应状态码计入 `/metrics` 的 `kiro_requests_total`。
+/// 经过访问日志中间件时，处理过程中将模型、凭据和用量填入请求扩展中的 [`AccessLogRecord`]
 pub async fn post_messages(
     State(state): State<AppState>,
+    access_log: Option<Extension<AccessLogRecord>>,
+    headers: HeaderMap,
     JsonExtractor(payload): JsonExtractor<MessagesRequest>,
+) -> Response {
+    let model = payload.model.clone();
+    let provider = state.kiro_provider.clone();
+    let access_log = access_log.map(|Extension(record)| record).unwrap_or_default();
+    let response = handle_messages(state, headers, payload, access_log).await;
+    if let Some(provider) = provider {
+        provider
+            .token_manager()
+            .record_request(&model, response.status().as_u16());
+    }
+    response
+}
+
+/// `/v1/messages` 的处理逻辑
+async fn handle_messages(
+    state: AppState,
+    headers: HeaderMap,
+    mut payload: MessagesRequest,
+    access_log: AccessLogRecord,
 ) -> Response {
     tracing::info!(
         model = %payload.model,
-        max_tokens = %payload.max_tokens,
+        max_tokens = ?payload.max_tokens,
         stream = %payload.stream,
         message_count = %payload.messages.len(),
         "Received POST /v1/messages request"
@@ -89,17 +161,66 @@ pub async fn post_messages(
         Some(p) => p.clone(),
         None => {
             tracing::error!("KiroProvider 未配置");
-            return (
-                StatusCode::SERVICE_UNAVAILABLE,
-                Json(ErrorResponse::new(
-                    "service_unavailable",
-                    "Kiro API provider not configured",
-                )),
+            return ApiError::from_code(
+                KiroErrorCode::ProviderNotConfigured,
+                "Kiro API provider not configured",
             )
-                .into_response();
+            .into_response();
+        }
+    };
+
+    // max_tokens：按配置拒绝缺失的请求或填充默认值
+    let config = &provider.token_manager().config();
+    // 模型覆盖请求头与模型别名（在白名单、限流和模型映射之前生效）
+    // 别名只用于白名单、限流和上游调用，响应中保留客户端请求的模型名
+    let overridden_model = apply_model_override(&headers, &mut payload.model, config);
+    let upstream_model = resolve_model_alias(&payload.model, &config.model_aliases);
+    access_log.set_request(&payload.model, payload.stream);
+    match resolve_max_tokens(payload.max_tokens, config) {
+        Ok(max_tokens) => payload.max_tokens = Some(max_tokens),
+        Err(message) => {
+            tracing::warn!("{}", message);
+            return ApiError::invalid_request(message).into_response();
+        }
+    }
+
+    // 模型白名单（在限流和凭据选择之前检查）
+    if !is_model_allowed(&upstream_model, &config.allowed_client_models) {
+        return model_not_allowed_response(&upstream_model, &config.allowed_client_models);
+    }
+
+    // 按模型的全局限流
+    if let Err(wait) = state.model_rate_limiter.try_acquire(&upstream_model) {
+        return model_rate_limited_response(&upstream_model, wait);
+    }
+
+    // 解析客户端指定的凭据排除列表和请求截止时间
+    let acquire_options = match parse_acquire_options(
+        &headers,
+        config.allow_client_credential_exclusion,
+    )
+    .and_then(|options| {
+        let budget = parse_deadline_budget(&headers, config)?;
+        Ok(AcquireOptions {
+            deadline: Some(Instant::now() + budget),
+            ..options
+        })
+    }) {
+        Ok(options) => options,
+        Err(message) => {
+            tracing::warn!("{}", message);
+            return ApiError::invalid_request(message).into_response();
         }
     };
 
+    // 校验图片内容块，`url` 图片源下载后改写为 base64
+    if let Err(e) =
+        image::prepare_images(&mut payload.messages, &image::ImagePolicy::from_config(config)).await
+    {
+        tracing::warn!("图片校验失败: {}", e);
+        return ApiError::invalid_request(e.to_string()).into_response();
+    }
+
     // 检查是否为 WebSearch 请求
     if websearch::has_web_search_tool(&payload) {
         tracing::info!("检测到 WebSearch 工具，路由到 WebSearch 处理");
@@ -112,27 +233,22 @@ pub async fn post_messages(
             payload.tools.clone(),
         ) as i32;
 
-        return websearch::handle_websearch_request(provider, &payload, input_tokens).await;
+        let response = websearch::handle_websearch_request(provider, &payload, input_tokens).await;
+        return with_model_overridden_header(response, overridden_model.as_deref());
     }
 
-    // 转换请求
+    // 转换请求（按解析别名后的模型名映射上游模型）
+    let client_model = std::mem::replace(&mut payload.model, upstream_model);
     let conversion_result = match convert_request(&payload) {
         Ok(result) => result,
         Err(e) => {
-            let (error_type, message) = match &e {
-                ConversionError::UnsupportedModel(model) => {
-                    ("invalid_request_error", format!("模型不支持: {}", model))
-                }
-                ConversionError::EmptyMessages => {
-                    ("invalid_request_error", "消息列表为空".to_string())
-                }
+            let message = match &e {
+                ConversionError::UnsupportedModel(model) => format!("模型不支持: {}", model),
+                ConversionError::EmptyMessages => "消息列表为空".to_string(),
+                ConversionError::UnknownToolChoice(_) => e.to_string(),
             };
             tracing::warn!("请求转换失败: {}", e);
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse::new(error_type, message)),
-            )
-                .into_response();
+            return ApiError::from_code(conversion_error_code(&e), message).into_response();
         }
     };
 
@@ -146,14 +262,11 @@ pub async fn post_messages(
         Ok(body) => body,
         Err(e) => {
             tracing::error!("序列化请求失败: {}", e);
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new(
-                    "internal_error",
-                    format!("序列化请求失败: {}", e),
-                )),
+            return ApiError::from_code(
+                KiroErrorCode::InternalError,
+                format!("序列化请求失败: {}", e),
             )
-                .into_response();
+            .into_response();
         }
     };
 
@@ -174,22 +287,398 @@ pub async fn post_messages(
         .map(|t| t.thinking_type == "enabled")
         .unwrap_or(false);
 
-    if payload.stream {
+    let response = if payload.stream {
         // 流式响应
         handle_stream_request(
             provider,
             &request_body,
-            &payload.model,
+            &client_model,
             input_tokens,
             thinking_enabled,
+            &acquire_options,
+            &access_log,
         )
         .await
     } else {
         // 非流式响应
-        handle_non_stream_request(provider, &request_body, &payload.model, input_tokens).await
+        handle_non_stream_request(
+            provider,
+            &request_body,
+            &client_model,
+            input_tokens,
+            &acquire_options,
+            &access_log,
+        )
+        .await
+    };
+    with_model_overridden_header(response, overridden_model.as_deref())
+}
+
+/// 客户端覆盖请求模型的请求头（A/B 测试用）
+const MODEL_OVERRIDE_HEADER: &str = "x-kiro-model-override";
+
+/// 返回被覆盖的原模型名的响应头
+const MODEL_OVERRIDDEN_HEADER: &str = "x-kiro-model-overridden";
+
+/// 按 `x-kiro-model-override` 请求头替换请求的模型（需开启 `allow_model_override_header`）
+///
+/// 替换发生在模型映射之前，返回被替换掉的原模型名
+fn apply_model_override(
+    headers: &HeaderMap,
+    model: &mut String,
+    config: &Config,
+) -> Option<String> {
+    if !config.allow_model_override_header {
+        return None;
+    }
+    let target = headers.get(MODEL_OVERRIDE_HEADER)?.to_str().ok()?.trim();
+    if target.is_empty() || target == model.as_str() {
+        return None;
+    }
+
+    tracing::info!(
+        original_model = %model,
+        model_override = %target,
+        "按请求头覆盖请求模型"
+    );
+    Some(std::mem::replace(model, target.to_string()))
+}
+
+/// 按 `model_aliases` 返回别名对应的实际请求模型名，未命中时返回原模型名
+fn resolve_model_alias(model: &str, aliases: &HashMap<String, String>) -> String {
+    match aliases.get(model) {
+        Some(target) => {
+            tracing::debug!(alias = %model, model = %target, "解析模型别名");
+            target.clone()
+        }
+        None => model.to_string(),
     }
 }
 
+/// 请求模型被覆盖时附加 `x-kiro-model-overridden` 响应头（值为原模型名）
+fn with_model_overridden_header(mut response: Response, original: Option<&str>) -> Response {
+    if let Some(value) = original.and_then(|original| HeaderValue::from_str(original).ok()) {
+        response.headers_mut().insert(MODEL_OVERRIDDEN_HEADER, value);
+    }
+    response
+}
+
+/// 由累积的增量 JSON 构建非流式响应中的 `tool_use` 内容块
+///
+/// 输入 JSON 无法解析时记录警告并以空对象代替
+fn tool_use_block(tool_use_id: &str, name: &str, input_json: &str) -> serde_json::Value {
+    let input: serde_json::Value = serde_json::from_str(input_json).unwrap_or_else(|e| {
+        tracing::warn!(
+            "工具输入 JSON 解析失败: {}, tool_use_id: {}, 原始内容: {}",
+            e,
+            tool_use_id,
+            input_json
+        );
+        json!({})
+    });
+
+    json!({
+        "type": "tool_use",
+        "id": tool_use_id,
+        "name": name,
+        "input": input
+    })
+}
+
+/// 请求转换错误对应的结构化错误码
+fn conversion_error_code(e: &ConversionError) -> KiroErrorCode {
+    match e {
+        ConversionError::UnsupportedModel(_) => KiroErrorCode::ModelUnsupported,
+        ConversionError::EmptyMessages | ConversionError::UnknownToolChoice(_) => {
+            KiroErrorCode::InvalidRequest
+        }
+    }
+}
+
+/// 确定请求的 max_tokens
+///
+/// 请求已携带时原样使用；缺失时 `require_max_tokens` 优先于默认值，开启时返回错误消息，
+/// 否则填充 `default_max_tokens`
+fn resolve_max_tokens(max_tokens: Option<i32>, config: &Config) -> Result<i32, String> {
+    match max_tokens {
+        Some(max_tokens) => Ok(max_tokens),
+        None if config.require_max_tokens => {
+            Err("max_tokens: Field required（请求必须携带 max_tokens）".to_string())
+        }
+        None => Ok(config.default_max_tokens),
+    }
+}
+
+/// 请求的模型是否在 `allowed_client_models` 白名单内（白名单为空时全部放行）
+///
+/// 客户端请求的模型名或其映射后的 Kiro 模型 ID 在白名单中即放行（不区分大小写）
+fn is_model_allowed(model: &str, allowed: &[String]) -> bool {
+    if allowed.is_empty() {
+        return true;
+    }
+    let resolved = map_model(model);
+    allowed.iter().any(|name| {
+        name.eq_ignore_ascii_case(model)
+            || resolved
+                .as_deref()
+                .is_some_and(|resolved| name.eq_ignore_ascii_case(resolved))
+    })
+}
+
+/// 构建模型不在白名单内的 400 响应（列出允许的模型）
+fn model_not_allowed_response(model: &str, allowed: &[String]) -> Response {
+    tracing::warn!("模型 {} 不在允许列表中", model);
+    ApiError::from_code(
+        KiroErrorCode::ModelUnsupported,
+        format!("模型不允许使用: {}，允许的模型: {}", model, allowed.join(", ")),
+    )
+    .into_response()
+}
+
+/// 构建模型限流的 429 响应（带 Retry-After）
+fn model_rate_limited_response(model: &str, wait: Duration) -> Response {
+    let retry_after = rate_limit::retry_after_secs(wait);
+    tracing::warn!("模型 {} 触发全局限流，{} 秒后重试", model, retry_after);
+    ApiError::from_code(
+        KiroErrorCode::RateLimited,
+        format!("模型 {} 请求过于频繁，请在 {} 秒后重试", model, retry_after),
+    )
+    .with_retry_after(retry_after)
+    .into_response()
+}
+
+/// 客户端排除凭据请求头（逗号分隔的凭据 ID）
+const EXCLUDE_CREDENTIALS_HEADER: &str = "x-kiro-exclude-credentials";
+
+/// 从请求头解析本次请求的凭据选择选项
+///
+/// 未启用 `allow_client_credential_exclusion` 时忽略该请求头
+fn parse_acquire_options(headers: &HeaderMap, allowed: bool) -> Result<AcquireOptions, String> {
+    let Some(value) = headers.get(EXCLUDE_CREDENTIALS_HEADER) else {
+        return Ok(AcquireOptions::default());
+    };
+
+    if !allowed {
+        tracing::debug!(
+            "未启用 allowClientCredentialExclusion，忽略 {} 请求头",
+            EXCLUDE_CREDENTIALS_HEADER
+        );
+        return Ok(AcquireOptions::default());
+    }
+
+    let value = value
+        .to_str()
+        .map_err(|_| format!("{} 请求头包含非法字符", EXCLUDE_CREDENTIALS_HEADER))?;
+
+    let ids = value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<u64>().map_err(|_| {
+                format!(
+                    "{} 请求头包含无效的凭据 ID: {}",
+                    EXCLUDE_CREDENTIALS_HEADER, s
+                )
+            })
+        })
+        .collect::<Result<Vec<u64>, String>>()?;
+
+    if !ids.is_empty() {
+        tracing::info!("本次请求排除凭据: {:?}", ids);
+    }
+
+    Ok(AcquireOptions::excluding(ids))
+}
+
+/// 客户端请求截止时间请求头（本次请求上游调用的总时限，毫秒）
+const DEADLINE_HEADER: &str = "x-kiro-deadline-ms";
+
+/// 从请求头解析本次请求的上游调用时限
+///
+/// 未携带时使用 `upstream_request_timeout_secs`
+fn parse_deadline_budget(headers: &HeaderMap, config: &Config) -> Result<Duration, String> {
+    let Some(value) = headers.get(DEADLINE_HEADER) else {
+        return Ok(Duration::from_secs(config.upstream_request_timeout_secs));
+    };
+
+    value
+        .to_str()
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("{} 请求头必须为正整数（毫秒）", DEADLINE_HEADER))
+}
+
+/// 按 `error_message_overrides` 改写返回给客户端的错误消息
+///
+/// 按配置顺序使用第一条匹配的规则：`match` 等于错误码或为错误消息的子串时，
+/// 替换为 `replacement`；没有匹配的规则时原样返回
+fn override_error_message(
+    message: String,
+    code: KiroErrorCode,
+    overrides: &[ErrorMessageOverride],
+) -> String {
+    overrides
+        .iter()
+        .find(|rule| {
+            !rule.pattern.is_empty()
+                && (rule.pattern == code.as_str() || message.contains(&rule.pattern))
+        })
+        .map(|rule| rule.replacement.clone())
+        .unwrap_or(message)
+}
+
+/// 将上游调用错误转换为 HTTP 响应
+///
+/// 按错误码确定 Anthropic 错误类型和状态码（见 `ApiErrorKind::of`）：无可用凭据或存储后端尚未连接返回 503，
+/// 月度 token 预算或上游额度用尽返回 402，超过请求截止时间或上游流式首字节超时返回 504，
+/// 上游限流返回 429，上游拒绝请求返回 400，其余上游失败返回 502。错误消息按 `overrides` 改写，状态码不变
+fn upstream_error_response(e: anyhow::Error, overrides: &[ErrorMessageOverride]) -> Response {
+    let code = KiroErrorCode::of(&e);
+
+    let message = match code {
+        KiroErrorCode::DeadlineExceeded
+        | KiroErrorCode::UpstreamTimeout
+        | KiroErrorCode::CredentialBudgetExhausted => {
+            tracing::warn!("{}", e);
+            e.to_string()
+        }
+        KiroErrorCode::NoCredentialsAvailable | KiroErrorCode::StorageUnavailable => {
+            tracing::warn!("无可用凭据: {}", e);
+            e.to_string()
+        }
+        _ => {
+            tracing::error!("Kiro API 调用失败: {}", e);
+            format!("上游 API 调用失败: {}", e)
+        }
+    };
+
+    let message = override_error_message(message, code, overrides);
+    ApiError::from_code(code, message).into_response()
+}
+
+/// 降级响应标记头
+const FALLBACK_HEADER: &str = "x-kiro-fallback";
+
+/// 上游调用失败时的响应
+///
+/// 启用 `enable_outage_fallback` 且配置了 `outage_fallback_message` 时，
+/// 上游完全不可用（5xx、网络错误或超时，重试/凭据均已耗尽）返回降级消息；
+/// 上游拒绝请求（4xx、认证失败、限流）、无可用凭据、预算用尽、存储后端尚未连接
+/// 或超过请求截止时间导致的失败不降级
+fn upstream_failure_response(
+    e: anyhow::Error,
+    config: &Config,
+    model: &str,
+    input_tokens: i32,
+    stream: bool,
+) -> Response {
+    let fallback_message = config
+        .outage_fallback_message
+        .as_deref()
+        .filter(|message| config.enable_outage_fallback && !message.is_empty());
+
+    match fallback_message {
+        Some(message)
+            if matches!(
+                KiroErrorCode::of(&e),
+                KiroErrorCode::UpstreamUnavailable | KiroErrorCode::UpstreamTimeout
+            ) =>
+        {
+            tracing::error!("Kiro API 调用失败，返回降级消息: {}", e);
+            outage_fallback_response(message, model, input_tokens, stream)
+        }
+        _ => upstream_error_response(e, &config.error_message_overrides),
+    }
+}
+
+/// 构建降级消息响应（非流式为 JSON 消息，流式为完整的 SSE 事件序列）
+fn outage_fallback_response(
+    message: &str,
+    model: &str,
+    input_tokens: i32,
+    stream: bool,
+) -> Response {
+    let content = vec![json!({ "type": "text", "text": message })];
+    let output_tokens = token::estimate_output_tokens(&content);
+    let usage = json!({
+        "input_tokens": input_tokens,
+        "output_tokens": output_tokens
+    });
+
+    if !stream {
+        let mut body = json!({
+            "type": "message",
+            "role": "assistant",
+            "content": content,
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": usage
+        });
+        finalize_message_response(&mut body, model);
+        return (StatusCode::OK, [(FALLBACK_HEADER, "true")], Json(body)).into_response();
+    }
+
+    let events = [
+        SseEvent::new(
+            "message_start",
+            json!({
+                "type": "message_start",
+                "message": {
+                    "id": generate_message_id(),
+                    "type": "message",
+                    "role": "assistant",
+                    "content": [],
+                    "model": model,
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": { "input_tokens": input_tokens, "output_tokens": 0 }
+                }
+            }),
+        ),
+        SseEvent::new(
+            "content_block_start",
+            json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": { "type": "text", "text": "" }
+            }),
+        ),
+        SseEvent::new(
+            "content_block_delta",
+            json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "text_delta", "text": message }
+            }),
+        ),
+        SseEvent::new(
+            "content_block_stop",
+            json!({ "type": "content_block_stop", "index": 0 }),
+        ),
+        SseEvent::new(
+            "message_delta",
+            json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "end_turn", "stop_sequence": null },
+                "usage": { "output_tokens": output_tokens }
+            }),
+        ),
+        SseEvent::new("message_stop", json!({ "type": "message_stop" })),
+    ];
+    let body: String = events.iter().map(SseEvent::to_sse_string).collect();
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(FALLBACK_HEADER, "true")
+        .body(Body::from(body))
+        .unwrap()
+}
+
 /// 处理流式请求
 async fn handle_stream_request(
     provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
@@ -197,69 +686,129 @@ async fn handle_stream_request(
     model: &str,
     input_tokens: i32,
     thinking_enabled: bool,
+    acquire_options: &AcquireOptions,
+    access_log: &AccessLogRecord,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api_stream(request_body).await {
-        Ok(resp) => resp,
+    let served = match provider.call_api_stream(request_body, acquire_options).await {
+        Ok(served) => served,
         Err(e) => {
-            tracing::error!("Kiro API 调用失败: {}", e);
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(ErrorResponse::new(
-                    "api_error",
-                    format!("上游 API 调用失败: {}", e),
-                )),
-            )
-                .into_response();
+            return upstream_failure_response(
+                e,
+                &provider.token_manager().config(),
+                model,
+                input_tokens,
+                true,
+            );
         }
     };
+    let served_by = served.served_by;
+    let config = &provider.token_manager().config();
+    access_log.set_served(&served_by, config);
+    let timeouts = StreamTimeouts::from_config(config);
+    let body_stream = match first_chunk_or_timeout(served.response, timeouts).await {
+        Ok(body_stream) => body_stream,
+        Err(e) => return upstream_failure_response(e, config, model, input_tokens, true),
+    };
 
-    // 创建流处理上下文
-    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled);
-
-    // 生成初始事件
-    let initial_events = ctx.generate_initial_events();
+    // 创建流处理上下文（message_start 在收到首批上游事件时发送）
+    let ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled);
 
     // 创建 SSE 流
-    let stream = create_sse_stream(response, ctx, initial_events);
+    let usage_recorder = StreamUsageRecorder {
+        provider: provider.clone(),
+        credential_id: served_by.credential_id,
+        access_log: access_log.clone(),
+    };
+    let stream = create_sse_stream(body_stream, ctx, usage_recorder);
 
     // 返回 SSE 响应
-    Response::builder()
+    let mut response = Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/event-stream")
         .header(header::CACHE_CONTROL, "no-cache")
         .header(header::CONNECTION, "keep-alive")
         .body(Body::from_stream(stream))
-        .unwrap()
+        .unwrap();
+    apply_served_headers(&mut response, &served_by, model, &provider.token_manager().config());
+    response
+}
+
+/// 为上游流式响应加上首字节/空闲超时
+///
+/// 配置了首字节超时时，先等待首个数据块再开始响应，超时返回 `StreamTimeoutError`，
+/// 由调用方转换为 504 错误响应；之后的空闲超时在 SSE 流中以 `error` 事件报告
+async fn first_chunk_or_timeout(
+    response: reqwest::Response,
+    timeouts: StreamTimeouts,
+) -> anyhow::Result<BoxStream<'static, anyhow::Result<Bytes>>> {
+    let mut body_stream =
+        stream_timeout::with_timeouts(response.bytes_stream(), timeouts).boxed();
+    if timeouts.first_byte.is_none() {
+        return Ok(body_stream);
+    }
+
+    match body_stream.next().await {
+        Some(Err(e)) if e.is::<StreamTimeoutError>() => Err(e),
+        Some(first) => Ok(stream::iter([first]).chain(body_stream).boxed()),
+        None => Ok(stream::empty().boxed()),
+    }
+}
+
+/// 构建流中途出错时的 SSE `error` 事件
+fn stream_error_sse(error_type: &str, message: String) -> Bytes {
+    Bytes::from(SseEvent::error(error_type, message).to_sse_string())
 }
 
 /// Ping 事件间隔（25秒）
 const PING_INTERVAL_SECS: u64 = 25;
 
+/// 等待首批上游事件以发送 message_start 的最长时间（毫秒），超时后按本地估算用量发送
+const MESSAGE_START_MAX_DELAY_MS: u64 = 1000;
+
 /// 创建 ping 事件的 SSE 字符串
 fn create_ping_sse() -> Bytes {
     Bytes::from("event: ping\ndata: {\"type\": \"ping\"}\n\n")
 }
 
+/// 流式响应结束时计入凭据月度 token 用量及访问日志
+struct StreamUsageRecorder {
+    provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
+    credential_id: u64,
+    access_log: AccessLogRecord,
+}
+
+impl StreamUsageRecorder {
+    fn record(&self, ctx: &StreamContext) {
+        let input_tokens = ctx.context_input_tokens.unwrap_or(ctx.input_tokens);
+        self.access_log.set_usage(input_tokens, ctx.output_tokens);
+        self.provider.token_manager().record_request_tokens(
+            self.credential_id,
+            input_tokens.max(0) as u64,
+            ctx.output_tokens.max(0) as u64,
+        );
+    }
+}
+
 /// 创建 SSE 事件流
+///
+/// message_start 延迟到首批上游事件（或首次 ping）时发送，以便携带上游给出的输入用量；
+/// 在此之前不会发送任何其他事件
 fn create_sse_stream(
-    response: reqwest::Response,
+    body_stream: BoxStream<'static, anyhow::Result<Bytes>>,
     ctx: StreamContext,
-    initial_events: Vec<SseEvent>,
+    usage_recorder: StreamUsageRecorder,
 ) -> impl Stream<Item = Result<Bytes, Infallible>> {
-    // 先发送初始事件
-    let initial_stream = stream::iter(
-        initial_events
-            .into_iter()
-            .map(|e| Ok(Bytes::from(e.to_sse_string()))),
+    // 处理 Kiro 响应流，同时每25秒发送 ping 保活
+    // （首次 tick 提前到 MESSAGE_START_MAX_DELAY_MS，保证 message_start 及时发出）
+    let ping_interval = interval_at(
+        Instant::now() + Duration::from_millis(MESSAGE_START_MAX_DELAY_MS),
+        Duration::from_secs(PING_INTERVAL_SECS),
     );
 
-    // 然后处理 Kiro 响应流，同时每25秒发送 ping 保活
-    let body_stream = response.bytes_stream();
-
-    let processing_stream = stream::unfold(
-        (body_stream, ctx, EventStreamDecoder::new(), false, interval(Duration::from_secs(PING_INTERVAL_SECS))),
-        |(mut body_stream, mut ctx, mut decoder, finished, mut ping_interval)| async move {
+    stream::unfold(
+        (body_stream, ctx, EventStreamDecoder::new(), false, ping_interval, usage_recorder),
+        |(mut body_stream, mut ctx, mut decoder, finished, mut ping_interval, usage_recorder)| async move {
             if finished {
                 return None;
             }
@@ -275,13 +824,12 @@ fn create_sse_stream(
                                 tracing::warn!("缓冲区溢出: {}", e);
                             }
 
-                            let mut events = Vec::new();
+                            let mut kiro_events = Vec::new();
                             for result in decoder.decode_iter() {
                                 match result {
                                     Ok(frame) => {
                                         if let Ok(event) = Event::from_frame(frame) {
-                                            let sse_events = ctx.process_kiro_event(&event);
-                                            events.extend(sse_events);
+                                            kiro_events.push(event);
                                         }
                                     }
                                     Err(e) => {
@@ -289,6 +837,7 @@ fn create_sse_stream(
                                     }
                                 }
                             }
+                            let events = ctx.process_kiro_events(kiro_events);
 
                             // 转换为 SSE 字节流
                             let bytes: Vec<Result<Bytes, Infallible>> = events
@@ -296,41 +845,66 @@ fn create_sse_stream(
                                 .map(|e| Ok(Bytes::from(e.to_sse_string())))
                                 .collect();
 
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
+                            // 上游返回了错误事件：已发送 error 事件，结束流
+                            let failed = ctx.upstream_failed;
+                            if failed {
+                                usage_recorder.record(&ctx);
+                            }
+                            Some((stream::iter(bytes), (body_stream, ctx, decoder, failed, ping_interval, usage_recorder)))
+                        }
+                        Some(Err(e)) if e.is::<StreamTimeoutError>() => {
+                            // 上游停止输出：以 error 事件结束，避免客户端误以为正常完成
+                            tracing::warn!("{}", e);
+                            usage_recorder.record(&ctx);
+                            let bytes = vec![Ok(stream_error_sse("timeout_error", e.to_string()))];
+                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval, usage_recorder)))
                         }
                         Some(Err(e)) => {
                             tracing::error!("读取响应流失败: {}", e);
-                            // 发送最终事件并结束
-                            let final_events = ctx.generate_final_events();
-                            let bytes: Vec<Result<Bytes, Infallible>> = final_events
+                            // 以 error 事件结束，避免客户端把截断的输出当作正常完成
+                            let mut bytes: Vec<Result<Bytes, Infallible>> = ctx
+                                .ensure_message_started()
                                 .into_iter()
                                 .map(|e| Ok(Bytes::from(e.to_sse_string())))
                                 .collect();
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
+                            usage_recorder.record(&ctx);
+                            bytes.push(Ok(stream_error_sse(
+                                "api_error",
+                                format!("读取上游响应流失败: {}", e),
+                            )));
+                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval, usage_recorder)))
                         }
                         None => {
                             // 流结束，发送最终事件
                             let final_events = ctx.generate_final_events();
+                            usage_recorder.record(&ctx);
                             let bytes: Vec<Result<Bytes, Infallible>> = final_events
                                 .into_iter()
                                 .map(|e| Ok(Bytes::from(e.to_sse_string())))
                                 .collect();
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
+                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval, usage_recorder)))
                         }
                     }
                 }
                 // 发送 ping 保活
                 _ = ping_interval.tick() => {
-                    tracing::trace!("发送 ping 保活事件");
-                    let bytes: Vec<Result<Bytes, Infallible>> = vec![Ok(create_ping_sse())];
-                    Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
+                    // ping 不能先于 message_start：尚未开始时改为发送初始事件（使用本地估算用量）
+                    let initial_events = ctx.ensure_message_started();
+                    let bytes: Vec<Result<Bytes, Infallible>> = if initial_events.is_empty() {
+                        tracing::trace!("发送 ping 保活事件");
+                        vec![Ok(create_ping_sse())]
+                    } else {
+                        initial_events
+                            .into_iter()
+                            .map(|e| Ok(Bytes::from(e.to_sse_string())))
+                            .collect()
+                    };
+                    Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval, usage_recorder)))
                 }
             }
         },
     )
-    .flatten();
-
-    initial_stream.chain(processing_stream)
+    .flatten()
 }
 
 /// 上下文窗口大小（200k tokens）
@@ -342,35 +916,32 @@ async fn handle_non_stream_request(
     request_body: &str,
     model: &str,
     input_tokens: i32,
+    acquire_options: &AcquireOptions,
+    access_log: &AccessLogRecord,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
-    let response = match provider.call_api(request_body).await {
-        Ok(resp) => resp,
+    let served = match provider.call_api(request_body, acquire_options).await {
+        Ok(served) => served,
         Err(e) => {
-            tracing::error!("Kiro API 调用失败: {}", e);
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(ErrorResponse::new(
-                    "api_error",
-                    format!("上游 API 调用失败: {}", e),
-                )),
-            )
-                .into_response();
+            return upstream_failure_response(
+                e,
+                &provider.token_manager().config(),
+                model,
+                input_tokens,
+                false,
+            );
         }
     };
+    let served_by = served.served_by;
+    access_log.set_served(&served_by, &provider.token_manager().config());
+    let response = served.response;
 
-    // 读取响应体
-    let body_bytes = match response.bytes().await {
+    // 读取响应体（受 max_upstream_response_bytes 限制）
+    let body_bytes = match provider.read_response_body(response).await {
         Ok(bytes) => bytes,
         Err(e) => {
             tracing::error!("读取响应体失败: {}", e);
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(ErrorResponse::new(
-                    "api_error",
-                    format!("读取响应失败: {}", e),
-                )),
-            )
+            return ApiError::from_code(KiroErrorCode::of(&e), format!("读取响应失败: {}", e))
                 .into_response();
         }
     };
@@ -411,21 +982,11 @@ async fn handle_non_stream_request(
 
                             // 如果是完整的工具调用，添加到列表
                             if tool_use.stop {
-                                let input: serde_json::Value = serde_json::from_str(buffer)
-                                    .unwrap_or_else(|e| {
-                                        tracing::warn!(
-                                            "工具输入 JSON 解析失败: {}, tool_use_id: {}, 原始内容: {}",
-                                            e, tool_use.tool_use_id, buffer
-                                        );
-                                        serde_json::json!({})
-                                    });
-
-                                tool_uses.push(json!({
-                                    "type": "tool_use",
-                                    "id": tool_use.tool_use_id,
-                                    "name": tool_use.name,
-                                    "input": input
-                                }));
+                                tool_uses.push(tool_use_block(
+                                    &tool_use.tool_use_id,
+                                    &tool_use.name,
+                                    buffer,
+                                ));
                             }
                         }
                         Event::ContextUsage(context_usage) => {
@@ -480,13 +1041,19 @@ async fn handle_non_stream_request(
     // 使用从 contextUsageEvent 计算的 input_tokens，如果没有则使用估算值
     let final_input_tokens = context_input_tokens.unwrap_or(input_tokens);
 
-    // 构建 Anthropic 响应
-    let response_body = json!({
-        "id": format!("msg_{}", Uuid::new_v4().to_string().replace('-', "")),
+    // 计入凭据月度 token 用量
+    access_log.set_usage(final_input_tokens, output_tokens);
+    provider.token_manager().record_request_tokens(
+        served_by.credential_id,
+        final_input_tokens.max(0) as u64,
+        output_tokens.max(0) as u64,
+    );
+
+    // 构建 Anthropic 响应（id/model 由 finalize_message_response 统一补全）
+    let mut response_body = json!({
         "type": "message",
         "role": "assistant",
         "content": content,
-        "model": model,
         "stop_reason": stop_reason,
         "stop_sequence": null,
         "usage": {
@@ -495,29 +1062,979 @@ async fn handle_non_stream_request(
         }
     });
 
-    (StatusCode::OK, Json(response_body)).into_response()
+    finalize_message_response(&mut response_body, model);
+
+    let mut response = (StatusCode::OK, Json(response_body)).into_response();
+    apply_served_headers(&mut response, &served_by, model, &provider.token_manager().config());
+    response
+}
+
+/// 服务本次请求的 region 响应头
+const REGION_HEADER: &str = "x-kiro-region";
+
+/// 本次请求实际发往上游的模型 ID 响应头
+const RESOLVED_MODEL_HEADER: &str = "x-kiro-resolved-model";
+
+/// 按配置为成功响应附加服务本次请求的凭据及模型相关响应头
+///
+/// `client_model` 为客户端请求的模型名（响应体中回显的仍是该名称），
+/// 实际发往上游的模型 ID 由 `map_model` 解析得到
+fn apply_served_headers(
+    response: &mut Response,
+    served_by: &ServedBy,
+    client_model: &str,
+    config: &Config,
+) {
+    tracing::debug!(
+        "请求由凭据 {} 服务（region: {}）",
+        credential_label(config, served_by.credential_id),
+        served_by.region
+    );
+
+    if config.expose_region_header
+        && let Ok(value) = HeaderValue::from_str(&served_by.region)
+    {
+        response.headers_mut().insert(REGION_HEADER, value);
+    }
+
+    if config.expose_resolved_model_header
+        && let Some(value) =
+            map_model(client_model).and_then(|resolved| HeaderValue::from_str(&resolved).ok())
+    {
+        response.headers_mut().insert(RESOLVED_MODEL_HEADER, value);
+    }
+}
+
+/// 消息 ID 前缀（与 Anthropic 官方格式一致）
+const MESSAGE_ID_PREFIX: &str = "msg_";
+
+/// 生成新的消息 ID
+fn generate_message_id() -> String {
+    format!(
+        "{}{}",
+        MESSAGE_ID_PREFIX,
+        Uuid::new_v4().to_string().replace('-', "")
+    )
+}
+
+/// 补全 `/v1/messages` 响应中的元数据字段
+///
+/// 部分下游工具依赖 `id` / `model` 字段，缺失时会直接报错：
+/// - `id` 缺失、为空或不是字符串时，生成 `msg_` 前缀的新 ID
+/// - `model` 始终回写为客户端请求的模型名（而非上游实际使用的 Kiro 模型 ID）
+fn finalize_message_response(body: &mut serde_json::Value, client_model: &str) {
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+
+    let has_valid_id = obj
+        .get("id")
+        .and_then(|v| v.as_str())
+        .is_some_and(|id| !id.trim().is_empty());
+    if !has_valid_id {
+        obj.insert("id".to_string(), json!(generate_message_id()));
+    }
+
+    obj.insert("model".to_string(), json!(client_model));
 }
 
 /// POST /v1/messages/count_tokens
 ///
-/// 计算消息的 token 数量
+/// 计算消息的 token 数量。未使用外部 count_tokens API 时返回本地估算值（`estimated: true`）；
+/// 外部 API 调用失败且关闭了 `count_tokens_allow_local_fallback` 时返回 502
 pub async fn count_tokens(
     JsonExtractor(payload): JsonExtractor<CountTokensRequest>,
-) -> impl IntoResponse {
+) -> Response {
     tracing::info!(
         model = %payload.model,
         message_count = %payload.messages.len(),
         "Received POST /v1/messages/count_tokens request"
     );
 
-    let total_tokens = token::count_all_tokens(
+    let count = match token::count_request_tokens(
         payload.model,
         payload.system,
         payload.messages,
         payload.tools,
-    ) as i32;
+    ) {
+        Ok(count) => count,
+        Err(message) => {
+            return ApiError::from_code(KiroErrorCode::UpstreamUnavailable, message)
+                .into_response();
+        }
+    };
 
     Json(CountTokensResponse {
-        input_tokens: total_tokens.max(1) as i32,
+        input_tokens: (count.input_tokens as i32).max(1),
+        estimated: count.estimated,
     })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::error::ApiErrorKind;
+    use crate::kiro::monthly_budget::MonthlyBudgetExhaustedError;
+    use crate::kiro::token_manager::{NoEligibleCredentialError, PinnedCredentialUnavailableError};
+
+    #[test]
+    fn test_parse_acquire_options_parses_ids() {
+        let mut headers = HeaderMap::new();
+        headers.insert(EXCLUDE_CREDENTIALS_HEADER, "3, 5,,7".parse().unwrap());
+
+        let options = parse_acquire_options(&headers, true).unwrap();
+        assert_eq!(options.excluded_ids.len(), 3);
+        assert!(options.excluded_ids.contains(&3));
+        assert!(options.excluded_ids.contains(&5));
+        assert!(options.excluded_ids.contains(&7));
+    }
+
+    #[test]
+    fn test_parse_deadline_budget() {
+        let config = Config::default();
+        assert_eq!(
+            parse_deadline_budget(&HeaderMap::new(), &config).unwrap(),
+            Duration::from_secs(config.upstream_request_timeout_secs)
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(DEADLINE_HEADER, "1500".parse().unwrap());
+        assert_eq!(
+            parse_deadline_budget(&headers, &config).unwrap(),
+            Duration::from_millis(1500)
+        );
+
+        for invalid in ["0", "-1", "soon"] {
+            headers.insert(DEADLINE_HEADER, invalid.parse().unwrap());
+            assert!(parse_deadline_budget(&headers, &config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_tool_use_block_reassembles_input_json() {
+        // 上游分片下发的工具输入在 stop 时拼接为完整 JSON
+        let mut buffer = String::new();
+        for chunk in [r#"{"path": "/tmp/"#, r#"a.txt", "lines": [1, 2]}"#] {
+            buffer.push_str(chunk);
+        }
+
+        let block = tool_use_block("tooluse_abc", "read_file", &buffer);
+        assert_eq!(
+            block,
+            json!({
+                "type": "tool_use",
+                "id": "tooluse_abc",
+                "name": "read_file",
+                "input": {"path": "/tmp/a.txt", "lines": [1, 2]}
+            })
+        );
+
+        // 无法解析的输入退化为空对象，仍保留 id/name
+        let block = tool_use_block("tooluse_def", "read_file", r#"{"path": "#);
+        assert_eq!(block["input"], json!({}));
+        assert_eq!(block["id"], "tooluse_def");
+    }
+
+    #[test]
+    fn test_parse_acquire_options_ignored_when_disabled() {
+        let mut headers = HeaderMap::new();
+        headers.insert(EXCLUDE_CREDENTIALS_HEADER, "1".parse().unwrap());
+
+        let options = parse_acquire_options(&headers, false).unwrap();
+        assert!(options.is_default());
+    }
+
+    #[test]
+    fn test_parse_acquire_options_rejects_invalid_id() {
+        let mut headers = HeaderMap::new();
+        headers.insert(EXCLUDE_CREDENTIALS_HEADER, "1,abc".parse().unwrap());
+
+        assert!(parse_acquire_options(&headers, true).is_err());
+    }
+
+    #[test]
+    fn test_upstream_error_response_no_eligible_is_overloaded() {
+        let err: anyhow::Error = NoEligibleCredentialError {
+            excluded_ids: vec![1],
+        }
+        .into();
+        assert_eq!(
+            upstream_error_response(err, &[]).status(),
+            ApiErrorKind::Overloaded.status()
+        );
+
+        let err = anyhow::anyhow!("boom");
+        assert_eq!(
+            upstream_error_response(err, &[]).status(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upstream_error_response_pinned_unavailable_is_overloaded() {
+        let err: anyhow::Error = PinnedCredentialUnavailableError {
+            id: 2,
+            reason: Some(crate::kiro::token_manager::SkipReason::Disabled),
+        }
+        .into();
+        let response = upstream_error_response(err, &[]);
+        assert_eq!(response.status(), ApiErrorKind::Overloaded.status());
+        assert_eq!(error_code_of(response).await, "no_credentials_available");
+    }
+
+    #[tokio::test]
+    async fn test_error_message_overrides() {
+        use crate::kiro::error_code::KiroError;
+
+        let overrides = vec![
+            ErrorMessageOverride {
+                pattern: "INSUFFICIENT_MODEL_CAPACITY".to_string(),
+                replacement: "模型繁忙，请稍后重试".to_string(),
+            },
+            ErrorMessageOverride {
+                pattern: "upstream_auth_failed".to_string(),
+                replacement: "服务暂时不可用".to_string(),
+            },
+        ];
+        let message_of = |response: Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            body["error"]["message"].as_str().unwrap().to_string()
+        };
+
+        // 按消息子串匹配，状态码不变
+        let err: anyhow::Error = KiroError::new(
+            KiroErrorCode::UpstreamUnavailable,
+            "流式 API 请求失败: 503 {\"reason\":\"INSUFFICIENT_MODEL_CAPACITY\"}",
+        )
+        .into();
+        let response = upstream_error_response(err, &overrides);
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(message_of(response).await, "模型繁忙，请稍后重试");
+
+        // 按错误码匹配
+        let err: anyhow::Error =
+            KiroError::new(KiroErrorCode::UpstreamAuthFailed, "403 Forbidden").into();
+        let response = upstream_error_response(err, &overrides);
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(message_of(response).await, "服务暂时不可用");
+
+        // 未匹配时原样返回
+        let err = anyhow::anyhow!("connection reset");
+        let response = upstream_error_response(err, &overrides);
+        assert_eq!(
+            message_of(response).await,
+            "上游 API 调用失败: connection reset"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_first_byte_aborts_stream_early() {
+        // mock 上游：立即返回响应头，首个数据块延迟 5 秒
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::get(|| async {
+                let body = stream::once(async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok::<_, Infallible>(Bytes::from_static(b"late"))
+                });
+                Body::from_stream(body)
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let upstream = reqwest::get(format!("http://{}/", addr)).await.unwrap();
+        let timeouts = StreamTimeouts {
+            first_byte: Some(Duration::from_millis(200)),
+            idle: None,
+        };
+        let started = std::time::Instant::now();
+        let err = first_chunk_or_timeout(upstream, timeouts).await.err().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let response = upstream_error_response(err, &[]);
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(error_code_of(response).await, "upstream_timeout");
+    }
+
+    #[tokio::test]
+    async fn test_mid_stream_read_error_ends_with_error_event() {
+        use crate::kiro::provider::KiroProvider;
+        use crate::kiro::token_manager::MultiTokenManager;
+
+        let manager = MultiTokenManager::new(Config::default(), vec![], None, None, false).unwrap();
+        let usage_recorder = StreamUsageRecorder {
+            provider: std::sync::Arc::new(KiroProvider::new(std::sync::Arc::new(manager))),
+            credential_id: 1,
+            access_log: AccessLogRecord::default(),
+        };
+        let body_stream =
+            stream::iter([Err(anyhow::anyhow!("connection reset by peer"))]).boxed();
+        let ctx = StreamContext::new_with_thinking("claude-sonnet-4", 10, false);
+
+        let chunks: Vec<Bytes> = create_sse_stream(body_stream, ctx, usage_recorder)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        let output = String::from_utf8(chunks.concat()).unwrap();
+
+        assert!(output.starts_with("event: message_start"));
+        assert!(output.contains("event: error"));
+        assert!(output.contains("connection reset by peer"));
+        assert!(!output.contains("message_stop"));
+    }
+
+    #[test]
+    fn test_model_rate_limited_response_sets_retry_after() {
+        let response = model_rate_limited_response("claude-opus-4-5", Duration::from_millis(1500));
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "2");
+    }
+
+    #[test]
+    fn test_model_override_header_changes_upstream_model_when_enabled() {
+        let request = |model: &str| -> MessagesRequest {
+            serde_json::from_value(json!({
+                "model": model,
+                "max_tokens": 1024,
+                "messages": [{"role": "user", "content": "hi"}]
+            }))
+            .unwrap()
+        };
+        let upstream_model = |payload: &MessagesRequest| {
+            convert_request(payload)
+                .unwrap()
+                .conversation_state
+                .current_message
+                .user_input_message
+                .model_id
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(MODEL_OVERRIDE_HEADER, "claude-opus-4-5-20251101".parse().unwrap());
+
+        let config = Config {
+            allow_model_override_header: true,
+            ..Default::default()
+        };
+        let mut payload = request("claude-sonnet-4-5-20250929");
+        let original = apply_model_override(&headers, &mut payload.model, &config);
+        assert_eq!(original.as_deref(), Some("claude-sonnet-4-5-20250929"));
+        assert_eq!(upstream_model(&payload), "claude-opus-4.5");
+
+        let response = with_model_overridden_header(
+            StatusCode::OK.into_response(),
+            original.as_deref(),
+        );
+        assert_eq!(
+            response.headers().get(MODEL_OVERRIDDEN_HEADER).unwrap(),
+            "claude-sonnet-4-5-20250929"
+        );
+
+        // 未开启时忽略请求头
+        let mut payload = request("claude-sonnet-4-5-20250929");
+        assert!(apply_model_override(&headers, &mut payload.model, &Config::default()).is_none());
+        assert_eq!(upstream_model(&payload), "claude-sonnet-4.5");
+        let response = with_model_overridden_header(StatusCode::OK.into_response(), None);
+        assert!(response.headers().get(MODEL_OVERRIDDEN_HEADER).is_none());
+    }
+
+    fn served_by_region(region: &str) -> ServedBy {
+        ServedBy {
+            credential_id: 2,
+            region: region.to_string(),
+            upstream_latency: Duration::from_millis(100),
+        }
+    }
+
+    #[test]
+    fn test_region_header_reflects_serving_credential() {
+        let config = Config {
+            expose_region_header: true,
+            ..Default::default()
+        };
+
+        let mut response = StatusCode::OK.into_response();
+        apply_served_headers(
+            &mut response,
+            &served_by_region("eu-central-1"),
+            "claude-sonnet-4-5-20250929",
+            &config,
+        );
+        assert_eq!(response.headers().get(REGION_HEADER).unwrap(), "eu-central-1");
+    }
+
+    #[test]
+    fn test_region_header_absent_when_disabled() {
+        let config = Config::default();
+
+        let mut response = StatusCode::OK.into_response();
+        apply_served_headers(
+            &mut response,
+            &served_by_region("eu-central-1"),
+            "claude-sonnet-4-5-20250929",
+            &config,
+        );
+        assert!(response.headers().get(REGION_HEADER).is_none());
+    }
+
+    #[test]
+    fn test_resolved_model_header_keeps_client_alias_in_body() {
+        let config = Config {
+            expose_resolved_model_header: true,
+            ..Default::default()
+        };
+        let alias = "claude-sonnet-4-5-20250929";
+
+        let mut body = json!({"type": "message", "content": []});
+        finalize_message_response(&mut body, alias);
+        let mut response = (StatusCode::OK, Json(body.clone())).into_response();
+        apply_served_headers(&mut response, &served_by_region("us-east-1"), alias, &config);
+
+        assert_eq!(
+            response.headers().get(RESOLVED_MODEL_HEADER).unwrap(),
+            "claude-sonnet-4.5"
+        );
+        assert_eq!(body["model"], alias);
+
+        let mut response = StatusCode::OK.into_response();
+        apply_served_headers(
+            &mut response,
+            &served_by_region("us-east-1"),
+            alias,
+            &Config::default(),
+        );
+        assert!(response.headers().get(RESOLVED_MODEL_HEADER).is_none());
+    }
+
+    #[test]
+    fn test_finalize_message_response_synthesizes_missing_id() {
+        let mut body = json!({
+            "type": "message",
+            "role": "assistant",
+            "content": []
+        });
+
+        finalize_message_response(&mut body, "claude-sonnet-4-5-20250929");
+
+        let id = body["id"].as_str().unwrap();
+        assert!(id.starts_with(MESSAGE_ID_PREFIX));
+        assert!(id.len() > MESSAGE_ID_PREFIX.len());
+        assert_eq!(body["model"], "claude-sonnet-4-5-20250929");
+    }
+
+    #[test]
+    fn test_finalize_message_response_replaces_empty_id() {
+        let mut body = json!({ "id": "  ", "type": "message" });
+        finalize_message_response(&mut body, "claude-opus-4-5-20251101");
+        assert!(body["id"].as_str().unwrap().starts_with(MESSAGE_ID_PREFIX));
+    }
+
+    #[test]
+    fn test_finalize_message_response_keeps_existing_id() {
+        let mut body = json!({ "id": "msg_upstream", "model": "claude-sonnet-4.5" });
+        finalize_message_response(&mut body, "claude-sonnet-4-5-20250929");
+
+        assert_eq!(body["id"], "msg_upstream");
+        // model 应回写为客户端请求的模型名
+        assert_eq!(body["model"], "claude-sonnet-4-5-20250929");
+    }
+
+    /// 没有任何凭据的 Provider：所有上游调用都会失败（不访问网络）
+    fn outage_state(config: Config) -> AppState {
+        use crate::kiro::provider::KiroProvider;
+        use crate::kiro::token_manager::MultiTokenManager;
+
+        let manager = MultiTokenManager::new(config, vec![], None, None, false).unwrap();
+        AppState::new("test-key")
+            .with_kiro_provider(KiroProvider::new(std::sync::Arc::new(manager)))
+    }
+
+    /// 上游始终返回 503 的 Provider：所有重试和凭据都会耗尽
+    async fn failing_upstream_state(mut config: Config) -> AppState {
+        use crate::kiro::model::credentials::KiroCredentials;
+        use crate::kiro::provider::KiroProvider;
+        use crate::kiro::token_manager::MultiTokenManager;
+
+        let app = axum::Router::new().route(
+            "/generateAssistantResponse",
+            axum::routing::post(|| async { (StatusCode::SERVICE_UNAVAILABLE, "unavailable") }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/generateAssistantResponse",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        config.retry_base_delay_ms = 1;
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(config, vec![credentials], None, None, false).unwrap();
+        let provider = KiroProvider::new(std::sync::Arc::new(manager)).with_upstream_url(url);
+        AppState::new("test-key").with_kiro_provider(provider)
+    }
+
+    fn outage_request(stream: bool) -> MessagesRequest {
+        MessagesRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            max_tokens: Some(64),
+            messages: vec![crate::anthropic::types::Message {
+                role: "user".to_string(),
+                content: json!("hello"),
+            }],
+            stream,
+            system: None,
+            tools: None,
+            tool_choice: None,
+            thinking: None,
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outage_fallback_returns_canned_message() {
+        let config = Config {
+            enable_outage_fallback: true,
+            outage_fallback_message: Some("服务暂时不可用，请稍后再试".to_string()),
+            ..Default::default()
+        };
+
+        let response = post_messages(
+            State(failing_upstream_state(config).await),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(outage_request(false)),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(FALLBACK_HEADER).unwrap(), "true");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["type"], "message");
+        assert_eq!(body["role"], "assistant");
+        assert_eq!(body["stop_reason"], "end_turn");
+        assert_eq!(body["model"], "claude-sonnet-4-5");
+        assert_eq!(body["content"][0]["text"], "服务暂时不可用，请稍后再试");
+        assert!(body["id"].as_str().unwrap().starts_with(MESSAGE_ID_PREFIX));
+    }
+
+    #[tokio::test]
+    async fn test_outage_fallback_streams_canned_message() {
+        let config = Config {
+            enable_outage_fallback: true,
+            outage_fallback_message: Some("degraded".to_string()),
+            ..Default::default()
+        };
+
+        let response = post_messages(
+            State(failing_upstream_state(config).await),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(outage_request(true)),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(FALLBACK_HEADER).unwrap(), "true");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with("event: message_start\n"));
+        assert!(body.contains(r#""text":"degraded""#));
+        assert!(body.contains(r#""stop_reason":"end_turn""#));
+        assert!(body.ends_with("event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n"));
+    }
+
+    #[test]
+    fn test_outage_fallback_skips_upstream_rejections() {
+        use crate::kiro::error_code::KiroError;
+
+        let config = Config {
+            enable_outage_fallback: true,
+            outage_fallback_message: Some("degraded".to_string()),
+            ..Default::default()
+        };
+        let failure = |code| {
+            upstream_failure_response(
+                KiroError::new(code, "upstream failed").into(),
+                &config,
+                "claude-sonnet-4-5",
+                1,
+                false,
+            )
+        };
+
+        for code in [
+            KiroErrorCode::UpstreamRejected,
+            KiroErrorCode::UpstreamAuthFailed,
+            KiroErrorCode::RateLimitedUpstream,
+        ] {
+            let response = failure(code);
+            assert_ne!(response.status(), StatusCode::OK);
+            assert!(response.headers().get(FALLBACK_HEADER).is_none());
+        }
+        for code in [KiroErrorCode::UpstreamUnavailable, KiroErrorCode::UpstreamTimeout] {
+            let response = failure(code);
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get(FALLBACK_HEADER).unwrap(), "true");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outage_without_fallback_returns_error() {
+        let config = Config {
+            outage_fallback_message: Some("unused".to_string()),
+            ..Default::default()
+        };
+
+        let response = post_messages(
+            State(outage_state(config)),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(outage_request(false)),
+        )
+        .await;
+
+        assert_eq!(response.status(), ApiErrorKind::Overloaded.status());
+        assert!(response.headers().get(FALLBACK_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pending_storage_returns_overloaded() {
+        use crate::kiro::provider::KiroProvider;
+        use crate::kiro::token_manager::MultiTokenManager;
+
+        let manager = MultiTokenManager::new(Config::default(), vec![], None, None, false).unwrap();
+        manager.mark_storage_pending();
+        let state = AppState::new("test-key")
+            .with_kiro_provider(KiroProvider::new(std::sync::Arc::new(manager)));
+
+        let response = post_messages(
+            State(state),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(outage_request(false)),
+        )
+        .await;
+
+        assert_eq!(response.status(), ApiErrorKind::Overloaded.status());
+        assert_eq!(error_code_of(response).await, "storage_unavailable");
+    }
+
+    async fn error_code_of(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        body["error"]["kiro_error_code"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_upstream_failures_carry_stable_error_codes() {
+        use crate::kiro::error_code::KiroError;
+
+        let cases: Vec<(anyhow::Error, &str)> = vec![
+            (
+                NoEligibleCredentialError {
+                    excluded_ids: vec![1],
+                }
+                .into(),
+                "no_credentials_available",
+            ),
+            (
+                MonthlyBudgetExhaustedError {
+                    period: "2026-10".to_string(),
+                }
+                .into(),
+                "credential_budget_exhausted",
+            ),
+            (
+                KiroError::new(KiroErrorCode::from_upstream_status(429), "429").into(),
+                "rate_limited_upstream",
+            ),
+            (anyhow::anyhow!("connection reset"), "upstream_unavailable"),
+            (
+                KiroError::new(KiroErrorCode::DeadlineExceeded, "deadline").into(),
+                "deadline_exceeded",
+            ),
+        ];
+
+        for (err, expected) in cases {
+            let response = upstream_error_response(err, &[]);
+            assert_eq!(error_code_of(response).await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_failures_carry_stable_error_codes() {
+        // 没有任何凭据
+        let response = post_messages(
+            State(outage_state(Config::default())),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(outage_request(false)),
+        )
+        .await;
+        assert_eq!(response.status(), ApiErrorKind::Overloaded.status());
+        assert_eq!(error_code_of(response).await, "no_credentials_available");
+
+        // 不支持的模型
+        let mut request = outage_request(false);
+        request.model = "gpt-4o".to_string();
+        let response = post_messages(
+            State(outage_state(Config::default())),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(request),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_code_of(response).await, "model_unsupported");
+
+        // 本地限流
+        let response = model_rate_limited_response("claude-opus-4-5", Duration::from_secs(1));
+        assert_eq!(error_code_of(response).await, "rate_limited");
+    }
+
+    #[test]
+    fn test_resolve_max_tokens_injects_default() {
+        let mut config = Config {
+            default_max_tokens: 4096,
+            ..Default::default()
+        };
+        assert_eq!(resolve_max_tokens(None, &config), Ok(4096));
+        assert_eq!(resolve_max_tokens(Some(64), &config), Ok(64));
+
+        // require 优先于默认值
+        config.require_max_tokens = true;
+        assert!(resolve_max_tokens(None, &config).is_err());
+        assert_eq!(resolve_max_tokens(Some(64), &config), Ok(64));
+    }
+
+    #[tokio::test]
+    async fn test_require_max_tokens_rejects_missing_field() {
+        let config = Config {
+            require_max_tokens: true,
+            ..Default::default()
+        };
+
+        let mut request = outage_request(false);
+        request.max_tokens = None;
+        let response = post_messages(
+            State(outage_state(config)),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(request),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["kiro_error_code"], "invalid_request");
+        assert!(body["error"]["message"].as_str().unwrap().contains("max_tokens"));
+
+        // 未开启时填充默认值后继续处理（此处没有凭据）
+        let mut request = outage_request(false);
+        request.max_tokens = None;
+        let response = post_messages(
+            State(outage_state(Config::default())),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(request),
+        )
+        .await;
+        assert_eq!(response.status(), ApiErrorKind::Overloaded.status());
+    }
+
+    #[tokio::test]
+    async fn test_allowed_client_models_rejects_unlisted_model() {
+        let config = Config {
+            allowed_client_models: vec!["claude-sonnet-4.5".to_string()],
+            ..Default::default()
+        };
+
+        let mut request = outage_request(false);
+        request.model = "claude-opus-4-5".to_string();
+        let response = post_messages(
+            State(outage_state(config.clone())),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(request),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["kiro_error_code"], "model_unsupported");
+        let message = body["error"]["message"].as_str().unwrap();
+        assert!(message.contains("claude-opus-4-5"));
+        assert!(message.contains("claude-sonnet-4.5"));
+
+        // 映射后在白名单内：通过检查，继续进入凭据选择（此处没有凭据）
+        let response = post_messages(
+            State(outage_state(config)),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(outage_request(false)),
+        )
+        .await;
+        assert_eq!(response.status(), ApiErrorKind::Overloaded.status());
+        assert_eq!(error_code_of(response).await, "no_credentials_available");
+    }
+
+    #[test]
+    fn test_model_alias_resolution_and_passthrough() {
+        let aliases = HashMap::from([
+            ("claude-fast".to_string(), "claude-haiku-4.5".to_string()),
+            ("claude-smart".to_string(), "claude-opus-4-5-20251101".to_string()),
+        ]);
+
+        let model = resolve_model_alias("claude-fast", &aliases);
+        assert_eq!(model, "claude-haiku-4.5");
+        assert_eq!(map_model(&model).as_deref(), Some("claude-haiku-4.5"));
+
+        // 非别名（包括大小写不同的别名）原样透传
+        for name in ["claude-sonnet-4-5-20250929", "CLAUDE-FAST", "gpt-4"] {
+            assert_eq!(resolve_model_alias(name, &aliases), name);
+        }
+
+        let models = alias_models(&aliases, &builtin_models());
+        let ids: Vec<_> = models.iter().map(|model| model.id.as_str()).collect();
+        assert_eq!(ids, ["claude-fast", "claude-smart"]);
+        assert_eq!(models[0].display_name, "claude-fast (alias of claude-haiku-4.5)");
+        assert_eq!(models[1].created, 1730419200);
+    }
+
+    #[tokio::test]
+    async fn test_model_alias_applied_before_allowed_client_models() {
+        let mut config = Config {
+            allowed_client_models: vec!["claude-haiku-4.5".to_string()],
+            ..Default::default()
+        };
+        config.model_aliases =
+            HashMap::from([("claude-fast".to_string(), "claude-haiku-4.5".to_string())]);
+        let state = outage_state(config);
+
+        // 别名解析后在白名单内：通过检查，继续进入凭据选择（此处没有凭据）
+        let mut request = outage_request(false);
+        request.model = "claude-fast".to_string();
+        let response = post_messages(
+            State(state.clone()),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(request),
+        )
+        .await;
+        assert_eq!(response.status(), ApiErrorKind::Overloaded.status());
+        assert_eq!(error_code_of(response).await, "no_credentials_available");
+
+        // 未配置别名的模型原样透传，按原名检查白名单
+        let response = post_messages(
+            State(state.clone()),
+            None,
+            HeaderMap::new(),
+            JsonExtractor(outage_request(false)),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = get_models(State(state)).await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let ids: Vec<_> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|model| model["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids[3], "claude-fast");
+    }
+
+    #[tokio::test]
+    async fn test_model_alias_only_changes_upstream_model() {
+        use crate::kiro::model::credentials::KiroCredentials;
+        use crate::kiro::provider::KiroProvider;
+        use crate::kiro::token_manager::MultiTokenManager;
+
+        // mock 上游：记录请求体后返回 503，触发降级响应
+        let captured = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+        let app = axum::Router::new().route(
+            "/generateAssistantResponse",
+            axum::routing::post({
+                let captured = captured.clone();
+                move |body: String| async move {
+                    *captured.lock().unwrap() = body;
+                    (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/generateAssistantResponse",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let config = Config {
+            retry_base_delay_ms: 1,
+            enable_outage_fallback: true,
+            outage_fallback_message: Some("degraded".to_string()),
+            model_aliases: HashMap::from([(
+                "claude-fast".to_string(),
+                "claude-haiku-4.5".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(config, vec![credentials], None, None, false).unwrap();
+        let provider = KiroProvider::new(std::sync::Arc::new(manager)).with_upstream_url(url);
+        let state = AppState::new("test-key").with_kiro_provider(provider);
+
+        let mut request = outage_request(false);
+        request.model = "claude-fast".to_string();
+        let response =
+            post_messages(State(state), None, HeaderMap::new(), JsonExtractor(request)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // 上游收到解析后的模型，响应保留客户端请求的别名
+        assert!(captured.lock().unwrap().contains(r#""modelId":"claude-haiku-4.5""#));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["model"], "claude-fast");
+    }
 }
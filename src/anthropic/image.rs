@@ -0,0 +1,470 @@
+//! 图片内容块预处理
+//!
+//! Kiro 上游只接受 base64 图片数据：转换请求前将 `url` 图片源解析（data URL）或下载
+//! 并改写为 base64 图片源；所有图片校验媒体类型，并按 `max_image_bytes` 限制单张图片大小。
+//!
+//! http(s) 图片 URL 只从 `image_url_allowed_hosts` 中的主机下载：下载前自行解析 DNS，
+//! 拒绝内网、回环、链路本地等非公网地址并固定解析结果，不跟随重定向，读取大小有硬上限
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Value, json};
+
+use crate::kiro::error_code::KiroErrorCode;
+use crate::kiro::provider::read_body_with_limit;
+use crate::model::config::Config;
+
+use super::types::Message;
+
+/// 上游支持的图片媒体类型
+pub const SUPPORTED_MEDIA_TYPES: [&str; 4] =
+    ["image/jpeg", "image/png", "image/gif", "image/webp"];
+
+/// 下载图片的字节数硬上限（`max_image_bytes` 为 0 时使用）
+const MAX_FETCH_BYTES: usize = 20 * 1024 * 1024;
+
+/// 下载图片的超时时间
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 图片校验或下载失败（对应 400 响应）
+#[derive(Debug)]
+pub struct ImageError(pub String);
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// 图片校验与下载策略
+#[derive(Debug, Clone, Default)]
+pub struct ImagePolicy {
+    /// 单张图片最大字节数（0 表示不限制，下载仍受 [`MAX_FETCH_BYTES`] 限制）
+    max_bytes: usize,
+    /// 允许下载的主机（小写，`*.` 开头表示匹配子域名）
+    allowed_hosts: Vec<String>,
+    /// 是否允许下载非公网地址（仅测试使用本地服务器时开启）
+    allow_private_addrs: bool,
+}
+
+impl ImagePolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_bytes: config.max_image_bytes,
+            allowed_hosts: config
+                .image_url_allowed_hosts
+                .iter()
+                .map(|host| host.trim().to_ascii_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+            allow_private_addrs: false,
+        }
+    }
+
+    /// 主机是否在允许下载的列表中
+    fn host_allowed(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.allowed_hosts.iter().any(|allowed| match allowed.strip_prefix("*.") {
+            Some(suffix) => host
+                .strip_suffix(suffix)
+                .is_some_and(|prefix| prefix.ends_with('.')),
+            None => *allowed == host,
+        })
+    }
+}
+
+/// 校验并规范化所有消息中的图片内容块
+///
+/// - `base64` 图片源：校验媒体类型和解码后大小
+/// - `url` 图片源：data URL 直接解析，http(s) URL 按 [`ImagePolicy`] 下载，
+///   媒体类型取自响应的 Content-Type，随后改写为 `base64` 图片源
+pub async fn prepare_images(
+    messages: &mut [Message],
+    policy: &ImagePolicy,
+) -> Result<(), ImageError> {
+    for message in messages.iter_mut() {
+        let Value::Array(blocks) = &mut message.content else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            if block.get("type").and_then(Value::as_str) != Some("image") {
+                continue;
+            }
+            let source = block
+                .get_mut("source")
+                .ok_or_else(|| ImageError("image 内容块缺少 source".to_string()))?;
+            prepare_source(source, policy).await?;
+        }
+    }
+    Ok(())
+}
+
+/// 校验单个图片源，`url` 图片源改写为 `base64` 图片源
+async fn prepare_source(source: &mut Value, policy: &ImagePolicy) -> Result<(), ImageError> {
+    let source_type = source.get("type").and_then(Value::as_str).unwrap_or_default();
+    match source_type {
+        "base64" => {
+            let media_type = source.get("media_type").and_then(Value::as_str).unwrap_or_default();
+            check_media_type(media_type)?;
+            let data = source.get("data").and_then(Value::as_str).unwrap_or_default();
+            check_size(decoded_len(data), policy.max_bytes)
+        }
+        "url" => {
+            let url = source.get("url").and_then(Value::as_str).unwrap_or_default();
+            let (media_type, data) = match parse_data_url(url) {
+                Some((media_type, data)) => {
+                    check_media_type(media_type)?;
+                    check_size(decoded_len(data), policy.max_bytes)?;
+                    (media_type.to_string(), data.to_string())
+                }
+                None => fetch_image(url, policy).await?,
+            };
+            *source = json!({"type": "base64", "media_type": media_type, "data": data});
+            Ok(())
+        }
+        other => Err(ImageError(format!("不支持的图片源类型: {}", other))),
+    }
+}
+
+/// 下载 http(s) 图片，返回媒体类型和 base64 数据
+///
+/// 错误信息不包含 URL 和上游状态码，避免把服务端网络探测结果回显给客户端
+async fn fetch_image(url: &str, policy: &ImagePolicy) -> Result<(String, String), ImageError> {
+    let parsed = reqwest::Url::parse(url)
+        .ok()
+        .filter(|parsed| matches!(parsed.scheme(), "http" | "https"))
+        .ok_or_else(|| ImageError("图片 URL 仅支持 http(s) 或 base64 data URL".to_string()))?;
+    let (Some(host), Some(port)) = (parsed.host_str(), parsed.port_or_known_default()) else {
+        return Err(ImageError("图片 URL 缺少主机".to_string()));
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+    if !policy.host_allowed(&host) {
+        return Err(ImageError(
+            "不允许下载该主机的图片（未在 imageUrlAllowedHosts 中配置），请改用 base64 图片源"
+                .to_string(),
+        ));
+    }
+
+    // 自行解析并校验地址，下载时固定使用校验过的地址（防止 DNS 重绑定）
+    let ip_literal = host.parse::<IpAddr>().ok();
+    let addrs: Vec<SocketAddr> = match ip_literal {
+        Some(ip) => vec![SocketAddr::new(ip, port)],
+        None => tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .map_err(|_| ImageError("下载图片失败: 无法解析图片主机".to_string()))?
+            .collect(),
+    };
+    if addrs.is_empty()
+        || (!policy.allow_private_addrs && addrs.iter().any(|addr| !is_public_ip(addr.ip())))
+    {
+        return Err(ImageError("图片主机解析到内网或保留地址，已拒绝下载".to_string()));
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .timeout(FETCH_TIMEOUT);
+    if ip_literal.is_none() {
+        builder = builder.resolve_to_addrs(&host, &addrs);
+    }
+    let client = builder
+        .build()
+        .map_err(|e| ImageError(format!("下载图片失败: {}", e)))?;
+
+    let response = client
+        .get(parsed)
+        .send()
+        .await
+        .map_err(|_| ImageError("下载图片失败: 无法连接图片主机".to_string()))?;
+    if !response.status().is_success() {
+        return Err(ImageError("下载图片失败: 图片主机未返回成功响应".to_string()));
+    }
+
+    let media_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    check_media_type(&media_type)?;
+
+    let limit = match policy.max_bytes {
+        0 => MAX_FETCH_BYTES,
+        max_bytes => max_bytes,
+    };
+    let bytes = read_body_with_limit(response, limit).await.map_err(|e| {
+        if KiroErrorCode::of(&e) == KiroErrorCode::UpstreamResponseTooLarge {
+            ImageError(format!("图片大小超过上限 {} 字节（maxImageBytes）", limit))
+        } else {
+            ImageError("下载图片失败: 读取图片数据出错".to_string())
+        }
+    })?;
+    Ok((media_type, STANDARD.encode(&bytes)))
+}
+
+/// 是否为公网地址（拒绝回环、私有、链路本地、CGNAT、组播、文档和保留地址）
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// 解析 `data:<media_type>;base64,<data>` 形式的 URL
+fn parse_data_url(url: &str) -> Option<(&str, &str)> {
+    url.strip_prefix("data:")?.split_once(";base64,")
+}
+
+fn check_media_type(media_type: &str) -> Result<(), ImageError> {
+    if SUPPORTED_MEDIA_TYPES.contains(&media_type) {
+        return Ok(());
+    }
+    Err(ImageError(format!(
+        "不支持的图片媒体类型: {}（支持 {}）",
+        if media_type.is_empty() { "未知" } else { media_type },
+        SUPPORTED_MEDIA_TYPES.join(", ")
+    )))
+}
+
+fn check_size(len: usize, max_bytes: usize) -> Result<(), ImageError> {
+    if max_bytes > 0 && len > max_bytes {
+        return Err(ImageError(format!(
+            "图片大小 {} 字节超过上限 {} 字节（maxImageBytes）",
+            len, max_bytes
+        )));
+    }
+    Ok(())
+}
+
+/// base64 数据解码后的字节数（不实际解码）
+fn decoded_len(data: &str) -> usize {
+    let data = data.trim_end_matches('=');
+    data.len() * 3 / 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::header, routing::get};
+
+    /// 1x1 透明 PNG
+    const PNG_1X1: &str = concat!(
+        "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJ",
+        "RU5ErkJggg=="
+    );
+
+    fn policy(max_bytes: usize) -> ImagePolicy {
+        ImagePolicy {
+            max_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// 允许从本地测试服务器下载的策略
+    fn local_policy(max_bytes: usize) -> ImagePolicy {
+        ImagePolicy {
+            max_bytes,
+            allowed_hosts: vec!["127.0.0.1".to_string()],
+            allow_private_addrs: true,
+        }
+    }
+
+    fn image_message(source: Value) -> Message {
+        Message {
+            role: "user".to_string(),
+            content: json!([
+                {"type": "text", "text": "What is in this image?"},
+                {"type": "image", "source": source}
+            ]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_valid_base64_png_passes_unchanged() {
+        let source = json!({"type": "base64", "media_type": "image/png", "data": PNG_1X1});
+        let mut messages = vec![image_message(source.clone())];
+
+        prepare_images(&mut messages, &policy(5 * 1024 * 1024))
+            .await
+            .unwrap();
+        assert_eq!(messages[0].content[1]["source"], source);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_or_unsupported_image_rejected() {
+        let big = STANDARD.encode(vec![0u8; 2048]);
+        let mut messages = vec![image_message(
+            json!({"type": "base64", "media_type": "image/png", "data": big}),
+        )];
+        let err = prepare_images(&mut messages, &policy(1024)).await.unwrap_err();
+        assert!(err.to_string().contains("超过上限 1024 字节"), "{}", err);
+
+        // 0 表示不限制
+        prepare_images(&mut messages, &policy(0)).await.unwrap();
+
+        let mut messages = vec![image_message(
+            json!({"type": "base64", "media_type": "image/bmp", "data": PNG_1X1}),
+        )];
+        let err = prepare_images(&mut messages, &policy(0)).await.unwrap_err();
+        assert!(err.to_string().contains("image/bmp"), "{}", err);
+    }
+
+    async fn serve(app: Router) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_url_source_downloaded_as_base64() {
+        let png = STANDARD.decode(PNG_1X1).unwrap();
+        let app = Router::new()
+            .route(
+                "/cat.png",
+                get(move || {
+                    let png = png.clone();
+                    async move { ([(header::CONTENT_TYPE, "image/png")], png) }
+                }),
+            )
+            .route(
+                "/big.png",
+                get(|| async { ([(header::CONTENT_TYPE, "image/png")], vec![0u8; 4096]) }),
+            )
+            .route("/page", get(|| async { "<html></html>" }));
+        let addr = serve(app).await;
+        let policy = local_policy(1024);
+
+        let url = format!("http://{}/cat.png", addr);
+        let mut messages = vec![image_message(json!({"type": "url", "url": url}))];
+        prepare_images(&mut messages, &policy).await.unwrap();
+        assert_eq!(
+            messages[0].content[1]["source"],
+            json!({"type": "base64", "media_type": "image/png", "data": PNG_1X1})
+        );
+
+        let url = format!("http://{}/big.png", addr);
+        let mut messages = vec![image_message(json!({"type": "url", "url": url}))];
+        let err = prepare_images(&mut messages, &policy).await.unwrap_err();
+        assert!(err.to_string().contains("maxImageBytes"), "{}", err);
+
+        let url = format!("http://{}/page", addr);
+        let mut messages = vec![image_message(json!({"type": "url", "url": url}))];
+        let err = prepare_images(&mut messages, &policy).await.unwrap_err();
+        assert!(err.to_string().contains("text/plain"), "{}", err);
+
+        // data URL 无需下载
+        let url = format!("data:image/png;base64,{}", PNG_1X1);
+        let mut messages = vec![image_message(json!({"type": "url", "url": url}))];
+        prepare_images(&mut messages, &policy).await.unwrap();
+        assert_eq!(messages[0].content[1]["source"]["data"], PNG_1X1);
+    }
+
+    #[tokio::test]
+    async fn test_url_fetch_restricted_to_allowed_public_hosts() {
+        let png = STANDARD.decode(PNG_1X1).unwrap();
+        let app = Router::new()
+            .route(
+                "/cat.png",
+                get(move || {
+                    let png = png.clone();
+                    async move { ([(header::CONTENT_TYPE, "image/png")], png) }
+                }),
+            )
+            .route(
+                "/redirect",
+                get(|| async {
+                    (
+                        axum::http::StatusCode::FOUND,
+                        [(header::LOCATION, "/cat.png")],
+                    )
+                }),
+            );
+        let addr = serve(app).await;
+        let fetch = |path: &str, policy: ImagePolicy| {
+            let url = format!("http://{}{}", addr, path);
+            async move {
+                let mut messages = vec![image_message(json!({"type": "url", "url": url}))];
+                prepare_images(&mut messages, &policy).await
+            }
+        };
+
+        // 默认不允许下载任何主机
+        let err = fetch("/cat.png", policy(1024)).await.unwrap_err();
+        assert!(err.to_string().contains("imageUrlAllowedHosts"), "{}", err);
+
+        // 主机在白名单中但解析到回环地址
+        let allowed = ImagePolicy {
+            allow_private_addrs: false,
+            ..local_policy(1024)
+        };
+        let err = fetch("/cat.png", allowed).await.unwrap_err();
+        assert!(err.to_string().contains("内网"), "{}", err);
+
+        // 不跟随重定向，错误信息不回显 URL 和状态码
+        let err = fetch("/redirect", local_policy(1024)).await.unwrap_err();
+        assert!(!err.to_string().contains("127.0.0.1"), "{}", err);
+        assert!(!err.to_string().contains("302"), "{}", err);
+    }
+
+    #[test]
+    fn test_host_allowlist_and_public_ip_filter() {
+        let policy = ImagePolicy {
+            allowed_hosts: vec!["cdn.example.com".to_string(), "*.images.example.org".to_string()],
+            ..Default::default()
+        };
+        assert!(policy.host_allowed("CDN.example.com"));
+        assert!(policy.host_allowed("a.images.example.org"));
+        assert!(!policy.host_allowed("images.example.org"));
+        assert!(!policy.host_allowed("evilimages.example.org"));
+        assert!(!policy.host_allowed("example.com"));
+
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:2800:220:1::".parse().unwrap()));
+    }
+}
@@ -5,26 +5,39 @@ use std::sync::Arc;
 use axum::{
     body::Body,
     extract::State,
-    http::{Request, StatusCode},
+    http::Request,
     middleware::Next,
-    response::{IntoResponse, Json, Response},
+    response::{IntoResponse, Response},
 };
+use futures::StreamExt;
 
 use crate::common::auth;
+use crate::common::body_limit;
+use crate::common::concurrency::KeyedConcurrencyLimiter;
+use crate::common::rate_limit::{self, ApiKeyRateLimiter, KeyedRateLimiter};
+use crate::kiro::error_code::KiroErrorCode;
 use crate::kiro::provider::KiroProvider;
 
-use super::types::ErrorResponse;
+use super::error::{ApiError, ApiErrorKind};
 
 /// 应用共享状态
 #[derive(Clone)]
 pub struct AppState {
     /// API 密钥
     pub api_key: String,
+    /// 额外的 API 密钥（`apiKeys`），与 `api_key` 同样可以通过认证
+    pub additional_api_keys: Arc<Vec<String>>,
     /// Kiro Provider（可选，用于实际 API 调用）
     /// 内部使用 MultiTokenManager，已支持线程安全的多凭据管理
     pub kiro_provider: Option<Arc<KiroProvider>>,
     /// Profile ARN（可选，用于请求）
     pub profile_arn: Option<String>,
+    /// 按模型的全局限流器
+    pub model_rate_limiter: Arc<KeyedRateLimiter>,
+    /// 每个 API Key 的并发请求限制（Anthropic 与 OpenAI 兼容端点共用名额）
+    pub concurrency_limiter: Option<Arc<KeyedConcurrencyLimiter>>,
+    /// 按 API Key 的限流器（Anthropic 与 OpenAI 兼容端点共用令牌桶）
+    pub api_key_rate_limiter: Option<Arc<ApiKeyRateLimiter>>,
 }
 
 impl AppState {
@@ -32,11 +45,21 @@ impl AppState {
     pub fn new(api_key: impl Into<String>) -> Self {
         Self {
             api_key: api_key.into(),
+            additional_api_keys: Arc::new(Vec::new()),
             kiro_provider: None,
             profile_arn: None,
+            model_rate_limiter: Arc::new(KeyedRateLimiter::default()),
+            concurrency_limiter: None,
+            api_key_rate_limiter: None,
         }
     }
 
+    /// 设置额外的 API 密钥
+    pub fn with_additional_api_keys(mut self, keys: Vec<String>) -> Self {
+        self.additional_api_keys = Arc::new(keys);
+        self
+    }
+
     /// 设置 KiroProvider
     pub fn with_kiro_provider(mut self, provider: KiroProvider) -> Self {
         self.kiro_provider = Some(Arc::new(provider));
@@ -48,6 +71,31 @@ impl AppState {
         self.profile_arn = Some(arn.into());
         self
     }
+
+    /// 设置按模型的全局限流器
+    pub fn with_model_rate_limiter(mut self, limiter: KeyedRateLimiter) -> Self {
+        self.model_rate_limiter = Arc::new(limiter);
+        self
+    }
+
+    /// 设置每个 API Key 的并发请求限制
+    pub fn with_concurrency_limiter(mut self, limiter: KeyedConcurrencyLimiter) -> Self {
+        self.concurrency_limiter = Some(Arc::new(limiter));
+        self
+    }
+
+    /// 设置按 API Key 的限流器
+    pub fn with_api_key_rate_limiter(mut self, limiter: ApiKeyRateLimiter) -> Self {
+        self.api_key_rate_limiter = Some(Arc::new(limiter));
+        self
+    }
+
+    /// API Key 是否为 `api_key` 或额外的 API 密钥之一
+    fn is_valid_api_key(&self, key: &str) -> bool {
+        std::iter::once(&self.api_key)
+            .chain(self.additional_api_keys.iter())
+            .any(|k| auth::constant_time_eq(key, k))
+    }
 }
 
 /// API Key 认证中间件
@@ -57,12 +105,87 @@ pub async fn auth_middleware(
     next: Next,
 ) -> Response {
     match auth::extract_api_key(&request) {
-        Some(key) if auth::constant_time_eq(&key, &state.api_key) => next.run(request).await,
-        _ => {
-            let error = ErrorResponse::authentication_error();
-            (StatusCode::UNAUTHORIZED, Json(error)).into_response()
-        }
+        Some(key) if state.is_valid_api_key(&key) => next.run(request).await,
+        _ => ApiError::new(ApiErrorKind::Authentication, "Invalid API key").into_response(),
+    }
+}
+
+/// 按 API Key 的并发限制中间件（位于认证之后）
+///
+/// 同一 API Key 的进行中请求达到 `max_concurrent_per_key` 时返回 429。
+/// 名额在响应体（含流式响应）发送完毕或客户端断开时释放
+pub async fn concurrency_limit_middleware(
+    State(limiter): State<Arc<KeyedConcurrencyLimiter>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let key = auth::extract_api_key(&request).unwrap_or_default();
+    let Some(permit) = limiter.try_acquire(&key) else {
+        tracing::warn!(
+            "API Key 并发请求数已达上限 {}，拒绝请求",
+            limiter.max_concurrent()
+        );
+        return ApiError::from_code(
+            KiroErrorCode::RateLimited,
+            format!(
+                "并发请求数已达上限 {}，请等待进行中的请求完成后重试",
+                limiter.max_concurrent()
+            ),
+        )
+        .into_response();
+    };
+
+    let (parts, body) = next.run(request).await.into_parts();
+    // 许可随响应体流一起释放
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &permit;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+/// 按 API Key 的限流中间件（位于认证之后，并发限制之前）
+///
+/// 令牌桶耗尽时返回 429 并通过 `Retry-After` 提示重试等待秒数
+pub async fn rate_limit_middleware(
+    State(limiter): State<Arc<ApiKeyRateLimiter>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let key = auth::extract_api_key(&request).unwrap_or_default();
+    let header_value = limiter
+        .key_header()
+        .and_then(|name| request.headers().get(name))
+        .and_then(|v| v.to_str().ok());
+    if let Err(wait) = limiter.try_acquire(&key, header_value) {
+        let retry_after = rate_limit::retry_after_secs(wait);
+        tracing::warn!("API Key 触发限流，{} 秒后重试", retry_after);
+        return ApiError::from_code(
+            KiroErrorCode::RateLimited,
+            format!("请求过于频繁，请在 {} 秒后重试", retry_after),
+        )
+        .with_retry_after(retry_after)
+        .into_response();
     }
+    next.run(request).await
+}
+
+/// 请求体大小限制中间件（`max_request_bytes`）
+///
+/// 超限时返回 413 `request_too_large`，分块上传在读取超过上限时同样返回 413
+pub async fn body_limit_middleware(
+    State(limit): State<usize>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    body_limit::limit_body(limit, request, next, |limit| {
+        ApiError::new(
+            ApiErrorKind::RequestTooLarge,
+            format!("请求体超过上限 {} 字节", limit),
+        )
+        .into_response()
+    })
+    .await
 }
 
 /// CORS 中间件层
@@ -82,3 +205,250 @@ pub fn cors_layer() -> tower_http::cors::CorsLayer {
         .allow_methods(Any)
         .allow_headers(Any)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Json, Router,
+        extract::DefaultBodyLimit,
+        middleware,
+        routing::{get, post},
+    };
+    use std::time::Duration;
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_body_over_limit_rejected_with_413() {
+        let limit = 1024;
+        let app = Router::new()
+            .route(
+                "/echo",
+                post(|Json(body): Json<serde_json::Value>| async move { Json(body) }),
+            )
+            .layer(middleware::from_fn_with_state(limit, body_limit_middleware))
+            .layer(DefaultBodyLimit::max(limit));
+        let url = format!("{}/echo", serve(app).await);
+        let client = reqwest::Client::new();
+        let oversized = serde_json::json!({ "text": "x".repeat(limit * 2) }).to_string();
+
+        let response = client
+            .post(&url)
+            .header("content-type", "application/json")
+            .body(oversized.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 413);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["type"], "error");
+        assert_eq!(body["error"]["type"], "request_too_large");
+
+        // 分块上传（无 Content-Length）读取超限时同样返回 413
+        let chunks: Vec<Result<String, std::io::Error>> = oversized
+            .as_bytes()
+            .chunks(256)
+            .map(|chunk| Ok(String::from_utf8_lossy(chunk).into_owned()))
+            .collect();
+        let response = client
+            .post(&url)
+            .header("content-type", "application/json")
+            .body(reqwest::Body::wrap_stream(futures::stream::iter(chunks)))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 413);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "request_too_large");
+
+        let response = client
+            .post(&url)
+            .header("content-type", "application/json")
+            .body(r#"{"text":"ok"}"#)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_key_over_concurrency_cap_rejected_while_other_keys_flow() {
+        let limiter = Arc::new(KeyedConcurrencyLimiter::new(1));
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    "done"
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                limiter,
+                concurrency_limit_middleware,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/slow", addr);
+        let send = |key: &'static str| client.get(&url).header("x-api-key", key).send();
+
+        let first = tokio::spawn(send("key-a"));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let rejected = send("key-a").await.unwrap();
+        assert_eq!(rejected.status(), 429);
+        let body: serde_json::Value = rejected.json().await.unwrap();
+        assert_eq!(body["error"]["kiro_error_code"], "rate_limited");
+
+        assert_eq!(send("key-b").await.unwrap().status(), 200);
+
+        let first = first.await.unwrap().unwrap();
+        assert_eq!(first.text().await.unwrap(), "done");
+        // 首个请求完成后名额归还
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(send("key-a").await.unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_response_holds_concurrency_slot_through_router() {
+        use crate::anthropic::create_router;
+        use crate::kiro::model::credentials::KiroCredentials;
+        use crate::kiro::token_manager::MultiTokenManager;
+        use crate::model::config::Config;
+
+        // mock 上游：响应头立即返回，响应体保持打开直到 release 置为 true
+        let (release, release_rx) = tokio::sync::watch::channel(false);
+        let upstream = Router::new().route(
+            "/generateAssistantResponse",
+            post(move || {
+                let mut release_rx = release_rx.clone();
+                async move {
+                    let body = futures::stream::once(async move {
+                        let _ = release_rx.wait_for(|released| *released).await;
+                        Ok::<_, std::convert::Infallible>(axum::body::Bytes::new())
+                    });
+                    Body::from_stream(body)
+                }
+            }),
+        );
+        let upstream_url = format!("{}/generateAssistantResponse", serve(upstream).await);
+
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![credentials], None, None, false)
+                .unwrap();
+        let provider = KiroProvider::new(Arc::new(manager)).with_upstream_url(upstream_url);
+        let app = create_router(
+            AppState::new("key-a")
+                .with_additional_api_keys(vec!["key-b".to_string()])
+                .with_concurrency_limiter(KeyedConcurrencyLimiter::new(1))
+                .with_kiro_provider(provider),
+        );
+        let base = serve(app).await;
+        let client = reqwest::Client::new();
+        let open_stream = |key: &'static str| {
+            client
+                .post(format!("{}/v1/messages", base))
+                .header("x-api-key", key)
+                .json(&serde_json::json!({
+                    "model": "claude-sonnet-4-5",
+                    "max_tokens": 16,
+                    "stream": true,
+                    "messages": [{ "role": "user", "content": "hi" }]
+                }))
+                .send()
+        };
+        let probe = |key: &'static str| {
+            let request = client
+                .get(format!("{}/v1/models", base))
+                .header("x-api-key", key);
+            async move { request.send().await.unwrap().status() }
+        };
+        // 名额在响应体结束后异步归还，轮询等待
+        let wait_for_slot = |key: &'static str| async move {
+            for _ in 0..50 {
+                if probe(key).await == 200 {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            panic!("concurrency slot for {key} was not released");
+        };
+
+        // 客户端在流结束前断开：名额随响应体一起释放
+        let dropped = open_stream("key-a").await.unwrap();
+        assert_eq!(dropped.status(), 200);
+        assert_eq!(probe("key-a").await, 429);
+        assert_eq!(probe("key-b").await, 200);
+        drop(dropped);
+        wait_for_slot("key-a").await;
+
+        // 流正常结束：读完响应体后名额归还
+        let streaming = open_stream("key-a").await.unwrap();
+        assert_eq!(streaming.status(), 200);
+        assert_eq!(probe("key-a").await, 429);
+        assert_eq!(probe("key-b").await, 200);
+        release.send(true).unwrap();
+        streaming.text().await.unwrap();
+        wait_for_slot("key-a").await;
+    }
+
+    #[tokio::test]
+    async fn test_api_key_rate_limit_returns_429_then_recovers_after_refill() {
+        let mut limits = std::collections::HashMap::new();
+        // 每 100ms 补充 1 个令牌，突发容量 2
+        limits.insert(
+            "key-a".to_string(),
+            crate::model::config::RateLimit {
+                requests_per_minute: 600,
+                burst: Some(2),
+            },
+        );
+        let state = AppState::new("key-a")
+            .with_additional_api_keys(vec!["key-b".to_string()])
+            .with_api_key_rate_limiter(ApiKeyRateLimiter::new(limits, None));
+        let limiter = state.api_key_rate_limiter.clone().unwrap();
+        let app = Router::new()
+            .route("/ping", get(|| async { "pong" }))
+            .layer(middleware::from_fn_with_state(limiter, rate_limit_middleware))
+            .layer(middleware::from_fn_with_state(state, auth_middleware));
+        let url = format!("{}/ping", serve(app).await);
+        let client = reqwest::Client::new();
+        let send = |key: &'static str| client.get(&url).header("x-api-key", key).send();
+
+        assert_eq!(send("key-a").await.unwrap().status(), 200);
+        assert_eq!(send("key-a").await.unwrap().status(), 200);
+        let limited = send("key-a").await.unwrap();
+        assert_eq!(limited.status(), 429);
+        assert_eq!(limited.headers()["retry-after"], "1");
+        let body: serde_json::Value = limited.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+        assert_eq!(body["error"]["kiro_error_code"], "rate_limited");
+
+        // 额外的 API Key 可以通过认证，且未配置限流时不受影响
+        for _ in 0..5 {
+            assert_eq!(send("key-b").await.unwrap().status(), 200);
+        }
+        assert_eq!(send("key-c").await.unwrap().status(), 401);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(send("key-a").await.unwrap().status(), 200);
+    }
+}
@@ -6,22 +6,40 @@
 //! - `GET /v1/models` - 获取可用模型列表
 //! - `POST /v1/messages` - 创建消息（对话）
 //! - `POST /v1/messages/count_tokens` - 计算 token 数量
+//! - `POST /v1/complete` - 旧版 Text Completions（转换后复用 `/v1/messages` 的处理链路）
 //!
 //! # 使用示例
 //! ```rust,ignore
 //! use kiro_rs::anthropic;
 //!
-//! let app = anthropic::create_router("your-api-key");
+//! let app = anthropic::create_router(anthropic::AppState::new("your-api-key"));
 //! let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
 //! axum::serve(listener, app).await?;
 //! ```
 
+mod access_log;
+mod archive;
+mod complete;
 mod converter;
+mod error;
 mod handlers;
+mod image;
 mod middleware;
 mod router;
+mod selftest;
 mod stream;
+mod strip_fields;
 pub mod types;
 mod websearch;
 
-pub use router::create_router_with_provider;
+pub use middleware::AppState;
+pub use router::{build_app_state, create_router};
+
+// 供 OpenAI 兼容端点复用 `/v1/messages` 的完整处理链路
+pub(crate) use handlers::post_messages;
+pub(crate) use middleware::{
+    auth_middleware, body_limit_middleware, concurrency_limit_middleware, cors_layer,
+    rate_limit_middleware,
+};
+pub(crate) use router::max_request_bytes;
+pub use selftest::startup_gate;
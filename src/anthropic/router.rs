@@ -1,23 +1,82 @@
 //! Anthropic API 路由配置
 
+use std::sync::Arc;
+
 use axum::{
-    Router, middleware,
+    Router,
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{get, post},
 };
 
+use crate::common::concurrency::KeyedConcurrencyLimiter;
+use crate::common::json_format::json_format_middleware;
+use crate::common::rate_limit::{ApiKeyRateLimiter, KeyedRateLimiter};
 use crate::kiro::provider::KiroProvider;
 
 use super::{
+    access_log::access_log_middleware,
+    archive::{Archiver, archive_middleware},
+    complete::post_complete,
     handlers::{count_tokens, get_models, post_messages},
-    middleware::{AppState, auth_middleware, cors_layer},
+    middleware::{
+        AppState, auth_middleware, body_limit_middleware, concurrency_limit_middleware, cors_layer,
+        rate_limit_middleware,
+    },
+    strip_fields::{FieldFilter, strip_fields_middleware},
 };
 
+/// 未配置 Kiro provider 时的请求体上限（与 `maxRequestBytes` 默认值一致）
+const DEFAULT_MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+
+/// 按配置构建的请求体上限
+pub(crate) fn max_request_bytes(state: &AppState) -> usize {
+    state
+        .kiro_provider
+        .as_ref()
+        .map(|p| p.token_manager().config().max_request_bytes)
+        .unwrap_or(DEFAULT_MAX_REQUEST_BYTES)
+}
+
+/// 按配置构建应用状态（Anthropic 与 OpenAI 兼容路由共用，限流和并发名额在两者之间共享）
+pub fn build_app_state(
+    api_key: impl Into<String>,
+    kiro_provider: Option<KiroProvider>,
+    profile_arn: Option<String>,
+) -> AppState {
+    let mut state = AppState::new(api_key);
+    if let Some(provider) = kiro_provider {
+        let config = provider.token_manager().config();
+        if !config.model_rate_limits.is_empty() {
+            state =
+                state.with_model_rate_limiter(KeyedRateLimiter::new(&config.model_rate_limits));
+        }
+        if let Some(max) = config.max_concurrent_per_key {
+            state = state.with_concurrency_limiter(KeyedConcurrencyLimiter::new(max));
+        }
+        if let Some(limiter) = ApiKeyRateLimiter::from_config(&config) {
+            state = state.with_api_key_rate_limiter(limiter);
+        }
+        if !config.api_keys.is_empty() {
+            state = state.with_additional_api_keys(
+                config.api_keys.iter().map(|k| k.api_key.clone()).collect(),
+            );
+        }
+        state = state.with_kiro_provider(provider);
+    }
+    if let Some(arn) = profile_arn {
+        state = state.with_profile_arn(arn);
+    }
+    state
+}
+
 /// 创建 Anthropic API 路由
 ///
 /// # 端点
 /// - `GET /v1/models` - 获取可用模型列表
 /// - `POST /v1/messages` - 创建消息（对话）
 /// - `POST /v1/messages/count_tokens` - 计算 token 数量
+/// - `POST /v1/complete` - 旧版 Text Completions（支持流式与非流式）
 ///
 /// # 认证
 /// 所有 `/v1` 路径需要 API Key 认证，支持：
@@ -25,32 +84,81 @@ use super::{
 /// - `Authorization: Bearer <token>` header
 ///
 /// # 参数
-/// - `api_key`: API 密钥，用于验证客户端请求
-/// - `kiro_provider`: 可选的 KiroProvider，用于调用上游 API
+/// - `state`: 由 [`build_app_state`] 构建的应用状态
+pub fn create_router(state: AppState) -> Router {
+    let pretty_json = state
+        .kiro_provider
+        .as_ref()
+        .map(|p| p.token_manager().config().pretty_json)
+        .unwrap_or(false);
+    let field_filter = Arc::new(
+        state
+            .kiro_provider
+            .as_ref()
+            .map(|p| {
+                let config = p.token_manager().config();
+                FieldFilter {
+                    pointers: config.strip_unsupported_fields.clone(),
+                    metadata_allowed_keys: Some(config.metadata_allowed_keys.clone()),
+                }
+            })
+            .unwrap_or_default(),
+    );
 
-/// 创建带有 KiroProvider 的 Anthropic API 路由
-pub fn create_router_with_provider(
-    api_key: impl Into<String>,
-    kiro_provider: Option<KiroProvider>,
-    profile_arn: Option<String>,
-) -> Router {
-    let mut state = AppState::new(api_key);
-    if let Some(provider) = kiro_provider {
-        state = state.with_kiro_provider(provider);
-    }
-    if let Some(arn) = profile_arn {
-        state = state.with_profile_arn(arn);
+    let archiver = state
+        .kiro_provider
+        .as_ref()
+        .and_then(|p| p.token_manager().config().archive.clone())
+        .map(|config| Arc::new(Archiver::new(config)));
+
+    let max_request_bytes = max_request_bytes(&state);
+
+    let mut messages_route = post(post_messages).layer(middleware::from_fn_with_state(
+        field_filter,
+        strip_fields_middleware,
+    ));
+    // 归档中间件位于字段过滤之外，记录客户端原始请求
+    if let Some(archiver) = archiver {
+        messages_route =
+            messages_route.layer(middleware::from_fn_with_state(archiver, archive_middleware));
     }
+    // 访问日志位于最外层，日志在响应体结束后输出
+    let messages_route = messages_route.layer(middleware::from_fn(access_log_middleware));
 
     // 需要认证的 /v1 路由
-    let v1_routes = Router::new()
+    let mut v1_routes = Router::new()
         .route("/models", get(get_models))
-        .route("/messages", post(post_messages))
+        .route("/messages", messages_route)
         .route("/messages/count_tokens", post(count_tokens))
+        .route("/complete", post(post_complete))
+        .layer(middleware::from_fn_with_state(
+            pretty_json,
+            json_format_middleware,
+        ))
+        // 请求体上限（含 base64 图片；axum 默认仅 2 MiB），位于字段过滤和归档的缓冲之外
         .layer(middleware::from_fn_with_state(
-            state.clone(),
-            auth_middleware,
+            max_request_bytes,
+            body_limit_middleware,
+        ))
+        .layer(DefaultBodyLimit::max(max_request_bytes));
+    // 并发限制位于认证之后，只对已认证的 API Key 计数
+    if let Some(limiter) = state.concurrency_limiter.clone() {
+        v1_routes = v1_routes.layer(middleware::from_fn_with_state(
+            limiter,
+            concurrency_limit_middleware,
         ));
+    }
+    // 按 API Key 限流位于认证之后、并发限制之前，被限流的请求不占用并发名额
+    if let Some(limiter) = state.api_key_rate_limiter.clone() {
+        v1_routes = v1_routes.layer(middleware::from_fn_with_state(
+            limiter,
+            rate_limit_middleware,
+        ));
+    }
+    let v1_routes = v1_routes.layer(middleware::from_fn_with_state(
+        state.clone(),
+        auth_middleware,
+    ));
 
     Router::new()
         .nest("/v1", v1_routes)
@@ -0,0 +1,159 @@
+//! 启动自检
+//!
+//! 在开始监听前通过完整请求链路（协议转换 -> KiroProvider -> 事件流解析）
+//! 发送一次真实请求，作为部署就绪的门禁
+
+use std::time::Instant;
+
+use crate::kiro::model::events::Event;
+use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::kiro::parser::decoder::EventStreamDecoder;
+use crate::kiro::provider::KiroProvider;
+use crate::kiro::token_manager::AcquireOptions;
+use crate::model::config::{SelftestMode, StartupSelftestConfig};
+
+use super::converter::convert_request;
+use super::types::{Message, MessagesRequest};
+
+/// 自检请求的最大输出 tokens
+const SELFTEST_MAX_TOKENS: i32 = 16;
+
+/// 执行一次启动自检请求
+///
+/// 上游返回至少一个助手响应事件即视为通过
+pub async fn run_startup_selftest(
+    provider: &KiroProvider,
+    config: &StartupSelftestConfig,
+    profile_arn: Option<String>,
+) -> anyhow::Result<()> {
+    let request = MessagesRequest {
+        model: config.model.clone(),
+        max_tokens: Some(SELFTEST_MAX_TOKENS),
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: serde_json::Value::String(config.prompt.clone()),
+        }],
+        stream: false,
+        system: None,
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: None,
+    };
+
+    let conversion =
+        convert_request(&request).map_err(|e| anyhow::anyhow!("自检请求转换失败: {}", e))?;
+    let request_body = serde_json::to_string(&KiroRequest {
+        conversation_state: conversion.conversation_state,
+        profile_arn,
+    })?;
+
+    let started = Instant::now();
+    let served = provider
+        .call_api(&request_body, &AcquireOptions::default())
+        .await?;
+    let body = provider.read_response_body(served.response).await?;
+
+    let mut decoder = EventStreamDecoder::new();
+    decoder.feed(&body)?;
+
+    let mut has_response = false;
+    for frame in decoder.decode_iter() {
+        match Event::from_frame(frame?)? {
+            Event::AssistantResponse(_) => has_response = true,
+            Event::Error {
+                error_code,
+                error_message,
+            } => anyhow::bail!("上游返回错误: {} {}", error_code, error_message),
+            Event::Exception {
+                exception_type,
+                message,
+            } => anyhow::bail!("上游返回异常: {} {}", exception_type, message),
+            _ => {}
+        }
+    }
+
+    if !has_response {
+        anyhow::bail!("上游响应中没有助手消息");
+    }
+
+    tracing::info!(
+        "启动自检通过（模型: {}，耗时 {} ms）",
+        config.model,
+        started.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+/// 启动门禁：按 `mode` 处理自检结果
+///
+/// - 未配置自检：直接通过
+/// - `required`：自检失败时返回错误，调用方应终止启动
+/// - `warn`：自检失败时仅记录警告
+pub async fn startup_gate(
+    provider: &KiroProvider,
+    config: Option<&StartupSelftestConfig>,
+    profile_arn: Option<String>,
+) -> anyhow::Result<()> {
+    let Some(config) = config else {
+        return Ok(());
+    };
+
+    tracing::info!("执行启动自检（模式: {:?}）...", config.mode);
+    match run_startup_selftest(provider, config, profile_arn).await {
+        Ok(()) => Ok(()),
+        Err(e) => match config.mode {
+            SelftestMode::Required => Err(e.context("启动自检失败")),
+            SelftestMode::Warn => {
+                tracing::warn!("启动自检失败（warn 模式，继续启动）: {}", e);
+                Ok(())
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::token_manager::MultiTokenManager;
+    use crate::model::config::Config;
+    use std::sync::Arc;
+
+    /// 没有任何凭据的 Provider，所有上游调用都会立即失败（不访问网络）
+    fn failing_provider() -> KiroProvider {
+        let manager = MultiTokenManager::new(Config::default(), vec![], None, None, false).unwrap();
+        KiroProvider::new(Arc::new(manager))
+    }
+
+    fn selftest_config(mode: SelftestMode) -> StartupSelftestConfig {
+        StartupSelftestConfig {
+            prompt: "ping".to_string(),
+            model: "claude-haiku-4-5".to_string(),
+            mode,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_required_selftest_failure_blocks_startup() {
+        let provider = failing_provider();
+        let config = selftest_config(SelftestMode::Required);
+
+        let result = startup_gate(&provider, Some(&config), None).await;
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("启动自检失败"));
+    }
+
+    #[tokio::test]
+    async fn test_warn_selftest_failure_allows_startup() {
+        let provider = failing_provider();
+        let config = selftest_config(SelftestMode::Warn);
+
+        assert!(startup_gate(&provider, Some(&config), None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_selftest_disabled_skips_request() {
+        let provider = failing_provider();
+        assert!(startup_gate(&provider, None, None).await.is_ok());
+    }
+}
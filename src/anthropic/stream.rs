@@ -186,6 +186,17 @@ impl SseEvent {
         }
     }
 
+    /// 流中途出错时的 `error` 事件
+    pub fn error(error_type: &str, message: impl Into<String>) -> Self {
+        Self::new(
+            "error",
+            json!({
+                "type": "error",
+                "error": { "type": error_type, "message": message.into() }
+            }),
+        )
+    }
+
     /// 格式化为 SSE 字符串
     pub fn to_sse_string(&self) -> String {
         format!(
@@ -293,6 +304,11 @@ impl SseStateManager {
         }
     }
 
+    /// message_start 是否已发送
+    pub fn is_message_started(&self) -> bool {
+        self.message_started
+    }
+
     /// 处理 message_start 事件
     pub fn handle_message_start(&mut self, event: serde_json::Value) -> Option<SseEvent> {
         if self.message_started {
@@ -477,6 +493,8 @@ pub struct StreamContext {
     pub thinking_block_index: Option<i32>,
     /// 文本块索引（thinking 启用时动态分配）
     pub text_block_index: Option<i32>,
+    /// 上游是否在流中途返回了错误事件（已发送 `error` 事件，不再发送结束事件）
+    pub upstream_failed: bool,
 }
 
 impl StreamContext {
@@ -500,10 +518,13 @@ impl StreamContext {
             thinking_extracted: false,
             thinking_block_index: None,
             text_block_index: None,
+            upstream_failed: false,
         }
     }
 
     /// 生成 message_start 事件
+    ///
+    /// input_tokens 优先使用上游 contextUsageEvent 计算的值，未收到时使用本地估算值
     pub fn create_message_start_event(&self) -> serde_json::Value {
         json!({
             "type": "message_start",
@@ -516,7 +537,7 @@ impl StreamContext {
                 "stop_reason": null,
                 "stop_sequence": null,
                 "usage": {
-                    "input_tokens": self.input_tokens,
+                    "input_tokens": self.context_input_tokens.unwrap_or(self.input_tokens),
                     "output_tokens": 1
                 }
             }
@@ -562,6 +583,32 @@ impl StreamContext {
         events
     }
 
+    /// 若尚未发送 message_start，生成初始事件序列；否则返回空
+    pub fn ensure_message_started(&mut self) -> Vec<SseEvent> {
+        if self.state_manager.is_message_started() {
+            return Vec::new();
+        }
+        self.generate_initial_events()
+    }
+
+    /// 处理一批 Kiro 事件
+    ///
+    /// message_start 延迟到第一个非用量事件之前发送，
+    /// 使其能携带上游在内容之前给出的输入 token 用量
+    pub fn process_kiro_events(
+        &mut self,
+        events: impl IntoIterator<Item = Event>,
+    ) -> Vec<SseEvent> {
+        let mut sse_events = Vec::new();
+        for event in events {
+            if !matches!(event, Event::ContextUsage(_)) {
+                sse_events.extend(self.ensure_message_started());
+            }
+            sse_events.extend(self.process_kiro_event(&event));
+        }
+        sse_events
+    }
+
     /// 处理 Kiro 事件并转换为 Anthropic SSE 事件
     pub fn process_kiro_event(&mut self, event: &Event) -> Vec<SseEvent> {
         match event {
@@ -586,7 +633,15 @@ impl StreamContext {
                 error_message,
             } => {
                 tracing::error!("收到错误事件: {} - {}", error_code, error_message);
-                Vec::new()
+                // 以 error 事件结束流，避免客户端把截断的输出当作正常完成
+                if self.upstream_failed {
+                    return Vec::new();
+                }
+                self.upstream_failed = true;
+                vec![SseEvent::error(
+                    "api_error",
+                    format!("上游返回错误: {} - {}", error_code, error_message),
+                )]
             }
             Event::Exception {
                 exception_type,
@@ -937,7 +992,8 @@ impl StreamContext {
 
     /// 生成最终事件序列
     pub fn generate_final_events(&mut self) -> Vec<SseEvent> {
-        let mut events = Vec::new();
+        // 上游未返回任何内容时也保证 message_start 在最前
+        let mut events = self.ensure_message_started();
 
         // Flush thinking_buffer 中的剩余内容
         if self.thinking_enabled && !self.thinking_buffer.is_empty() {
@@ -1082,6 +1138,101 @@ mod tests {
         assert!(event.is_none());
     }
 
+    fn assistant_event(content: &str) -> Event {
+        Event::AssistantResponse(serde_json::from_value(json!({ "content": content })).unwrap())
+    }
+
+    #[test]
+    fn test_message_start_precedes_content_with_upstream_usage() {
+        let mut ctx = StreamContext::new_with_thinking("claude-sonnet-4-5", 12, false);
+        let context_usage = Event::ContextUsage(
+            serde_json::from_value(json!({ "contextUsagePercentage": 1.5 })).unwrap(),
+        );
+
+        let events = ctx.process_kiro_events(vec![context_usage, assistant_event("hi")]);
+
+        assert_eq!(events[0].event, "message_start");
+        let message = &events[0].data["message"];
+        assert_eq!(message["model"], "claude-sonnet-4-5");
+        assert!(message["id"].as_str().unwrap().starts_with("msg_"));
+        // 1.5% * 200k
+        assert_eq!(message["usage"]["input_tokens"], 3000);
+        assert!(events.iter().any(|e| e.event == "content_block_delta"));
+
+        // message_start 只发送一次
+        let events = ctx.process_kiro_events(vec![assistant_event("again")]);
+        assert!(events.iter().all(|e| e.event != "message_start"));
+    }
+
+    #[test]
+    fn test_message_start_uses_local_count_without_upstream_usage() {
+        let mut ctx = StreamContext::new_with_thinking("claude-opus-4-5", 42, true);
+
+        let events = ctx.process_kiro_events(vec![assistant_event("hello")]);
+        assert_eq!(events[0].event, "message_start");
+        assert_eq!(events[0].data["message"]["model"], "claude-opus-4-5");
+        assert_eq!(events[0].data["message"]["usage"]["input_tokens"], 42);
+
+        // 上游没有任何内容时，最终事件中仍以 message_start 开头
+        let mut ctx = StreamContext::new_with_thinking("claude-opus-4-5", 42, false);
+        let events = ctx.generate_final_events();
+        assert_eq!(events[0].event, "message_start");
+        assert_eq!(events.last().unwrap().event, "message_stop");
+    }
+
+    #[test]
+    fn test_streamed_tool_use_round_trips_as_input_json_delta() {
+        use crate::kiro::model::events::ToolUseEvent;
+
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false);
+        let _initial_events = ctx.generate_initial_events();
+
+        // 上游分两片下发同一个工具调用的输入
+        let mut events = Vec::new();
+        for (input, stop) in [(r#"{"city": "Pa"#, false), (r#"ris"}"#, true)] {
+            events.extend(ctx.process_tool_use(&ToolUseEvent {
+                name: "get_weather".to_string(),
+                tool_use_id: "toolu_01".to_string(),
+                input: input.to_string(),
+                stop,
+            }));
+        }
+
+        let starts: Vec<_> = events
+            .iter()
+            .filter(|e| {
+                e.event == "content_block_start" && e.data["content_block"]["type"] == "tool_use"
+            })
+            .collect();
+        assert_eq!(starts.len(), 1, "同一工具调用只开始一个内容块");
+        let block = &starts[0].data["content_block"];
+        assert_eq!(block["id"], "toolu_01");
+        assert_eq!(block["name"], "get_weather");
+        assert_eq!(block["input"], serde_json::json!({}));
+        let index = starts[0].data["index"].clone();
+
+        let partial_json: String = events
+            .iter()
+            .filter(|e| {
+                e.event == "content_block_delta"
+                    && e.data["index"] == index
+                    && e.data["delta"]["type"] == "input_json_delta"
+            })
+            .map(|e| e.data["delta"]["partial_json"].as_str().unwrap())
+            .collect();
+        let input: serde_json::Value = serde_json::from_str(&partial_json).unwrap();
+        assert_eq!(input, serde_json::json!({"city": "Paris"}));
+        assert!(
+            events
+                .iter()
+                .any(|e| e.event == "content_block_stop" && e.data["index"] == index)
+        );
+
+        let final_events = ctx.generate_final_events();
+        let message_delta = final_events.iter().find(|e| e.event == "message_delta").unwrap();
+        assert_eq!(message_delta.data["delta"]["stop_reason"], "tool_use");
+    }
+
     #[test]
     fn test_text_delta_after_tool_use_restarts_text_block() {
         let mut ctx = StreamContext::new_with_thinking("test-model", 1, false);
@@ -1420,4 +1571,24 @@ mod tests {
             "`</thinking>` should be filtered during final flush"
         );
     }
+
+    #[test]
+    fn test_upstream_error_event_becomes_sse_error() {
+        let mut ctx = StreamContext::new_with_thinking("claude-sonnet-4", 10, false);
+        let events = ctx.process_kiro_events([
+            assistant_event("部分输出"),
+            Event::Error {
+                error_code: "InternalServerException".to_string(),
+                error_message: "boom".to_string(),
+            },
+        ]);
+
+        let last = events.last().unwrap();
+        assert_eq!(last.event, "error");
+        assert_eq!(last.data["error"]["type"], "api_error");
+        assert!(last.data["error"]["message"].as_str().unwrap().contains("boom"));
+        assert!(ctx.upstream_failed);
+        assert!(!events.iter().any(|e| e.event == "message_stop"));
+    }
+
 }
@@ -0,0 +1,301 @@
+//! 请求字段剔除
+//!
+//! 按 `config.strip_unsupported_fields`（JSON Pointer 列表）在转发前移除
+//! 上游不支持的请求字段，作为上游尚未支持新字段时的临时兜底；
+//! 同时按 `config.metadata_allowed_keys` 过滤客户端传入的 `metadata`
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+use super::error::{ApiError, ApiErrorKind};
+
+/// 过滤后 metadata 序列化的最大字节数，超过时整体丢弃
+const MAX_METADATA_BYTES: usize = 4 * 1024;
+
+/// 请求字段过滤规则（中间件状态）
+#[derive(Debug, Clone, Default)]
+pub struct FieldFilter {
+    /// 需要剔除的字段（JSON Pointer）
+    pub pointers: Vec<String>,
+    /// metadata 中允许保留的键，`None` 表示不过滤 metadata
+    pub metadata_allowed_keys: Option<Vec<String>>,
+}
+
+impl FieldFilter {
+    /// 是否没有任何过滤规则
+    fn is_empty(&self) -> bool {
+        self.pointers.is_empty() && self.metadata_allowed_keys.is_none()
+    }
+}
+
+/// 解码 JSON Pointer 的单个引用片段（`~1` -> `/`，`~0` -> `~`）
+fn unescape_token(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// 按 JSON Pointer 移除字段
+///
+/// 返回是否实际移除了字段；指针非法或目标不存在时不做任何修改
+fn remove_pointer(value: &mut Value, pointer: &str) -> bool {
+    let Some((parent, last)) = pointer.rsplit_once('/') else {
+        return false;
+    };
+    let Some(target) = value.pointer_mut(parent) else {
+        return false;
+    };
+    let key = unescape_token(last);
+
+    match target {
+        Value::Object(map) => map.remove(&key).is_some(),
+        Value::Array(items) => match key.parse::<usize>() {
+            Ok(index) if index < items.len() => {
+                items.remove(index);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// 从请求 JSON 中移除所有配置的字段，返回实际被移除的指针
+pub fn strip_fields(value: &mut Value, pointers: &[String]) -> Vec<String> {
+    pointers
+        .iter()
+        .filter(|pointer| remove_pointer(value, pointer))
+        .cloned()
+        .collect()
+}
+
+/// 按允许列表过滤请求中的 `metadata`
+///
+/// 移除不在允许列表中的键；允许列表为空或过滤后仍超过 `MAX_METADATA_BYTES` 时
+/// 移除整个 metadata。返回被丢弃的键（整体丢弃时为 `metadata` 本身）
+pub fn sanitize_metadata(value: &mut Value, allowed_keys: &[String]) -> Vec<String> {
+    let Some(request) = value.as_object_mut() else {
+        return Vec::new();
+    };
+    if !request.contains_key("metadata") {
+        return Vec::new();
+    }
+    if allowed_keys.is_empty() {
+        request.remove("metadata");
+        return vec!["metadata".to_string()];
+    }
+    let Some(metadata) = request.get_mut("metadata").and_then(Value::as_object_mut) else {
+        return Vec::new();
+    };
+
+    let mut dropped: Vec<String> = metadata
+        .keys()
+        .filter(|key| !allowed_keys.contains(key))
+        .cloned()
+        .collect();
+    for key in &dropped {
+        metadata.remove(key);
+    }
+
+    let size = serde_json::to_vec(metadata).map(|v| v.len()).unwrap_or(0);
+    if size > MAX_METADATA_BYTES {
+        tracing::warn!(
+            "metadata 过大（{} 字节，上限 {} 字节），已整体丢弃",
+            size,
+            MAX_METADATA_BYTES
+        );
+        request.remove("metadata");
+        dropped = vec!["metadata".to_string()];
+    }
+    dropped
+}
+
+/// 字段剔除中间件
+///
+/// 状态为配置的过滤规则。没有规则或请求体不是合法 JSON 时原样放行，
+/// 交由后续提取器处理
+pub async fn strip_fields_middleware(
+    State(filter): State<Arc<FieldFilter>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if filter.is_empty() {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    // 请求体大小已由外层 `body_limit_middleware` 限制
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("读取请求体失败: {}", e);
+            return ApiError::new(ApiErrorKind::RequestTooLarge, "Request body too large")
+                .into_response();
+        }
+    };
+
+    let mut value = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value) => value,
+        Err(_) => return next.run(Request::from_parts(parts, Body::from(bytes))).await,
+    };
+
+    let stripped = strip_fields(&mut value, &filter.pointers);
+    let dropped_metadata = match &filter.metadata_allowed_keys {
+        Some(allowed_keys) => sanitize_metadata(&mut value, allowed_keys),
+        None => Vec::new(),
+    };
+    if stripped.is_empty() && dropped_metadata.is_empty() {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+
+    if !stripped.is_empty() {
+        tracing::info!("已剔除上游不支持的请求字段: {}", stripped.join(", "));
+    }
+    if !dropped_metadata.is_empty() {
+        tracing::info!("已移除不在允许列表中的 metadata: {}", dropped_metadata.join(", "));
+    }
+    let body = match serde_json::to_vec(&value) {
+        Ok(body) => body,
+        Err(_) => return next.run(Request::from_parts(parts, Body::from(bytes))).await,
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::post};
+    use serde_json::json;
+
+    #[test]
+    fn test_strip_fields_by_pointer() {
+        let mut value = json!({
+            "model": "claude-sonnet-4-5",
+            "thinking": {"type": "adaptive"},
+            "messages": [{"role": "user", "content": "hi", "cache_control": {"type": "ephemeral"}}],
+            "a/b": 1
+        });
+
+        let stripped = strip_fields(
+            &mut value,
+            &[
+                "/thinking".to_string(),
+                "/messages/0/cache_control".to_string(),
+                "/a~1b".to_string(),
+                "/missing".to_string(),
+                "invalid".to_string(),
+            ],
+        );
+
+        assert_eq!(stripped, vec!["/thinking", "/messages/0/cache_control", "/a~1b"]);
+        assert_eq!(
+            value,
+            json!({
+                "model": "claude-sonnet-4-5",
+                "messages": [{"role": "user", "content": "hi"}]
+            })
+        );
+    }
+
+    #[test]
+    fn test_sanitize_metadata_by_allowlist() {
+        let allowed = vec!["user_id".to_string()];
+
+        let mut value = json!({"metadata": {"user_id": "u1", "blob": "x"}});
+        assert_eq!(sanitize_metadata(&mut value, &allowed), vec!["blob"]);
+        assert_eq!(value, json!({"metadata": {"user_id": "u1"}}));
+
+        // 空允许列表丢弃全部 metadata
+        let mut value = json!({"model": "m", "metadata": {"user_id": "u1"}});
+        assert_eq!(sanitize_metadata(&mut value, &[]), vec!["metadata"]);
+        assert_eq!(value, json!({"model": "m"}));
+
+        // 过滤后仍超过上限时整体丢弃
+        let mut value = json!({"metadata": {"user_id": "u".repeat(MAX_METADATA_BYTES)}});
+        assert_eq!(sanitize_metadata(&mut value, &allowed), vec!["metadata"]);
+        assert!(value.get("metadata").is_none());
+
+        let mut value = json!({"model": "m"});
+        assert!(sanitize_metadata(&mut value, &allowed).is_empty());
+    }
+
+    /// 启动一个回显请求体的 mock 上游，并在前面挂上剔除中间件
+    async fn spawn_echo_server(filter: FieldFilter) -> String {
+        let app = Router::new()
+            .route("/v1/messages", post(|body: String| async move { body }))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(filter),
+                strip_fields_middleware,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/v1/messages", addr)
+    }
+
+    #[tokio::test]
+    async fn test_configured_field_removed_before_upstream() {
+        let url = spawn_echo_server(FieldFilter {
+            pointers: vec!["/thinking".to_string()],
+            metadata_allowed_keys: None,
+        })
+        .await;
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 16,
+            "thinking": {"type": "adaptive"},
+            "metadata": {"user_id": "u1"}
+        });
+
+        let echoed: Value = reqwest::Client::new()
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert!(echoed.get("thinking").is_none());
+        assert_eq!(echoed["model"], "claude-sonnet-4-5");
+        assert_eq!(echoed["max_tokens"], 16);
+        assert_eq!(echoed["metadata"]["user_id"], "u1");
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_metadata_key_removed_before_upstream() {
+        let url = spawn_echo_server(FieldFilter {
+            pointers: Vec::new(),
+            metadata_allowed_keys: Some(vec!["user_id".to_string()]),
+        })
+        .await;
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 16,
+            "metadata": {"user_id": "u1", "tracking_blob": "x".repeat(1024)}
+        });
+
+        let echoed: Value = reqwest::Client::new()
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(echoed["metadata"], json!({"user_id": "u1"}));
+        assert_eq!(echoed["max_tokens"], 16);
+    }
+}
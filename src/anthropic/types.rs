@@ -3,11 +3,16 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::kiro::error_code::KiroErrorCode;
+
 // === 错误响应 ===
 
-/// API 错误响应
+/// API 错误响应（由 `ApiError` 构建）
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
+    /// 固定为 "error"
+    #[serde(rename = "type")]
+    pub response_type: &'static str,
     pub error: ErrorDetail,
 }
 
@@ -17,22 +22,28 @@ pub struct ErrorDetail {
     #[serde(rename = "type")]
     pub error_type: String,
     pub message: String,
+    /// 稳定的结构化错误码，供客户端分支处理
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kiro_error_code: Option<KiroErrorCode>,
 }
 
 impl ErrorResponse {
     /// 创建新的错误响应
     pub fn new(error_type: impl Into<String>, message: impl Into<String>) -> Self {
         Self {
+            response_type: "error",
             error: ErrorDetail {
                 error_type: error_type.into(),
                 message: message.into(),
+                kiro_error_code: None,
             },
         }
     }
 
-    /// 创建认证错误响应
-    pub fn authentication_error() -> Self {
-        Self::new("authentication_error", "Invalid API key")
+    /// 附加结构化错误码
+    pub fn with_code(mut self, code: KiroErrorCode) -> Self {
+        self.error.kiro_error_code = Some(code);
+        self
     }
 }
 
@@ -97,7 +108,9 @@ pub struct Metadata {
 #[derive(Debug, Deserialize)]
 pub struct MessagesRequest {
     pub model: String,
-    pub max_tokens: i32,
+    /// 未携带时按 `config.require_max_tokens` 拒绝或填充 `config.default_max_tokens`
+    #[serde(default)]
+    pub max_tokens: Option<i32>,
     pub messages: Vec<Message>,
     #[serde(default)]
     pub stream: bool,
@@ -182,12 +195,19 @@ pub struct ContentBlock {
 }
 
 /// 图片数据源
+///
+/// `base64` 图片源携带 `media_type` 和 `data`；`url` 图片源携带 `url`，
+/// 转换前由 handler 下载并改写为 `base64` 图片源
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ImageSource {
     #[serde(rename = "type")]
     pub source_type: String,
+    #[serde(default)]
     pub media_type: String,
+    #[serde(default)]
     pub data: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 // === Count Tokens 端点类型 ===
@@ -207,4 +227,39 @@ pub struct CountTokensRequest {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CountTokensResponse {
     pub input_tokens: i32,
+    /// 是否为本地估算值（未使用外部 count_tokens API 时为 true，否则不输出）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+}
+
+// === Complete 端点类型（旧版 Text Completions） ===
+
+/// Text Completions 请求体
+///
+/// 仅解析转换到 Messages 请求所需的字段，其余字段（如 temperature、stop_sequences）忽略
+#[derive(Debug, Deserialize)]
+pub struct CompleteRequest {
+    pub model: String,
+    /// `\n\nHuman:` / `\n\nAssistant:` 格式的对话文本
+    pub prompt: String,
+    /// 最大输出 token 数（对应 Messages 请求的 `max_tokens`）
+    #[serde(default)]
+    pub max_tokens_to_sample: Option<i32>,
+    #[serde(default)]
+    pub stream: bool,
+    pub metadata: Option<Metadata>,
+}
+
+/// Text Completions 响应（流式响应的每个 `completion` 事件也使用该结构）
+#[derive(Debug, Serialize)]
+pub struct CompletionResponse {
+    /// 固定为 "completion"
+    #[serde(rename = "type")]
+    pub response_type: &'static str,
+    pub id: String,
+    /// 生成的文本（流式响应中为增量）
+    pub completion: String,
+    /// `stop_sequence` 或 `max_tokens`（流式响应中仅最后一个事件携带）
+    pub stop_reason: Option<&'static str>,
+    pub model: String,
 }
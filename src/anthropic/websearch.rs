@@ -7,7 +7,7 @@ use std::convert::Infallible;
 use axum::{
     body::Body,
     http::{StatusCode, header},
-    response::{IntoResponse, Json, Response},
+    response::{IntoResponse, Response},
 };
 use bytes::Bytes;
 use futures::{Stream, stream};
@@ -15,8 +15,9 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use uuid::Uuid;
 
+use super::error::ApiError;
 use super::stream::SseEvent;
-use super::types::{ErrorResponse, MessagesRequest};
+use super::types::MessagesRequest;
 
 /// MCP 请求
 #[derive(Debug, Serialize)]
@@ -446,14 +447,7 @@ pub async fn handle_websearch_request(
     let query = match extract_search_query(payload) {
         Some(q) => q,
         None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse::new(
-                    "invalid_request_error",
-                    "无法从消息中提取搜索查询",
-                )),
-            )
-                .into_response();
+            return ApiError::invalid_request("无法从消息中提取搜索查询").into_response();
         }
     };
 
@@ -527,7 +521,7 @@ mod tests {
 
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
-            max_tokens: 1024,
+            max_tokens: Some(1024),
             messages: vec![Message {
                 role: "user".to_string(),
                 content: serde_json::json!("test"),
@@ -555,7 +549,7 @@ mod tests {
 
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
-            max_tokens: 1024,
+            max_tokens: Some(1024),
             messages: vec![Message {
                 role: "user".to_string(),
                 content: serde_json::json!("test"),
@@ -593,7 +587,7 @@ mod tests {
 
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
-            max_tokens: 1024,
+            max_tokens: Some(1024),
             messages: vec![Message {
                 role: "user".to_string(),
                 content: serde_json::json!([{
@@ -620,7 +614,7 @@ mod tests {
 
         let req = MessagesRequest {
             model: "claude-sonnet-4".to_string(),
-            max_tokens: 1024,
+            max_tokens: Some(1024),
             messages: vec![Message {
                 role: "user".to_string(),
                 content: serde_json::json!("What is the weather today?"),
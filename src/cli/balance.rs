@@ -0,0 +1,275 @@
+//! `balance` 子命令
+//!
+//! 从配置的存储后端加载凭据，复用 Admin API 的余额查询逻辑，
+//! 以表格或 JSON 形式输出一个或全部凭据的余额
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::admin::AdminService;
+use crate::admin::types::BalanceResponse;
+use crate::model::arg::BalanceArgs;
+
+/// 单个凭据的余额查询结果
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceEntry {
+    /// 凭据 ID
+    pub id: u64,
+    /// 余额信息（查询成功时存在）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<BalanceResponse>,
+    /// 错误信息（查询失败时存在）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 执行 `balance` 子命令
+///
+/// 返回是否所有凭据都查询成功
+pub async fn run(service: &AdminService, args: &BalanceArgs) -> anyhow::Result<bool> {
+    run_to(service, args, &mut std::io::stdout()).await
+}
+
+/// 执行 `balance` 子命令并将结果写入 `out`
+async fn run_to(
+    service: &AdminService,
+    args: &BalanceArgs,
+    out: &mut impl Write,
+) -> anyhow::Result<bool> {
+    let ids = select_ids(service, args)?;
+    let entries = query_balances(service, &ids).await;
+
+    if args.json {
+        writeln!(out, "{}", render_json(&entries)?)?;
+    } else {
+        write!(out, "{}", render_table(&entries))?;
+    }
+
+    Ok(entries.iter().all(|e| e.error.is_none()))
+}
+
+/// 根据参数确定需要查询的凭据 ID（未指定 `--index` 时查询全部）
+fn select_ids(service: &AdminService, args: &BalanceArgs) -> anyhow::Result<Vec<u64>> {
+    let all_ids = service.credential_ids();
+
+    match args.index {
+        Some(id) if all_ids.contains(&id) => Ok(vec![id]),
+        Some(id) => anyhow::bail!("凭据不存在: {}", id),
+        None => Ok(all_ids),
+    }
+}
+
+/// 依次查询凭据余额，单个凭据失败不影响其他凭据
+pub async fn query_balances(service: &AdminService, ids: &[u64]) -> Vec<BalanceEntry> {
+    let mut entries = Vec::with_capacity(ids.len());
+    for &id in ids {
+        let entry = match service.get_balance(id).await {
+            Ok(balance) => BalanceEntry {
+                id,
+                balance: Some(balance),
+                error: None,
+            },
+            Err(e) => BalanceEntry {
+                id,
+                balance: None,
+                error: Some(e.to_string()),
+            },
+        };
+        entries.push(entry);
+    }
+    entries
+}
+
+/// 以 JSON 数组形式输出
+pub fn render_json(entries: &[BalanceEntry]) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(entries)?)
+}
+
+/// 以文本表格形式输出
+pub fn render_table(entries: &[BalanceEntry]) -> String {
+    let header = [
+        "ID",
+        "SUBSCRIPTION",
+        "USED",
+        "LIMIT",
+        "REMAINING",
+        "USAGE",
+        "NEXT RESET",
+    ];
+
+    let rows: Vec<Vec<String>> = entries
+        .iter()
+        .map(|entry| match (&entry.balance, &entry.error) {
+            (Some(b), _) => vec![
+                entry.id.to_string(),
+                b.subscription_title.clone().unwrap_or_else(|| "-".to_string()),
+                format!("{:.2}", b.current_usage),
+                format!("{:.2}", b.usage_limit),
+                format!("{:.2}", b.remaining),
+                format!("{:.1}%", b.usage_percentage),
+                format_reset_time(b.next_reset_at),
+            ],
+            (None, error) => vec![
+                entry.id.to_string(),
+                format!("ERROR: {}", error.as_deref().unwrap_or("unknown")),
+            ],
+        })
+        .collect();
+
+    // 计算列宽（错误行只参与前两列）
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows.iter().filter(|r| r.len() == header.len()) {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: &[String]| -> String {
+        let line = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| match widths.get(i) {
+                Some(&w) if cells.len() == header.len() => format!("{:<w$}", cell, w = w),
+                _ => cell.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        format!("{}\n", line.trim_end())
+    };
+
+    let mut out = format_row(&header.map(String::from));
+    for row in &rows {
+        out.push_str(&format_row(row));
+    }
+    out
+}
+
+/// 将 Unix 时间戳格式化为 UTC 时间
+fn format_reset_time(timestamp: Option<f64>) -> String {
+    timestamp
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts as i64, 0))
+        .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::kiro::model::credentials::KiroCredentials;
+    use crate::kiro::token_manager::MultiTokenManager;
+    use crate::model::config::Config;
+
+    /// 启动本地 mock getUsageLimits 服务的 AdminService：凭据 #1 查询成功，#2 的上游返回 500
+    async fn mock_service() -> AdminService {
+        let app = axum::Router::new().route(
+            "/getUsageLimits",
+            axum::routing::get(|headers: axum::http::HeaderMap| async move {
+                if headers["authorization"] == "Bearer bad-token" {
+                    return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+                }
+                Ok(axum::Json(serde_json::json!({
+                    "nextDateReset": 1767225600.0,
+                    "subscriptionInfo": { "subscriptionTitle": "KIRO PRO" },
+                    "usageBreakdownList": [{
+                        "currentUsageWithPrecision": 250.0,
+                        "usageLimitWithPrecision": 1000.0
+                    }]
+                })))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let expires_at = (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+        let credentials = ["good-token", "bad-token"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, token)| KiroCredentials {
+                id: Some(i as u64 + 1),
+                access_token: Some(token.to_string()),
+                refresh_token: Some(format!("{}{}", i, "r".repeat(150))),
+                profile_arn: Some("arn:aws:codewhisperer:us-east-1:1:profile/P".to_string()),
+                expires_at: Some(expires_at.clone()),
+                ..Default::default()
+            })
+            .collect();
+        let token_manager =
+            MultiTokenManager::new(Config::default(), credentials, None, None, false)
+                .unwrap()
+                .with_usage_limits_url(format!("http://{}", addr));
+        AdminService::new(Arc::new(token_manager))
+    }
+
+    fn args(index: Option<u64>, json: bool) -> BalanceArgs {
+        BalanceArgs {
+            index,
+            all: index.is_none(),
+            json,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_prints_table_for_all_credentials() {
+        let service = mock_service().await;
+        let mut out = Vec::new();
+
+        // 单个凭据失败不影响其他凭据，但整体返回失败
+        let all_ok = run_to(&service, &args(None, false), &mut out)
+            .await
+            .unwrap();
+        assert!(!all_ok);
+
+        let table = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("ID"));
+        assert!(lines[1].starts_with("1 "));
+        assert!(lines[1].contains("KIRO PRO"));
+        assert!(lines[1].contains("250.00"));
+        assert!(lines[1].contains("1000.00"));
+        assert!(lines[1].contains("750.00"));
+        assert!(lines[1].contains("25.0%"));
+        assert!(lines[1].contains("2026-01-01 00:00 UTC"));
+        assert!(lines[2].starts_with("2  ERROR: "), "{}", lines[2]);
+    }
+
+    #[tokio::test]
+    async fn test_run_prints_json_for_one_credential() {
+        let service = mock_service().await;
+        let mut out = Vec::new();
+
+        let all_ok = run_to(&service, &args(Some(1), true), &mut out)
+            .await
+            .unwrap();
+        assert!(all_ok);
+
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let items = json.as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["id"], 1);
+        assert!(items[0].get("error").is_none());
+        assert_eq!(items[0]["balance"]["subscriptionTitle"], "KIRO PRO");
+        assert_eq!(items[0]["balance"]["currentUsage"], 250.0);
+        assert_eq!(items[0]["balance"]["usageLimit"], 1000.0);
+        assert_eq!(items[0]["balance"]["remaining"], 750.0);
+    }
+
+    #[tokio::test]
+    async fn test_run_rejects_unknown_credential_id() {
+        let service = mock_service().await;
+        let mut out = Vec::new();
+
+        let err = run_to(&service, &args(Some(9), false), &mut out)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("凭据不存在: 9"), "{}", err);
+        assert!(out.is_empty());
+    }
+}
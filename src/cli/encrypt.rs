@@ -0,0 +1,17 @@
+//! `encrypt-credentials` 子命令
+//!
+//! 启用 `credentialEncryptionKey` 前，将现有明文凭据文件就地加密为 AES-256-GCM 格式
+
+use crate::kiro::storage::{EncryptionKey, FileCredentialStorage};
+
+/// 执行 `encrypt-credentials` 子命令
+pub async fn run(path: &str, key: Option<EncryptionKey>) -> anyhow::Result<()> {
+    let key = key.ok_or_else(|| {
+        anyhow::anyhow!("未配置 credentialEncryptionKey 或 KIRO_ENCRYPTION_KEY，无法加密凭据文件")
+    })?;
+
+    FileCredentialStorage::new(path, true)
+        .with_encryption_key(Some(key))
+        .encrypt_legacy_file()
+        .await
+}
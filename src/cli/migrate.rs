@@ -0,0 +1,389 @@
+//! `migrate` 子命令
+//!
+//! 从源存储后端读取全部凭据，保留 ID 和优先级写入目标存储后端，
+//! 用于在文件、PostgreSQL、Redis、SQLite、MySQL 之间切换存储
+
+use std::fmt;
+
+use crate::kiro::model::credentials::CredentialsConfig;
+use crate::kiro::storage::{
+    CredentialStorage, EncryptionKey, FileCredentialStorage, InvalidCredential, StorageType,
+    validate_batch,
+};
+use crate::model::arg::MigrateArgs;
+use crate::model::config::Config;
+
+/// 存储规格：`file[:<路径>]`、`postgres`、`redis`、`sqlite` 或 `mysql`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageSpec {
+    /// 存储类型
+    pub storage_type: StorageType,
+    /// 文件路径（仅 `file` 类型，未指定时使用 `--credentials` 或默认路径）
+    pub path: Option<String>,
+}
+
+impl StorageSpec {
+    /// 解析存储规格
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let (name, path) = match spec.split_once(':') {
+            Some((name, path)) => (name, Some(path.to_string())),
+            None => (spec, None),
+        };
+        let storage_type = StorageType::parse(name)?;
+
+        if path.is_some() && storage_type != StorageType::File {
+            anyhow::bail!(
+                "存储规格 \"{}\" 无效：仅 file 类型可指定路径，{} 使用配置文件中的连接配置",
+                spec,
+                storage_type.as_str()
+            );
+        }
+        if path.as_deref().is_some_and(str::is_empty) {
+            anyhow::bail!("存储规格 \"{}\" 无效：文件路径为空", spec);
+        }
+
+        Ok(Self { storage_type, path })
+    }
+}
+
+impl fmt::Display for StorageSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}", self.storage_type.as_str(), path),
+            None => f.write_str(self.storage_type.as_str()),
+        }
+    }
+}
+
+/// 迁移结果
+#[derive(Debug)]
+pub struct MigrationSummary {
+    /// 已写入目标存储的凭据数量
+    pub migrated: usize,
+    /// 未迁移的凭据（缺少 ID、ID 重复或未通过校验）
+    pub skipped: Vec<InvalidCredential>,
+}
+
+/// 执行 `migrate` 子命令
+///
+/// 返回是否所有凭据都已迁移（存在跳过的凭据时返回 false）
+pub async fn run(
+    args: &MigrateArgs,
+    config: &Config,
+    credentials_path: &str,
+    key: Option<EncryptionKey>,
+) -> anyhow::Result<bool> {
+    let from = StorageSpec::parse(&args.from)?;
+    let to = StorageSpec::parse(&args.to)?;
+    let from_path = from.path.as_deref().unwrap_or(credentials_path);
+    let to_path = to.path.as_deref().unwrap_or(credentials_path);
+    if from.storage_type == to.storage_type
+        && (from.storage_type != StorageType::File || from_path == to_path)
+    {
+        anyhow::bail!("源存储与目标存储相同: {}", from);
+    }
+
+    let source = open(&from, config, credentials_path, key.clone()).await?;
+    let target = open(&to, config, credentials_path, key).await?;
+
+    let summary = migrate(source.as_ref(), target.as_ref()).await?;
+    for item in &summary.skipped {
+        tracing::warn!(
+            "跳过凭据 #{}（id={:?}）: {}",
+            item.index,
+            item.id,
+            item.reason
+        );
+    }
+
+    println!("已将 {} 个凭据从 {} 迁移到 {}", summary.migrated, from, to);
+    if !summary.skipped.is_empty() {
+        println!("跳过 {} 个凭据，详见上方警告日志", summary.skipped.len());
+    }
+
+    Ok(summary.skipped.is_empty())
+}
+
+/// 将源存储的全部凭据写入目标存储
+///
+/// 以 ID 为主键的后端要求每个凭据都有 ID，因此缺少 ID、ID 重复或未通过校验的凭据
+/// 被跳过并记录在结果中，其余凭据一次性写入（文件存储加载时已为缺少 ID 的凭据分配 ID）。
+/// 目标存储不可写或已有凭据时拒绝迁移，避免覆盖现有数据
+pub async fn migrate(
+    source: &dyn CredentialStorage,
+    target: &dyn CredentialStorage,
+) -> anyhow::Result<MigrationSummary> {
+    if !target.is_writable() {
+        anyhow::bail!(
+            "目标存储 {} 不可写（单凭据格式的文件不支持写入）",
+            target.storage_type()
+        );
+    }
+    let existing = target.load_all().await?.len();
+    if existing > 0 {
+        anyhow::bail!(
+            "目标存储 {} 已有 {} 个凭据，为避免覆盖，请迁移到空的存储",
+            target.storage_type(),
+            existing
+        );
+    }
+
+    let credentials = source.load_all().await?;
+    let skipped = match validate_batch(&credentials, true) {
+        Ok(()) => Vec::new(),
+        Err(e) => e.invalid,
+    };
+    let credentials: Vec<_> = credentials
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !skipped.iter().any(|item| item.index == *index))
+        .map(|(_, credential)| credential)
+        .collect();
+
+    if !credentials.is_empty() {
+        target.save_all(&credentials).await?;
+    }
+
+    Ok(MigrationSummary {
+        migrated: credentials.len(),
+        skipped,
+    })
+}
+
+/// 按存储规格打开存储后端（不限制加载数量）
+// 未启用任何数据库 feature 时 config 仅用于类型签名
+#[allow(unused_variables)]
+async fn open(
+    spec: &StorageSpec,
+    config: &Config,
+    credentials_path: &str,
+    key: Option<EncryptionKey>,
+) -> anyhow::Result<Box<dyn CredentialStorage>> {
+    match spec.storage_type {
+        StorageType::File => {
+            let path = spec.path.as_deref().unwrap_or(credentials_path);
+            // 文件不存在时视为空的多凭据文件，迁移后自动创建
+            let is_multiple_format =
+                CredentialsConfig::load_with_key(path, key.as_ref())?.is_multiple();
+            Ok(Box::new(
+                FileCredentialStorage::new(path, is_multiple_format).with_encryption_key(key),
+            ))
+        }
+        #[cfg(feature = "postgres")]
+        StorageType::Postgres => {
+            let section = config
+                .postgres
+                .as_ref()
+                .ok_or_else(|| missing_section(spec))?;
+            let storage = crate::kiro::storage::PostgresCredentialStorage::new(section).await?;
+            Ok(Box::new(storage))
+        }
+        #[cfg(feature = "redis")]
+        StorageType::Redis => {
+            let section = config.redis.as_ref().ok_or_else(|| missing_section(spec))?;
+            let storage = crate::kiro::storage::RedisCredentialStorage::new(section).await?;
+            Ok(Box::new(storage))
+        }
+        #[cfg(feature = "sqlite")]
+        StorageType::Sqlite => {
+            let section = config
+                .sqlite
+                .as_ref()
+                .ok_or_else(|| missing_section(spec))?;
+            let storage = crate::kiro::storage::SqliteCredentialStorage::new(section).await?;
+            Ok(Box::new(storage))
+        }
+        #[cfg(feature = "mysql")]
+        StorageType::Mysql => {
+            let section = config.mysql.as_ref().ok_or_else(|| missing_section(spec))?;
+            let storage = crate::kiro::storage::MysqlCredentialStorage::new(section).await?;
+            Ok(Box::new(storage))
+        }
+        #[cfg(not(feature = "postgres"))]
+        StorageType::Postgres => Err(feature_disabled(spec)),
+        #[cfg(not(feature = "redis"))]
+        StorageType::Redis => Err(feature_disabled(spec)),
+        #[cfg(not(feature = "sqlite"))]
+        StorageType::Sqlite => Err(feature_disabled(spec)),
+        #[cfg(not(feature = "mysql"))]
+        StorageType::Mysql => Err(feature_disabled(spec)),
+    }
+}
+
+/// 当前构建未启用对应的存储 feature
+#[cfg(not(all(
+    feature = "postgres",
+    feature = "redis",
+    feature = "sqlite",
+    feature = "mysql"
+)))]
+fn feature_disabled(spec: &StorageSpec) -> anyhow::Error {
+    let name = spec.storage_type.as_str();
+    anyhow::anyhow!("迁移 {} 需要启用 {} feature 重新编译", name, name)
+}
+
+/// 配置文件缺少数据库连接配置
+#[cfg(any(
+    feature = "postgres",
+    feature = "redis",
+    feature = "sqlite",
+    feature = "mysql"
+))]
+fn missing_section(spec: &StorageSpec) -> anyhow::Error {
+    anyhow::anyhow!(
+        "迁移 {} 需要在配置文件中提供 {} 连接配置",
+        spec,
+        spec.storage_type.as_str()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::credentials::KiroCredentials;
+    use async_trait::async_trait;
+    use parking_lot::Mutex;
+    use std::io::Write;
+
+    /// 以 ID 为主键的内存存储（模拟数据库后端）
+    #[derive(Default)]
+    struct MemoryStorage {
+        credentials: Mutex<Vec<KiroCredentials>>,
+    }
+
+    #[async_trait]
+    impl CredentialStorage for MemoryStorage {
+        async fn load_all(&self) -> anyhow::Result<Vec<KiroCredentials>> {
+            let mut credentials = self.credentials.lock().clone();
+            credentials.sort_by_key(|c| (c.priority, c.id));
+            Ok(credentials)
+        }
+        async fn save(&self, credential: &KiroCredentials) -> anyhow::Result<()> {
+            self.save_all(std::slice::from_ref(credential)).await
+        }
+        async fn save_all(&self, credentials: &[KiroCredentials]) -> anyhow::Result<()> {
+            validate_batch(credentials, true)?;
+            let mut stored = self.credentials.lock();
+            for credential in credentials {
+                stored.retain(|c| c.id != credential.id);
+                stored.push(credential.clone());
+            }
+            Ok(())
+        }
+        async fn delete(&self, id: u64) -> anyhow::Result<()> {
+            self.credentials.lock().retain(|c| c.id != Some(id));
+            Ok(())
+        }
+        fn storage_type(&self) -> &'static str {
+            "memory"
+        }
+    }
+
+    fn credentials_file(content: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "{}", content).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_parse_storage_spec() {
+        assert_eq!(
+            StorageSpec::parse("file:/tmp/credentials.json").unwrap(),
+            StorageSpec {
+                storage_type: StorageType::File,
+                path: Some("/tmp/credentials.json".to_string()),
+            }
+        );
+        assert_eq!(
+            StorageSpec::parse("Postgres").unwrap().storage_type,
+            StorageType::Postgres
+        );
+        assert!(StorageSpec::parse("postgres:postgres://localhost/db").is_err());
+        assert!(StorageSpec::parse("file:").is_err());
+        assert!(StorageSpec::parse("dynamo").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_multiple_file_preserves_ids_and_priorities() {
+        let file = credentials_file(
+            r#"[
+                {"id": 3, "refreshToken": "r3", "priority": 0, "tags": ["team-a"]},
+                {"id": 1, "refreshToken": "r1", "priority": 2, "region": "us-east-1"},
+                {"id": 7, "refreshToken": "r7", "priority": 1, "authMethod": "idc",
+                 "clientId": "c", "clientSecret": "s"},
+                {"id": 3, "refreshToken": "dup", "priority": 0},
+                {"refreshToken": "no-id", "priority": 3}
+            ]"#,
+        );
+        let source = FileCredentialStorage::new(file.path(), true);
+        let target = MemoryStorage::default();
+
+        let summary = migrate(&source, &target).await.unwrap();
+
+        assert_eq!(summary.migrated, 4);
+        assert_eq!(summary.skipped.len(), 1);
+        assert_eq!(summary.skipped[0].id, Some(3));
+        assert_eq!(summary.skipped[0].reason, "id 重复");
+
+        // 除跳过的凭据外，两侧内容完全一致
+        let expected: Vec<_> = source
+            .load_all()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.refresh_token.as_deref() != Some("dup"))
+            .map(|c| serde_json::to_value(c).unwrap())
+            .collect();
+        let migrated: Vec<_> = target
+            .load_all()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| serde_json::to_value(c).unwrap())
+            .collect();
+        assert_eq!(migrated, expected);
+        assert_eq!(
+            migrated
+                .iter()
+                .map(|c| (c["id"].as_u64().unwrap(), c["priority"].as_u64().unwrap_or(0)))
+                .collect::<Vec<_>>(),
+            vec![(3, 0), (7, 1), (1, 2), (8, 3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migrate_single_file_uses_assigned_id() {
+        let file = credentials_file(r#"{"refreshToken": "single"}"#);
+        let source = FileCredentialStorage::new(file.path(), false);
+        let target = MemoryStorage::default();
+
+        let summary = migrate(&source, &target).await.unwrap();
+
+        assert_eq!(summary.migrated, 1);
+        assert!(summary.skipped.is_empty());
+        assert_eq!(target.load_all().await.unwrap()[0].id, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_rejects_non_empty_or_read_only_target() {
+        let file = credentials_file(r#"[{"id": 1, "refreshToken": "r1"}]"#);
+        let source = FileCredentialStorage::new(file.path(), true);
+
+        let target = MemoryStorage::default();
+        target
+            .save(&KiroCredentials {
+                id: Some(9),
+                refresh_token: Some("existing".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let err = migrate(&source, &target).await.unwrap_err();
+        assert!(err.to_string().contains("已有 1 个凭据"), "{}", err);
+
+        let single = credentials_file(r#"{"refreshToken": "single"}"#);
+        let read_only = FileCredentialStorage::new(single.path(), false);
+        let err = migrate(&source, &read_only).await.unwrap_err();
+        assert!(err.to_string().contains("不可写"), "{}", err);
+    }
+}
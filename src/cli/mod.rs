@@ -0,0 +1,7 @@
+//! 命令行子命令
+//!
+//! 提供无需启动 API 服务即可执行的运维命令
+
+pub mod balance;
+pub mod encrypt;
+pub mod migrate;
@@ -2,7 +2,7 @@
 
 use axum::{
     body::Body,
-    http::{Request, header},
+    http::{HeaderMap, Request, header},
 };
 use subtle::ConstantTimeEq;
 
@@ -12,9 +12,13 @@ use subtle::ConstantTimeEq;
 /// - `x-api-key` header
 /// - `Authorization: Bearer <token>` header
 pub fn extract_api_key(request: &Request<Body>) -> Option<String> {
+    extract_api_key_from_headers(request.headers())
+}
+
+/// 从请求头中提取 API Key（规则同 [`extract_api_key`]）
+pub fn extract_api_key_from_headers(headers: &HeaderMap) -> Option<String> {
     // 优先检查 x-api-key
-    if let Some(key) = request
-        .headers()
+    if let Some(key) = headers
         .get("x-api-key")
         .and_then(|v| v.to_str().ok())
     {
@@ -22,8 +26,7 @@ pub fn extract_api_key(request: &Request<Body>) -> Option<String> {
     }
 
     // 其次检查 Authorization: Bearer
-    request
-        .headers()
+    headers
         .get(header::AUTHORIZATION)
         .and_then(|v| v.to_str().ok())
         .and_then(|v| v.strip_prefix("Bearer "))
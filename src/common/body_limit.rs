@@ -0,0 +1,45 @@
+//! 请求体大小限制
+//!
+//! 声明的 `Content-Length` 超过上限时直接拒绝；未声明长度的分块上传用 [`Limited`] 包装，
+//! 读取超过上限时中止。提取器读取超限时 axum 返回纯文本 413，这里替换为调用方构建的 JSON 错误体
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+use http_body_util::Limited;
+
+/// 限制请求体大小，超限时返回 `too_large(limit)` 构建的响应
+pub async fn limit_body(
+    limit: usize,
+    request: Request<Body>,
+    next: Next,
+    too_large: fn(usize) -> Response,
+) -> Response {
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if let Some(length) = declared
+        && length > limit as u64
+    {
+        tracing::warn!("请求体 {} 字节超过上限 {} 字节，拒绝请求", length, limit);
+        return too_large(limit);
+    }
+
+    let request = request.map(|body| Body::new(Limited::new(body, limit)));
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+        tracing::warn!("请求体读取时超过上限 {} 字节，拒绝请求", limit);
+        return too_large(limit);
+    }
+    response
+}
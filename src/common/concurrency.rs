@@ -0,0 +1,64 @@
+//! 并发限制
+//!
+//! 提供按 key（如 API Key）独立计数的并发上限
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 按 key 独立限制并发数的限流器
+///
+/// 每个 key 首次出现时创建一个容量为 `max_concurrent` 的信号量
+#[derive(Debug)]
+pub struct KeyedConcurrencyLimiter {
+    max_concurrent: usize,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl KeyedConcurrencyLimiter {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 每个 key 的并发上限
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// 尝试为指定 key 占用一个并发名额
+    ///
+    /// 达到上限时立即返回 None（不排队等待）；返回的许可释放时归还名额
+    pub fn try_acquire(&self, key: &str) -> Option<OwnedSemaphorePermit> {
+        let semaphore = self
+            .semaphores
+            .lock()
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_concurrent)))
+            .clone();
+        semaphore.try_acquire_owned().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_limited_independently() {
+        let limiter = KeyedConcurrencyLimiter::new(2);
+
+        let a1 = limiter.try_acquire("key-a").unwrap();
+        let _a2 = limiter.try_acquire("key-a").unwrap();
+        assert!(limiter.try_acquire("key-a").is_none());
+        assert!(limiter.try_acquire("key-b").is_some());
+
+        // 释放后名额归还
+        drop(a1);
+        assert!(limiter.try_acquire("key-a").is_some());
+    }
+}
@@ -0,0 +1,164 @@
+//! 空闲关闭
+//!
+//! 记录最近一次请求活动时间和进行中的请求数，连续空闲达到指定时长后触发优雅关闭
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use axum::{body::Body, extract::State, http::Request, middleware::Next, response::Response};
+use futures::StreamExt;
+use tokio::time::Instant;
+
+/// 不计入活动的探活/管理路径前缀（`idle_ignore_health` 开启时）
+const PROBE_PATH_PREFIXES: [&str; 5] =
+    ["/healthz", "/readyz", "/metrics", "/api/admin", "/admin"];
+
+/// 请求活动跟踪器
+#[derive(Debug)]
+pub struct IdleTracker {
+    /// 计时起点
+    started: Instant,
+    /// 最近一次活动距计时起点的毫秒数
+    last_activity_ms: AtomicU64,
+    /// 进行中的请求数（流式请求在响应体发送完毕前一直计入）
+    in_flight: AtomicUsize,
+    /// 探活和管理请求是否不计入活动
+    ignore_probes: bool,
+}
+
+impl IdleTracker {
+    pub fn new(ignore_probes: bool) -> Self {
+        Self {
+            started: Instant::now(),
+            last_activity_ms: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            ignore_probes,
+        }
+    }
+
+    /// 记录一次活动
+    pub fn touch(&self) {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        self.last_activity_ms.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    /// 距最近一次活动的时长；有进行中的请求时为 0
+    pub fn idle_for(&self) -> Duration {
+        if self.in_flight.load(Ordering::Relaxed) > 0 {
+            return Duration::ZERO;
+        }
+        let last = Duration::from_millis(self.last_activity_ms.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(last)
+    }
+
+    /// 进行中的请求数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// 指定路径的请求是否计入活动
+    fn counts(&self, path: &str) -> bool {
+        !self.ignore_probes
+            || !PROBE_PATH_PREFIXES
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+    }
+
+    /// 开始一次请求，返回的守卫释放时结束该请求并记录活动
+    fn begin(self: &Arc<Self>) -> ActivityGuard {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        self.touch();
+        ActivityGuard(self.clone())
+    }
+
+    /// 等待连续空闲达到 `idle_timeout`
+    pub async fn wait_for_idle(&self, idle_timeout: Duration) {
+        loop {
+            let idle = self.idle_for();
+            if idle >= idle_timeout {
+                return;
+            }
+            tokio::time::sleep(idle_timeout - idle).await;
+        }
+    }
+}
+
+/// 进行中请求的守卫
+struct ActivityGuard(Arc<IdleTracker>);
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        self.0.touch();
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 记录请求活动的中间件
+///
+/// 请求在响应体（含流式响应）发送完毕或客户端断开前一直视为进行中
+pub async fn idle_activity_middleware(
+    State(tracker): State<Arc<IdleTracker>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if !tracker.counts(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let guard = tracker.begin();
+    let (parts, body) = next.run(request).await.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &guard;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::get};
+
+    #[tokio::test]
+    async fn test_shutdown_fires_after_idle_timeout() {
+        let tracker = IdleTracker::new(false);
+        let started = std::time::Instant::now();
+        tokio::time::timeout(
+            Duration::from_secs(2),
+            tracker.wait_for_idle(Duration::from_millis(100)),
+        )
+        .await
+        .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_probes_ignored_when_configured() {
+        let tracker = Arc::new(IdleTracker::new(true));
+        let app = Router::new()
+            .route("/readyz", get(|| async { "ok" }))
+            .route("/v1/models", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(
+                tracker.clone(),
+                idle_activity_middleware,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        reqwest::get(format!("http://{}/readyz", addr)).await.unwrap();
+        assert!(tracker.idle_for() >= Duration::from_millis(200));
+
+        reqwest::get(format!("http://{}/v1/models", addr))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(tracker.idle_for() < Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,140 @@
+//! JSON 响应格式化
+//!
+//! 根据 `config.pretty_json` 或请求参数 `?pretty=true|false` 决定
+//! JSON 响应使用美化格式还是紧凑格式。流式响应（非 application/json）不受影响
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, header},
+    middleware::Next,
+    response::Response,
+};
+
+/// 从查询字符串中解析 `pretty` 参数
+///
+/// `pretty` / `pretty=true` / `pretty=1` 为 true，`pretty=false` / `pretty=0` 为 false，
+/// 其他值或未提供时返回 None
+fn parse_pretty_param(query: Option<&str>) -> Option<bool> {
+    query?
+        .split('&')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (key == "pretty").then_some(value)
+        })
+        .find_map(|value| match value {
+            "" | "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        })
+}
+
+/// 判断响应是否为 JSON
+fn is_json_response(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("application/json"))
+        .unwrap_or(false)
+}
+
+/// JSON 格式化中间件
+///
+/// 状态为 `config.pretty_json` 的默认值。handler 输出的 JSON 本身是紧凑格式，
+/// 仅在需要美化时重新序列化响应体
+pub async fn json_format_middleware(
+    State(default_pretty): State<bool>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let pretty = parse_pretty_param(request.uri().query()).unwrap_or(default_pretty);
+    let response = next.run(request).await;
+
+    if !pretty || !is_json_response(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("读取 JSON 响应体失败，无法美化输出: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    let formatted = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .and_then(|value| serde_json::to_vec_pretty(&value));
+
+    match formatted {
+        Ok(pretty_bytes) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(pretty_bytes))
+        }
+        // 非法 JSON 原样返回
+        Err(_) => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, middleware, routing::get};
+
+    async fn spawn_server(default_pretty: bool) -> String {
+        let app = Router::new()
+            .route(
+                "/data",
+                get(|| async { Json(serde_json::json!({"id": 1, "items": ["a", "b"]})) }),
+            )
+            .layer(middleware::from_fn_with_state(
+                default_pretty,
+                json_format_middleware,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/data", addr)
+    }
+
+    async fn fetch(url: &str) -> String {
+        reqwest::get(url).await.unwrap().text().await.unwrap()
+    }
+
+    #[test]
+    fn test_parse_pretty_param() {
+        assert_eq!(parse_pretty_param(None), None);
+        assert_eq!(parse_pretty_param(Some("pretty=true")), Some(true));
+        assert_eq!(parse_pretty_param(Some("a=1&pretty")), Some(true));
+        assert_eq!(parse_pretty_param(Some("pretty=0")), Some(false));
+        assert_eq!(parse_pretty_param(Some("pretty=maybe")), None);
+    }
+
+    #[tokio::test]
+    async fn test_pretty_vs_compact_by_setting() {
+        let compact_url = spawn_server(false).await;
+        let pretty_url = spawn_server(true).await;
+
+        let compact = fetch(&compact_url).await;
+        let pretty = fetch(&pretty_url).await;
+
+        assert_eq!(compact, r#"{"id":1,"items":["a","b"]}"#);
+        assert!(pretty.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&compact).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&pretty).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_param_overrides_setting() {
+        let compact_url = spawn_server(false).await;
+        let pretty_url = spawn_server(true).await;
+
+        assert!(fetch(&format!("{}?pretty=true", compact_url)).await.contains('\n'));
+        assert!(!fetch(&format!("{}?pretty=false", pretty_url)).await.contains('\n'));
+    }
+}
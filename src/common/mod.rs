@@ -1,3 +1,9 @@
 //! 公共工具模块
 
 pub mod auth;
+pub mod body_limit;
+pub mod concurrency;
+pub mod idle;
+pub mod json_format;
+pub mod rate_limit;
+pub mod sse;
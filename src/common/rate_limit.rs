@@ -0,0 +1,357 @@
+//! 令牌桶限流
+//!
+//! 提供按 key（如模型名、API Key）独立计数的令牌桶限流器
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::model::config::{Config, RateLimit};
+
+/// 令牌桶
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// 桶容量（突发上限）
+    capacity: f64,
+    /// 每秒补充的令牌数
+    refill_per_sec: f64,
+    /// 当前令牌数
+    tokens: f64,
+    /// 上次补充时间
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// 根据限流配置创建令牌桶（初始为满）
+    pub fn new(limit: &RateLimit) -> Self {
+        let capacity = f64::from(limit.burst.unwrap_or(limit.requests_per_minute).max(1));
+        Self {
+            capacity,
+            refill_per_sec: f64::from(limit.requests_per_minute) / 60.0,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// 尝试取出一个令牌
+    ///
+    /// 成功返回 `Ok(())`，令牌不足时返回需要等待的时长
+    pub fn try_acquire(&mut self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill_at(now);
+        self.check()?;
+        self.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// 按经过的时间补充令牌
+    fn refill_at(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// 是否有可用令牌（不取出），不足时返回需要等待的时长
+    fn check(&self) -> Result<(), Duration> {
+        if self.tokens >= 1.0 {
+            return Ok(());
+        }
+
+        // 补充速率为 0 时无法恢复，按 1 分钟提示重试
+        if self.refill_per_sec <= 0.0 {
+            return Err(Duration::from_secs(60));
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec))
+    }
+
+    /// 到 `now` 时是否已补满（与新建的令牌桶等价，可以回收）
+    fn is_full_at(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens + elapsed * self.refill_per_sec >= self.capacity
+    }
+}
+
+/// 按 key 独立限流的限流器
+///
+/// 未配置限流的 key 不受限制
+#[derive(Debug, Default)]
+pub struct KeyedRateLimiter {
+    buckets: HashMap<String, Mutex<TokenBucket>>,
+}
+
+impl KeyedRateLimiter {
+    /// 根据 key -> 限流配置 创建限流器
+    pub fn new(limits: &HashMap<String, RateLimit>) -> Self {
+        Self {
+            buckets: limits
+                .iter()
+                .map(|(key, limit)| (key.clone(), Mutex::new(TokenBucket::new(limit))))
+                .collect(),
+        }
+    }
+
+    /// 尝试为指定 key 取出一个令牌
+    ///
+    /// 未配置限流的 key 总是成功；受限时返回建议的重试等待时长
+    pub fn try_acquire(&self, key: &str) -> Result<(), Duration> {
+        match self.buckets.get(key) {
+            Some(bucket) => bucket.lock().try_acquire(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 回收已补满的令牌桶前允许缓存的令牌桶数量
+const API_KEY_BUCKET_SWEEP_THRESHOLD: usize = 1024;
+
+/// 按 API Key 独立限流的限流器（`rateLimitRpm` 与 `apiKeys`）
+///
+/// 每个 API Key 使用自己的限流配置；配置了 `rateLimitKeyHeader` 时，
+/// 同一 API Key 下按该请求头的值再分出独立的令牌桶，但请求同时受 API Key 总令牌桶约束，
+/// 客户端更换请求头的值无法绕过 API Key 的限流。
+/// 令牌桶在首次请求时创建，已补满的令牌桶（等价于新建）在数量过多时回收；
+/// 未配置限流的 API Key 不受限制
+#[derive(Debug, Default)]
+pub struct ApiKeyRateLimiter {
+    /// API Key -> 限流配置
+    limits: HashMap<String, RateLimit>,
+    /// 区分令牌桶的请求头（可选）
+    key_header: Option<String>,
+    /// (API Key, 请求头的值) -> 令牌桶，请求头的值为 None 的是 API Key 总令牌桶
+    buckets: Mutex<HashMap<(String, Option<String>), TokenBucket>>,
+}
+
+impl ApiKeyRateLimiter {
+    /// 根据 API Key -> 限流配置 创建限流器
+    pub fn new(limits: HashMap<String, RateLimit>, key_header: Option<String>) -> Self {
+        Self {
+            limits,
+            key_header,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 按配置为 `apiKey` 和 `apiKeys` 中的每个 Key 创建限流器，所有 Key 都不限流时返回 None
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let limits: HashMap<String, RateLimit> = config
+            .api_key
+            .iter()
+            .chain(config.api_keys.iter().map(|k| &k.api_key))
+            .filter_map(|key| {
+                config
+                    .rate_limit_for_key(key)
+                    .map(|limit| (key.clone(), limit))
+            })
+            .collect();
+        if limits.is_empty() {
+            return None;
+        }
+        Some(Self::new(limits, config.rate_limit_key_header.clone()))
+    }
+
+    /// 区分令牌桶的请求头名称
+    pub fn key_header(&self) -> Option<&str> {
+        self.key_header.as_deref()
+    }
+
+    /// 尝试为指定 API Key（及请求头的值）取出一个令牌
+    ///
+    /// 请求头的值对应的令牌桶和 API Key 总令牌桶都有令牌时才同时扣减；
+    /// 未配置限流的 API Key 总是成功；受限时返回建议的重试等待时长
+    pub fn try_acquire(&self, api_key: &str, header_value: Option<&str>) -> Result<(), Duration> {
+        self.try_acquire_at(api_key, header_value, Instant::now())
+    }
+
+    fn try_acquire_at(
+        &self,
+        api_key: &str,
+        header_value: Option<&str>,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let Some(limit) = self.limits.get(api_key) else {
+            return Ok(());
+        };
+
+        let mut buckets = self.buckets.lock();
+        if buckets.len() >= API_KEY_BUCKET_SWEEP_THRESHOLD {
+            buckets.retain(|_, bucket| !bucket.is_full_at(now));
+        }
+
+        let mut keys = vec![(api_key.to_string(), None)];
+        if let Some(value) = header_value {
+            keys.push((api_key.to_string(), Some(value.to_string())));
+        }
+        for key in &keys {
+            let bucket = buckets
+                .entry(key.clone())
+                .or_insert_with(|| TokenBucket::new(limit));
+            bucket.refill_at(now);
+            bucket.check()?;
+        }
+        for key in &keys {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// 当前缓存的令牌桶数量
+    #[cfg(test)]
+    fn bucket_count(&self) -> usize {
+        self.buckets.lock().len()
+    }
+}
+
+/// 将等待时长转换为 `Retry-After` 秒数（向上取整，至少 1 秒）
+pub fn retry_after_secs(wait: Duration) -> u64 {
+    wait.as_secs_f64().ceil().max(1.0) as u64
+}
+
+/// 解析 `Retry-After` 响应头的值（秒数或 HTTP 日期），返回需要等待的时长
+///
+/// 日期已过去时返回 0，无法解析时返回 None
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(requests_per_minute: u32, burst: Option<u32>) -> RateLimit {
+        RateLimit {
+            requests_per_minute,
+            burst,
+        }
+    }
+
+    #[test]
+    fn test_token_bucket_refills_over_time() {
+        let mut bucket = TokenBucket::new(&limit(60, Some(1)));
+        let start = bucket.last_refill;
+
+        assert!(bucket.try_acquire_at(start).is_ok());
+        let wait = bucket.try_acquire_at(start).unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+
+        // 每秒补充 1 个令牌
+        assert!(bucket.try_acquire_at(start + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn test_capped_model_throttled_while_uncapped_flows() {
+        let mut limits = HashMap::new();
+        limits.insert("claude-opus-4-5".to_string(), limit(2, None));
+        let limiter = KeyedRateLimiter::new(&limits);
+
+        assert!(limiter.try_acquire("claude-opus-4-5").is_ok());
+        assert!(limiter.try_acquire("claude-opus-4-5").is_ok());
+        let wait = limiter.try_acquire("claude-opus-4-5").unwrap_err();
+        assert!(retry_after_secs(wait) >= 1);
+
+        for _ in 0..100 {
+            assert!(limiter.try_acquire("claude-sonnet-4-5").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_api_key_limiter_uses_per_key_limits_and_header_buckets() {
+        let mut limits = HashMap::new();
+        limits.insert("sk-team-a".to_string(), limit(60, Some(1)));
+        limits.insert("sk-team-b".to_string(), limit(60, Some(3)));
+        let limiter = ApiKeyRateLimiter::new(limits, Some("x-team-id".to_string()));
+
+        assert!(limiter.try_acquire("sk-team-a", Some("search")).is_ok());
+        assert!(limiter.try_acquire("sk-team-a", Some("search")).is_err());
+        // 更换请求头的值无法绕过 API Key 总令牌桶
+        assert!(limiter.try_acquire("sk-team-a", Some("chat")).is_err());
+        assert!(limiter.try_acquire("sk-team-a", None).is_err());
+
+        // 同一 API Key 下每个请求头的值使用独立的令牌桶
+        assert!(limiter.try_acquire("sk-team-b", Some("search")).is_ok());
+        assert!(limiter.try_acquire("sk-team-b", Some("search")).is_ok());
+        assert!(limiter.try_acquire("sk-team-b", Some("chat")).is_ok());
+        assert!(limiter.try_acquire("sk-team-b", None).is_err());
+        // 未配置限流的 Key 不受限制
+        assert!(limiter.try_acquire("sk-other", None).is_ok());
+    }
+
+    #[test]
+    fn test_api_key_limiter_evicts_refilled_buckets() {
+        let mut limits = HashMap::new();
+        limits.insert("sk-team-a".to_string(), limit(6000, None));
+        let limiter = ApiKeyRateLimiter::new(limits, Some("x-team-id".to_string()));
+        let start = Instant::now();
+
+        // API Key 总令牌桶 + 每个请求头的值一个令牌桶
+        for i in 1..API_KEY_BUCKET_SWEEP_THRESHOLD {
+            let value = i.to_string();
+            limiter
+                .try_acquire_at("sk-team-a", Some(&value), start)
+                .unwrap();
+        }
+        assert_eq!(limiter.bucket_count(), API_KEY_BUCKET_SWEEP_THRESHOLD);
+
+        // 所有令牌桶都已补满，下次请求时回收
+        let later = start + Duration::from_secs(60);
+        limiter.try_acquire_at("sk-team-a", Some("new"), later).unwrap();
+        assert_eq!(limiter.bucket_count(), 2);
+    }
+
+    #[test]
+    fn test_api_key_limiter_from_config() {
+        let mut config = Config {
+            api_key: Some("sk-main".to_string()),
+            ..Config::default()
+        };
+        assert!(ApiKeyRateLimiter::from_config(&config).is_none());
+
+        config.api_keys = serde_json::from_value(serde_json::json!([
+            {"apiKey": "sk-team-a", "rateLimitRpm": 60, "rateLimitBurst": 1}
+        ]))
+        .unwrap();
+        let limiter = ApiKeyRateLimiter::from_config(&config).unwrap();
+        assert!(limiter.try_acquire("sk-team-a", None).is_ok());
+        assert!(limiter.try_acquire("sk-team-a", None).is_err());
+        for _ in 0..100 {
+            assert!(limiter.try_acquire("sk-main", None).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_zero_rate_always_rejects_after_burst() {
+        let mut bucket = TokenBucket::new(&limit(0, Some(1)));
+        assert!(bucket.try_acquire().is_ok());
+        assert_eq!(bucket.try_acquire().unwrap_err(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_parse_retry_after_seconds_and_http_date() {
+        assert_eq!(parse_retry_after(" 3 "), Some(Duration::from_secs(3)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let wait = parse_retry_after(&later).unwrap();
+        assert!(wait > Duration::from_secs(25) && wait <= Duration::from_secs(30));
+        assert!(parse_retry_after("soon").is_none());
+    }
+
+    #[test]
+    fn test_retry_after_secs_rounds_up() {
+        assert_eq!(retry_after_secs(Duration::from_millis(10)), 1);
+        assert_eq!(retry_after_secs(Duration::from_millis(1500)), 2);
+    }
+}
@@ -0,0 +1,25 @@
+//! SSE 事件解析
+//!
+//! 供端点转换层（OpenAI 兼容、旧版 Text Completions）逐块解析 `/v1/messages` 输出的 SSE 字节
+
+use serde_json::Value;
+
+/// 查找第一个完整事件的结束位置（事件之间以空行分隔）
+pub fn find_event_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(2).position(|window| window == b"\n\n")
+}
+
+/// 解析一个 SSE 事件的事件名和 JSON 数据
+pub fn parse_event(raw: &str) -> Option<(String, Value)> {
+    let mut event = None;
+    let mut data = String::new();
+    for line in raw.lines() {
+        if let Some(name) = line.strip_prefix("event:") {
+            event = Some(name.trim().to_string());
+        } else if let Some(chunk) = line.strip_prefix("data:") {
+            data.push_str(chunk.trim_start());
+        }
+    }
+    let data = serde_json::from_str(&data).ok()?;
+    Some((event?, data))
+}
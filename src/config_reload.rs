@@ -0,0 +1,359 @@
+//! 配置热加载
+//!
+//! 进程收到 SIGHUP 时重新读取配置文件，只应用可在运行中安全替换的字段：
+//! 全局代理（`proxyUrl`、`proxyUsername`、`proxyPassword`）、`selectionStrategy`、
+//! `credentialSyncIntervalSecs` 和 `modelAliases`。其他字段（如 `host`、`port`）的变更记录为已忽略，
+//! 需要重启才能生效；监听地址和已建立的连接不受影响
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::kiro::storage::CredentialSyncManager;
+use crate::kiro::token_manager::MultiTokenManager;
+use crate::model::config::Config;
+
+/// 可热加载的字段（配置文件中的名称）
+const RELOADABLE_FIELDS: &[&str] = &[
+    "proxyUrl",
+    "proxyUsername",
+    "proxyPassword",
+    "selectionStrategy",
+    "credentialSyncIntervalSecs",
+    "modelAliases",
+];
+
+/// 一次热加载的结果
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReloadOutcome {
+    /// 已应用的变更字段
+    pub applied: Vec<String>,
+    /// 有变更但需要重启才能生效的字段
+    pub ignored: Vec<String>,
+}
+
+/// 配置热加载器
+pub struct ConfigReloader {
+    path: PathBuf,
+    token_manager: Arc<MultiTokenManager>,
+    sync_manager: Option<Arc<CredentialSyncManager>>,
+}
+
+impl ConfigReloader {
+    pub fn new(path: impl Into<PathBuf>, token_manager: Arc<MultiTokenManager>) -> Self {
+        Self {
+            path: path.into(),
+            token_manager,
+            sync_manager: None,
+        }
+    }
+
+    /// 附加凭据同步管理器（未附加时 `credentialSyncIntervalSecs` 的变更视为需要重启）
+    pub fn with_sync_manager(mut self, sync_manager: Arc<CredentialSyncManager>) -> Self {
+        self.sync_manager = Some(sync_manager);
+        self
+    }
+
+    /// 重新读取配置文件并应用可热加载的字段
+    ///
+    /// 配置文件无法解析、未通过 [`Config::validate`] 或新的代理配置无效时返回错误，
+    /// 当前配置保持不变
+    pub fn reload(&self) -> anyhow::Result<ReloadOutcome> {
+        let mut new = Config::load(&self.path)?;
+        if let Err(errors) = new.validate() {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            anyhow::bail!("配置校验失败: {}", errors.join("; "));
+        }
+        let current = self.token_manager.config();
+        // 未显式配置 systemVersion 时每次加载都会随机取默认值，不应视为变更
+        if !system_version_configured(&self.path) {
+            new.system_version = current.system_version.clone();
+        }
+        let proxy = new.proxy_config();
+        if let Some(proxy) = &proxy {
+            proxy.scheme()?;
+        }
+
+        let (mut applied, mut ignored): (Vec<_>, Vec<_>) = changed_fields(&current, &new)?
+            .into_iter()
+            .partition(|field| RELOADABLE_FIELDS.contains(&field.as_str()));
+
+        let mut merged = (*current).clone();
+        merged.proxy_url = new.proxy_url;
+        merged.proxy_username = new.proxy_username;
+        merged.proxy_password = new.proxy_password;
+        merged.selection_strategy = new.selection_strategy;
+        merged.model_aliases = new.model_aliases;
+
+        let sync_interval = new.credential_sync_interval_secs;
+        if sync_interval != current.credential_sync_interval_secs {
+            let interval_applied = self
+                .sync_manager
+                .as_ref()
+                .is_some_and(|manager| manager.set_interval(sync_interval));
+            if interval_applied {
+                merged.credential_sync_interval_secs = sync_interval;
+            } else {
+                applied.retain(|field| field != "credentialSyncIntervalSecs");
+                ignored.push("credentialSyncIntervalSecs".to_string());
+            }
+        }
+
+        if !applied.is_empty() {
+            self.token_manager.update_config(merged, proxy);
+        }
+        ignored.sort();
+        Ok(ReloadOutcome { applied, ignored })
+    }
+
+    /// 执行一次热加载并记录结果
+    fn reload_and_log(&self) {
+        match self.reload() {
+            Ok(outcome) => {
+                if !outcome.ignored.is_empty() {
+                    tracing::warn!(
+                        "以下配置项的变更需要重启才能生效，已忽略: {}",
+                        outcome.ignored.join(", ")
+                    );
+                }
+                if outcome.applied.is_empty() {
+                    tracing::info!("配置已重新读取，无可热加载的变更");
+                } else {
+                    tracing::info!("配置已热加载: {}", outcome.applied.join(", "));
+                }
+            }
+            Err(e) => tracing::error!(
+                "配置热加载失败（{}），继续使用当前配置: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+
+    /// 启动信号监听任务：收到 SIGHUP 时热加载配置
+    ///
+    /// 返回前即完成信号注册，避免之后收到的 SIGHUP 按默认行为终止进程
+    #[cfg(unix)]
+    pub fn start_signal_task(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                tracing::warn!("注册 SIGHUP 处理失败，配置热加载不可用: {}", e);
+                return tokio::spawn(async {});
+            }
+        };
+
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                tracing::info!("收到 SIGHUP，重新加载配置: {}", self.path.display());
+                self.reload_and_log();
+            }
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn start_signal_task(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async {
+            tracing::warn!("当前平台不支持通过信号热加载配置");
+        })
+    }
+}
+
+/// 配置文件或环境变量是否显式指定了 `systemVersion`
+fn system_version_configured(path: &Path) -> bool {
+    if std::env::var_os("KIRO_SYSTEM_VERSION").is_some() {
+        return true;
+    }
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .is_some_and(|value| value.get("systemVersion").is_some())
+}
+
+/// 比较两份配置，返回值不同的顶层字段（配置文件中的名称，按字母排序）
+fn changed_fields(current: &Config, new: &Config) -> anyhow::Result<Vec<String>> {
+    let (Value::Object(current), Value::Object(new)) =
+        (serde_json::to_value(current)?, serde_json::to_value(new)?)
+    else {
+        anyhow::bail!("配置序列化结果不是对象");
+    };
+
+    let mut fields: Vec<String> = current
+        .keys()
+        .chain(new.keys())
+        .filter(|key| current.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields.dedup();
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::credentials::KiroCredentials;
+    use crate::kiro::storage::FileCredentialStorage;
+    use crate::model::config::SelectionStrategy;
+
+    fn valid_credential() -> KiroCredentials {
+        let mut credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            ..Default::default()
+        };
+        credentials.expires_at =
+            Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339());
+        credentials
+    }
+
+    /// 写入配置文件并创建使用该配置的管理器
+    fn manager_for(file: &tempfile::NamedTempFile, content: &str) -> Arc<MultiTokenManager> {
+        std::fs::write(file.path(), content).unwrap();
+        let config = Config::load(file.path()).unwrap();
+        let credentials = vec![valid_credential(); 2];
+        Arc::new(MultiTokenManager::new(config, credentials, None, None, false).unwrap())
+    }
+
+    #[test]
+    fn test_reload_applies_reloadable_fields_and_ignores_the_rest() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let token_manager = manager_for(&file, r#"{"apiKey": "test-key", "port": 8990}"#);
+        let reloader = ConfigReloader::new(file.path(), token_manager.clone());
+
+        std::fs::write(
+            file.path(),
+            r#"{"apiKey": "test-key",
+                "port": 9000,
+                "proxyUrl": "socks5://127.0.0.1:1080",
+                "modelAliases": {"fast": "claude-haiku-4-5"},
+                "credentialSyncIntervalSecs": 30
+            }"#,
+        )
+        .unwrap();
+        let outcome = reloader.reload().unwrap();
+        assert_eq!(outcome.applied, vec!["modelAliases", "proxyUrl"]);
+        // 未附加同步管理器时同步间隔无法热加载
+        assert_eq!(outcome.ignored, vec!["credentialSyncIntervalSecs", "port"]);
+
+        let config = token_manager.config();
+        assert_eq!(config.port, 8990);
+        assert_eq!(config.model_aliases["fast"], "claude-haiku-4-5");
+        assert_eq!(
+            token_manager.proxy().unwrap().url,
+            "socks5://127.0.0.1:1080"
+        );
+
+        // 再次加载相同内容不产生变更
+        let outcome = reloader.reload().unwrap();
+        assert!(outcome.applied.is_empty());
+    }
+
+    #[test]
+    fn test_reload_with_sync_manager_adjusts_interval() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let token_manager = manager_for(
+            &file,
+            r#"{"apiKey": "test-key", "credentialSyncIntervalSecs": 60}"#,
+        );
+        let credentials_file = tempfile::NamedTempFile::new().unwrap();
+        let storage = Arc::new(FileCredentialStorage::new(credentials_file.path(), true));
+        let sync_manager = Arc::new(CredentialSyncManager::new(storage, 60));
+        let reloader = ConfigReloader::new(file.path(), token_manager.clone())
+            .with_sync_manager(sync_manager.clone());
+
+        std::fs::write(
+            file.path(),
+            r#"{"apiKey": "test-key", "credentialSyncIntervalSecs": 15}"#,
+        )
+        .unwrap();
+        let outcome = reloader.reload().unwrap();
+        assert_eq!(outcome.applied, vec!["credentialSyncIntervalSecs"]);
+        assert_eq!(sync_manager.status().interval_secs, 15);
+        assert_eq!(token_manager.config().credential_sync_interval_secs, 15);
+    }
+
+    #[test]
+    fn test_invalid_reload_keeps_current_config() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let token_manager = manager_for(
+            &file,
+            r#"{"apiKey": "test-key", "selectionStrategy": "weighted"}"#,
+        );
+        let reloader = ConfigReloader::new(file.path(), token_manager.clone());
+
+        std::fs::write(
+            file.path(),
+            r#"{"apiKey": "test-key", "selectionStrategy": "round-robin", "#,
+        )
+        .unwrap();
+        assert!(reloader.reload().is_err());
+        // 未通过 Config::validate 的配置整体拒绝，不应用其中可热加载的字段
+        std::fs::write(
+            file.path(),
+            r#"{"apiKey": "test-key", "selectionStrategy": "round-robin",
+                "proxyUrl": "http://127.0.0.1:8080", "proxyUsername": "user"}"#,
+        )
+        .unwrap();
+        let err = reloader.reload().unwrap_err();
+        assert!(err.to_string().contains("配置校验失败"), "{}", err);
+        std::fs::write(
+            file.path(),
+            r#"{"apiKey": "test-key", "selectionStrategy": "round-robin",
+                "proxyUrl": "ftp://127.0.0.1:21"}"#,
+        )
+        .unwrap();
+        let err = reloader.reload().unwrap_err();
+        assert!(err.to_string().contains("ftp"), "{}", err);
+
+        assert_eq!(
+            token_manager.config().selection_strategy,
+            SelectionStrategy::Weighted
+        );
+        assert!(token_manager.proxy().is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sighup_switches_selection_strategy() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let token_manager = manager_for(
+            &file,
+            r#"{"apiKey": "test-key", "selectionStrategy": "priority"}"#,
+        );
+        let reloader = Arc::new(ConfigReloader::new(file.path(), token_manager.clone()));
+        let handle = reloader.start_signal_task();
+
+        // priority 策略持续使用当前凭据
+        for _ in 0..2 {
+            assert_eq!(token_manager.acquire_context().await.unwrap().id, 1);
+        }
+
+        std::fs::write(
+            file.path(),
+            r#"{"apiKey": "test-key", "selectionStrategy": "round-robin"}"#,
+        )
+        .unwrap();
+        let status = std::process::Command::new("kill")
+            .args(["-HUP", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while token_manager.config().selection_strategy != SelectionStrategy::RoundRobin {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("SIGHUP 后选择策略未更新");
+
+        // round-robin 策略轮流使用各凭据
+        let first = token_manager.acquire_context().await.unwrap().id;
+        let second = token_manager.acquire_context().await.unwrap().id;
+        assert_ne!(first, second);
+        handle.abort();
+    }
+}
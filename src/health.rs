@@ -0,0 +1,668 @@
+//! 存活/就绪检查与指标端点
+//!
+//! `GET /healthz` 无需认证：进程存活即返回 200。
+//! `GET /readyz` 无需认证：凭据存储已就绪、存储后端可读取（`load_all` 成功），且至少有一个
+//! 未禁用、熔断器未打开的凭据时返回 200；否则返回 503，并在 `reasons` 中列出未就绪原因。
+//! 存储健康检查结果缓存 [`STORAGE_HEALTH_TTL`]，频繁探活不会反复读取整个存储后端。
+//! `?verbose=true` 时额外返回凭据数量；存储后端健康详情和存储错误信息只返回给携带有效
+//! API Key 或 Admin API Key 的调用方，便于排查故障。
+//! `GET /metrics`（`metrics_enabled` 为 false 时不提供）以 Prometheus 文本格式输出凭据选择相关计数、
+//! 按模型和状态码的请求数、Token 刷新结果、凭据可用数与熔断数，以及按凭据（可选按凭据标签）的
+//! 选中次数、请求数和 token 用量。
+//! 按凭据的序列数超过 `metrics_max_series` 后，新出现的凭据归入 `credential="other"`，已输出的序列保持不变
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::Mutex as TokioMutex;
+
+use crate::common::auth;
+use crate::kiro::storage::StorageHealth;
+use crate::kiro::token_manager::{BreakerState, CredentialUsageMetric, MultiTokenManager};
+use crate::model::config::Config;
+
+/// `/readyz` 存储健康检查结果的缓存时长
+const STORAGE_HEALTH_TTL: Duration = Duration::from_secs(5);
+
+/// `/readyz` 查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct ReadyzQuery {
+    /// 是否返回详细信息
+    #[serde(default)]
+    pub verbose: bool,
+}
+
+/// 超出序列上限的凭据归入的标签
+const OTHER_SERIES_LABELS: &str = "credential=\"other\"";
+
+/// 按凭据指标的序列上限
+///
+/// 记录已输出过的标签组合：已登记的序列始终原样输出，未登记的序列在达到上限后归入 `other`
+struct SeriesLimiter {
+    max_series: usize,
+    admitted: Mutex<HashSet<String>>,
+    warned: AtomicBool,
+}
+
+impl SeriesLimiter {
+    fn new(max_series: usize) -> Self {
+        Self {
+            max_series,
+            admitted: Mutex::new(HashSet::new()),
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// 返回序列实际使用的标签（超出上限时为 `other`）
+    fn admit(&self, labels: String) -> String {
+        if self.max_series == 0 {
+            return labels;
+        }
+
+        let mut admitted = self.admitted.lock();
+        if admitted.contains(&labels) || admitted.len() < self.max_series {
+            admitted.insert(labels.clone());
+            return labels;
+        }
+        drop(admitted);
+
+        if !self.warned.swap(true, Ordering::Relaxed) {
+            tracing::warn!(
+                "按凭据的指标序列数已达到上限 {}（metricsMaxSeries），新的凭据归入 credential=\"other\"",
+                self.max_series
+            );
+        }
+        OTHER_SERIES_LABELS.to_string()
+    }
+}
+
+/// 就绪检查与指标路由状态
+#[derive(Clone)]
+struct HealthState {
+    token_manager: Arc<MultiTokenManager>,
+    series: Arc<SeriesLimiter>,
+    /// 最近一次存储健康检查的结果及检查时间
+    storage_health: Arc<TokioMutex<Option<(Instant, StorageHealth)>>>,
+}
+
+impl HealthState {
+    fn new(token_manager: Arc<MultiTokenManager>) -> Self {
+        let series = Arc::new(SeriesLimiter::new(token_manager.config().metrics_max_series));
+        Self {
+            token_manager,
+            series,
+            storage_health: Arc::new(TokioMutex::new(None)),
+        }
+    }
+
+    /// 存储健康详情（未配置存储时为 None）
+    ///
+    /// 结果缓存 [`STORAGE_HEALTH_TTL`]；并发的探活请求等待同一次检查完成，不会重复读取存储
+    async fn storage_health(&self) -> Option<StorageHealth> {
+        let storage = self.token_manager.storage()?;
+        let mut cached = self.storage_health.lock().await;
+        if let Some((checked_at, health)) = cached.as_ref()
+            && checked_at.elapsed() < STORAGE_HEALTH_TTL
+        {
+            return Some(health.clone());
+        }
+        let health = storage.health_detail().await;
+        *cached = Some((Instant::now(), health.clone()));
+        Some(health)
+    }
+}
+
+/// 请求是否携带有效的 API Key 或 Admin API Key
+fn is_authenticated(headers: &HeaderMap, config: &Config) -> bool {
+    let Some(key) = auth::extract_api_key_from_headers(headers) else {
+        return false;
+    };
+    config
+        .api_key
+        .iter()
+        .chain(config.admin_api_key.iter())
+        .chain(config.api_keys.iter().map(|k| &k.api_key))
+        .any(|expected| auth::constant_time_eq(&key, expected))
+}
+
+/// 创建存活/就绪检查路由
+pub fn create_health_router(token_manager: Arc<MultiTokenManager>) -> Router {
+    let mut router = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz));
+    if token_manager.config().metrics_enabled {
+        router = router.route("/metrics", get(metrics));
+    }
+    router.with_state(HealthState::new(token_manager))
+}
+
+/// GET /healthz
+///
+/// 存活检查：不检查凭据和存储，进程能响应即视为存活
+async fn healthz() -> Response {
+    Json(json!({ "status": "ok" })).into_response()
+}
+
+/// GET /readyz
+async fn readyz(
+    State(state): State<HealthState>,
+    Query(query): Query<ReadyzQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let token_manager = &state.token_manager;
+    let authenticated = is_authenticated(&headers, &token_manager.config());
+    let snapshot = token_manager.snapshot();
+    // 熔断器打开（冷却中）的凭据暂时不会被选中，不计入可服务凭据
+    let usable = snapshot
+        .entries
+        .iter()
+        .filter(|e| !e.disabled && e.breaker_state != BreakerState::Open)
+        .count();
+    let storage_health = state.storage_health().await;
+
+    let mut reasons = Vec::new();
+    if !token_manager.is_storage_ready() {
+        reasons.push(json!({
+            "reason": "storage_pending",
+            "message": "凭据存储尚未完成首次加载",
+        }));
+    }
+    if let Some(health) = storage_health.as_ref().filter(|health| !health.reachable) {
+        // 存储错误可能包含连接地址等内部信息，只返回给已认证的调用方
+        let message = health
+            .error
+            .as_deref()
+            .filter(|_| authenticated)
+            .unwrap_or("凭据存储不可访问");
+        reasons.push(json!({
+            "reason": "storage_error",
+            "message": message,
+        }));
+    }
+    if usable == 0 {
+        reasons.push(json!({
+            "reason": "no_credentials",
+            "message": format!(
+                "没有可用凭据（共 {} 个，已禁用 {} 个，熔断中 {} 个）",
+                snapshot.total,
+                snapshot.total - snapshot.available,
+                snapshot.available - usable
+            ),
+        }));
+    }
+
+    let ready = reasons.is_empty();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let mut body = json!({ "status": if ready { "ready" } else { "not_ready" } });
+    if !ready {
+        body["reasons"] = json!(reasons);
+    }
+    if query.verbose {
+        body["credentials"] = json!({
+            "total": snapshot.total,
+            "available": snapshot.available,
+            "usable": usable,
+        });
+        if let Some(health) = storage_health.filter(|_| authenticated) {
+            body["storage"] = json!(health);
+        }
+    }
+
+    (status, Json(body)).into_response()
+}
+
+/// GET /metrics
+async fn metrics(State(state): State<HealthState>) -> Response {
+    let token_manager = &state.token_manager;
+    let mut body = String::from(
+        "# HELP kiro_credential_selection_skips_total Credentials skipped during selection, by reason\n\
+         # TYPE kiro_credential_selection_skips_total counter\n",
+    );
+    for (reason, count) in token_manager.selection_skip_counts() {
+        body.push_str(&format!(
+            "kiro_credential_selection_skips_total{{reason=\"{}\"}} {}\n",
+            reason.as_str(),
+            count
+        ));
+    }
+
+    body.push_str(
+        "# HELP kiro_requests_total Client requests, by model and response status\n\
+         # TYPE kiro_requests_total counter\n",
+    );
+    for (model, status, count) in token_manager.request_counts() {
+        body.push_str(&format!(
+            "kiro_requests_total{{model=\"{}\",status=\"{}\"}} {}\n",
+            escape_label_value(&model),
+            status,
+            count
+        ));
+    }
+
+    let (refresh_successes, refresh_failures) = token_manager.refresh_counts();
+    body.push_str(&format!(
+        "# HELP kiro_token_refresh_total Token refresh attempts, by result\n\
+         # TYPE kiro_token_refresh_total counter\n\
+         kiro_token_refresh_total{{result=\"success\"}} {}\n\
+         kiro_token_refresh_total{{result=\"failure\"}} {}\n",
+        refresh_successes, refresh_failures
+    ));
+
+    let snapshot = token_manager.snapshot();
+    let breakers_open = snapshot
+        .entries
+        .iter()
+        .filter(|e| e.breaker_state == BreakerState::Open)
+        .count();
+    body.push_str(&format!(
+        "# HELP kiro_credentials_available Credentials currently enabled\n\
+         # TYPE kiro_credentials_available gauge\n\
+         kiro_credentials_available {}\n\
+         # HELP kiro_credential_breakers_open Credentials whose circuit breaker is cooling down\n\
+         # TYPE kiro_credential_breakers_open gauge\n\
+         kiro_credential_breakers_open {}\n",
+        snapshot.available, breakers_open
+    ));
+
+    let config = token_manager.config();
+    let tag_label = config.metrics_tag_label.as_ref();
+    let labels_of = |metric: &CredentialUsageMetric| {
+        let mut labels = format!("credential=\"{}\"", escape_label_value(&metric.label));
+        if let (Some(tag_label), Some(tag)) = (tag_label, &metric.tag) {
+            labels.push_str(&format!(",{}=\"{}\"", tag_label.name, escape_label_value(tag)));
+        }
+        labels
+    };
+
+    // (标签, 选中次数, 请求数, token 数)，超出序列上限的凭据累加到末尾的 other 序列
+    let mut series: Vec<(String, u64, u64, u64)> = Vec::new();
+    let mut other: Option<(u64, u64, u64)> = None;
+    for metric in token_manager.credential_usage_metrics() {
+        let labels = state.series.admit(labels_of(&metric));
+        if labels == OTHER_SERIES_LABELS {
+            let (selections, requests, tokens) = other.get_or_insert((0, 0, 0));
+            *selections += metric.selections;
+            *requests += metric.requests;
+            *tokens += metric.tokens;
+        } else {
+            series.push((labels, metric.selections, metric.requests, metric.tokens));
+        }
+    }
+    if let Some((selections, requests, tokens)) = other {
+        series.push((OTHER_SERIES_LABELS.to_string(), selections, requests, tokens));
+    }
+
+    body.push_str(
+        "# HELP kiro_credential_selected_total Times each credential was selected for a request\n\
+         # TYPE kiro_credential_selected_total counter\n",
+    );
+    for (labels, selections, _, _) in &series {
+        body.push_str(&format!(
+            "kiro_credential_selected_total{{{}}} {}\n",
+            labels, selections
+        ));
+    }
+
+    body.push_str(
+        "# HELP kiro_credential_requests_total Successful upstream requests, by credential\n\
+         # TYPE kiro_credential_requests_total counter\n",
+    );
+    for (labels, _, requests, _) in &series {
+        body.push_str(&format!("kiro_credential_requests_total{{{}}} {}\n", labels, requests));
+    }
+    body.push_str(
+        "# HELP kiro_credential_tokens_total Tokens consumed, by credential\n\
+         # TYPE kiro_credential_tokens_total counter\n",
+    );
+    for (labels, _, _, tokens) in &series {
+        body.push_str(&format!("kiro_credential_tokens_total{{{}}} {}\n", labels, tokens));
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        body,
+    )
+        .into_response()
+}
+
+/// 转义 Prometheus 标签值中的反斜杠、双引号和换行
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::credentials::KiroCredentials;
+    use crate::kiro::storage::FileCredentialStorage;
+    use crate::model::config::Config;
+    use chrono::{Duration, Utc};
+    use std::io::Write;
+
+    fn health_state(manager: MultiTokenManager) -> HealthState {
+        HealthState::new(Arc::new(manager))
+    }
+
+    async fn scrape(state: &HealthState) -> String {
+        let response = metrics(State(state.clone())).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_readyz_verbose_includes_storage_detail() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, r#"[{{"id":1,"refreshToken":"r1"}},{{"id":2,"refreshToken":"r2"}}]"#)
+            .unwrap();
+
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let config = Config {
+            admin_api_key: Some("admin-key".to_string()),
+            ..Default::default()
+        };
+        let mut manager =
+            MultiTokenManager::new(config, vec![credentials], None, None, false).unwrap();
+        manager.set_storage(Arc::new(FileCredentialStorage::new(file.path(), true)));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_health_router(Arc::new(manager));
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let response = reqwest::get(format!("http://{}/readyz", addr)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body, json!({"status": "ready"}));
+
+        let verbose = |key: Option<&'static str>| {
+            let mut request =
+                reqwest::Client::new().get(format!("http://{}/readyz?verbose=true", addr));
+            if let Some(key) = key {
+                request = request.header("x-api-key", key);
+            }
+            async move {
+                let response = request.send().await.unwrap();
+                response.json::<serde_json::Value>().await.unwrap()
+            }
+        };
+
+        // 未认证时只返回凭据数量，不返回存储详情
+        let body = verbose(None).await;
+        assert_eq!(body["credentials"]["available"], 1);
+        assert!(body.get("storage").is_none());
+        let body = verbose(Some("wrong-key")).await;
+        assert!(body.get("storage").is_none());
+
+        let body = verbose(Some("admin-key")).await;
+        assert_eq!(body["credentials"]["available"], 1);
+        assert_eq!(body["storage"]["backend"], "file");
+        assert_eq!(body["storage"]["reachable"], true);
+        assert_eq!(body["storage"]["credentialCount"], 2);
+
+        let metrics = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(metrics.contains("kiro_credential_selection_skips_total{reason=\"disabled\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_carry_configured_credential_tag() {
+        use crate::model::config::MetricsTagLabel;
+
+        let config = Config {
+            metrics_tag_label: Some(MetricsTagLabel {
+                name: "team".to_string(),
+                values: vec!["platform".to_string()],
+            }),
+            ..Default::default()
+        };
+        let creds: Vec<KiroCredentials> = [vec!["oncall", "platform"], vec!["unlisted"]]
+            .into_iter()
+            .map(|tags| KiroCredentials {
+                access_token: Some("token".to_string()),
+                tags: tags.into_iter().map(str::to_string).collect(),
+                ..Default::default()
+            })
+            .collect();
+        let manager = MultiTokenManager::new(config, creds, None, None, false).unwrap();
+        manager.report_success(1);
+        manager.record_token_usage(1, 42);
+        manager.report_success(2);
+
+        let body = scrape(&health_state(manager)).await;
+
+        assert!(
+            body.contains("kiro_credential_requests_total{credential=\"#1\",team=\"platform\"} 1\n")
+        );
+        assert!(
+            body.contains("kiro_credential_tokens_total{credential=\"#1\",team=\"platform\"} 42\n")
+        );
+        // 未配置的标签值不输出，限制指标基数
+        assert!(body.contains("kiro_credential_requests_total{credential=\"#2\"} 1\n"));
+        assert!(!body.contains("unlisted"));
+        assert!(!body.contains("oncall"));
+    }
+
+    #[tokio::test]
+    async fn test_series_over_cap_collapse_into_other() {
+        let config = Config {
+            metrics_max_series: 2,
+            ..Default::default()
+        };
+        let creds: Vec<KiroCredentials> = (0..4)
+            .map(|_| KiroCredentials {
+                access_token: Some("token".to_string()),
+                ..Default::default()
+            })
+            .collect();
+        let manager = MultiTokenManager::new(config, creds, None, None, false).unwrap();
+        for id in 1..=4 {
+            manager.report_success(id);
+            manager.record_token_usage(id, 10 * id);
+        }
+        let state = health_state(manager);
+
+        let body = scrape(&state).await;
+        assert!(body.contains("kiro_credential_requests_total{credential=\"#1\"} 1\n"));
+        assert!(body.contains("kiro_credential_requests_total{credential=\"#2\"} 1\n"));
+        assert!(body.contains("kiro_credential_requests_total{credential=\"other\"} 2\n"));
+        assert!(body.contains("kiro_credential_tokens_total{credential=\"other\"} 70\n"));
+        assert!(!body.contains("#3") && !body.contains("#4"));
+
+        // 已输出的序列在后续抓取中保持不变
+        state.token_manager.report_success(1);
+        let body = scrape(&state).await;
+        assert!(body.contains("kiro_credential_requests_total{credential=\"#1\"} 2\n"));
+        assert!(body.contains("kiro_credential_requests_total{credential=\"other\"} 2\n"));
+        assert_eq!(state.series.admitted.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_expose_requests_selections_and_gauges() {
+        let creds = vec![KiroCredentials {
+            access_token: Some("token".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        }];
+        let manager = MultiTokenManager::new(Config::default(), creds, None, None, false).unwrap();
+        manager.acquire_context().await.unwrap();
+        manager.record_request("claude-sonnet-4", 200);
+        manager.record_request("claude-sonnet-4", 429);
+        manager.record_request("claude-sonnet-4", 200);
+
+        let body = scrape(&health_state(manager)).await;
+
+        assert!(body.contains(
+            "kiro_requests_total{model=\"claude-sonnet-4\",status=\"200\"} 2\n"
+        ));
+        assert!(body.contains(
+            "kiro_requests_total{model=\"claude-sonnet-4\",status=\"429\"} 1\n"
+        ));
+        assert!(body.contains("kiro_credential_selected_total{credential=\"#1\"} 1\n"));
+        assert!(body.contains("kiro_token_refresh_total{result=\"success\"} 0\n"));
+        assert!(body.contains("kiro_token_refresh_total{result=\"failure\"} 0\n"));
+        assert!(body.contains("# TYPE kiro_credentials_available gauge\n"));
+        assert!(body.contains("kiro_credentials_available 1\n"));
+        assert!(body.contains("kiro_credential_breakers_open 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_route_disabled_by_config() {
+        let config = Config {
+            metrics_enabled: false,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(config, vec![], None, None, false).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_health_router(Arc::new(manager));
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let response = reqwest::get(format!("http://{}/metrics", addr)).await.unwrap();
+        assert_eq!(response.status(), 404);
+        let response = reqwest::get(format!("http://{}/readyz", addr)).await.unwrap();
+        assert_eq!(response.status(), 503);
+    }
+
+    async fn ready_check(state: &HealthState) -> (StatusCode, serde_json::Value) {
+        let response = readyz(
+            State(state.clone()),
+            Query(ReadyzQuery::default()),
+            HeaderMap::new(),
+        )
+        .await;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn valid_credentials(count: usize) -> Vec<KiroCredentials> {
+        (0..count)
+            .map(|_| KiroCredentials {
+                access_token: Some("token".to_string()),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_readyz_flips_when_all_credentials_disabled() {
+        let manager =
+            MultiTokenManager::new(Config::default(), valid_credentials(2), None, None, false)
+                .unwrap();
+        let state = health_state(manager);
+        assert_eq!(healthz().await.status(), StatusCode::OK);
+        assert_eq!(ready_check(&state).await.0, StatusCode::OK);
+
+        state.token_manager.set_disabled(1, true).unwrap();
+        state.token_manager.set_disabled(2, true).unwrap();
+        let (status, body) = ready_check(&state).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["reasons"][0]["reason"], "no_credentials");
+        // 存活检查不受凭据状态影响
+        assert_eq!(healthz().await.status(), StatusCode::OK);
+
+        state.token_manager.set_disabled(2, false).unwrap();
+        let (status, body) = ready_check(&state).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.get("reasons").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_readyz_not_ready_when_only_credential_breaker_open() {
+        let config = Config {
+            breaker_cooldown_secs: 60,
+            ..Default::default()
+        };
+        let threshold = config.breaker_failure_threshold;
+        let manager = MultiTokenManager::new(config, valid_credentials(1), None, None, false)
+            .unwrap();
+        for _ in 0..threshold {
+            manager.report_failure(1);
+        }
+
+        let (status, body) = ready_check(&health_state(manager)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["reasons"][0]["reason"], "no_credentials");
+        assert!(body["reasons"][0]["message"].as_str().unwrap().contains("熔断中 1 个"));
+    }
+
+    #[tokio::test]
+    async fn test_readyz_reports_storage_error() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "not json").unwrap();
+        let mut manager =
+            MultiTokenManager::new(Config::default(), valid_credentials(1), None, None, false)
+                .unwrap();
+        manager.set_storage(Arc::new(FileCredentialStorage::new(file.path(), true)));
+
+        let (status, body) = ready_check(&health_state(manager)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let reasons = body["reasons"].as_array().unwrap();
+        assert_eq!(reasons.len(), 1);
+        assert_eq!(reasons[0]["reason"], "storage_error");
+        // 未认证的探活请求只看到通用错误信息
+        assert_eq!(reasons[0]["message"], "凭据存储不可访问");
+    }
+
+    #[tokio::test]
+    async fn test_readyz_caches_storage_health() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, r#"[{{"id":1,"refreshToken":"r1"}}]"#).unwrap();
+        let mut manager =
+            MultiTokenManager::new(Config::default(), valid_credentials(1), None, None, false)
+                .unwrap();
+        manager.set_storage(Arc::new(FileCredentialStorage::new(file.path(), true)));
+        let state = health_state(manager);
+        assert_eq!(ready_check(&state).await.0, StatusCode::OK);
+
+        // 缓存有效期内不重新读取存储
+        std::fs::write(file.path(), "not json").unwrap();
+        assert_eq!(ready_check(&state).await.0, StatusCode::OK);
+
+        // 缓存过期后重新检查
+        let expired = Instant::now() - STORAGE_HEALTH_TTL;
+        state.storage_health.lock().await.as_mut().unwrap().0 = expired;
+        let (status, body) = ready_check(&state).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["reasons"][0]["reason"], "storage_error");
+    }
+}
@@ -2,11 +2,19 @@
 //!
 //! 提供统一的 HTTP Client 构建功能，支持代理配置
 
-use reqwest::{Client, Proxy};
+use reqwest::{Client, Proxy, redirect};
 use std::time::Duration;
 
+use crate::model::config::FollowRedirects;
+
+/// 跟随重定向的最大次数
+const MAX_REDIRECTS: usize = 10;
+
+/// 支持的代理协议（socks5h 由代理服务器解析域名）
+pub const SUPPORTED_PROXY_SCHEMES: &[&str] = &["http", "https", "socks5", "socks5h"];
+
 /// 代理配置
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ProxyConfig {
     /// 代理地址，支持 http/https/socks5
     pub url: String,
@@ -26,12 +34,61 @@ impl ProxyConfig {
         }
     }
 
-    /// 设置认证信息
+    /// 设置认证信息（HTTP 代理使用 Basic 认证，SOCKS5 代理使用用户名/密码认证）
     pub fn with_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
         self.username = Some(username.into());
         self.password = Some(password.into());
         self
     }
+
+    /// 用于日志和错误信息的代理地址（去掉 `user:pass@` 认证信息）
+    pub fn redacted_url(&self) -> String {
+        let url = self.url.trim();
+        let (prefix, rest) = match url.find("://") {
+            Some(index) => url.split_at(index + 3),
+            None => ("", url),
+        };
+        let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        match rest[..authority_end].rfind('@') {
+            Some(at) => format!("{}{}", prefix, &rest[at + 1..]),
+            None => url.to_string(),
+        }
+    }
+
+    /// 代理协议（小写）
+    ///
+    /// 地址未携带协议时按 http 处理（与 reqwest 一致）；协议不受支持时返回错误，
+    /// 避免拼写错误的代理地址被静默忽略而直连上游
+    pub fn scheme(&self) -> anyhow::Result<String> {
+        let Some((scheme, _)) = self.url.trim().split_once("://") else {
+            return Ok("http".to_string());
+        };
+        let scheme = scheme.to_ascii_lowercase();
+        if !SUPPORTED_PROXY_SCHEMES.contains(&scheme.as_str()) {
+            anyhow::bail!(
+                "不支持的代理协议 \"{}\"（{}），支持: {}",
+                scheme,
+                self.redacted_url(),
+                SUPPORTED_PROXY_SCHEMES.join(", ")
+            );
+        }
+        Ok(scheme)
+    }
+
+    /// 构建 reqwest 代理
+    fn to_proxy(&self) -> anyhow::Result<Proxy> {
+        let scheme = self.scheme()?;
+        let mut proxy = Proxy::all(self.url.trim())
+            .map_err(|e| anyhow::anyhow!("代理地址无效（{}）: {}", self.redacted_url(), e))?;
+
+        // 设置代理认证
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            proxy = proxy.basic_auth(username, password);
+        }
+
+        tracing::debug!("HTTP Client 使用 {} 代理: {}", scheme, self.redacted_url());
+        Ok(proxy)
+    }
 }
 
 /// 构建 HTTP Client
@@ -43,18 +100,56 @@ impl ProxyConfig {
 /// # Returns
 /// 配置好的 reqwest::Client
 pub fn build_client(proxy: Option<&ProxyConfig>, timeout_secs: u64) -> anyhow::Result<Client> {
-    let mut builder = Client::builder().timeout(Duration::from_secs(timeout_secs));
+    build_client_with(proxy, timeout_secs, None)
+}
 
-    if let Some(proxy_config) = proxy {
-        let mut proxy = Proxy::all(&proxy_config.url)?;
+/// 构建使用指定重定向策略的 HTTP Client（用于上游 API 调用）
+pub fn build_client_with_redirects(
+    proxy: Option<&ProxyConfig>,
+    timeout_secs: u64,
+    follow_redirects: FollowRedirects,
+) -> anyhow::Result<Client> {
+    build_client_with(proxy, timeout_secs, Some(redirect_policy(follow_redirects)))
+}
 
-        // 设置代理认证
-        if let (Some(username), Some(password)) = (&proxy_config.username, &proxy_config.password) {
-            proxy = proxy.basic_auth(username, password);
-        }
+/// 重定向策略
+///
+/// reqwest 在跨主机（host 或端口不同）重定向时会移除 Authorization、Cookie 等敏感请求头，
+/// `Any` 依赖这一行为避免把凭据 Token 发给其他主机
+fn redirect_policy(follow_redirects: FollowRedirects) -> redirect::Policy {
+    match follow_redirects {
+        FollowRedirects::None => redirect::Policy::none(),
+        FollowRedirects::SameHost => redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() > MAX_REDIRECTS {
+                return attempt.error("重定向次数过多");
+            }
+            let same_host = attempt.previous().last().is_some_and(|previous| {
+                previous.host_str() == attempt.url().host_str()
+                    && previous.port_or_known_default() == attempt.url().port_or_known_default()
+            });
+            if same_host {
+                attempt.follow()
+            } else {
+                tracing::warn!("上游重定向到其他主机，不跟随: {}", attempt.url());
+                attempt.stop()
+            }
+        }),
+        FollowRedirects::Any => redirect::Policy::limited(MAX_REDIRECTS),
+    }
+}
+
+fn build_client_with(
+    proxy: Option<&ProxyConfig>,
+    timeout_secs: u64,
+    redirect_policy: Option<redirect::Policy>,
+) -> anyhow::Result<Client> {
+    let mut builder = Client::builder().timeout(Duration::from_secs(timeout_secs));
+    if let Some(policy) = redirect_policy {
+        builder = builder.redirect(policy);
+    }
 
-        builder = builder.proxy(proxy);
-        tracing::debug!("HTTP Client 使用代理: {}", proxy_config.url);
+    if let Some(proxy_config) = proxy {
+        builder = builder.proxy(proxy_config.to_proxy()?);
     }
 
     Ok(builder.build()?)
@@ -80,6 +175,22 @@ mod tests {
         assert_eq!(config.password, Some("pass".to_string()));
     }
 
+    #[test]
+    fn test_redacted_url_strips_userinfo() {
+        let redacted = |url: &str| ProxyConfig::new(url).redacted_url();
+        assert_eq!(
+            redacted("http://user:p@ss@proxy.example.com:8080/path"),
+            "http://proxy.example.com:8080/path"
+        );
+        assert_eq!(
+            redacted("socks5://user@127.0.0.1:1080"),
+            "socks5://127.0.0.1:1080"
+        );
+        assert_eq!(redacted("user:pass@127.0.0.1:7890"), "127.0.0.1:7890");
+        assert_eq!(redacted("http://127.0.0.1:7890"), "http://127.0.0.1:7890");
+        assert_eq!(redacted("http://proxy/a@b"), "http://proxy/a@b");
+    }
+
     #[test]
     fn test_build_client_without_proxy() {
         let client = build_client(None, 30);
@@ -92,4 +203,97 @@ mod tests {
         let client = build_client(Some(&config), 30);
         assert!(client.is_ok());
     }
+
+    #[test]
+    fn test_build_client_for_each_proxy_scheme() {
+        for url in [
+            "http://127.0.0.1:7890",
+            "https://proxy.example.com:443",
+            "socks5://127.0.0.1:1080",
+            "socks5h://proxy.example.com:1080",
+            "SOCKS5://127.0.0.1:1080",
+            "127.0.0.1:7890",
+        ] {
+            let config = ProxyConfig::new(url).with_auth("user", "pass");
+            assert!(build_client(Some(&config), 30).is_ok(), "{}", url);
+        }
+        assert_eq!(
+            ProxyConfig::new("socks5h://proxy.example.com:1080").scheme().unwrap(),
+            "socks5h"
+        );
+        assert_eq!(ProxyConfig::new("127.0.0.1:7890").scheme().unwrap(), "http");
+    }
+
+    #[test]
+    fn test_unknown_proxy_scheme_is_rejected() {
+        for url in ["ftp://127.0.0.1:21", "sock5://127.0.0.1:1080"] {
+            let config = ProxyConfig::new(url);
+            let err = build_client(Some(&config), 30).unwrap_err().to_string();
+            assert!(err.contains("不支持的代理协议"), "{}", err);
+            assert!(err.contains("socks5h"), "{}", err);
+        }
+    }
+
+    /// 启动本地 HTTP 服务，返回其地址
+    async fn spawn_server(app: axum::Router) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_cross_host_redirect_does_not_forward_auth() {
+        use axum::http::{HeaderMap, StatusCode, header};
+        use std::sync::{Arc, Mutex};
+
+        // 目标主机：记录收到的 Authorization 头
+        let received: Arc<Mutex<Vec<Option<String>>>> = Arc::default();
+        let target = spawn_server(axum::Router::new().route(
+            "/",
+            axum::routing::post({
+                let received = received.clone();
+                move |headers: HeaderMap| async move {
+                    let auth = headers
+                        .get(header::AUTHORIZATION)
+                        .map(|v| v.to_str().unwrap().to_string());
+                    received.lock().unwrap().push(auth);
+                    "ok"
+                }
+            }),
+        ))
+        .await;
+
+        // 上游：307 重定向到另一主机（端口不同）
+        let location = format!("http://localhost:{}/", target.port());
+        let upstream = spawn_server(axum::Router::new().route(
+            "/",
+            axum::routing::post(move || async move {
+                (StatusCode::TEMPORARY_REDIRECT, [(header::LOCATION, location)])
+            }),
+        ))
+        .await;
+        let send = |follow_redirects| async move {
+            build_client_with_redirects(None, 30, follow_redirects)
+                .unwrap()
+                .post(format!("http://127.0.0.1:{}/", upstream.port()))
+                .header(header::AUTHORIZATION, "Bearer secret")
+                .body("{}")
+                .send()
+                .await
+                .unwrap()
+                .status()
+        };
+
+        // 默认不跟随；same-host 不跟随跨主机重定向
+        assert_eq!(send(FollowRedirects::None).await, 307);
+        assert_eq!(send(FollowRedirects::SameHost).await, 307);
+        assert!(received.lock().unwrap().is_empty());
+
+        // any 跟随，但不转发 Authorization
+        assert_eq!(send(FollowRedirects::Any).await, 200);
+        assert_eq!(*received.lock().unwrap(), vec![None]);
+    }
 }
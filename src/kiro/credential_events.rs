@@ -0,0 +1,108 @@
+//! 凭据运行时事件
+//!
+//! `MultiTokenManager` 在凭据禁用/启用、熔断器打开/关闭时发布事件，凭据同步重新加载后由同步回调发布，
+//! Admin API 通过 WebSocket（`GET /api/admin/events`）推送给管理面板，替代轮询凭据列表
+
+use serde::Serialize;
+
+use super::storage::CredentialChangeEvent;
+use super::token_manager::BreakerState;
+
+/// 事件广播通道容量，订阅者落后超过该数量时丢弃最旧的事件
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// 凭据运行时事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum CredentialEvent {
+    /// 凭据已从存储后端重新加载
+    CredentialsReloaded { count: usize },
+    /// 凭据被禁用（`reason`: manual、too_many_failures、quota_exceeded）
+    CredentialDisabled { id: u64, reason: &'static str },
+    /// 凭据被启用
+    CredentialEnabled { id: u64 },
+    /// 凭据熔断器状态变化（只发布打开和关闭，半开由冷却到期隐式进入）
+    BreakerStateChanged { id: u64, state: BreakerState },
+    /// 定期推送的统计快照
+    StatsSnapshot(StatsSnapshot),
+    /// 订阅者处理过慢，已丢弃 `skipped` 个最旧的事件
+    Lagged { skipped: u64 },
+}
+
+impl From<&CredentialChangeEvent> for CredentialEvent {
+    fn from(event: &CredentialChangeEvent) -> Self {
+        match event {
+            CredentialChangeEvent::Reloaded(credentials) => CredentialEvent::CredentialsReloaded {
+                count: credentials.len(),
+            },
+        }
+    }
+}
+
+/// 凭据统计快照
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsSnapshot {
+    /// 凭据总数
+    pub total: usize,
+    /// 可用凭据数量（未禁用）
+    pub available: usize,
+    /// 熔断器处于打开或半开状态的凭据数量
+    pub breaker_open: usize,
+    /// 当前活跃凭据 ID
+    pub current_id: u64,
+    /// 固定使用的凭据 ID（未固定时不返回）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_id: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::credentials::KiroCredentials;
+
+    #[test]
+    fn test_events_serialize_with_type_tag() {
+        let event = CredentialEvent::CredentialDisabled {
+            id: 3,
+            reason: "manual",
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"type": "credential_disabled", "id": 3, "reason": "manual"})
+        );
+
+        let event = CredentialEvent::StatsSnapshot(StatsSnapshot {
+            total: 2,
+            available: 1,
+            breaker_open: 0,
+            current_id: 1,
+            pinned_id: None,
+        });
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "type": "stats_snapshot",
+                "total": 2,
+                "available": 1,
+                "breakerOpen": 0,
+                "currentId": 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_reload_event_does_not_expose_credentials() {
+        let change = CredentialChangeEvent::Reloaded(vec![KiroCredentials {
+            refresh_token: Some("secret".to_string()),
+            ..Default::default()
+        }]);
+        let event = CredentialEvent::from(&change);
+        assert_eq!(event, CredentialEvent::CredentialsReloaded { count: 1 });
+        assert!(!serde_json::to_string(&event).unwrap().contains("secret"));
+    }
+}
@@ -0,0 +1,57 @@
+//! 凭据日志标识
+//!
+//! 启用 `config.log_credential_refs` 时，日志中以加盐哈希得到的短标识（`ref:xxxxxxxxxxxx`）
+//! 代替凭据 ID，既能在同一进程内关联同一凭据的路由记录，又不暴露真实 ID
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::OnceLock;
+
+use crate::model::config::Config;
+
+/// 进程级随机盐（进程重启后同一凭据的 ref 会变化）
+fn process_salt() -> u64 {
+    static SALT: OnceLock<u64> = OnceLock::new();
+    *SALT.get_or_init(|| fastrand::u64(..))
+}
+
+/// 计算凭据的伪名标识
+///
+/// 仅由凭据 ID 和进程盐派生；不使用 refreshToken，因为刷新后 refreshToken 可能轮换，
+/// 会导致同一凭据在进程内出现多个 ref
+pub fn credential_ref(id: u64) -> String {
+    let mut hasher = DefaultHasher::new();
+    process_salt().hash(&mut hasher);
+    id.hash(&mut hasher);
+    format!("ref:{:012x}", hasher.finish() >> 16)
+}
+
+/// 日志中使用的凭据标识：启用 `log_credential_refs` 时为伪名标识，否则为 `#id`
+pub fn credential_label(config: &Config, id: u64) -> String {
+    if config.log_credential_refs {
+        credential_ref(id)
+    } else {
+        format!("#{}", id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credential_ref_stable_within_process() {
+        assert_eq!(credential_ref(1), credential_ref(1));
+        assert_ne!(credential_ref(1), credential_ref(2));
+        assert!(credential_ref(1).starts_with("ref:"));
+        assert_eq!(credential_ref(1).len(), "ref:".len() + 12);
+    }
+
+    #[test]
+    fn test_credential_label_follows_config() {
+        let mut config = Config::default();
+        assert_eq!(credential_label(&config, 7), "#7");
+
+        config.log_credential_refs = true;
+        assert_eq!(credential_label(&config, 7), credential_ref(7));
+    }
+}
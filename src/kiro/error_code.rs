@@ -0,0 +1,211 @@
+//! 结构化错误码
+//!
+//! 在 Anthropic 错误响应中以 `kiro_error_code` 字段返回，供客户端按稳定的错误码分支处理，
+//! 而不必解析 `message` 文本
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::kiro::monthly_budget::MonthlyBudgetExhaustedError;
+use crate::kiro::stream_timeout::StreamTimeoutError;
+use crate::kiro::token_manager::{
+    NoEligibleCredentialError, PinnedCredentialUnavailableError, StorageUnavailableError,
+};
+
+/// 稳定的错误码（序列化为 snake_case）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KiroErrorCode {
+    /// 请求格式或参数无效
+    InvalidRequest,
+    /// 请求的模型不受支持
+    ModelUnsupported,
+    /// 触发本地限流（按模型请求速率或按 API Key 并发数）
+    RateLimited,
+    /// Kiro provider 未配置
+    ProviderNotConfigured,
+    /// 没有可用凭据（全部禁用、刷新失败、被本次请求排除或固定凭据不可用）
+    NoCredentialsAvailable,
+    /// 所有可用凭据的月度 token 预算均已用尽
+    CredentialBudgetExhausted,
+    /// 凭据存储后端尚未连接
+    StorageUnavailable,
+    /// 上游额度已用尽（402）
+    UpstreamQuotaExhausted,
+    /// 上游认证失败（401/403）
+    UpstreamAuthFailed,
+    /// 上游限流（429）
+    RateLimitedUpstream,
+    /// 上游拒绝请求（400 及其他 4xx）
+    UpstreamRejected,
+    /// 上游不可用（5xx、超时、网络错误）
+    UpstreamUnavailable,
+    /// 上游响应体超过上限
+    UpstreamResponseTooLarge,
+    /// 超过请求截止时间（`x-kiro-deadline-ms`）
+    DeadlineExceeded,
+    /// 上游流式响应超时（首个数据块或数据块间隔）
+    UpstreamTimeout,
+    /// 服务内部错误
+    InternalError,
+}
+
+impl KiroErrorCode {
+    /// 错误码字符串（与序列化结果一致）
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KiroErrorCode::InvalidRequest => "invalid_request",
+            KiroErrorCode::ModelUnsupported => "model_unsupported",
+            KiroErrorCode::RateLimited => "rate_limited",
+            KiroErrorCode::ProviderNotConfigured => "provider_not_configured",
+            KiroErrorCode::NoCredentialsAvailable => "no_credentials_available",
+            KiroErrorCode::CredentialBudgetExhausted => "credential_budget_exhausted",
+            KiroErrorCode::StorageUnavailable => "storage_unavailable",
+            KiroErrorCode::UpstreamQuotaExhausted => "upstream_quota_exhausted",
+            KiroErrorCode::UpstreamAuthFailed => "upstream_auth_failed",
+            KiroErrorCode::RateLimitedUpstream => "rate_limited_upstream",
+            KiroErrorCode::UpstreamRejected => "upstream_rejected",
+            KiroErrorCode::UpstreamUnavailable => "upstream_unavailable",
+            KiroErrorCode::UpstreamResponseTooLarge => "upstream_response_too_large",
+            KiroErrorCode::DeadlineExceeded => "deadline_exceeded",
+            KiroErrorCode::UpstreamTimeout => "upstream_timeout",
+            KiroErrorCode::InternalError => "internal_error",
+        }
+    }
+
+    /// 按上游 HTTP 状态码分类
+    pub fn from_upstream_status(status: u16) -> Self {
+        match status {
+            402 => KiroErrorCode::UpstreamQuotaExhausted,
+            401 | 403 => KiroErrorCode::UpstreamAuthFailed,
+            429 => KiroErrorCode::RateLimitedUpstream,
+            408 | 500..=599 => KiroErrorCode::UpstreamUnavailable,
+            400..=499 => KiroErrorCode::UpstreamRejected,
+            _ => KiroErrorCode::UpstreamUnavailable,
+        }
+    }
+
+    /// 从调用链错误中识别错误码
+    ///
+    /// 优先使用显式携带的 `KiroError`，其次识别已知的类型化错误；
+    /// 其余未分类的上游调用失败视为 `upstream_unavailable`
+    pub fn of(error: &anyhow::Error) -> Self {
+        if let Some(e) = error.downcast_ref::<KiroError>() {
+            return e.code;
+        }
+        if error.is::<NoEligibleCredentialError>()
+            || error.is::<PinnedCredentialUnavailableError>()
+        {
+            return KiroErrorCode::NoCredentialsAvailable;
+        }
+        if error.is::<MonthlyBudgetExhaustedError>() {
+            return KiroErrorCode::CredentialBudgetExhausted;
+        }
+        if error.is::<StorageUnavailableError>() {
+            return KiroErrorCode::StorageUnavailable;
+        }
+        if error.is::<StreamTimeoutError>() {
+            return KiroErrorCode::UpstreamTimeout;
+        }
+        KiroErrorCode::UpstreamUnavailable
+    }
+}
+
+impl fmt::Display for KiroErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 携带错误码的错误
+///
+/// provider / token manager 在失败路径上返回此错误，
+/// 调用方可通过 `KiroErrorCode::of` 取回错误码
+#[derive(Debug)]
+pub struct KiroError {
+    pub code: KiroErrorCode,
+    pub message: String,
+}
+
+impl KiroError {
+    pub fn new(code: KiroErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for KiroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for KiroError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_errors_map_to_stable_codes() {
+        let e: anyhow::Error = NoEligibleCredentialError {
+            excluded_ids: vec![1],
+        }
+        .into();
+        assert_eq!(KiroErrorCode::of(&e), KiroErrorCode::NoCredentialsAvailable);
+
+        let e: anyhow::Error = MonthlyBudgetExhaustedError {
+            period: "2026-10".to_string(),
+        }
+        .into();
+        assert_eq!(KiroErrorCode::of(&e), KiroErrorCode::CredentialBudgetExhausted);
+
+        let e: anyhow::Error = KiroError::new(KiroErrorCode::RateLimitedUpstream, "429").into();
+        assert_eq!(KiroErrorCode::of(&e), KiroErrorCode::RateLimitedUpstream);
+
+        let e = anyhow::anyhow!("connection reset");
+        assert_eq!(KiroErrorCode::of(&e), KiroErrorCode::UpstreamUnavailable);
+    }
+
+    #[test]
+    fn test_upstream_status_codes() {
+        assert_eq!(
+            KiroErrorCode::from_upstream_status(429),
+            KiroErrorCode::RateLimitedUpstream
+        );
+        assert_eq!(
+            KiroErrorCode::from_upstream_status(403),
+            KiroErrorCode::UpstreamAuthFailed
+        );
+        assert_eq!(
+            KiroErrorCode::from_upstream_status(402),
+            KiroErrorCode::UpstreamQuotaExhausted
+        );
+        assert_eq!(
+            KiroErrorCode::from_upstream_status(400),
+            KiroErrorCode::UpstreamRejected
+        );
+        assert_eq!(
+            KiroErrorCode::from_upstream_status(503),
+            KiroErrorCode::UpstreamUnavailable
+        );
+    }
+
+    #[test]
+    fn test_serialized_code_matches_as_str() {
+        for code in [
+            KiroErrorCode::NoCredentialsAvailable,
+            KiroErrorCode::ModelUnsupported,
+            KiroErrorCode::RateLimitedUpstream,
+            KiroErrorCode::UpstreamResponseTooLarge,
+        ] {
+            assert_eq!(
+                serde_json::to_value(code).unwrap(),
+                serde_json::Value::String(code.as_str().to_string())
+            );
+        }
+    }
+}
@@ -1,8 +1,14 @@
 //! Kiro API 客户端模块
 
+pub mod credential_events;
+pub mod credential_ref;
+pub mod error_code;
 pub mod machine_id;
+pub mod monthly_budget;
 pub mod model;
 pub mod parser;
 pub mod provider;
+pub mod recent_errors;
 pub mod storage;
+pub mod stream_timeout;
 pub mod token_manager;
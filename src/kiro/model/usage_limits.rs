@@ -34,6 +34,10 @@ pub struct SubscriptionInfo {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UsageBreakdown {
+    /// 资源类型 (AGENTIC_REQUEST 等，IdC 账号会返回多条明细)
+    #[serde(default)]
+    pub resource_type: Option<String>,
+
     /// 当前使用量
     #[serde(default)]
     pub current_usage: i64,
@@ -107,9 +111,22 @@ impl UsageLimitsResponse {
             .and_then(|info| info.subscription_title.as_deref())
     }
 
-    /// 获取第一个使用量明细
+    /// 获取主使用量明细
+    ///
+    /// 优先取 AGENTIC_REQUEST 明细，未标注资源类型时取第一条
     fn primary_breakdown(&self) -> Option<&UsageBreakdown> {
-        self.usage_breakdown_list.first()
+        self.usage_breakdown_list
+            .iter()
+            .find(|b| b.resource_type.as_deref() == Some("AGENTIC_REQUEST"))
+            .or_else(|| self.usage_breakdown_list.first())
+    }
+
+    /// 获取下次重置时间 (Unix 时间戳)
+    ///
+    /// Social 账号在顶层返回，IdC 账号位于使用量明细中
+    pub fn next_reset(&self) -> Option<f64> {
+        self.next_date_reset
+            .or_else(|| self.primary_breakdown().and_then(|b| b.next_date_reset))
     }
 
     /// 获取总使用限额（精确值）
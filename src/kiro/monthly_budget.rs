@@ -0,0 +1,176 @@
+//! 凭据月度 token 预算
+//!
+//! 按 `config.usage_reset_timezone` 计算用量所属月份，每月 1 日零点重置
+
+use std::fmt;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+
+use crate::kiro::model::credentials::{KiroCredentials, MonthlyUsage};
+
+/// 解析用量重置时区
+///
+/// 支持 `UTC` / `Z` 以及 `+08:00`、`-0530`、`+8` 形式的固定偏移
+pub fn parse_utc_offset(value: &str) -> anyhow::Result<FixedOffset> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("utc") || value == "Z" {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+
+    let invalid = || anyhow::anyhow!("无效的 usageResetTimezone: {}（示例: UTC、+08:00）", value);
+
+    let (sign, rest) = if let Some(rest) = value.strip_prefix('+') {
+        (1, rest)
+    } else if let Some(rest) = value.strip_prefix('-') {
+        (-1, rest)
+    } else {
+        return Err(invalid());
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() == 4 && rest.is_ascii() => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes >= 60 {
+        return Err(invalid());
+    }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
+
+/// 计算指定时刻在给定时区下的用量月份（YYYY-MM）
+pub fn usage_period(now: DateTime<Utc>, offset: FixedOffset) -> String {
+    now.with_timezone(&offset).format("%Y-%m").to_string()
+}
+
+/// 计算指定时刻在给定时区下的下一个用量月份起点（下月 1 日零点）
+pub fn next_period_start(now: DateTime<Utc>, offset: FixedOffset) -> DateTime<Utc> {
+    let local = now.with_timezone(&offset).date_naive();
+    let (year, month) = if local.month() == 12 {
+        (local.year() + 1, 1)
+    } else {
+        (local.year(), local.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|naive| naive.and_local_timezone(offset).single())
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or(now)
+}
+
+/// 凭据的额度用尽窗口（`quota_exhausted_until`）在指定时刻是否仍然有效
+pub fn is_quota_window_active(credentials: &KiroCredentials, now: DateTime<Utc>) -> bool {
+    credentials
+        .quota_exhausted_until
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .is_some_and(|until| until > now)
+}
+
+/// 凭据在指定月份是否已达到月度 token 上限
+pub fn is_exhausted(credentials: &KiroCredentials, period: &str) -> bool {
+    match (credentials.monthly_token_limit, &credentials.monthly_usage) {
+        (Some(limit), Some(usage)) => usage.period == period && usage.tokens >= limit,
+        _ => false,
+    }
+}
+
+/// 累加凭据在指定月份的用量（跨月时从 0 重新计数）
+///
+/// 返回累加后的本月用量
+pub fn add_usage(credentials: &mut KiroCredentials, period: &str, tokens: u64) -> u64 {
+    let usage = credentials.monthly_usage.get_or_insert_with(|| MonthlyUsage {
+        period: period.to_string(),
+        tokens: 0,
+    });
+    if usage.period != period {
+        usage.period = period.to_string();
+        usage.tokens = 0;
+    }
+    usage.tokens = usage.tokens.saturating_add(tokens);
+    usage.tokens
+}
+
+/// 所有可用凭据的月度 token 预算均已用尽
+#[derive(Debug, Clone)]
+pub struct MonthlyBudgetExhaustedError {
+    /// 当前用量月份
+    pub period: String,
+}
+
+impl fmt::Display for MonthlyBudgetExhaustedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "所有可用凭据的月度 token 预算均已用尽（{}）", self.period)
+    }
+}
+
+impl std::error::Error for MonthlyBudgetExhaustedError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_utc_offset() {
+        assert_eq!(parse_utc_offset("UTC").unwrap().local_minus_utc(), 0);
+        assert_eq!(parse_utc_offset("").unwrap().local_minus_utc(), 0);
+        assert_eq!(parse_utc_offset("+08:00").unwrap().local_minus_utc(), 8 * 3600);
+        assert_eq!(parse_utc_offset("-0530").unwrap().local_minus_utc(), -(5 * 3600 + 1800));
+        assert_eq!(parse_utc_offset("+9").unwrap().local_minus_utc(), 9 * 3600);
+        assert!(parse_utc_offset("Asia/Shanghai").is_err());
+        assert!(parse_utc_offset("+25:00").is_err());
+    }
+
+    #[test]
+    fn test_usage_period_follows_timezone() {
+        let now = DateTime::parse_from_rfc3339("2026-10-31T20:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(usage_period(now, parse_utc_offset("UTC").unwrap()), "2026-10");
+        assert_eq!(usage_period(now, parse_utc_offset("+08:00").unwrap()), "2026-11");
+    }
+
+    #[test]
+    fn test_next_period_start_follows_timezone() {
+        let now = DateTime::parse_from_rfc3339("2026-12-31T20:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            next_period_start(now, parse_utc_offset("UTC").unwrap()).to_rfc3339(),
+            "2027-01-01T00:00:00+00:00"
+        );
+        // +08:00 下已是 2027-01-01，下一个月份从 2027-02-01 零点（本地）开始
+        assert_eq!(
+            next_period_start(now, parse_utc_offset("+08:00").unwrap()).to_rfc3339(),
+            "2027-01-31T16:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_exhausted_until_month_boundary() {
+        let mut credentials = KiroCredentials {
+            monthly_token_limit: Some(100),
+            ..Default::default()
+        };
+
+        assert_eq!(add_usage(&mut credentials, "2026-10", 60), 60);
+        assert!(!is_exhausted(&credentials, "2026-10"));
+        assert_eq!(add_usage(&mut credentials, "2026-10", 40), 100);
+        assert!(is_exhausted(&credentials, "2026-10"));
+
+        // 次月恢复，且用量从 0 重新计数
+        assert!(!is_exhausted(&credentials, "2026-11"));
+        assert_eq!(add_usage(&mut credentials, "2026-11", 10), 10);
+    }
+
+    #[test]
+    fn test_no_limit_never_exhausted() {
+        let mut credentials = KiroCredentials::default();
+        add_usage(&mut credentials, "2026-10", u64::MAX);
+        assert!(!is_exhausted(&credentials, "2026-10"));
+    }
+}
@@ -4,16 +4,31 @@
 //! 支持流式和非流式请求
 //! 支持多凭据故障转移和重试
 
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
 use reqwest::Client;
-use reqwest::header::{AUTHORIZATION, CONNECTION, CONTENT_TYPE, HOST, HeaderMap, HeaderValue};
+use reqwest::header::{
+    AUTHORIZATION, CONNECTION, CONTENT_TYPE, HOST, HeaderMap, HeaderName, HeaderValue,
+};
+use parking_lot::Mutex;
+use sha2::Sha256;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::time::{Instant, sleep};
 use uuid::Uuid;
 
-use crate::http_client::{ProxyConfig, build_client};
+use crate::common::rate_limit;
+use crate::http_client::{ProxyConfig, build_client_with_redirects};
+use crate::kiro::credential_ref::credential_label;
+use crate::kiro::error_code::{KiroError, KiroErrorCode};
 use crate::kiro::machine_id;
-use crate::kiro::token_manager::{CallContext, MultiTokenManager};
+use crate::kiro::monthly_budget::MonthlyBudgetExhaustedError;
+use crate::kiro::token_manager::{
+    AcquireOptions, CallContext, MultiTokenManager, NoEligibleCredentialError,
+    PinnedCredentialUnavailableError, StorageUnavailableError,
+};
 
 #[cfg(test)]
 use crate::kiro::model::credentials::KiroCredentials;
@@ -21,8 +36,45 @@ use crate::kiro::model::credentials::KiroCredentials;
 /// 每个凭据的最大重试次数
 const MAX_RETRIES_PER_CREDENTIAL: usize = 3;
 
-/// 总重试次数硬上限（避免无限重试）
-const MAX_TOTAL_RETRIES: usize = 9;
+/// 上游 `Retry-After` 的最长等待时长，超过时本次请求切换到下一个凭据
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// 启用流式空闲超时后流式请求的总时长上限（取代客户端级别的总超时）
+const STREAM_MAX_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 计算请求体的 HMAC-SHA256 签名（十六进制小写）
+fn body_hmac_hex(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC 接受任意长度的密钥");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// 最后一次上游尝试的凭据和响应状态（用于记录最近错误）
+#[derive(Debug, Default)]
+struct AttemptTrace {
+    credential_id: Option<u64>,
+    status: Option<u16>,
+}
+
+/// 服务本次请求的凭据信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServedBy {
+    /// 凭据 ID
+    pub credential_id: u64,
+    /// 凭据所属 region（凭据未配置时为全局 region）
+    pub region: String,
+    /// 上游响应延迟（发送请求到收到响应头）
+    pub upstream_latency: Duration,
+}
+
+/// 上游响应及服务本次请求的凭据信息
+pub struct ServedResponse {
+    /// 原始 HTTP 响应
+    pub response: reqwest::Response,
+    /// 服务本次请求的凭据
+    pub served_by: ServedBy,
+}
 
 /// Kiro API Provider
 ///
@@ -30,24 +82,95 @@ const MAX_TOTAL_RETRIES: usize = 9;
 /// 支持多凭据故障转移和重试机制
 pub struct KiroProvider {
     token_manager: Arc<MultiTokenManager>,
-    client: Client,
+    /// 全局 HTTP 客户端及其构建时使用的全局代理（未配置凭据级代理的凭据共用）
+    client: Mutex<(Option<ProxyConfig>, Client)>,
+    /// 凭据级代理的 HTTP 客户端缓存：凭据 ID → (代理配置, 客户端)
+    proxy_clients: Mutex<HashMap<u64, (ProxyConfig, Client)>>,
+    /// 覆盖上游 API 地址（测试时指向本地模拟上游）
+    upstream_url: Option<String>,
 }
 
 impl KiroProvider {
     /// 创建新的 KiroProvider 实例
+    ///
+    /// 全局代理取自 `token_manager`，配置热加载替换代理后按需重建 HTTP 客户端
     pub fn new(token_manager: Arc<MultiTokenManager>) -> Self {
-        Self::with_proxy(token_manager, None)
-    }
-
-    /// 创建带代理配置的 KiroProvider 实例
-    pub fn with_proxy(token_manager: Arc<MultiTokenManager>, proxy: Option<ProxyConfig>) -> Self {
-        let client = build_client(proxy.as_ref(), 720) // 12 分钟超时
-            .expect("创建 HTTP 客户端失败");
+        let proxy = token_manager.proxy().as_deref().cloned();
+        let config = token_manager.config();
+        let client = build_client_with_redirects(
+            proxy.as_ref(),
+            config.upstream_request_timeout_secs,
+            config.follow_redirects,
+        )
+        .expect("创建 HTTP 客户端失败");
 
         Self {
             token_manager,
-            client,
+            client: Mutex::new((proxy, client)),
+            proxy_clients: Mutex::new(HashMap::new()),
+            upstream_url: None,
+        }
+    }
+
+    /// 将上游 API 请求发往指定地址
+    #[cfg(test)]
+    pub(crate) fn with_upstream_url(mut self, url: impl Into<String>) -> Self {
+        self.upstream_url = Some(url.into());
+        self
+    }
+
+    /// 全局 HTTP 客户端（下载 `url` 图片等与凭据无关的请求使用）
+    ///
+    /// 全局代理变化后重建；重建失败时记录警告并沿用原客户端
+    pub fn http_client(&self) -> Client {
+        let proxy = self.token_manager.proxy();
+        let mut cached = self.client.lock();
+        if cached.0.as_ref() != proxy.as_deref() {
+            let config = self.token_manager.config();
+            match build_client_with_redirects(
+                proxy.as_deref(),
+                config.upstream_request_timeout_secs,
+                config.follow_redirects,
+            ) {
+                Ok(client) => {
+                    tracing::info!("全局代理已变更，已重建 HTTP 客户端");
+                    cached.1 = client;
+                }
+                Err(e) => tracing::warn!("按新的全局代理重建 HTTP 客户端失败，沿用原客户端: {}", e),
+            }
+            cached.0 = proxy.as_deref().cloned();
         }
+        cached.1.clone()
+    }
+
+    /// 获取凭据使用的 HTTP 客户端
+    ///
+    /// 凭据配置了代理时复用按凭据缓存的客户端，仅在代理字段变化（如凭据重新加载后）时重建；
+    /// 未配置时使用全局客户端。已删除凭据的缓存客户端在此一并清理
+    fn client_for(&self, ctx: &CallContext) -> anyhow::Result<Client> {
+        let mut clients = self.proxy_clients.lock();
+        clients.retain(|id, _| *id == ctx.id || self.token_manager.has_credential(*id));
+        let Some(proxy) = ctx.credentials.proxy_config() else {
+            clients.remove(&ctx.id);
+            return Ok(self.http_client());
+        };
+
+        if let Some((_, client)) = clients.get(&ctx.id).filter(|(cached, _)| *cached == proxy) {
+            return Ok(client.clone());
+        }
+        let config = self.token_manager.config();
+        let client = build_client_with_redirects(
+            Some(&proxy),
+            config.upstream_request_timeout_secs,
+            config.follow_redirects,
+        )?;
+        tracing::info!(
+            "凭据 {} 使用凭据级代理: {}",
+            credential_label(&config, ctx.id),
+            proxy.redacted_url()
+        );
+        clients.insert(ctx.id, (proxy, client.clone()));
+        Ok(client)
     }
 
     /// 获取 token_manager 的引用
@@ -57,6 +180,9 @@ impl KiroProvider {
 
     /// 获取 API 基础 URL
     pub fn base_url(&self) -> String {
+        if let Some(url) = &self.upstream_url {
+            return url.clone();
+        }
         format!(
             "https://q.{}.amazonaws.com/generateAssistantResponse",
             self.token_manager.config().region
@@ -83,7 +209,7 @@ impl KiroProvider {
     fn build_headers(&self, ctx: &CallContext) -> anyhow::Result<HeaderMap> {
         let config = self.token_manager.config();
 
-        let machine_id = machine_id::generate_from_credentials(&ctx.credentials, config)
+        let machine_id = machine_id::generate_from_credentials(&ctx.credentials, &config)
             .ok_or_else(|| anyhow::anyhow!("无法生成 machine_id，请检查凭证配置"))?;
 
         let kiro_version = &config.kiro_version;
@@ -131,11 +257,57 @@ impl KiroProvider {
         Ok(headers)
     }
 
+    /// 按服务凭据设置请求体中的 profileArn
+    ///
+    /// 优先使用凭据自身的 profileArn，缺失时回退到 `default_profile_arn`，
+    /// 都没有时移除该字段，避免把其他凭据的 profileArn 发给上游
+    fn body_for_credential(&self, request_body: &str, ctx: &CallContext) -> String {
+        let config = self.token_manager.config();
+        let profile_arn = ctx
+            .credentials
+            .profile_arn
+            .as_ref()
+            .or(config.default_profile_arn.as_ref());
+
+        let Ok(serde_json::Value::Object(mut body)) = serde_json::from_str(request_body) else {
+            return request_body.to_string();
+        };
+        match profile_arn {
+            Some(arn) => {
+                body.insert("profileArn".to_string(), arn.clone().into());
+            }
+            None => {
+                body.remove("profileArn");
+            }
+        }
+        serde_json::Value::Object(body).to_string()
+    }
+
+    /// 按配置为上游请求附加请求体签名头（`upstream_hmac_secret`）
+    ///
+    /// 必须在请求体最终确定后调用，签名覆盖实际发送的字节
+    fn sign_body(&self, headers: &mut HeaderMap, body: &str) -> anyhow::Result<()> {
+        let config = self.token_manager.config();
+        let Some(secret) = config.upstream_hmac_secret.as_deref() else {
+            return Ok(());
+        };
+
+        let name = HeaderName::from_bytes(config.upstream_hmac_header.as_bytes()).map_err(|_| {
+            anyhow::anyhow!(
+                "upstreamHmacHeader 不是合法的请求头名称: {}",
+                config.upstream_hmac_header
+            )
+        })?;
+        let signature = body_hmac_hex(secret, body.as_bytes());
+        headers.insert(name, HeaderValue::from_str(&signature)?);
+        Ok(())
+    }
+
     /// 构建 MCP 请求头
     fn build_mcp_headers(&self, ctx: &CallContext) -> anyhow::Result<HeaderMap> {
         let config = self.token_manager.config();
 
-        let machine_id = machine_id::generate_from_credentials(&ctx.credentials, config)
+        let machine_id = machine_id::generate_from_credentials(&ctx.credentials, &config)
             .ok_or_else(|| anyhow::anyhow!("无法生成 machine_id，请检查凭证配置"))?;
 
         let kiro_version = &config.kiro_version;
@@ -195,11 +367,25 @@ impl KiroProvider {
     ///
     /// # Arguments
     /// * `request_body` - JSON 格式的请求体字符串
+    /// * `options` - 本次请求的凭据选择选项（如排除的凭据）
     ///
     /// # Returns
-    /// 返回原始的 HTTP Response，不做解析
-    pub async fn call_api(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        self.call_api_with_retry(request_body, false).await
+    /// 返回原始的 HTTP Response（不做解析）及服务本次请求的凭据信息
+    pub async fn call_api(
+        &self,
+        request_body: &str,
+        options: &AcquireOptions,
+    ) -> anyhow::Result<ServedResponse> {
+        self.call_api_with_retry(request_body, false, options).await
+    }
+
+    /// 读取非流式响应体
+    ///
+    /// 按 `max_upstream_response_bytes` 限制缓冲大小，超出时立即中止读取并返回错误，
+    /// 避免异常上游返回超大响应体导致内存耗尽
+    pub async fn read_response_body(&self, response: reqwest::Response) -> anyhow::Result<Bytes> {
+        let limit = self.token_manager.config().max_upstream_response_bytes;
+        read_body_with_limit(response, limit).await
     }
 
     /// 发送流式 API 请求
@@ -212,11 +398,16 @@ impl KiroProvider {
     ///
     /// # Arguments
     /// * `request_body` - JSON 格式的请求体字符串
+    /// * `options` - 本次请求的凭据选择选项（如排除的凭据）
     ///
     /// # Returns
-    /// 返回原始的 HTTP Response，调用方负责处理流式数据
-    pub async fn call_api_stream(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        self.call_api_with_retry(request_body, true).await
+    /// 返回原始的 HTTP Response（调用方负责处理流式数据）及服务本次请求的凭据信息
+    pub async fn call_api_stream(
+        &self,
+        request_body: &str,
+        options: &AcquireOptions,
+    ) -> anyhow::Result<ServedResponse> {
+        self.call_api_with_retry(request_body, true, options).await
     }
 
     /// 发送 MCP API 请求
@@ -234,8 +425,7 @@ impl KiroProvider {
 
     /// 内部方法：带重试逻辑的 MCP API 调用
     async fn call_mcp_with_retry(&self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        let total_credentials = self.token_manager.total_count();
-        let max_retries = (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(MAX_TOTAL_RETRIES);
+        let max_retries = self.max_attempts();
         let mut last_error: Option<anyhow::Error> = None;
 
         for attempt in 0..max_retries {
@@ -249,17 +439,24 @@ impl KiroProvider {
             };
 
             let url = self.mcp_url();
-            let headers = match self.build_mcp_headers(&ctx) {
+            let mut headers = match self.build_mcp_headers(&ctx) {
                 Ok(h) => h,
                 Err(e) => {
                     last_error = Some(e);
                     continue;
                 }
             };
+            self.sign_body(&mut headers, request_body)?;
+            let client = match self.client_for(&ctx) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
 
             // 发送请求
-            let response = match self
-                .client
+            let response = match client
                 .post(&url)
                 .headers(headers)
                 .body(request_body.to_string())
@@ -274,9 +471,10 @@ impl KiroProvider {
                         max_retries,
                         e
                     );
+                    self.token_manager.record_upstream_failure(ctx.id);
                     last_error = Some(e.into());
                     if attempt + 1 < max_retries {
-                        sleep(Self::retry_delay(attempt)).await;
+                        sleep(self.retry_delay(attempt)).await;
                     }
                     continue;
                 }
@@ -291,6 +489,7 @@ impl KiroProvider {
             }
 
             // 失败响应
+            self.token_manager.record_upstream_failure(ctx.id);
             let body = response.text().await.unwrap_or_default();
 
             // 402 额度用尽
@@ -329,7 +528,7 @@ impl KiroProvider {
                 );
                 last_error = Some(anyhow::anyhow!("MCP 请求失败: {} {}", status, body));
                 if attempt + 1 < max_retries {
-                    sleep(Self::retry_delay(attempt)).await;
+                    sleep(self.retry_delay(attempt)).await;
                 }
                 continue;
             }
@@ -342,7 +541,7 @@ impl KiroProvider {
             // 兜底
             last_error = Some(anyhow::anyhow!("MCP 请求失败: {} {}", status, body));
             if attempt + 1 < max_retries {
-                sleep(Self::retry_delay(attempt)).await;
+                sleep(self.retry_delay(attempt)).await;
             }
         }
 
@@ -351,50 +550,118 @@ impl KiroProvider {
         }))
     }
 
-    /// 内部方法：带重试逻辑的 API 调用
+    /// 内部方法：带重试逻辑的 API 调用，失败时记录到最近错误
+    async fn call_api_with_retry(
+        &self,
+        request_body: &str,
+        is_stream: bool,
+        options: &AcquireOptions,
+    ) -> anyhow::Result<ServedResponse> {
+        let mut trace = AttemptTrace::default();
+        let result = self.call_api_attempts(request_body, is_stream, options, &mut trace).await;
+        if let Err(e) = &result {
+            self.token_manager.record_request_error(e, trace.status, trace.credential_id);
+        }
+        result
+    }
+
+    /// 执行带重试的 API 调用，`trace` 记录最后一次尝试的凭据和上游状态码
     ///
     /// 重试策略：
-    /// - 每个凭据最多重试 MAX_RETRIES_PER_CREDENTIAL 次
-    /// - 总重试次数 = min(凭据数量 × 每凭据重试次数, MAX_TOTAL_RETRIES)
-    /// - 硬上限 9 次，避免无限重试
-    async fn call_api_with_retry(
+    /// - 每个凭据最多尝试 MAX_RETRIES_PER_CREDENTIAL 次
+    /// - 总尝试次数 = min(凭据数量 × 每凭据尝试次数, `max_retries` 配置 + 1)
+    /// - 429/408/5xx 和网络错误按指数退避重试，上游返回 `Retry-After` 时按其等待；
+    ///   `Retry-After` 超过 MAX_RETRY_AFTER 时本次请求排除该凭据并切换到下一个凭据，
+    ///   连续 429 交给凭据熔断器处理
+    /// - 流式请求只在收到响应头之前重试，响应交给调用方后不再重试
+    /// - 配置了 `options.deadline` 时，获取凭据、发送请求和重试退避均不超过截止时间，
+    ///   超过时返回 `deadline_exceeded` 错误
+    async fn call_api_attempts(
         &self,
         request_body: &str,
         is_stream: bool,
-    ) -> anyhow::Result<reqwest::Response> {
-        let total_credentials = self.token_manager.total_count();
-        let max_retries = (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(MAX_TOTAL_RETRIES);
+        options: &AcquireOptions,
+        trace: &mut AttemptTrace,
+    ) -> anyhow::Result<ServedResponse> {
+        // 存储后端尚未连接（lazy_storage_connect）：此时凭据列表为空，直接返回
+        if !self.token_manager.is_storage_ready() {
+            return Err(StorageUnavailableError.into());
+        }
+
+        let max_retries = self.max_attempts();
         let mut last_error: Option<anyhow::Error> = None;
         let api_type = if is_stream { "流式" } else { "非流式" };
 
+        let deadline = options.deadline;
+        // 本次请求内因 Retry-After 过长而切换掉的凭据追加到排除列表
+        let mut failover = options.clone();
+
         for attempt in 0..max_retries {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(Self::deadline_exceeded(api_type, last_error.as_ref()));
+            }
+
             // 获取调用上下文（绑定 index、credentials、token）
-            let ctx = match self.token_manager.acquire_context().await {
+            let Some(acquired) =
+                with_deadline(deadline, self.token_manager.acquire_context_with(&failover)).await
+            else {
+                return Err(Self::deadline_exceeded(api_type, last_error.as_ref()));
+            };
+            let ctx = match acquired {
                 Ok(c) => c,
                 Err(e) => {
+                    // 切换后没有其他凭据：返回最后一次上游错误
+                    if e.is::<NoEligibleCredentialError>()
+                        && failover.excluded_ids.len() > options.excluded_ids.len()
+                        && let Some(last_error) = last_error
+                    {
+                        return Err(last_error);
+                    }
+                    // 排除后没有可用凭据 / 固定凭据不可用 / 月度预算用尽：重试无意义，直接返回
+                    if e.is::<NoEligibleCredentialError>()
+                        || e.is::<PinnedCredentialUnavailableError>()
+                        || e.is::<MonthlyBudgetExhaustedError>()
+                    {
+                        return Err(e);
+                    }
                     last_error = Some(e);
                     continue;
                 }
             };
+            trace.credential_id = Some(ctx.id);
+            trace.status = None;
 
             let url = self.base_url();
-            let headers = match self.build_headers(&ctx) {
+            let mut headers = match self.build_headers(&ctx) {
                 Ok(h) => h,
                 Err(e) => {
                     last_error = Some(e);
                     continue;
                 }
             };
+            // 签名基于实际发送的最终请求体
+            let body = self.body_for_credential(request_body, &ctx);
+            self.sign_body(&mut headers, &body)?;
+            let client = match self.client_for(&ctx) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
 
             // 发送请求
-            let response = match self
-                .client
-                .post(&url)
-                .headers(headers)
-                .body(request_body.to_string())
-                .send()
-                .await
-            {
+            let sent_at = std::time::Instant::now();
+            let mut request = client.post(&url).headers(headers).body(body);
+            // 流式响应由空闲超时兜底，不再受客户端总超时限制
+            if is_stream && self.token_manager.config().stream_idle_timeout_secs > 0 {
+                request = request.timeout(STREAM_MAX_DURATION);
+            }
+            let request = request.send();
+            let Some(sent) = with_deadline(deadline, request).await else {
+                return Err(Self::deadline_exceeded(api_type, last_error.as_ref()));
+            };
+            let response = match sent {
                 Ok(resp) => resp,
                 Err(e) => {
                     tracing::warn!(
@@ -405,24 +672,42 @@ impl KiroProvider {
                     );
                     // 网络错误通常是上游/链路瞬态问题，不应导致"禁用凭据"或"切换凭据"
                     // （否则一段时间网络抖动会把所有凭据都误禁用，需要重启才能恢复）
+                    self.token_manager.record_upstream_failure(ctx.id);
                     last_error = Some(e.into());
-                    if attempt + 1 < max_retries {
-                        sleep(Self::retry_delay(attempt)).await;
+                    let delay = self.retry_delay(attempt);
+                    if attempt + 1 < max_retries && !Self::backoff(delay, deadline).await {
+                        return Err(Self::deadline_exceeded(api_type, last_error.as_ref()));
                     }
                     continue;
                 }
             };
 
             let status = response.status();
+            trace.status = Some(status.as_u16());
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(rate_limit::parse_retry_after);
 
             // 成功响应
             if status.is_success() {
+                let latency = sent_at.elapsed();
                 self.token_manager.report_success(ctx.id);
-                return Ok(response);
+                self.token_manager.report_latency(ctx.id, latency);
+                return Ok(ServedResponse {
+                    response,
+                    served_by: self.served_by(&ctx, latency),
+                });
             }
 
+            self.token_manager.record_upstream_failure(ctx.id);
+
             // 失败响应：读取 body 用于日志/错误信息
-            let body = response.text().await.unwrap_or_default();
+            let Some(body) = with_deadline(deadline, response.text()).await else {
+                return Err(Self::deadline_exceeded(api_type, last_error.as_ref()));
+            };
+            let body = body.unwrap_or_default();
 
             // 402 Payment Required 且额度用尽：禁用凭据并故障转移
             if status.as_u16() == 402 && Self::is_monthly_request_limit(&body) {
@@ -436,21 +721,28 @@ impl KiroProvider {
 
                 let has_available = self.token_manager.report_quota_exhausted(ctx.id);
                 if !has_available {
-                    anyhow::bail!(
-                        "{} API 请求失败（所有凭据已用尽）: {} {}",
-                        api_type,
+                    return Err(Self::upstream_error(
                         status,
-                        body
-                    );
+                        format!(
+                            "{} API 请求失败（所有凭据已用尽）: {} {}",
+                            api_type, status, body
+                        ),
+                    ));
                 }
 
-                last_error = Some(anyhow::anyhow!("{} API 请求失败: {} {}", api_type, status, body));
+                last_error = Some(Self::upstream_error(
+                    status,
+                    format!("{} API 请求失败: {} {}", api_type, status, body),
+                ));
                 continue;
             }
 
             // 400 Bad Request - 请求问题，重试/切换凭据无意义
             if status.as_u16() == 400 {
-                anyhow::bail!("{} API 请求失败: {} {}", api_type, status, body);
+                return Err(Self::upstream_error(
+                    status,
+                    format!("{} API 请求失败: {} {}", api_type, status, body),
+                ));
             }
 
             // 401/403 - 更可能是凭据/权限问题：计入失败并允许故障转移
@@ -465,15 +757,19 @@ impl KiroProvider {
 
                 let has_available = self.token_manager.report_failure(ctx.id);
                 if !has_available {
-                    anyhow::bail!(
-                        "{} API 请求失败（所有凭据已用尽）: {} {}",
-                        api_type,
+                    return Err(Self::upstream_error(
                         status,
-                        body
-                    );
+                        format!(
+                            "{} API 请求失败（所有凭据已用尽）: {} {}",
+                            api_type, status, body
+                        ),
+                    ));
                 }
 
-                last_error = Some(anyhow::anyhow!("{} API 请求失败: {} {}", api_type, status, body));
+                last_error = Some(Self::upstream_error(
+                    status,
+                    format!("{} API 请求失败: {} {}", api_type, status, body),
+                ));
                 continue;
             }
 
@@ -487,16 +783,41 @@ impl KiroProvider {
                     status,
                     body
                 );
-                last_error = Some(anyhow::anyhow!("{} API 请求失败: {} {}", api_type, status, body));
-                if attempt + 1 < max_retries {
-                    sleep(Self::retry_delay(attempt)).await;
+                if status.as_u16() == 429 {
+                    self.token_manager.report_rate_limited(ctx.id);
+                }
+                let error = Self::upstream_error(
+                    status,
+                    format!("{} API 请求失败: {} {}", api_type, status, body),
+                );
+                // 上游要求等待过久：本次请求不再使用该凭据，立即切换到下一个凭据
+                let delay = match retry_after {
+                    Some(wait) if wait > MAX_RETRY_AFTER => {
+                        tracing::warn!(
+                            "上游 Retry-After {} 秒，凭据 #{} 切换到下一个凭据",
+                            wait.as_secs(),
+                            ctx.id
+                        );
+                        failover.excluded_ids.insert(ctx.id);
+                        last_error = Some(error);
+                        continue;
+                    }
+                    Some(wait) => wait,
+                    None => self.retry_delay(attempt),
+                };
+                last_error = Some(error);
+                if attempt + 1 < max_retries && !Self::backoff(delay, deadline).await {
+                    return Err(Self::deadline_exceeded(api_type, last_error.as_ref()));
                 }
                 continue;
             }
 
             // 其他 4xx - 通常为请求/配置问题：直接返回，不计入凭据失败
             if status.is_client_error() {
-                anyhow::bail!("{} API 请求失败: {} {}", api_type, status, body);
+                return Err(Self::upstream_error(
+                    status,
+                    format!("{} API 请求失败: {} {}", api_type, status, body),
+                ));
             }
 
             // 兜底：当作可重试的瞬态错误处理（不切换凭据）
@@ -507,28 +828,83 @@ impl KiroProvider {
                 status,
                 body
             );
-            last_error = Some(anyhow::anyhow!("{} API 请求失败: {} {}", api_type, status, body));
-            if attempt + 1 < max_retries {
-                sleep(Self::retry_delay(attempt)).await;
+            last_error = Some(Self::upstream_error(
+                status,
+                format!("{} API 请求失败: {} {}", api_type, status, body),
+            ));
+            let delay = self.retry_delay(attempt);
+            if attempt + 1 < max_retries && !Self::backoff(delay, deadline).await {
+                return Err(Self::deadline_exceeded(api_type, last_error.as_ref()));
             }
         }
 
-        // 所有重试都失败
+        // 所有重试都失败（未进入循环说明没有任何凭据）
         Err(last_error.unwrap_or_else(|| {
-            anyhow::anyhow!(
-                "{} API 请求失败：已达到最大重试次数（{}次）",
-                api_type,
-                max_retries
+            KiroError::new(
+                KiroErrorCode::NoCredentialsAvailable,
+                format!(
+                    "{} API 请求失败：已达到最大重试次数（{}次）",
+                    api_type, max_retries
+                ),
             )
+            .into()
         }))
     }
 
-    fn retry_delay(attempt: usize) -> Duration {
+    /// 重试前等待 `delay`
+    ///
+    /// 退避结束时将超过截止时间则不再等待，返回 false（调用方应停止重试）
+    async fn backoff(delay: Duration, deadline: Option<Instant>) -> bool {
+        if deadline.is_some_and(|deadline| Instant::now() + delay >= deadline) {
+            return false;
+        }
+        sleep(delay).await;
+        true
+    }
+
+    /// 构建超过截止时间的错误（附带最后一次失败原因）
+    fn deadline_exceeded(api_type: &str, last_error: Option<&anyhow::Error>) -> anyhow::Error {
+        let message = match last_error {
+            Some(e) => format!(
+                "{} API 请求超过截止时间，已停止重试（最后一次错误: {}）",
+                api_type, e
+            ),
+            None => format!("{} API 请求超过截止时间", api_type),
+        };
+        KiroError::new(KiroErrorCode::DeadlineExceeded, message).into()
+    }
+
+    /// 构建携带错误码的上游失败错误
+    fn upstream_error(status: reqwest::StatusCode, message: String) -> anyhow::Error {
+        KiroError::new(KiroErrorCode::from_upstream_status(status.as_u16()), message).into()
+    }
+
+    /// 构建服务本次请求的凭据信息
+    fn served_by(&self, ctx: &CallContext, upstream_latency: Duration) -> ServedBy {
+        ServedBy {
+            credential_id: ctx.id,
+            region: ctx
+                .credentials
+                .region
+                .clone()
+                .unwrap_or_else(|| self.token_manager.config().region.clone()),
+            upstream_latency,
+        }
+    }
+
+    /// 单次请求的上游最大尝试次数：min(凭据数量 × 每凭据尝试次数, `max_retries` 配置 + 1)
+    fn max_attempts(&self) -> usize {
+        let total_credentials = self.token_manager.total_count();
+        let max_attempts = self.token_manager.config().max_retries.saturating_add(1);
+        (total_credentials * MAX_RETRIES_PER_CREDENTIAL).min(max_attempts)
+    }
+
+    fn retry_delay(&self, attempt: usize) -> Duration {
         // 指数退避 + 少量抖动，避免上游抖动时放大故障
-        const BASE_MS: u64 = 200;
-        const MAX_MS: u64 = 2_000;
-        let exp = BASE_MS.saturating_mul(2u64.saturating_pow(attempt.min(6) as u32));
-        let backoff = exp.min(MAX_MS);
+        let base_ms = self.token_manager.config().retry_base_delay_ms;
+        let max_ms = base_ms.saturating_mul(10);
+        let exp = base_ms.saturating_mul(2u64.saturating_pow(attempt.min(6) as u32));
+        let backoff = exp.min(max_ms);
         let jitter_max = (backoff / 4).max(1);
         let jitter = fastrand::u64(0..=jitter_max);
         Duration::from_millis(backoff.saturating_add(jitter))
@@ -558,11 +934,67 @@ impl KiroProvider {
     }
 }
 
+/// 在截止时间前等待 future 完成，超过截止时间返回 None
+async fn with_deadline<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// 按字节上限读取响应体（limit 为 0 表示不限制）
+pub(crate) async fn read_body_with_limit(
+    response: reqwest::Response,
+    limit: usize,
+) -> anyhow::Result<Bytes> {
+    if limit == 0 {
+        return Ok(response.bytes().await?);
+    }
+
+    // Content-Length 已知且超限时无需读取
+    if let Some(len) = response.content_length()
+        && len > limit as u64
+    {
+        tracing::error!(
+            "上游响应体过大（Content-Length: {} 字节），超过上限 {} 字节",
+            len,
+            limit
+        );
+        return Err(KiroError::new(
+            KiroErrorCode::UpstreamResponseTooLarge,
+            format!("上游响应体超过上限 {} 字节", limit),
+        )
+        .into());
+    }
+
+    let mut buffer = BytesMut::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if buffer.len() + chunk.len() > limit {
+            tracing::error!(
+                "上游响应体已读取 {} 字节，超过上限 {} 字节，中止读取",
+                buffer.len() + chunk.len(),
+                limit
+            );
+            return Err(KiroError::new(
+                KiroErrorCode::UpstreamResponseTooLarge,
+                format!("上游响应体超过上限 {} 字节", limit),
+            )
+            .into());
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+
+    Ok(buffer.freeze())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::kiro::token_manager::CallContext;
     use crate::model::config::Config;
+    use serde_json::json;
 
     fn create_test_provider(config: Config, credentials: KiroCredentials) -> KiroProvider {
         let tm = MultiTokenManager::new(config, vec![credentials], None, None, false).unwrap();
@@ -619,6 +1051,83 @@ mod tests {
         assert_eq!(headers.get(CONNECTION).unwrap(), "close");
     }
 
+    fn call_context(profile_arn: Option<&str>) -> CallContext {
+        CallContext {
+            id: 1,
+            credentials: KiroCredentials {
+                profile_arn: profile_arn.map(str::to_string),
+                ..Default::default()
+            },
+            token: "token".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_body_uses_serving_credential_profile_arn() {
+        let body = r#"{"conversationState":{},"profileArn":"arn-first"}"#;
+        let profile_arn_of = |provider: &KiroProvider, ctx: &CallContext| {
+            let body: serde_json::Value =
+                serde_json::from_str(&provider.body_for_credential(body, ctx)).unwrap();
+            body.get("profileArn").cloned()
+        };
+
+        // 凭据自带 profileArn
+        let provider = create_test_provider(Config::default(), KiroCredentials::default());
+        assert_eq!(
+            profile_arn_of(&provider, &call_context(Some("arn-own"))),
+            Some(json!("arn-own"))
+        );
+
+        // 缺失且未配置默认值：不携带其他凭据的 profileArn
+        assert_eq!(profile_arn_of(&provider, &call_context(None)), None);
+
+        // 缺失时回退到 defaultProfileArn
+        let config = Config {
+            default_profile_arn: Some("arn-default".to_string()),
+            ..Default::default()
+        };
+        let provider = create_test_provider(config, KiroCredentials::default());
+        assert_eq!(
+            profile_arn_of(&provider, &call_context(None)),
+            Some(json!("arn-default"))
+        );
+    }
+
+    #[test]
+    fn test_body_signature_matches_independent_hmac() {
+        let config = Config {
+            upstream_hmac_secret: Some("Jefe".to_string()),
+            upstream_hmac_header: "x-proxy-signature".to_string(),
+            ..Default::default()
+        };
+        let provider = create_test_provider(config, KiroCredentials::default());
+
+        // RFC 4231 测试用例 2
+        let mut headers = HeaderMap::new();
+        provider
+            .sign_body(&mut headers, "what do ya want for nothing?")
+            .unwrap();
+        assert_eq!(
+            headers.get("x-proxy-signature").unwrap(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        // 签名覆盖改写 profileArn 后的最终请求体
+        let raw = r#"{"conversationState":{},"profileArn":"arn-first"}"#;
+        let body = provider.body_for_credential(raw, &call_context(Some("arn-own")));
+        let mut headers = HeaderMap::new();
+        provider.sign_body(&mut headers, &body).unwrap();
+        let signature = headers.get("x-proxy-signature").unwrap();
+        assert_eq!(signature, body_hmac_hex("Jefe", body.as_bytes()).as_str());
+        assert_ne!(signature, body_hmac_hex("Jefe", raw.as_bytes()).as_str());
+
+        // 未配置密钥时不附加签名头
+        let provider = create_test_provider(Config::default(), KiroCredentials::default());
+        let mut headers = HeaderMap::new();
+        provider.sign_body(&mut headers, &body).unwrap();
+        assert!(headers.is_empty());
+    }
+
     #[test]
     fn test_is_monthly_request_limit_detects_reason() {
         let body = r#"{"message":"You have reached the limit.","reason":"MONTHLY_REQUEST_COUNT"}"#;
@@ -636,4 +1145,270 @@ mod tests {
         let body = r#"{"message":"nope","reason":"DAILY_REQUEST_COUNT"}"#;
         assert!(!KiroProvider::is_monthly_request_limit(body));
     }
+
+    /// 启动返回固定响应体的本地 HTTP 服务，返回其地址
+    async fn spawn_body_server(body: Vec<u8>) -> String {
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::get(move || {
+                let body = body.clone();
+                async move { body }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/", addr)
+    }
+
+    #[tokio::test]
+    async fn test_read_body_with_limit_rejects_oversized_body() {
+        let url = spawn_body_server(vec![b'x'; 4096]).await;
+        let response = reqwest::get(&url).await.unwrap();
+
+        let err = read_body_with_limit(response, 1024).await.unwrap_err();
+        assert!(err.to_string().contains("超过上限 1024 字节"));
+    }
+
+    #[tokio::test]
+    async fn test_read_body_with_limit_allows_small_body() {
+        let url = spawn_body_server(vec![b'x'; 512]).await;
+        let response = reqwest::get(&url).await.unwrap();
+
+        let body = read_body_with_limit(response, 1024).await.unwrap();
+        assert_eq!(body.len(), 512);
+    }
+
+    #[tokio::test]
+    async fn test_read_body_with_limit_zero_means_unlimited() {
+        let url = spawn_body_server(vec![b'x'; 4096]).await;
+        let response = reqwest::get(&url).await.unwrap();
+
+        let body = read_body_with_limit(response, 0).await.unwrap();
+        assert_eq!(body.len(), 4096);
+    }
+
+    #[tokio::test]
+    async fn test_tight_deadline_cuts_off_retries() {
+        // 通过无法连接的代理让每次发送都立即失败，从而进入重试退避
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = ProxyConfig::new(format!("http://{}", listener.local_addr().unwrap()));
+        drop(listener);
+
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let tm =
+            MultiTokenManager::new(Config::default(), vec![credentials], Some(proxy), None, false)
+                .unwrap();
+        let provider = KiroProvider::new(Arc::new(tm));
+
+        let started = Instant::now();
+        let options = AcquireOptions {
+            deadline: Some(started + Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let err = provider.call_api("{}", &options).await.err().unwrap();
+
+        assert_eq!(KiroErrorCode::of(&err), KiroErrorCode::DeadlineExceeded);
+        // 首次退避至少 200ms，截止时间内不会进入第二次尝试
+        assert!(started.elapsed() < Duration::from_millis(200));
+    }
+
+    /// 启动模拟上游：按调用序号依次返回 `responses` 中的状态码和响应头，之后一律返回 200
+    async fn spawn_flaky_upstream(
+        responses: Vec<(u16, Option<&'static str>)>,
+    ) -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        use axum::{Router, http::StatusCode, response::IntoResponse, routing::post};
+
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/generateAssistantResponse",
+            post(move || {
+                let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let (status, retry_after) = responses.get(n).copied().unwrap_or((200, None));
+                async move {
+                    let mut response =
+                        (StatusCode::from_u16(status).unwrap(), "upstream").into_response();
+                    if let Some(retry_after) = retry_after {
+                        response
+                            .headers_mut()
+                            .insert("retry-after", HeaderValue::from_static(retry_after));
+                    }
+                    response
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/generateAssistantResponse", addr), calls)
+    }
+
+    fn retrying_provider(upstream_url: String) -> KiroProvider {
+        retrying_provider_with(upstream_url, 1, Config::default().max_retries)
+    }
+
+    /// 使用 `count` 个有效凭据、退避 1ms 的 Provider
+    fn retrying_provider_with(
+        upstream_url: String,
+        count: usize,
+        max_retries: usize,
+    ) -> KiroProvider {
+        let config = Config {
+            retry_base_delay_ms: 1,
+            max_retries,
+            ..Default::default()
+        };
+        let credentials = (0..count)
+            .map(|_| KiroCredentials {
+                access_token: Some("token".to_string()),
+                refresh_token: Some("a".repeat(150)),
+                expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            })
+            .collect();
+        let tm = MultiTokenManager::new(config, credentials, None, None, false).unwrap();
+        KiroProvider::new(Arc::new(tm)).with_upstream_url(upstream_url)
+    }
+
+    #[tokio::test]
+    async fn test_transient_upstream_errors_retried_until_success() {
+        let (url, calls) = spawn_flaky_upstream(vec![(503, None), (503, Some("0"))]).await;
+        let provider = retrying_provider(url);
+
+        let served = provider.call_api("{}", &AcquireOptions::default()).await.unwrap();
+        assert_eq!(served.response.status(), reqwest::StatusCode::OK);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_max_retries_counts_retries_after_first_attempt() {
+        let (url, calls) = spawn_flaky_upstream(vec![(503, None); 10]).await;
+        let provider = retrying_provider_with(url, 3, 1);
+
+        let err = provider.call_api("{}", &AcquireOptions::default()).await.err().unwrap();
+        assert_eq!(KiroErrorCode::of(&err), KiroErrorCode::UpstreamUnavailable);
+        // 首次请求 + 1 次重试
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_long_retry_after_fails_over_to_next_credential() {
+        let (url, calls) = spawn_flaky_upstream(vec![(429, Some("120"))]).await;
+        let provider = retrying_provider_with(url, 2, 9);
+
+        let served = provider.call_api("{}", &AcquireOptions::default()).await.unwrap();
+        assert_eq!(served.response.status(), reqwest::StatusCode::OK);
+        assert_eq!(served.served_by.credential_id, 2);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_long_retry_after_without_other_credentials_returns_error() {
+        let (url, calls) = spawn_flaky_upstream(vec![(429, Some("120"))]).await;
+        let provider = retrying_provider(url);
+
+        let err = provider.call_api("{}", &AcquireOptions::default()).await.err().unwrap();
+        assert_eq!(KiroErrorCode::of(&err), KiroErrorCode::RateLimitedUpstream);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_credential_proxy_client_cached_until_proxy_changes() {
+        let tm = MultiTokenManager::new(Config::default(), vec![], None, None, false).unwrap();
+        let provider = KiroProvider::new(Arc::new(tm));
+        let mut ctx = call_context(None);
+        ctx.credentials.proxy_url = Some("http://127.0.0.1:7890".to_string());
+        let cached = |provider: &KiroProvider| {
+            provider
+                .proxy_clients
+                .lock()
+                .get(&1)
+                .map(|(proxy, _)| proxy.clone())
+        };
+
+        provider.client_for(&ctx).unwrap();
+        provider.client_for(&ctx).unwrap();
+        assert_eq!(provider.proxy_clients.lock().len(), 1);
+        assert_eq!(cached(&provider).unwrap().url, "http://127.0.0.1:7890");
+
+        // 代理字段变化后重建
+        ctx.credentials.proxy_username = Some("user".to_string());
+        ctx.credentials.proxy_password = Some("pass".to_string());
+        provider.client_for(&ctx).unwrap();
+        assert_eq!(cached(&provider).unwrap().username.as_deref(), Some("user"));
+
+        // 移除代理后回退到全局客户端
+        ctx.credentials.proxy_url = None;
+        provider.client_for(&ctx).unwrap();
+        assert!(provider.proxy_clients.lock().is_empty());
+    }
+
+    #[test]
+    fn test_credential_proxy_client_dropped_after_delete() {
+        let credentials = KiroCredentials {
+            id: Some(2),
+            refresh_token: Some("a".repeat(150)),
+            ..Default::default()
+        };
+        let tm =
+            MultiTokenManager::new(Config::default(), vec![credentials], None, None, false)
+                .unwrap();
+        let provider = KiroProvider::new(Arc::new(tm));
+        let mut deleted = call_context(None);
+        deleted.credentials.proxy_url = Some("http://127.0.0.1:7890".to_string());
+        let mut remaining = call_context(None);
+        remaining.id = 2;
+        remaining.credentials.proxy_url = Some("http://127.0.0.1:7891".to_string());
+
+        // 凭据 1 不在管理器中（已删除），下次获取客户端时清理其缓存
+        provider.client_for(&deleted).unwrap();
+        provider.client_for(&remaining).unwrap();
+        let cached: Vec<u64> = provider.proxy_clients.lock().keys().copied().collect();
+        assert_eq!(cached, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_credential_proxy_used_for_upstream_calls() {
+        // 凭据级代理：接受连接后立即关闭，记录连接次数
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let connections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        tokio::spawn({
+            let connections = connections.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    connections.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    drop(stream);
+                }
+            }
+        });
+
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            proxy_url: Some(format!("http://{}", proxy_addr)),
+            ..Default::default()
+        };
+        let tm = MultiTokenManager::new(Config::default(), vec![credentials], None, None, false)
+            .unwrap();
+        let provider = KiroProvider::new(Arc::new(tm));
+
+        let options = AcquireOptions {
+            deadline: Some(Instant::now() + Duration::from_millis(100)),
+            ..Default::default()
+        };
+        assert!(provider.call_api("{}", &options).await.is_err());
+        assert!(connections.load(std::sync::atomic::Ordering::SeqCst) >= 1);
+    }
 }
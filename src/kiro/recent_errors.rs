@@ -0,0 +1,120 @@
+//! 最近请求错误记录
+//!
+//! 按 `config.recent_errors_capacity` 保留最近 N 次失败的上游请求（环形缓冲，超出时丢弃最旧的），
+//! 供 Admin API 排查问题。错误消息写入前会去除连接串密码并截断
+
+use std::collections::VecDeque;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::kiro::error_code::KiroErrorCode;
+use crate::kiro::storage::redact_secrets;
+
+/// 单条错误消息的最大保留字符数
+const MAX_MESSAGE_CHARS: usize = 512;
+
+/// 一次失败请求的记录
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentError {
+    /// 发生时间（RFC3339）
+    pub timestamp: String,
+    /// 稳定错误码（与响应中的 `error.code` 一致）
+    pub code: KiroErrorCode,
+    /// 最后一次上游响应的 HTTP 状态码（未收到响应时为 None）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// 最后一次尝试使用的凭据 ID（未获取到凭据时为 None）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<u64>,
+    /// 错误消息（已脱敏）
+    pub message: String,
+}
+
+impl RecentError {
+    pub fn new(error: &anyhow::Error, status: Option<u16>, credential_id: Option<u64>) -> Self {
+        let mut message = redact_secrets(&format!("{:#}", error));
+        if let Some((cut, _)) = message.char_indices().nth(MAX_MESSAGE_CHARS) {
+            message.truncate(cut);
+            message.push('…');
+        }
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            code: KiroErrorCode::of(error),
+            status,
+            credential_id,
+            message,
+        }
+    }
+}
+
+/// 最近错误环形缓冲
+pub struct RecentErrors {
+    capacity: usize,
+    entries: Mutex<VecDeque<RecentError>>,
+}
+
+impl RecentErrors {
+    /// 创建缓冲（容量为 0 时不记录）
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 记录一条错误，超出容量时丢弃最旧的记录
+    pub fn push(&self, error: RecentError) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock();
+        while entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(error);
+    }
+
+    /// 最近的错误（最新的在前），`limit` 为 None 时返回全部
+    pub fn latest(&self, limit: Option<usize>) -> Vec<RecentError> {
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .rev()
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_keeps_latest_entries_and_redacts_messages() {
+        let ring = RecentErrors::new(2);
+        for i in 1..=3 {
+            let error = anyhow::anyhow!("失败 {} postgres://kiro:hunter2@db/kiro", i);
+            ring.push(RecentError::new(&error, Some(500), Some(i)));
+        }
+
+        let latest = ring.latest(None);
+        assert_eq!(
+            latest.iter().map(|e| e.credential_id).collect::<Vec<_>>(),
+            vec![Some(3), Some(2)]
+        );
+        assert!(latest[0].message.contains("kiro:***@db"));
+        assert!(!latest[0].message.contains("hunter2"));
+        assert_eq!(ring.latest(Some(1)).len(), 1);
+
+        let disabled = RecentErrors::new(0);
+        disabled.push(RecentError::new(&anyhow::anyhow!("x"), None, None));
+        assert!(disabled.latest(None).is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+//! 凭据文件压缩
+//!
+//! 路径以 `.gz` 结尾的凭据文件以 gzip 压缩格式写入；
+//! 读取时按 gzip 魔数自动识别，未压缩的 JSON 文件照常读取
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+/// gzip 文件头魔数
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// 路径是否表示 gzip 压缩的凭据文件（扩展名为 `.gz`）
+pub fn is_gzip_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// 将读取到的文件字节解码为字符串，gzip 压缩的内容自动解压
+pub fn decode_to_string(bytes: Vec<u8>) -> std::io::Result<String> {
+    if !bytes.starts_with(&GZIP_MAGIC) {
+        return String::from_utf8(bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+    }
+
+    let mut content = String::new();
+    GzDecoder::new(bytes.as_slice()).read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// 按路径编码待写入的内容：`.gz` 路径压缩，其余原样返回
+pub fn encode_for_path(path: &Path, contents: &[u8]) -> std::io::Result<Vec<u8>> {
+    if !is_gzip_path(path) {
+        return Ok(contents.to_vec());
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(contents)?;
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gzip_detected_by_magic_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = r#"[{"id":1}]"#;
+
+        // 内容为 gzip 但扩展名不是 .gz：按魔数识别
+        let compressed = encode_for_path(Path::new("c.json.gz"), json.as_bytes()).unwrap();
+        assert!(compressed.starts_with(&GZIP_MAGIC));
+        let path = dir.path().join("credentials.json");
+        std::fs::write(&path, &compressed).unwrap();
+        assert_eq!(decode_to_string(std::fs::read(&path).unwrap()).unwrap(), json);
+
+        // 非 .gz 路径不压缩
+        assert_eq!(
+            encode_for_path(&path, json.as_bytes()).unwrap(),
+            json.as_bytes()
+        );
+    }
+}
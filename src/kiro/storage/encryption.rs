@@ -0,0 +1,149 @@
+//! 凭据文件静态加密
+//!
+//! 配置 `credentialEncryptionKey`（或 `KIRO_ENCRYPTION_KEY`）后，文件存储以 AES-256-GCM 加密读写。
+//! 文件格式为魔数 `KIROENC1` + 12 字节随机 nonce + 密文（含 16 字节认证标签）；
+//! 加密作用于压缩之后的字节，`.json.gz` 路径先 gzip 压缩再加密
+
+use std::fmt;
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use sha2::{Digest, Sha256};
+
+use super::compression;
+
+/// 加密文件头魔数
+const ENCRYPTED_MAGIC: &[u8; 8] = b"KIROENC1";
+
+/// AES-GCM nonce 长度
+const NONCE_LEN: usize = 12;
+
+/// 凭据文件加密密钥（配置字符串的 SHA-256 摘要）
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// 由配置的密钥字符串派生 256 位密钥
+    ///
+    /// 仅做一次 SHA-256，不做密钥拉伸，配置值应为高熵随机串
+    pub fn derive(secret: &str) -> Self {
+        Self(Sha256::digest(secret.as_bytes()).into())
+    }
+
+    /// 从配置值派生密钥，未配置或为空时返回 None
+    pub fn from_config(secret: Option<&str>) -> Option<Self> {
+        secret.filter(|s| !s.trim().is_empty()).map(Self::derive)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(***)")
+    }
+}
+
+/// 内容是否为加密格式（按魔数识别）
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(ENCRYPTED_MAGIC)
+}
+
+/// 加密内容，每次调用使用新的随机 nonce
+pub fn encrypt(key: &EncryptionKey, plaintext: &[u8]) -> std::io::Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = key
+        .cipher()
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| std::io::Error::other("凭据文件加密失败"))?;
+
+    let mut out = Vec::with_capacity(ENCRYPTED_MAGIC.len() + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(ENCRYPTED_MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// 解密 [`encrypt`] 生成的内容
+///
+/// 密钥错误或内容被篡改时认证失败，返回错误而不是乱码
+pub fn decrypt(key: &EncryptionKey, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let body = bytes
+        .strip_prefix(ENCRYPTED_MAGIC.as_slice())
+        .ok_or_else(|| anyhow::anyhow!("内容不是加密格式"))?;
+    if body.len() < NONCE_LEN {
+        anyhow::bail!("加密内容不完整");
+    }
+
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+    key.cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("凭据文件解密失败：密钥错误或文件已损坏"))
+}
+
+/// 按密钥配置解码读取到的文件字节
+///
+/// - 已配置密钥：解密；明文旧文件返回迁移提示（空文件视为无凭据，照常读取）
+/// - 未配置密钥：明文原样返回；加密文件返回缺少密钥的错误
+pub fn decode(bytes: Vec<u8>, key: Option<&EncryptionKey>) -> anyhow::Result<Vec<u8>> {
+    match key {
+        Some(key) if is_encrypted(&bytes) => decrypt(key, &bytes),
+        Some(_) if bytes.trim_ascii().is_empty() => Ok(bytes),
+        Some(_) => anyhow::bail!(
+            "凭据文件未加密（明文旧格式），已配置加密密钥时拒绝加载；\
+             请先执行 `kiro-rs encrypt-credentials` 迁移"
+        ),
+        None if is_encrypted(&bytes) => anyhow::bail!(
+            "凭据文件已加密，但未配置 credentialEncryptionKey 或 KIRO_ENCRYPTION_KEY"
+        ),
+        None => Ok(bytes),
+    }
+}
+
+/// 读取凭据文件内容：按密钥配置解密，gzip 压缩的内容自动解压
+pub fn read_to_string(path: &Path, key: Option<&EncryptionKey>) -> anyhow::Result<String> {
+    let bytes = decode(std::fs::read(path)?, key)
+        .map_err(|e| anyhow::anyhow!("读取凭据文件 {:?} 失败: {}", path, e))?;
+    Ok(compression::decode_to_string(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_uses_fresh_nonce_and_round_trips() {
+        let key = EncryptionKey::derive("0123456789abcdef");
+        let first = encrypt(&key, b"[]").unwrap();
+        let second = encrypt(&key, b"[]").unwrap();
+
+        assert!(is_encrypted(&first));
+        assert_ne!(first, second);
+        assert_eq!(decrypt(&key, &first).unwrap(), b"[]");
+        assert_eq!(decrypt(&key, &second).unwrap(), b"[]");
+    }
+
+    #[test]
+    fn test_decode_rejects_mismatched_formats() {
+        let key = EncryptionKey::derive("0123456789abcdef");
+        let encrypted = encrypt(&key, b"[]").unwrap();
+
+        // 截断或篡改的内容认证失败
+        assert!(decrypt(&key, &encrypted[..ENCRYPTED_MAGIC.len() + 4]).is_err());
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&key, &tampered).is_err());
+
+        let err = decode(encrypted, None).unwrap_err().to_string();
+        assert!(err.contains("KIRO_ENCRYPTION_KEY"), "{}", err);
+        let err = decode(b"[]".to_vec(), Some(&key)).unwrap_err().to_string();
+        assert!(err.contains("encrypt-credentials"), "{}", err);
+
+        // 空文件视为无凭据
+        assert_eq!(decode(b"\n".to_vec(), Some(&key)).unwrap(), b"\n");
+        assert!(EncryptionKey::from_config(Some("  ")).is_none());
+    }
+}
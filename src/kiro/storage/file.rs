@@ -2,22 +2,43 @@
 //!
 //! 向后兼容现有的 credentials.json 文件格式
 
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde_json::Value;
+use tokio::sync::Mutex as TokioMutex;
 
 use crate::kiro::model::credentials::{CredentialsConfig, KiroCredentials};
 
-use super::traits::CredentialStorage;
+use super::compression;
+use super::encryption::{self, EncryptionKey};
+use super::traits::{
+    CredentialStorage, apply_max_credentials, unloaded_credentials, validate_batch,
+};
 
 /// 文件凭据存储
 ///
-/// 支持单凭据和多凭据 JSON 格式
+/// 支持单凭据和多凭据 JSON 格式；路径以 `.gz` 结尾时以 gzip 压缩格式读写，
+/// 设置加密密钥后以 AES-256-GCM 加密格式读写
 pub struct FileCredentialStorage {
     /// 凭据文件路径
     path: PathBuf,
     /// 是否为多凭据格式（数组格式才回写）
     is_multiple_format: bool,
+    /// 最多加载的凭据数量（None 表示不限制）
+    max_credentials: Option<usize>,
+    /// 本实例加载或写入过的凭据 ID（其余凭据超出上限未加载，回写时原样保留）
+    loaded_ids: Mutex<HashSet<u64>>,
+    /// 文件写锁（回写与压缩互斥）
+    file_lock: TokioMutex<()>,
+    /// 上次压缩后文件的修改时间（未变化时跳过压缩）
+    last_compacted: Mutex<Option<SystemTime>>,
+    /// 文件加密密钥（None 表示明文）
+    encryption_key: Option<EncryptionKey>,
 }
 
 impl FileCredentialStorage {
@@ -30,18 +51,32 @@ impl FileCredentialStorage {
         Self {
             path: path.into(),
             is_multiple_format,
+            max_credentials: None,
+            loaded_ids: Mutex::new(HashSet::new()),
+            file_lock: TokioMutex::new(()),
+            last_compacted: Mutex::new(None),
+            encryption_key: None,
         }
     }
 
-    /// 从文件加载并自动检测格式
-    pub fn from_file(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+    /// 从文件加载并自动检测格式（配置了密钥时按加密格式读取，后续读写沿用该密钥）
+    pub fn from_file(path: impl Into<PathBuf>, key: Option<EncryptionKey>) -> anyhow::Result<Self> {
         let path = path.into();
-        let config = CredentialsConfig::load(&path)?;
+        let config = CredentialsConfig::load_with_key(&path, key.as_ref())?;
         let is_multiple_format = config.is_multiple();
-        Ok(Self {
-            path,
-            is_multiple_format,
-        })
+        Ok(Self::new(path, is_multiple_format).with_encryption_key(key))
+    }
+
+    /// 设置最多加载的凭据数量
+    pub fn with_max_credentials(mut self, max_credentials: Option<usize>) -> Self {
+        self.max_credentials = max_credentials;
+        self
+    }
+
+    /// 设置文件加密密钥（None 表示明文读写）
+    pub fn with_encryption_key(mut self, key: Option<EncryptionKey>) -> Self {
+        self.encryption_key = key;
+        self
     }
 
     /// 获取文件路径
@@ -53,19 +88,240 @@ impl FileCredentialStorage {
     pub fn is_multiple_format(&self) -> bool {
         self.is_multiple_format
     }
-}
 
-#[async_trait]
-impl CredentialStorage for FileCredentialStorage {
-    async fn load_all(&self) -> anyhow::Result<Vec<KiroCredentials>> {
+    /// 读取文件中的全部凭据（按优先级排序，不受 `max_credentials` 限制）
+    async fn load_uncapped(&self) -> anyhow::Result<Vec<KiroCredentials>> {
+        Ok(self.read_credentials().await?.0)
+    }
+
+    /// 读取文件中的全部凭据，同时返回是否为缺少 ID 的凭据分配了 ID
+    async fn read_credentials(&self) -> anyhow::Result<(Vec<KiroCredentials>, bool)> {
         // 使用 spawn_blocking 避免阻塞异步运行时
         let path = self.path.clone();
-        let credentials = tokio::task::spawn_blocking(move || {
-            let config = CredentialsConfig::load(&path)?;
-            Ok::<_, anyhow::Error>(config.into_sorted_credentials())
+        let key = self.encryption_key.clone();
+        tokio::task::spawn_blocking(move || {
+            let config = CredentialsConfig::load_with_key(&path, key.as_ref())?;
+            let assigned = config.has_missing_ids();
+            Ok::<_, anyhow::Error>((config.into_sorted_credentials(), assigned))
+        })
+        .await?
+    }
+
+    /// 将凭据文件重写为规范形式（键排序、按 ID 排序、去除 null、无多余空白）
+    ///
+    /// 仅多凭据格式生效；文件自上次压缩后未修改或已是规范形式时跳过。
+    /// 返回是否实际重写了文件
+    pub async fn compact(&self) -> anyhow::Result<bool> {
+        if !self.is_multiple_format {
+            return Ok(false);
+        }
+
+        let _guard = self.file_lock.lock().await;
+
+        let modified = file_modified(&self.path)?;
+        if *self.last_compacted.lock() == Some(modified) {
+            return Ok(false);
+        }
+
+        let path = self.path.clone();
+        let key = self.encryption_key.clone();
+        let rewritten = tokio::task::spawn_blocking(move || {
+            let content = encryption::read_to_string(&path, key.as_ref())?;
+            let credentials: Vec<KiroCredentials> = serde_json::from_str(&content)?;
+            let canonical = to_canonical_json(&credentials)?;
+            if canonical == content {
+                return Ok::<_, anyhow::Error>(false);
+            }
+            write_atomic(&path, canonical.as_bytes(), key.as_ref())
+                .map_err(|e| anyhow::anyhow!("写入凭据文件失败: {}", e))?;
+            Ok(true)
+        })
+        .await??;
+
+        *self.last_compacted.lock() = Some(file_modified(&self.path)?);
+        if rewritten {
+            tracing::debug!("已压缩凭据文件: {:?}", self.path);
+        }
+        Ok(rewritten)
+    }
+
+    /// 将明文凭据文件就地加密（启用加密前迁移旧文件）
+    ///
+    /// 需已设置加密密钥；文件已加密或内容不是合法的凭据 JSON 时返回错误，原文件保持不变
+    pub async fn encrypt_legacy_file(&self) -> anyhow::Result<()> {
+        let key = self
+            .encryption_key
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("未配置凭据文件加密密钥"))?;
+
+        let _guard = self.file_lock.lock().await;
+
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let raw = std::fs::read(&path)?;
+            if encryption::is_encrypted(&raw) {
+                anyhow::bail!("凭据文件已加密，无需迁移: {:?}", path);
+            }
+            let content = compression::decode_to_string(raw)?;
+            serde_json::from_str::<CredentialsConfig>(&content)?;
+            write_atomic(&path, content.as_bytes(), Some(&key))
+                .map_err(|e| anyhow::anyhow!("写入凭据文件失败: {}", e))
         })
         .await??;
 
+        tracing::info!("已加密凭据文件: {:?}", self.path);
+        Ok(())
+    }
+
+    /// 启动定时压缩任务
+    pub fn start_compaction_task(
+        self: Arc<Self>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // 跳过立即触发的第一次 tick
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.compact().await {
+                    tracing::warn!("凭据文件压缩失败: {}", e);
+                }
+            }
+        })
+    }
+
+    /// 序列化为规范形式并原子写入文件（调用方需持有 `file_lock`）
+    ///
+    /// 与 `compact` 使用同一规范形式，回写后的文件无需再次压缩
+    async fn write_file(&self, credentials: &[KiroCredentials]) -> anyhow::Result<()> {
+        let json = to_canonical_json(credentials)?;
+        let path = self.path.clone();
+        let key = self.encryption_key.clone();
+
+        tokio::task::spawn_blocking(move || write_atomic(&path, json.as_bytes(), key.as_ref()))
+            .await?
+            .map_err(|e| anyhow::anyhow!("写入凭据文件失败: {}", e))?;
+
+        tracing::debug!("已回写凭据到文件: {:?}", self.path);
+        Ok(())
+    }
+}
+
+/// 写入时使用的临时文件路径
+///
+/// 与目标文件同目录（保证 rename 不跨文件系统退化为复制），文件名带进程 ID，
+/// 避免多个进程同时写入同一凭据文件时互相覆盖临时文件
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp.{}", name, std::process::id()))
+}
+
+/// 原子写入文件
+///
+/// 先完整写入同目录下的临时文件并落盘，再 rename 覆盖目标文件；
+/// 任一步骤失败时目标文件保持原样。`.gz` 路径写入前先压缩，设置密钥时压缩后再加密
+fn write_atomic(
+    path: &Path,
+    contents: &[u8],
+    key: Option<&EncryptionKey>,
+) -> std::io::Result<()> {
+    use std::io::Write;
+
+    write_atomic_with(path, contents, key, |file, contents| file.write_all(contents))
+}
+
+/// 原子写入文件，由 `write` 负责写入临时文件内容（测试中用于注入写入失败）
+fn write_atomic_with(
+    path: &Path,
+    contents: &[u8],
+    key: Option<&EncryptionKey>,
+    write: impl FnOnce(&mut std::fs::File, &[u8]) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut contents = compression::encode_for_path(path, contents)?;
+    if let Some(key) = key {
+        contents = encryption::encrypt(key, &contents)?;
+    }
+    let tmp = temp_path(path);
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp)?;
+        write(&mut file, &contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        sync_parent_dir(path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// 将目录项落盘，保证断电后 rename 的结果不会丢失（仅 Unix 支持对目录 fsync）
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => std::fs::File::open(dir)?.sync_all(),
+        None => std::fs::File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// 读取文件修改时间
+fn file_modified(path: &Path) -> anyhow::Result<SystemTime> {
+    Ok(std::fs::metadata(path)?.modified()?)
+}
+
+/// 将凭据序列化为规范化的 JSON（回写与压缩共用，保证两者输出一致）
+fn to_canonical_json(credentials: &[KiroCredentials]) -> anyhow::Result<String> {
+    Ok(canonicalize(serde_json::to_value(credentials)?))
+}
+
+/// 生成规范化的凭据 JSON
+///
+/// 对象键按字典序输出（serde_json 默认 Map 为有序 Map），凭据按 ID 升序排列
+/// （无 ID 的保持原有相对顺序排在最后），并移除值为 null 的字段
+fn canonicalize(mut value: Value) -> String {
+    if let Value::Array(items) = &mut value {
+        items.sort_by_key(|item| item.get("id").and_then(Value::as_u64).unwrap_or(u64::MAX));
+        for item in items.iter_mut() {
+            if let Value::Object(map) = item {
+                map.retain(|_, v| !v.is_null());
+            }
+        }
+    }
+
+    let mut json = value.to_string();
+    json.push('\n');
+    json
+}
+
+#[async_trait]
+impl CredentialStorage for FileCredentialStorage {
+    async fn load_all(&self) -> anyhow::Result<Vec<KiroCredentials>> {
+        let (mut credentials, assigned) = self.read_credentials().await?;
+        if assigned && self.is_multiple_format {
+            // 分配的 ID 立即写回文件，避免之后新增更大的显式 ID 时重新分配
+            let _guard = self.file_lock.lock().await;
+            let (current, assigned) = self.read_credentials().await?;
+            credentials = current;
+            if assigned {
+                match self.write_file(&credentials).await {
+                    Ok(()) => tracing::info!("已为缺少 ID 的凭据分配 ID 并写回文件"),
+                    Err(e) => tracing::warn!("分配的凭据 ID 写回文件失败: {}", e),
+                }
+            }
+        }
+        let credentials = apply_max_credentials(credentials, self.max_credentials);
+        *self.loaded_ids.lock() = credentials.iter().filter_map(|c| c.id).collect();
         Ok(credentials)
     }
 
@@ -74,8 +330,10 @@ impl CredentialStorage for FileCredentialStorage {
             return Ok(()); // 单凭据格式不支持单个保存
         }
 
-        // 加载现有凭据，更新或添加
-        let mut credentials = self.load_all().await?;
+        let _guard = self.file_lock.lock().await;
+
+        // 加载现有凭据（含超出上限未加载的），更新或添加
+        let mut credentials = self.load_uncapped().await?;
 
         if let Some(id) = credential.id {
             if let Some(existing) = credentials.iter_mut().find(|c| c.id == Some(id)) {
@@ -87,7 +345,9 @@ impl CredentialStorage for FileCredentialStorage {
             credentials.push(credential.clone());
         }
 
-        self.save_all(&credentials).await
+        self.write_file(&credentials).await?;
+        self.loaded_ids.lock().extend(credential.id);
+        Ok(())
     }
 
     async fn save_all(&self, credentials: &[KiroCredentials]) -> anyhow::Result<()> {
@@ -96,14 +356,22 @@ impl CredentialStorage for FileCredentialStorage {
             return Ok(());
         }
 
-        let json = serde_json::to_string_pretty(credentials)?;
-        let path = self.path.clone();
+        // 先校验整批凭据，任一无效时不写入
+        validate_batch(credentials, false)?;
 
-        tokio::task::spawn_blocking(move || std::fs::write(&path, json))
-            .await?
-            .map_err(|e| anyhow::anyhow!("写入凭据文件失败: {}", e))?;
+        let _guard = self.file_lock.lock().await;
 
-        tracing::debug!("已回写凭据到文件: {:?}", self.path);
+        // 超出上限而未加载的凭据不在本批中，原样保留
+        let mut merged = credentials.to_vec();
+        if self.max_credentials.is_some() {
+            let stored = self.load_uncapped().await?;
+            let loaded_ids = self.loaded_ids.lock().clone();
+            merged.extend(unloaded_credentials(stored, credentials, &loaded_ids));
+        }
+        self.write_file(&merged).await?;
+        self.loaded_ids
+            .lock()
+            .extend(credentials.iter().filter_map(|c| c.id));
         Ok(())
     }
 
@@ -112,9 +380,10 @@ impl CredentialStorage for FileCredentialStorage {
             return Ok(());
         }
 
-        let mut credentials = self.load_all().await?;
+        let _guard = self.file_lock.lock().await;
+        let mut credentials = self.load_uncapped().await?;
         credentials.retain(|c| c.id != Some(id));
-        self.save_all(&credentials).await
+        self.write_file(&credentials).await
     }
 
     fn storage_type(&self) -> &'static str {
@@ -124,6 +393,25 @@ impl CredentialStorage for FileCredentialStorage {
     fn is_writable(&self) -> bool {
         self.is_multiple_format
     }
+
+    /// 比较文件修改时间与上次同步时间
+    ///
+    /// 修改时间精度按秒计，与上次同步处于同一秒的修改也视为变更（内容未变化时由同步管理器的指纹跳过）；
+    /// 修改时间晚于当前时间（时钟偏差）时总是视为变更；文件不存在时返回错误
+    async fn has_changes_since(&self, since_timestamp: i64) -> anyhow::Result<bool> {
+        let modified = file_modified(&self.path)
+            .map_err(|e| anyhow::anyhow!("读取凭据文件修改时间失败（{:?}）: {}", self.path, e))?;
+        let modified_secs = match modified.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() as i64,
+            Err(_) => return Ok(true),
+        };
+
+        if modified_secs > chrono::Utc::now().timestamp() {
+            tracing::debug!("凭据文件修改时间晚于当前时间，视为有变更: {:?}", self.path);
+            return Ok(true);
+        }
+        Ok(modified_secs >= since_timestamp)
+    }
 }
 
 #[cfg(test)]
@@ -141,7 +429,7 @@ mod tests {
         )
         .unwrap();
 
-        let storage = FileCredentialStorage::from_file(file.path()).unwrap();
+        let storage = FileCredentialStorage::from_file(file.path(), None).unwrap();
         assert!(!storage.is_multiple_format());
 
         let credentials = storage.load_all().await.unwrap();
@@ -161,7 +449,7 @@ mod tests {
         )
         .unwrap();
 
-        let storage = FileCredentialStorage::from_file(file.path()).unwrap();
+        let storage = FileCredentialStorage::from_file(file.path(), None).unwrap();
         assert!(storage.is_multiple_format());
 
         let credentials = storage.load_all().await.unwrap();
@@ -171,6 +459,90 @@ mod tests {
         assert_eq!(credentials[1].refresh_token, Some("t1".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_load_respects_max_credentials() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"[
+                {{"refreshToken": "t1", "priority": 2}},
+                {{"refreshToken": "t2", "priority": 0}},
+                {{"refreshToken": "t3", "priority": 1}}
+            ]"#
+        )
+        .unwrap();
+
+        let storage = FileCredentialStorage::from_file(file.path(), None)
+            .unwrap()
+            .with_max_credentials(Some(2));
+
+        let credentials = storage.load_all().await.unwrap();
+        // 仅保留优先级最高的 2 个
+        assert_eq!(credentials.len(), 2);
+        assert_eq!(credentials[0].refresh_token, Some("t2".to_string()));
+        assert_eq!(credentials[1].refresh_token, Some("t3".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_save_all_keeps_credentials_over_max() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"[
+                {{"id": 1, "refreshToken": "t1", "priority": 2}},
+                {{"id": 2, "refreshToken": "t2", "priority": 0}},
+                {{"id": 3, "refreshToken": "t3", "priority": 1}}
+            ]"#
+        )
+        .unwrap();
+
+        let storage = FileCredentialStorage::from_file(file.path(), None)
+            .unwrap()
+            .with_max_credentials(Some(2));
+
+        // 回写加载到的子集，未加载的 #1 不应被删除
+        let mut loaded = storage.load_all().await.unwrap();
+        loaded[0].access_token = Some("refreshed".to_string());
+        storage.save_all(&loaded).await.unwrap();
+
+        // 单个保存同样不丢失未加载的凭据
+        storage.save(&loaded[1]).await.unwrap();
+
+        let all = storage.load_uncapped().await.unwrap();
+        let ids: Vec<u64> = all.iter().filter_map(|c| c.id).collect();
+        assert_eq!(ids, vec![2, 3, 1]);
+        assert_eq!(all[0].access_token, Some("refreshed".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_save_all_after_delete_keeps_credentials_over_max() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"[
+                {{"id": 1, "refreshToken": "t1", "priority": 0}},
+                {{"id": 2, "refreshToken": "t2", "priority": 1}},
+                {{"id": 3, "refreshToken": "t3", "priority": 2}},
+                {{"id": 4, "refreshToken": "t4", "priority": 3}}
+            ]"#
+        )
+        .unwrap();
+
+        let storage = FileCredentialStorage::from_file(file.path(), None)
+            .unwrap()
+            .with_max_credentials(Some(2));
+
+        // 删除已加载的 #1 后，未加载的 #3 排到第 2 位，仍不应被回写删除
+        let mut loaded = storage.load_all().await.unwrap();
+        storage.delete(1).await.unwrap();
+        loaded.retain(|c| c.id != Some(1));
+        storage.save_all(&loaded).await.unwrap();
+
+        let all = storage.load_uncapped().await.unwrap();
+        let ids: Vec<u64> = all.iter().filter_map(|c| c.id).collect();
+        assert_eq!(ids, vec![2, 3, 4]);
+    }
+
     #[tokio::test]
     async fn test_save_all_multiple_format() {
         let file = NamedTempFile::new().unwrap();
@@ -196,6 +568,333 @@ mod tests {
         assert_eq!(loaded.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_missing_ids_persisted_on_first_load() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"[
+                {{"refreshToken": "t1"}},
+                {{"id": 4, "refreshToken": "t2"}},
+                {{"refreshToken": "t3"}}
+            ]"#
+        )
+        .unwrap();
+        let storage = FileCredentialStorage::from_file(file.path(), None).unwrap();
+
+        let ids = |credentials: &[KiroCredentials]| {
+            credentials
+                .iter()
+                .map(|c| (c.refresh_token.clone().unwrap(), c.id))
+                .collect::<Vec<_>>()
+        };
+        let loaded = storage.load_all().await.unwrap();
+        assert_eq!(
+            ids(&loaded),
+            vec![
+                ("t1".to_string(), Some(5)),
+                ("t2".to_string(), Some(4)),
+                ("t3".to_string(), Some(6))
+            ]
+        );
+
+        // 首次加载即写回分配的 ID
+        let persisted = || {
+            let content = std::fs::read_to_string(file.path()).unwrap();
+            serde_json::from_str::<Vec<KiroCredentials>>(&content).unwrap()
+        };
+        let mut stored = persisted();
+        assert_eq!(
+            ids(&stored),
+            vec![
+                ("t2".to_string(), Some(4)),
+                ("t1".to_string(), Some(5)),
+                ("t3".to_string(), Some(6))
+            ]
+        );
+
+        // 之后手动加入更大的显式 ID，已分配的 ID 保持不变
+        stored.push(KiroCredentials {
+            id: Some(9),
+            refresh_token: Some("t4".to_string()),
+            ..Default::default()
+        });
+        std::fs::write(file.path(), serde_json::to_string(&stored).unwrap()).unwrap();
+        let reloaded = storage.load_all().await.unwrap();
+        assert_eq!(ids(&reloaded), ids(&stored));
+
+        storage.delete(5).await.unwrap();
+        assert_eq!(
+            ids(&persisted()),
+            vec![
+                ("t2".to_string(), Some(4)),
+                ("t3".to_string(), Some(6)),
+                ("t4".to_string(), Some(9))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_all_write_failure_leaves_no_partial_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        let storage = FileCredentialStorage::new(&path, true);
+
+        let original = vec![KiroCredentials {
+            id: Some(1),
+            refresh_token: Some("t1".to_string()),
+            ..Default::default()
+        }];
+        storage.save_all(&original).await.unwrap();
+        let before = std::fs::read_to_string(&path).unwrap();
+
+        // 临时文件路径被目录占用，模拟写入中途失败
+        std::fs::create_dir(temp_path(&path)).unwrap();
+        let updated = vec![
+            KiroCredentials {
+                id: Some(1),
+                refresh_token: Some("t1-new".to_string()),
+                ..Default::default()
+            },
+            KiroCredentials {
+                id: Some(2),
+                refresh_token: Some("t2".to_string()),
+                ..Default::default()
+            },
+        ];
+        assert!(storage.save_all(&updated).await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+
+        // 批次中含无效凭据时整体拒绝，并报告具体是哪个
+        std::fs::remove_dir(temp_path(&path)).unwrap();
+        let invalid = vec![
+            updated[0].clone(),
+            KiroCredentials {
+                id: Some(2),
+                refresh_token: Some("t2".to_string()),
+                expires_at: Some("not-a-date".to_string()),
+                ..Default::default()
+            },
+        ];
+        let err = storage.save_all(&invalid).await.unwrap_err();
+        let err = err.downcast_ref::<super::super::traits::BatchValidationError>().unwrap();
+        assert_eq!(err.invalid.len(), 1);
+        assert_eq!(err.invalid[0].index, 1);
+        assert_eq!(err.invalid[0].id, Some(2));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+
+        storage.save_all(&updated).await.unwrap();
+        let loaded = storage.load_all().await.unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].refresh_token.as_deref(), Some("t1-new"));
+    }
+
+    #[test]
+    fn test_interrupted_write_keeps_original_file() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        std::fs::write(&path, r#"[{"id":1,"refreshToken":"t1"}]"#).unwrap();
+
+        // 只写入一半内容后失败，模拟写入过程中崩溃或磁盘写满
+        let updated = br#"[{"id":1,"refreshToken":"t1-new"}]"#;
+        let result = write_atomic_with(&path, updated, None, |file, contents| {
+            file.write_all(&contents[..contents.len() / 2])?;
+            Err(std::io::Error::other("模拟写入失败"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"[{"id":1,"refreshToken":"t1"}]"#
+        );
+        assert!(!temp_path(&path).exists());
+        assert!(temp_path(&path).starts_with(dir.path()));
+        assert!(
+            temp_path(&path)
+                .to_string_lossy()
+                .ends_with(&format!(".tmp.{}", std::process::id()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_has_changes_since_follows_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        std::fs::write(&path, r#"[{"id":1,"refreshToken":"t1"}]"#).unwrap();
+        let storage = FileCredentialStorage::new(&path, true);
+
+        let set_mtime = |secs: i64| {
+            let time = SystemTime::UNIX_EPOCH + Duration::from_secs(secs as u64);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        };
+        let now = chrono::Utc::now().timestamp();
+
+        // 上次同步之后未修改
+        set_mtime(now - 120);
+        assert!(!storage.has_changes_since(now - 60).await.unwrap());
+        assert!(storage.has_changes_since(0).await.unwrap());
+
+        // 文件被修改（touch）后检测到变更
+        set_mtime(now - 30);
+        assert!(storage.has_changes_since(now - 60).await.unwrap());
+
+        // 时钟偏差：修改时间在未来时总是视为变更
+        set_mtime(now + 3600);
+        assert!(storage.has_changes_since(now + 7200).await.unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(storage.has_changes_since(now).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_gzip_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json.gz");
+        let storage = FileCredentialStorage::new(&path, true);
+
+        let credentials = vec![
+            KiroCredentials {
+                id: Some(1),
+                refresh_token: Some("t1".to_string()),
+                ..Default::default()
+            },
+            KiroCredentials {
+                id: Some(2),
+                refresh_token: Some("t2".to_string()),
+                priority: 1,
+                ..Default::default()
+            },
+        ];
+        storage.save_all(&credentials).await.unwrap();
+
+        // 磁盘上为 gzip 格式，且不残留临时文件
+        let raw = std::fs::read(&path).unwrap();
+        assert_eq!(&raw[..2], &[0x1f, 0x8b]);
+        assert!(!temp_path(&path).exists());
+
+        // 重新打开时自动识别格式并解压
+        let reopened = FileCredentialStorage::from_file(&path, None).unwrap();
+        assert!(reopened.is_multiple_format());
+        let loaded = reopened.load_all().await.unwrap();
+        assert_eq!(
+            loaded.iter().map(|c| c.refresh_token.as_deref()).collect::<Vec<_>>(),
+            vec![Some("t1"), Some("t2")]
+        );
+
+        // 手动改为非规范形式后，压缩重写仍为 gzip
+        let pretty = serde_json::to_string_pretty(&credentials).unwrap();
+        write_atomic(&path, pretty.as_bytes(), None).unwrap();
+        assert!(reopened.compact().await.unwrap());
+        assert_eq!(&std::fs::read(&path).unwrap()[..2], &[0x1f, 0x8b]);
+        assert_eq!(reopened.load_all().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json.gz");
+        let key = EncryptionKey::derive("0123456789abcdef0123456789abcdef");
+        let storage =
+            FileCredentialStorage::new(&path, true).with_encryption_key(Some(key.clone()));
+
+        let credentials = vec![
+            KiroCredentials {
+                id: Some(1),
+                refresh_token: Some("secret-refresh-token".to_string()),
+                ..Default::default()
+            },
+            KiroCredentials {
+                id: Some(2),
+                refresh_token: Some("t2".to_string()),
+                ..Default::default()
+            },
+        ];
+        storage.save_all(&credentials).await.unwrap();
+
+        // 磁盘上为加密格式，不含明文 token
+        let first = std::fs::read(&path).unwrap();
+        assert!(encryption::is_encrypted(&first));
+        assert!(!String::from_utf8_lossy(&first).contains("secret-refresh-token"));
+
+        // 每次写入使用新的 nonce
+        storage.save_all(&credentials).await.unwrap();
+        assert_ne!(std::fs::read(&path).unwrap(), first);
+
+        let reopened = FileCredentialStorage::from_file(&path, Some(key.clone())).unwrap();
+        assert!(reopened.is_multiple_format());
+        let loaded = reopened.load_all().await.unwrap();
+        assert_eq!(
+            loaded.iter().map(|c| c.refresh_token.as_deref()).collect::<Vec<_>>(),
+            vec![Some("secret-refresh-token"), Some("t2")]
+        );
+
+        // 手动改为非规范形式后，压缩重写仍为加密格式
+        let pretty = serde_json::to_string_pretty(&credentials).unwrap();
+        write_atomic(&path, pretty.as_bytes(), Some(&key)).unwrap();
+        assert!(reopened.compact().await.unwrap());
+        assert!(encryption::is_encrypted(&std::fs::read(&path).unwrap()));
+        assert_eq!(reopened.load_all().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_file_wrong_key_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        let storage = FileCredentialStorage::new(&path, true)
+            .with_encryption_key(Some(EncryptionKey::derive("right-key")));
+        storage
+            .save_all(&[KiroCredentials {
+                id: Some(1),
+                refresh_token: Some("t1".to_string()),
+                ..Default::default()
+            }])
+            .await
+            .unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        let wrong = FileCredentialStorage::new(&path, true)
+            .with_encryption_key(Some(EncryptionKey::derive("wrong-key")));
+        let err = wrong.load_all().await.unwrap_err().to_string();
+        assert!(err.contains("解密失败"), "{}", err);
+        assert!(wrong.compact().await.is_err());
+
+        let plain = FileCredentialStorage::new(&path, true);
+        let err = plain.load_all().await.unwrap_err().to_string();
+        assert!(err.contains("未配置"), "{}", err);
+
+        // 解密失败不会改写文件
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_plaintext_file_detected_and_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        std::fs::write(&path, r#"[{"id": 1, "refreshToken": "t1"}]"#).unwrap();
+
+        let storage = FileCredentialStorage::new(&path, true)
+            .with_encryption_key(Some(EncryptionKey::derive("key")));
+        let err = storage.load_all().await.unwrap_err().to_string();
+        assert!(err.contains("明文"), "{}", err);
+        assert!(err.contains("encrypt-credentials"), "{}", err);
+
+        storage.encrypt_legacy_file().await.unwrap();
+        assert!(encryption::is_encrypted(&std::fs::read(&path).unwrap()));
+        let loaded = storage.load_all().await.unwrap();
+        assert_eq!(loaded[0].refresh_token.as_deref(), Some("t1"));
+
+        // 已加密的文件不重复加密
+        let err = storage.encrypt_legacy_file().await.unwrap_err().to_string();
+        assert!(err.contains("无需迁移"), "{}", err);
+    }
+
     #[tokio::test]
     async fn test_save_all_single_format_skipped() {
         let file = NamedTempFile::new().unwrap();
@@ -210,4 +909,86 @@ mod tests {
         // 单凭据格式不回写，应该成功但不写入
         storage.save_all(&credentials).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_stream_all_matches_load_all() {
+        use futures::TryStreamExt;
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"[
+                {{"id": 1, "refreshToken": "t1", "priority": 2}},
+                {{"id": 2, "refreshToken": "t2", "priority": 0}},
+                {{"id": 3, "refreshToken": "t3", "priority": 1}}
+            ]"#
+        )
+        .unwrap();
+
+        let storage = FileCredentialStorage::from_file(file.path(), None).unwrap();
+
+        let loaded: Vec<Option<u64>> = storage
+            .load_all()
+            .await
+            .unwrap()
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        let streamed: Vec<Option<u64>> = storage
+            .stream_all()
+            .map_ok(|c| c.id)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(streamed, loaded);
+        assert_eq!(streamed, vec![Some(2), Some(3), Some(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_output_is_stable() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"[
+                {{"refreshToken": "t3", "priority": 1, "id": 3}},
+                {{"id": 1,   "region": null, "refreshToken": "t1"}},
+                {{"priority": 0, "refreshToken": "t2", "id": 2}}
+            ]"#
+        )
+        .unwrap();
+
+        let storage = FileCredentialStorage::from_file(file.path(), None).unwrap();
+        let before = storage.load_all().await.unwrap();
+
+        assert!(storage.compact().await.unwrap());
+        let compacted = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(
+            compacted,
+            concat!(
+                r#"[{"id":1,"refreshToken":"t1"},"#,
+                r#"{"id":2,"refreshToken":"t2"},"#,
+                r#"{"id":3,"priority":1,"refreshToken":"t3"}]"#,
+                "\n"
+            )
+        );
+
+        // 未修改时跳过；新实例重复压缩得到相同输出
+        assert!(!storage.compact().await.unwrap());
+        let fresh = FileCredentialStorage::from_file(file.path(), None).unwrap();
+        assert!(!fresh.compact().await.unwrap());
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), compacted);
+
+        // 内容不变
+        let after = storage.load_all().await.unwrap();
+        assert_eq!(
+            after.iter().map(|c| c.id).collect::<Vec<_>>(),
+            before.iter().map(|c| c.id).collect::<Vec<_>>()
+        );
+
+        // 回写与压缩使用同一规范形式，回写后压缩不再改写文件
+        storage.save_all(&after).await.unwrap();
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), compacted);
+        assert!(!storage.compact().await.unwrap());
+    }
 }
@@ -1,8 +1,11 @@
 //! 凭据存储抽象层
 //!
 //! 支持多种存储后端：
-//! - 文件存储（默认，向后兼容）
+//! - 文件存储（默认，向后兼容，`.json.gz` 路径自动 gzip 压缩，可选 AES-256-GCM 加密）
 //! - PostgreSQL 存储（可选）
+//! - Redis 存储（可选，写入后通过 pub/sub 通知其他实例）
+//! - SQLite 存储（可选，适合单机部署）
+//! - MySQL/MariaDB 存储（可选）
 //!
 //! # 使用方式
 //!
@@ -12,19 +15,58 @@
 //!
 //! // PostgreSQL 存储（需要启用 postgres feature）
 //! #[cfg(feature = "postgres")]
-//! let storage = PostgresCredentialStorage::new("postgres://...", "kiro_credentials").await?;
+//! let storage = PostgresCredentialStorage::new(&config.postgres.unwrap()).await?;
+//!
+//! // Redis 存储（需要启用 redis feature）
+//! #[cfg(feature = "redis")]
+//! let storage = RedisCredentialStorage::new(&config.redis.unwrap()).await?;
+//!
+//! // SQLite 存储（需要启用 sqlite feature）
+//! #[cfg(feature = "sqlite")]
+//! let storage = SqliteCredentialStorage::new(&config.sqlite.unwrap()).await?;
+//!
+//! // MySQL/MariaDB 存储（需要启用 mysql feature）
+//! #[cfg(feature = "mysql")]
+//! let storage = MysqlCredentialStorage::new(&config.mysql.unwrap()).await?;
 //! ```
 
 mod traits;
+pub mod compression;
+pub mod encryption;
 mod file;
+mod sanitize;
+mod storage_type;
 mod sync;
 
 #[cfg(feature = "postgres")]
 mod postgres;
 
-pub use traits::CredentialStorage;
+#[cfg(feature = "redis")]
+mod redis;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "mysql")]
+mod mysql;
+
+pub use traits::{
+    CredentialStorage, InvalidCredential, StorageHealth, validate_batch, validate_credential,
+};
+pub use encryption::EncryptionKey;
 pub use file::FileCredentialStorage;
+pub use sanitize::{SanitizedError, redact_secrets};
+pub use storage_type::StorageType;
 pub use sync::{CredentialSyncManager, CredentialChangeEvent};
 
 #[cfg(feature = "postgres")]
 pub use postgres::PostgresCredentialStorage;
+
+#[cfg(feature = "redis")]
+pub use redis::RedisCredentialStorage;
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteCredentialStorage;
+
+#[cfg(feature = "mysql")]
+pub use mysql::MysqlCredentialStorage;
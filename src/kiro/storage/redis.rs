@@ -0,0 +1,488 @@
+//! Redis 凭据存储实现
+//!
+//! 需要启用 `redis` feature。数据布局（`{prefix}` 为 `keyPrefix`）：
+//! - `{prefix}:credential:{id}`：凭据哈希，每个字段为凭据 JSON 中对应字段的 JSON 编码值
+//! - `{prefix}:priority`：按优先级排序的有序集合（成员为凭据 ID），`load_all` 按此顺序读取
+//! - `{prefix}:updated`：按更新时间（毫秒，单调递增）排序的有序集合，用于 `has_changes_since`
+//! - `{prefix}:clock`：上次写入使用的更新时间，更新时间取 Redis 服务器时间（`TIME`），
+//!   不依赖各实例的本地时钟
+//! - `{prefix}:tombstone:{id}`：删除标记（值为删除时间），`tombstoneTtlSecs` 后自动过期；
+//!   标记存在时 `save_all` 不恢复该凭据，`save` 显式保存时清除标记
+//!
+//! 每次写入后向 `{prefix}:changes` 频道发布通知，其他实例订阅后立即同步，无需等待同步间隔
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use parking_lot::Mutex;
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+
+use crate::kiro::model::credentials::KiroCredentials;
+use crate::model::config::RedisConfig;
+
+use super::traits::{CredentialStorage, apply_max_credentials, validate_batch};
+
+/// 取 Redis 服务器时间（毫秒）作为更新时间，且严格大于 `KEYS[1]` 中记录的上次更新时间
+const NEXT_UPDATE_SCORE_SCRIPT: &str = r#"
+local now = redis.call('TIME')
+local score = tonumber(now[1]) * 1000 + math.floor(tonumber(now[2]) / 1000)
+local last = tonumber(redis.call('GET', KEYS[1]) or '0')
+if score <= last then
+    score = last + 1
+end
+redis.call('SET', KEYS[1], score)
+return score
+"#;
+
+/// Redis 凭据存储
+pub struct RedisCredentialStorage {
+    /// 客户端（用于建立订阅连接）
+    client: redis::Client,
+    /// 自动重连的连接
+    conn: ConnectionManager,
+    /// 键前缀
+    prefix: String,
+    /// 删除标记的保留时长（秒）
+    tombstone_ttl_secs: u64,
+    /// 最多加载的凭据数量（None 表示不限制）
+    max_credentials: Option<usize>,
+    /// 本实例加载或写入过的凭据 ID（其余凭据超出上限未加载，`save_all` 时不视为删除）
+    loaded_ids: Mutex<HashSet<u64>>,
+}
+
+impl RedisCredentialStorage {
+    /// 创建 Redis 存储实例（立即建立连接）
+    pub async fn new(config: &RedisConfig) -> anyhow::Result<Self> {
+        let client = redis::Client::open(config.url.as_str())?;
+        let conn = ConnectionManager::new(client.clone()).await?;
+
+        tracing::info!("Redis 连接已建立，键前缀: {}", config.key_prefix);
+
+        Ok(Self {
+            client,
+            conn,
+            prefix: config.key_prefix.clone(),
+            tombstone_ttl_secs: config.tombstone_ttl_secs,
+            max_credentials: None,
+            loaded_ids: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// 设置最多加载的凭据数量
+    pub fn with_max_credentials(mut self, max_credentials: Option<usize>) -> Self {
+        self.max_credentials = max_credentials;
+        self
+    }
+
+    fn credential_key(&self, id: u64) -> String {
+        format!("{}:credential:{}", self.prefix, id)
+    }
+
+    fn tombstone_key(&self, id: u64) -> String {
+        format!("{}:tombstone:{}", self.prefix, id)
+    }
+
+    fn priority_key(&self) -> String {
+        format!("{}:priority", self.prefix)
+    }
+
+    fn updated_key(&self) -> String {
+        format!("{}:updated", self.prefix)
+    }
+
+    fn clock_key(&self) -> String {
+        format!("{}:clock", self.prefix)
+    }
+
+    fn changes_channel(&self) -> String {
+        format!("{}:changes", self.prefix)
+    }
+
+    /// 下一个更新时间分数（毫秒）：取 Redis 服务器时间，且严格大于所有实例上次写入的分数
+    async fn next_update_score(&self, conn: &mut ConnectionManager) -> anyhow::Result<i64> {
+        let score: i64 = redis::Script::new(NEXT_UPDATE_SCORE_SCRIPT)
+            .key(self.clock_key())
+            .invoke_async(conn)
+            .await?;
+        Ok(score)
+    }
+
+    /// 向事务追加一次凭据写入（覆盖哈希、更新优先级和更新时间、清除删除标记）
+    fn queue_save(
+        &self,
+        pipe: &mut redis::Pipeline,
+        credential: &KiroCredentials,
+        score: i64,
+    ) -> anyhow::Result<()> {
+        let id = credential.id.context("Redis 存储要求凭据带有 id")?;
+        let key = self.credential_key(id);
+        let fields = credential_to_fields(credential)?;
+
+        pipe.del(&key).ignore();
+        pipe.hset_multiple(&key, fields.as_slice()).ignore();
+        pipe.zadd(self.priority_key(), id, credential.priority).ignore();
+        pipe.zadd(self.updated_key(), id, score).ignore();
+        pipe.del(self.tombstone_key(id)).ignore();
+        Ok(())
+    }
+
+    /// 向事务追加一次删除（删除哈希和优先级，写入带过期时间的删除标记）
+    fn queue_delete(&self, pipe: &mut redis::Pipeline, id: u64, score: i64) {
+        pipe.del(self.credential_key(id)).ignore();
+        pipe.zrem(self.priority_key(), id).ignore();
+        pipe.zadd(self.updated_key(), id, score).ignore();
+        pipe.set_ex(
+            self.tombstone_key(id),
+            chrono::Utc::now().to_rfc3339(),
+            self.tombstone_ttl_secs,
+        )
+        .ignore();
+    }
+
+    /// 向事务追加收尾命令：清理早于删除标记保留时长的更新记录，并发布变更通知
+    fn queue_finish(&self, pipe: &mut redis::Pipeline, score: i64) {
+        let retain_since = score - (self.tombstone_ttl_secs as i64).saturating_mul(1000);
+        pipe.zrembyscore(self.updated_key(), "-inf", format!("({}", retain_since)).ignore();
+        pipe.publish(self.changes_channel(), score).ignore();
+    }
+
+    /// 按 `load_all` 顺序排列的全部凭据 ID
+    ///
+    /// 优先级相同时按 ID 排序（有序集合对同分成员按字典序排列）
+    async fn ordered_ids(&self, conn: &mut ConnectionManager) -> anyhow::Result<Vec<u64>> {
+        let mut ordered: Vec<(u64, f64)> =
+            conn.zrange_withscores(self.priority_key(), 0, -1).await?;
+        ordered.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        Ok(ordered.into_iter().map(|(id, _)| id).collect())
+    }
+}
+
+/// 将凭据转换为哈希字段（仅包含已设置的字段，值为 JSON 编码）
+fn credential_to_fields(credential: &KiroCredentials) -> anyhow::Result<Vec<(String, String)>> {
+    let serde_json::Value::Object(map) = serde_json::to_value(credential)? else {
+        anyhow::bail!("凭据序列化结果不是 JSON 对象");
+    };
+    Ok(map
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(field, value)| (field, value.to_string()))
+        .collect())
+}
+
+/// 从哈希字段还原凭据
+fn fields_to_credential(fields: HashMap<String, String>) -> anyhow::Result<KiroCredentials> {
+    let map = fields
+        .into_iter()
+        .map(|(field, value)| {
+            let value = serde_json::from_str(&value)
+                .with_context(|| format!("凭据字段 {} 不是合法的 JSON", field))?;
+            Ok((field, value))
+        })
+        .collect::<anyhow::Result<serde_json::Map<_, _>>>()?;
+    Ok(serde_json::from_value(serde_json::Value::Object(map))?)
+}
+
+#[async_trait]
+impl CredentialStorage for RedisCredentialStorage {
+    async fn load_all(&self) -> anyhow::Result<Vec<KiroCredentials>> {
+        let mut conn = self.conn.clone();
+        let ordered = self.ordered_ids(&mut conn).await?;
+
+        let mut pipe = redis::pipe();
+        for id in &ordered {
+            pipe.hgetall(self.credential_key(*id));
+        }
+        let hashes: Vec<HashMap<String, String>> = if ordered.is_empty() {
+            Vec::new()
+        } else {
+            pipe.query_async(&mut conn).await?
+        };
+
+        let mut credentials = Vec::with_capacity(hashes.len());
+        for (id, fields) in ordered.iter().zip(hashes) {
+            // 优先级集合中残留的成员（哈希已被删除）直接跳过
+            if fields.is_empty() {
+                continue;
+            }
+            let mut credential = fields_to_credential(fields)
+                .with_context(|| format!("解析 Redis 中的凭据 #{} 失败", id))?;
+            credential.id = Some(*id);
+            credentials.push(credential);
+        }
+
+        let credentials = apply_max_credentials(credentials, self.max_credentials);
+        *self.loaded_ids.lock() = credentials.iter().filter_map(|c| c.id).collect();
+        tracing::info!("从 Redis 加载了 {} 个凭据", credentials.len());
+        Ok(credentials)
+    }
+
+    async fn save(&self, credential: &KiroCredentials) -> anyhow::Result<()> {
+        let mut conn = self.conn.clone();
+        let score = self.next_update_score(&mut conn).await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        self.queue_save(&mut pipe, credential, score)?;
+        self.queue_finish(&mut pipe, score);
+        let () = pipe.query_async(&mut conn).await?;
+        self.loaded_ids.lock().extend(credential.id);
+
+        tracing::debug!("已保存凭据到 Redis: id={:?}", credential.id);
+        Ok(())
+    }
+
+    async fn save_all(&self, credentials: &[KiroCredentials]) -> anyhow::Result<()> {
+        validate_batch(credentials, true)?;
+
+        let mut conn = self.conn.clone();
+        let existing = self.ordered_ids(&mut conn).await?;
+        // 超出上限而未加载的凭据不在本批中，不视为删除（按 ID 判断，不受删除后顺序变化影响）
+        let mut keep: HashSet<u64> = credentials.iter().filter_map(|c| c.id).collect();
+        if self.max_credentials.is_some() {
+            let loaded_ids = self.loaded_ids.lock();
+            keep.extend(existing.iter().filter(|id| !loaded_ids.contains(id)));
+        }
+
+        // 带删除标记的凭据不由整批回写恢复：删除前排队的回写可能晚于删除执行
+        let ids: Vec<u64> = credentials.iter().filter_map(|c| c.id).collect();
+        let tombstones: Vec<Option<String>> = if ids.is_empty() {
+            Vec::new()
+        } else {
+            let keys: Vec<String> = ids.iter().map(|id| self.tombstone_key(*id)).collect();
+            conn.mget(keys).await?
+        };
+        let deleted: HashSet<u64> = ids
+            .into_iter()
+            .zip(tombstones)
+            .filter_map(|(id, tombstone)| tombstone.map(|_| id))
+            .collect();
+
+        // MULTI/EXEC：整批替换，任一命令失败时不会留下部分写入
+        let score = self.next_update_score(&mut conn).await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for id in existing.into_iter().filter(|id| !keep.contains(id)) {
+            self.queue_delete(&mut pipe, id, score);
+        }
+        for credential in credentials
+            .iter()
+            .filter(|c| c.id.is_none_or(|id| !deleted.contains(&id)))
+        {
+            self.queue_save(&mut pipe, credential, score)?;
+        }
+        self.queue_finish(&mut pipe, score);
+        let () = pipe.query_async(&mut conn).await?;
+        self.loaded_ids
+            .lock()
+            .extend(credentials.iter().filter_map(|c| c.id));
+
+        tracing::debug!("已批量保存 {} 个凭据到 Redis", credentials.len());
+        Ok(())
+    }
+
+    async fn delete(&self, id: u64) -> anyhow::Result<()> {
+        let mut conn = self.conn.clone();
+        let score = self.next_update_score(&mut conn).await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        self.queue_delete(&mut pipe, id, score);
+        self.queue_finish(&mut pipe, score);
+        let () = pipe.query_async(&mut conn).await?;
+
+        tracing::debug!("已从 Redis 删除凭据: id={}", id);
+        Ok(())
+    }
+
+    fn storage_type(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn has_changes_since(&self, since_timestamp: i64) -> anyhow::Result<bool> {
+        // 从未同步过：总是重新加载（更新记录可能已被清理）
+        if since_timestamp <= 0 {
+            return Ok(true);
+        }
+
+        // 同步时间精度为秒，同一秒内的写入也视为变更（内容未变化时由同步管理器的指纹跳过）
+        let mut conn = self.conn.clone();
+        let count: u64 = conn
+            .zcount(self.updated_key(), since_timestamp.saturating_mul(1000), "+inf")
+            .await?;
+        Ok(count > 0)
+    }
+
+    async fn subscribe_changes(&self) -> anyhow::Result<Option<BoxStream<'static, ()>>> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(self.changes_channel()).await?;
+        Ok(Some(pubsub.into_on_message().map(|_| ()).boxed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试用配置：使用随机键前缀，未设置 KIRO_TEST_REDIS_URL 时返回 None（跳过测试）
+    fn test_config() -> Option<RedisConfig> {
+        let url = std::env::var("KIRO_TEST_REDIS_URL").ok()?;
+        Some(RedisConfig {
+            url,
+            key_prefix: format!("kiro-test-{}", uuid::Uuid::new_v4().simple()),
+            tombstone_ttl_secs: 60,
+        })
+    }
+
+    /// 凭据的删除标记（删除时间），不存在或已过期时为 None
+    async fn tombstone(storage: &RedisCredentialStorage, id: u64) -> Option<String> {
+        let mut conn = storage.conn.clone();
+        conn.get(storage.tombstone_key(id)).await.unwrap()
+    }
+
+    fn credential(id: u64, priority: u32) -> KiroCredentials {
+        KiroCredentials {
+            id: Some(id),
+            refresh_token: Some(format!("refresh-{}", id)),
+            priority,
+            tags: vec!["team-a".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hash_fields_round_trip() {
+        let mut original = credential(7, 3);
+        original.plan_cost = Some(19.5);
+        original.monthly_usage = Some(crate::kiro::model::credentials::MonthlyUsage {
+            period: "2026-01".to_string(),
+            tokens: 42,
+        });
+
+        let fields: HashMap<String, String> =
+            credential_to_fields(&original).unwrap().into_iter().collect();
+        assert!(!fields.contains_key("accessToken"));
+        assert_eq!(fields["refreshToken"], "\"refresh-7\"");
+
+        let restored = fields_to_credential(fields).unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&original).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_order_changes_and_tombstone() {
+        let Some(config) = test_config() else {
+            return;
+        };
+        let storage = RedisCredentialStorage::new(&config).await.unwrap();
+        let mut changes = storage.subscribe_changes().await.unwrap().unwrap();
+
+        storage
+            .save_all(&[credential(3, 0), credential(1, 5), credential(2, 0)])
+            .await
+            .unwrap();
+        let ids: Vec<u64> = storage
+            .load_all()
+            .await
+            .unwrap()
+            .iter()
+            .filter_map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![2, 3, 1]);
+        tokio::time::timeout(std::time::Duration::from_secs(2), changes.next())
+            .await
+            .expect("未收到变更通知");
+
+        let since = chrono::Utc::now().timestamp() + 1;
+        assert!(!storage.has_changes_since(since).await.unwrap());
+
+        storage.delete(3).await.unwrap();
+        assert!(storage.has_changes_since(since - 1).await.unwrap());
+        assert!(tombstone(&storage, 3).await.is_some());
+        let ids: Vec<u64> = storage
+            .load_all()
+            .await
+            .unwrap()
+            .iter()
+            .filter_map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![2, 1]);
+
+        // 删除前取得的快照在删除之后才整批回写，不会让已删除的凭据复活
+        storage
+            .save_all(&[credential(3, 0), credential(1, 5), credential(2, 0)])
+            .await
+            .unwrap();
+        assert_eq!(storage.load_all().await.unwrap().len(), 2);
+        assert!(tombstone(&storage, 3).await.is_some());
+
+        // 重新保存被删除的凭据会清除删除标记
+        storage.save(&credential(3, 1)).await.unwrap();
+        assert!(tombstone(&storage, 3).await.is_none());
+        assert_eq!(storage.load_all().await.unwrap().len(), 3);
+
+        // save_all 替换整批：未包含的凭据被删除
+        storage.save_all(&[credential(1, 0)]).await.unwrap();
+        assert_eq!(storage.load_all().await.unwrap().len(), 1);
+        assert!(tombstone(&storage, 2).await.is_some());
+
+        let mut conn = storage.conn.clone();
+        let keys: Vec<String> = conn.keys(format!("{}:*", config.key_prefix)).await.unwrap();
+        if !keys.is_empty() {
+            let () = conn.del(keys).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_all_keeps_credentials_over_max() {
+        let Some(config) = test_config() else {
+            return;
+        };
+        let storage = RedisCredentialStorage::new(&config).await.unwrap();
+        storage
+            .save_all(&[credential(1, 0), credential(2, 1), credential(3, 2)])
+            .await
+            .unwrap();
+
+        let capped = RedisCredentialStorage::new(&config)
+            .await
+            .unwrap()
+            .with_max_credentials(Some(2));
+        let mut loaded = capped.load_all().await.unwrap();
+        assert_eq!(loaded.len(), 2);
+        loaded[0].priority = 9;
+        capped.save_all(&loaded).await.unwrap();
+
+        // 超出上限而未加载的凭据 3 不会被删除
+        let ids: Vec<u64> = storage
+            .load_all()
+            .await
+            .unwrap()
+            .iter()
+            .filter_map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![2, 3, 1]);
+        assert!(tombstone(&storage, 3).await.is_none());
+
+        // 删除已加载的凭据后，未加载的凭据排到上限以内，仍不视为删除
+        capped.delete(2).await.unwrap();
+        loaded.retain(|c| c.id != Some(2));
+        capped.save_all(&loaded).await.unwrap();
+        let ids: Vec<u64> = storage
+            .load_all()
+            .await
+            .unwrap()
+            .iter()
+            .filter_map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![3, 1]);
+
+        let mut conn = storage.conn.clone();
+        let keys: Vec<String> = conn.keys(format!("{}:*", config.key_prefix)).await.unwrap();
+        if !keys.is_empty() {
+            let () = conn.del(keys).await.unwrap();
+        }
+    }
+}
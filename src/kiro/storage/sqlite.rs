@@ -0,0 +1,525 @@
+//! SQLite 凭据存储实现
+//!
+//! 需要启用 `sqlite` feature。表结构与 PostgreSQL 实现一致（含 `updated_at`、`deleted_at` 软删除），
+//! 时间列以 Unix 毫秒整数存储，`tags` 以 JSON 数组文本存储。
+//! 数据库以 WAL 模式打开，读写互不阻塞，适合单机部署
+
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::{
+    Row, SqlitePool,
+    sqlite::{
+        SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow, SqliteSynchronous,
+    },
+};
+
+use crate::kiro::model::credentials::{KiroCredentials, MonthlyUsage};
+use crate::model::config::SqliteConfig;
+
+use super::sanitize::SanitizedError;
+use super::traits::{
+    CredentialStorage, PoolStats, StorageHealth, apply_max_credentials, validate_batch,
+};
+
+/// 凭据表名
+const TABLE_NAME: &str = "kiro_credentials";
+
+/// 写锁被其他连接占用时的最长等待时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 创建凭据表的 SQL（与 PostgreSQL 的 `CREATE_TABLE_SQL` 对应，类型按 SQLite 调整）
+pub const CREATE_TABLE_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS kiro_credentials (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    access_token    TEXT,
+    refresh_token   TEXT NOT NULL,
+    profile_arn     TEXT,
+    expires_at      TEXT,
+    auth_method     TEXT DEFAULT 'social',
+    client_id       TEXT,
+    client_secret   TEXT,
+    priority        INTEGER DEFAULT 0,
+    region          TEXT,
+    machine_id      TEXT,
+    monthly_token_limit  INTEGER,
+    monthly_usage_period TEXT,
+    monthly_usage_tokens INTEGER,
+    plan_cost       REAL,
+    quota_exhausted_until TEXT,
+    tags            TEXT,
+    proxy_url       TEXT,
+    proxy_username  TEXT,
+    proxy_password  TEXT,
+    weight          INTEGER,
+    created_at      INTEGER DEFAULT (CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)),
+    updated_at      INTEGER DEFAULT (CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)),
+    deleted_at      INTEGER,
+    CONSTRAINT valid_auth_method CHECK (auth_method IN ('social', 'idc', 'builder-id'))
+)
+"#;
+
+/// 索引 SQL（每条语句单独执行）
+const INDEX_SQLS: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_credentials_priority ON kiro_credentials(priority) WHERE deleted_at IS NULL",
+    "CREATE INDEX IF NOT EXISTS idx_credentials_updated_at ON kiro_credentials(updated_at)",
+];
+
+/// 按优先级查询未删除凭据的 SQL（`?1` 为 LIMIT，-1 表示不限制）
+const SELECT_SQL: &str = r#"
+SELECT
+    id, access_token, refresh_token, profile_arn, expires_at,
+    auth_method, client_id, client_secret, priority, region, machine_id,
+    monthly_token_limit, monthly_usage_period, monthly_usage_tokens, plan_cost,
+    quota_exhausted_until, tags, proxy_url, proxy_username, proxy_password, weight
+FROM kiro_credentials
+WHERE deleted_at IS NULL
+ORDER BY priority ASC, id ASC
+LIMIT ?1
+"#;
+
+/// 插入或更新凭据的 SQL（`?23` 为是否恢复已软删除的凭据）
+const UPSERT_SQL: &str = r#"
+INSERT INTO kiro_credentials (id, access_token, refresh_token, profile_arn, expires_at,
+                              auth_method, client_id, client_secret, priority, region, machine_id,
+                              monthly_token_limit, monthly_usage_period, monthly_usage_tokens,
+                              plan_cost, quota_exhausted_until, tags, created_at, updated_at,
+                              proxy_url, proxy_username, proxy_password, weight)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?18,
+        ?19, ?20, ?21, ?22)
+ON CONFLICT (id) DO UPDATE SET
+    access_token = excluded.access_token,
+    refresh_token = excluded.refresh_token,
+    profile_arn = excluded.profile_arn,
+    expires_at = excluded.expires_at,
+    auth_method = excluded.auth_method,
+    client_id = excluded.client_id,
+    client_secret = excluded.client_secret,
+    priority = excluded.priority,
+    region = excluded.region,
+    machine_id = excluded.machine_id,
+    monthly_token_limit = excluded.monthly_token_limit,
+    monthly_usage_period = excluded.monthly_usage_period,
+    monthly_usage_tokens = excluded.monthly_usage_tokens,
+    plan_cost = excluded.plan_cost,
+    quota_exhausted_until = excluded.quota_exhausted_until,
+    tags = excluded.tags,
+    proxy_url = excluded.proxy_url,
+    proxy_username = excluded.proxy_username,
+    proxy_password = excluded.proxy_password,
+    weight = excluded.weight,
+    updated_at = excluded.updated_at,
+    deleted_at = CASE WHEN ?23 THEN NULL ELSE deleted_at END
+"#;
+
+/// SQLite 凭据存储
+pub struct SqliteCredentialStorage {
+    /// 数据库连接池
+    pool: SqlitePool,
+    /// 上次同步时间戳（Unix 秒）
+    last_sync: AtomicI64,
+    /// 最多加载的凭据数量（None 表示不限制）
+    max_credentials: Option<usize>,
+}
+
+impl SqliteCredentialStorage {
+    /// 创建 SQLite 存储实例
+    ///
+    /// 数据库文件不存在时自动创建，以 WAL 模式打开并自动创建凭据表
+    pub async fn new(config: &SqliteConfig) -> anyhow::Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(Path::new(&config.database_path))
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .busy_timeout(BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections.max(1))
+            .connect_with(options)
+            .await?;
+
+        let storage = Self {
+            pool,
+            last_sync: AtomicI64::new(0),
+            max_credentials: None,
+        };
+        storage.ensure_credentials_table().await?;
+
+        tracing::info!(
+            "SQLite 数据库已打开: {}，最大连接数: {}",
+            config.database_path,
+            config.max_connections
+        );
+
+        Ok(storage)
+    }
+
+    /// 设置最多加载的凭据数量
+    ///
+    /// 设置后 `load_all` 会通过 `LIMIT` 只读取优先级最高的前 N 个凭据
+    pub fn with_max_credentials(mut self, max_credentials: Option<usize>) -> Self {
+        self.max_credentials = max_credentials;
+        self
+    }
+
+    /// 确保凭据表和索引存在
+    async fn ensure_credentials_table(&self) -> anyhow::Result<()> {
+        sqlx::query(CREATE_TABLE_SQL).execute(&self.pool).await?;
+        for sql in INDEX_SQLS {
+            sqlx::query(sql).execute(&self.pool).await?;
+        }
+
+        tracing::info!("凭据表 {} 已就绪", TABLE_NAME);
+        Ok(())
+    }
+
+    /// 更新最后同步时间
+    pub fn update_last_sync(&self) {
+        let now = chrono::Utc::now().timestamp();
+        self.last_sync.store(now, Ordering::Relaxed);
+    }
+
+    /// 获取最后同步时间
+    pub fn last_sync_timestamp(&self) -> i64 {
+        self.last_sync.load(Ordering::Relaxed)
+    }
+
+    /// 统计未删除的凭据数量
+    async fn count_active(&self) -> anyhow::Result<usize> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS count FROM kiro_credentials WHERE deleted_at IS NULL",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let count: i64 = row.get("count");
+        Ok(count.max(0) as usize)
+    }
+
+    /// 在指定连接上插入或更新凭据
+    ///
+    /// `restore` 为 true 时清除软删除标记（显式保存单个凭据）；整批回写时保留标记，
+    /// 避免删除前排队的回写让已删除的凭据复活
+    async fn upsert<'e, E>(
+        executor: E,
+        credential: &KiroCredentials,
+        restore: bool,
+    ) -> anyhow::Result<()>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let tags = if credential.tags.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&credential.tags)?)
+        };
+
+        sqlx::query(UPSERT_SQL)
+            .bind(credential.id.map(|id| id as i64))
+            .bind(&credential.access_token)
+            .bind(&credential.refresh_token)
+            .bind(&credential.profile_arn)
+            .bind(normalize_timestamp(&credential.expires_at))
+            .bind(&credential.auth_method)
+            .bind(&credential.client_id)
+            .bind(&credential.client_secret)
+            .bind(credential.priority as i64)
+            .bind(&credential.region)
+            .bind(&credential.machine_id)
+            .bind(credential.monthly_token_limit.map(|limit| limit as i64))
+            .bind(credential.monthly_usage.as_ref().map(|usage| usage.period.clone()))
+            .bind(credential.monthly_usage.as_ref().map(|usage| usage.tokens as i64))
+            .bind(credential.plan_cost)
+            .bind(normalize_timestamp(&credential.quota_exhausted_until))
+            .bind(tags)
+            .bind(now_millis())
+            .bind(&credential.proxy_url)
+            .bind(&credential.proxy_username)
+            .bind(&credential.proxy_password)
+            .bind(credential.weight.map(|weight| weight as i64))
+            .bind(restore)
+            .execute(executor)
+            .await?;
+        Ok(())
+    }
+}
+
+/// 当前时间（Unix 毫秒）
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// 规范化 RFC3339 时间字符串（无效时视为未设置，与 PostgreSQL 实现一致）
+fn normalize_timestamp(value: &Option<String>) -> Option<String> {
+    value
+        .as_ref()
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&chrono::Utc).to_rfc3339())
+}
+
+/// 将查询行转换为凭据
+fn row_to_credentials(row: &SqliteRow) -> KiroCredentials {
+    let id: i64 = row.get("id");
+    KiroCredentials {
+        id: Some(id as u64),
+        access_token: row.get("access_token"),
+        refresh_token: row.get("refresh_token"),
+        profile_arn: row.get("profile_arn"),
+        expires_at: row.get("expires_at"),
+        auth_method: row.get("auth_method"),
+        client_id: row.get("client_id"),
+        client_secret: row.get("client_secret"),
+        priority: row.get::<Option<i64>, _>("priority").unwrap_or(0).max(0) as u32,
+        region: row.get("region"),
+        machine_id: row.get("machine_id"),
+        monthly_token_limit: row
+            .get::<Option<i64>, _>("monthly_token_limit")
+            .map(|limit| limit.max(0) as u64),
+        monthly_usage: row
+            .get::<Option<String>, _>("monthly_usage_period")
+            .map(|period| MonthlyUsage {
+                period,
+                tokens: row
+                    .get::<Option<i64>, _>("monthly_usage_tokens")
+                    .unwrap_or(0)
+                    .max(0) as u64,
+            }),
+        plan_cost: row.get("plan_cost"),
+        quota_exhausted_until: row.get("quota_exhausted_until"),
+        tags: row
+            .get::<Option<String>, _>("tags")
+            .and_then(|tags| serde_json::from_str(&tags).ok())
+            .unwrap_or_default(),
+        proxy_url: row.get("proxy_url"),
+        proxy_username: row.get("proxy_username"),
+        proxy_password: row.get("proxy_password"),
+        weight: row
+            .get::<Option<i64>, _>("weight")
+            .map(|weight| weight.max(0) as u32),
+    }
+}
+
+#[async_trait]
+impl CredentialStorage for SqliteCredentialStorage {
+    async fn load_all(&self) -> anyhow::Result<Vec<KiroCredentials>> {
+        // 多取一行用于判断是否发生截断
+        let limit = self.max_credentials.map_or(-1, |max| max as i64 + 1);
+
+        let rows = sqlx::query(SELECT_SQL)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let credentials: Vec<KiroCredentials> = rows.iter().map(row_to_credentials).collect();
+        let credentials = apply_max_credentials(credentials, self.max_credentials);
+
+        self.update_last_sync();
+        tracing::info!("从 SQLite 加载了 {} 个凭据", credentials.len());
+
+        Ok(credentials)
+    }
+
+    async fn save(&self, credential: &KiroCredentials) -> anyhow::Result<()> {
+        Self::upsert(&self.pool, credential, true).await?;
+
+        tracing::debug!("已保存凭据到 SQLite: id={:?}", credential.id);
+        Ok(())
+    }
+
+    async fn save_all(&self, credentials: &[KiroCredentials]) -> anyhow::Result<()> {
+        // 先校验整批凭据，避免单个无效凭据导致事务中途回滚
+        validate_batch(credentials, true)?;
+
+        // 使用事务批量保存（任一语句失败时整体回滚）
+        let mut tx = self.pool.begin().await?;
+        for credential in credentials {
+            Self::upsert(&mut *tx, credential, false).await?;
+        }
+        tx.commit().await?;
+
+        tracing::debug!("已批量保存 {} 个凭据到 SQLite", credentials.len());
+        Ok(())
+    }
+
+    async fn delete(&self, id: u64) -> anyhow::Result<()> {
+        // 软删除
+        let now = now_millis();
+        sqlx::query("UPDATE kiro_credentials SET deleted_at = ?1, updated_at = ?1 WHERE id = ?2")
+            .bind(now)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+
+        tracing::debug!("已从 SQLite 删除凭据: id={}", id);
+        Ok(())
+    }
+
+    fn storage_type(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn has_changes_since(&self, since_timestamp: i64) -> anyhow::Result<bool> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS count FROM kiro_credentials WHERE updated_at > ?1 OR deleted_at > ?1",
+        )
+        .bind(since_timestamp.saturating_mul(1000))
+        .fetch_one(&self.pool)
+        .await?;
+
+        let count: i64 = row.get("count");
+        Ok(count > 0)
+    }
+
+    async fn health_detail(&self) -> StorageHealth {
+        let pool = PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max_connections: self.pool.options().get_max_connections(),
+        };
+        let last_sync = self.last_sync_timestamp();
+        let last_sync_age_secs =
+            (last_sync > 0).then(|| chrono::Utc::now().timestamp() - last_sync);
+
+        let (credential_count, error) = match self.count_active().await {
+            Ok(count) => (Some(count), None),
+            Err(e) => (None, Some(SanitizedError(&e).to_string())),
+        };
+
+        StorageHealth {
+            backend: self.storage_type(),
+            reachable: error.is_none(),
+            writable: self.is_writable(),
+            credential_count,
+            last_sync_age_secs,
+            pool: Some(pool),
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn open(dir: &tempfile::TempDir, max_connections: u32) -> SqliteCredentialStorage {
+        let config = SqliteConfig {
+            database_path: dir.path().join("credentials.db").display().to_string(),
+            max_connections,
+        };
+        SqliteCredentialStorage::new(&config).await.unwrap()
+    }
+
+    fn credential(id: u64, priority: u32) -> KiroCredentials {
+        KiroCredentials {
+            id: Some(id),
+            refresh_token: Some(format!("refresh-{}", id)),
+            priority,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_order_and_soft_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open(&dir, 2).await;
+
+        let mut first = credential(3, 0);
+        first.expires_at = Some("2030-01-01T08:00:00+08:00".to_string());
+        first.tags = vec!["team-a".to_string()];
+        first.monthly_usage = Some(MonthlyUsage {
+            period: "2026-01".to_string(),
+            tokens: 42,
+        });
+        storage
+            .save_all(&[first, credential(1, 5), credential(2, 0)])
+            .await
+            .unwrap();
+
+        let loaded = storage.load_all().await.unwrap();
+        assert_eq!(
+            loaded.iter().filter_map(|c| c.id).collect::<Vec<_>>(),
+            vec![2, 3, 1]
+        );
+        assert_eq!(loaded[1].expires_at.as_deref(), Some("2030-01-01T00:00:00+00:00"));
+        assert_eq!(loaded[1].tags, vec!["team-a".to_string()]);
+        assert_eq!(loaded[1].monthly_usage.as_ref().unwrap().tokens, 42);
+
+        let since = chrono::Utc::now().timestamp() + 1;
+        assert!(!storage.has_changes_since(since).await.unwrap());
+
+        storage.delete(3).await.unwrap();
+        assert!(storage.has_changes_since(since - 1).await.unwrap());
+        assert_eq!(storage.load_all().await.unwrap().len(), 2);
+        assert_eq!(storage.health_detail().await.credential_count, Some(2));
+
+        // 软删除：行仍保留在表中
+        let row = sqlx::query("SELECT deleted_at FROM kiro_credentials WHERE id = 3")
+            .fetch_one(&storage.pool)
+            .await
+            .unwrap();
+        assert!(row.get::<Option<i64>, _>("deleted_at").is_some());
+
+        // 重新保存软删除的 ID 后恢复可见
+        storage.save(&credential(3, 1)).await.unwrap();
+        assert_eq!(storage.load_all().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_pending_save_all_does_not_restore_deleted_credential() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open(&dir, 1).await;
+        storage
+            .save_all(&[credential(1, 0), credential(2, 1)])
+            .await
+            .unwrap();
+
+        // 删除前取得的快照在删除之后才回写
+        let snapshot = storage.load_all().await.unwrap();
+        storage.delete(2).await.unwrap();
+        storage.save_all(&snapshot).await.unwrap();
+
+        let ids: Vec<u64> = storage
+            .load_all()
+            .await
+            .unwrap()
+            .iter()
+            .filter_map(|c| c.id)
+            .collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_wal_allows_concurrent_writers_and_readers() {
+        let dir = tempfile::tempdir().unwrap();
+        // 两个独立的存储实例打开同一个数据库文件
+        let writer = std::sync::Arc::new(open(&dir, 4).await);
+        let reader = open(&dir, 4).await;
+
+        let journal_mode: String = sqlx::query("PRAGMA journal_mode")
+            .fetch_one(&writer.pool)
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(journal_mode.to_ascii_lowercase(), "wal");
+
+        let writes = (1..=50u64).map(|id| {
+            let writer = writer.clone();
+            tokio::spawn(async move { writer.save(&credential(id, (id % 3) as u32)).await })
+        });
+        let reads = (0..10).map(|_| reader.load_all());
+
+        let (writes, reads) =
+            tokio::join!(futures::future::join_all(writes), futures::future::join_all(reads));
+        for result in writes {
+            result.unwrap().unwrap();
+        }
+        for result in reads {
+            result.unwrap();
+        }
+
+        let loaded = reader.load_all().await.unwrap();
+        assert_eq!(loaded.len(), 50);
+        assert!(loaded.windows(2).all(|w| (w[0].priority, w[0].id) <= (w[1].priority, w[1].id)));
+    }
+}
@@ -0,0 +1,112 @@
+//! 凭据存储类型解析
+
+use std::fmt;
+
+/// 支持的存储类型取值
+pub const SUPPORTED_STORAGE_TYPES: &[&str] = &["file", "postgres", "redis", "sqlite", "mysql"];
+
+/// 凭据存储后端类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageType {
+    /// 文件存储（默认）
+    File,
+    /// PostgreSQL 存储（需要启用 postgres feature）
+    Postgres,
+    /// Redis 存储（需要启用 redis feature）
+    Redis,
+    /// SQLite 存储（需要启用 sqlite feature）
+    Sqlite,
+    /// MySQL/MariaDB 存储（需要启用 mysql feature）
+    Mysql,
+}
+
+/// 无法识别的存储类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedStorageTypeError {
+    /// 配置中的原始取值
+    pub value: String,
+}
+
+impl fmt::Display for UnsupportedStorageTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "不支持的凭据存储类型: \"{}\"（支持: {}）",
+            self.value,
+            SUPPORTED_STORAGE_TYPES.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedStorageTypeError {}
+
+impl StorageType {
+    /// 解析配置中的存储类型
+    ///
+    /// 空字符串与 "file" 均视为文件存储（向后兼容），大小写不敏感；
+    /// 其他无法识别的取值返回错误，避免拼写错误时静默回退到文件存储
+    pub fn parse(value: &str) -> Result<Self, UnsupportedStorageTypeError> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "file" => Ok(StorageType::File),
+            "postgres" => Ok(StorageType::Postgres),
+            "redis" => Ok(StorageType::Redis),
+            "sqlite" => Ok(StorageType::Sqlite),
+            "mysql" | "mariadb" => Ok(StorageType::Mysql),
+            _ => Err(UnsupportedStorageTypeError {
+                value: value.to_string(),
+            }),
+        }
+    }
+
+    /// 存储类型名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageType::File => "file",
+            StorageType::Postgres => "postgres",
+            StorageType::Redis => "redis",
+            StorageType::Sqlite => "sqlite",
+            StorageType::Mysql => "mysql",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_defaults_to_file() {
+        assert_eq!(StorageType::parse("file").unwrap(), StorageType::File);
+        assert_eq!(StorageType::parse("").unwrap(), StorageType::File);
+        assert_eq!(StorageType::parse("  ").unwrap(), StorageType::File);
+    }
+
+    #[test]
+    fn test_parse_postgres() {
+        assert_eq!(StorageType::parse("postgres").unwrap(), StorageType::Postgres);
+        assert_eq!(StorageType::parse("Postgres").unwrap(), StorageType::Postgres);
+    }
+
+    #[test]
+    fn test_parse_redis() {
+        assert_eq!(StorageType::parse("redis").unwrap(), StorageType::Redis);
+        assert_eq!(StorageType::parse(" REDIS ").unwrap(), StorageType::Redis);
+        assert_eq!(StorageType::parse("sqlite").unwrap(), StorageType::Sqlite);
+    }
+
+    #[test]
+    fn test_parse_mysql_accepts_mariadb_alias() {
+        assert_eq!(StorageType::parse("mysql").unwrap(), StorageType::Mysql);
+        assert_eq!(StorageType::parse("MariaDB").unwrap(), StorageType::Mysql);
+        assert_eq!(StorageType::Mysql.as_str(), "mysql");
+    }
+
+    #[test]
+    fn test_parse_typo_is_explicit_error() {
+        let err = StorageType::parse("postgre").unwrap_err();
+        assert_eq!(err.value, "postgre");
+        let message = err.to_string();
+        assert!(message.contains("\"postgre\""));
+        assert!(message.contains("file, postgres, redis, sqlite, mysql"));
+    }
+}
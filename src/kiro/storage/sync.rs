@@ -1,16 +1,24 @@
 //! 凭据同步管理器
 //!
-//! 定时检查存储后端的凭据变更，并通知监听器
+//! 定时检查存储后端的凭据变更，并通知监听器；
+//! 存储后端支持变更推送（如 Redis pub/sub）时，收到通知后立即同步
 
-use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use futures::StreamExt;
+use futures::stream::BoxStream;
 use parking_lot::Mutex;
-use tokio::time::interval;
+use serde::Serialize;
+use tokio::sync::Notify;
 
 use crate::kiro::model::credentials::KiroCredentials;
 
+use super::sanitize::SanitizedError;
 use super::traits::CredentialStorage;
 
 /// 凭据变更事件
@@ -23,20 +31,126 @@ pub enum CredentialChangeEvent {
 /// 凭据变更回调函数类型
 pub type CredentialChangeCallback = Box<dyn Fn(CredentialChangeEvent) + Send + Sync>;
 
+/// 自动同步失败后的最大退避时长
+const MAX_SYNC_BACKOFF: Duration = Duration::from_secs(600);
+
+/// 同步结果
+#[derive(Debug, Clone)]
+pub struct SyncOutcome {
+    /// 是否检测到变更并重新加载
+    pub changed: bool,
+    /// 同步失败时的错误信息
+    pub error: Option<String>,
+    /// 距离下次自动同步的时长（定时同步禁用时为 None）
+    pub next_auto_sync_in: Option<Duration>,
+}
+
+/// 同步状态（诊断快照使用）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    /// 是否启用定时同步
+    pub enabled: bool,
+    /// 同步间隔（秒）
+    pub interval_secs: u64,
+    /// 存储后端类型
+    pub storage_type: &'static str,
+    /// 上次同步时间（Unix 秒，从未同步时为 None）
+    pub last_sync_at: Option<i64>,
+    /// 自动同步连续失败次数
+    pub consecutive_failures: u32,
+    /// 距离下次自动同步的秒数（定时同步禁用时为 None）
+    pub next_auto_sync_in_secs: Option<u64>,
+}
+
+/// 自动同步退避状态
+///
+/// - 自动同步失败：连续失败次数 +1，按指数退避推迟下次自动同步
+/// - 自动同步成功：重置退避，按正常间隔调度
+/// - 手动同步成功：重置退避，下次自动同步不晚于一个正常间隔
+/// - 手动同步失败：不影响退避状态，不会把自动同步推迟到比定时任务自身更晚
+#[derive(Debug)]
+struct SyncBackoff {
+    /// 正常同步间隔
+    interval: Duration,
+    /// 自动同步连续失败次数
+    consecutive_failures: u32,
+    /// 下次自动同步时间
+    next_auto_sync_at: Instant,
+}
+
+impl SyncBackoff {
+    fn new(interval: Duration, now: Instant) -> Self {
+        Self {
+            interval,
+            consecutive_failures: 0,
+            next_auto_sync_at: now,
+        }
+    }
+
+    /// 按连续失败次数计算退避时长（指数增长，上限 MAX_SYNC_BACKOFF 或同步间隔中较大者）
+    fn backoff_delay(&self) -> Duration {
+        let exponent = self.consecutive_failures.min(16);
+        self.interval
+            .saturating_mul(1u32 << exponent)
+            .min(MAX_SYNC_BACKOFF.max(self.interval))
+    }
+
+    /// 记录一次自动同步结果
+    fn record_auto(&mut self, success: bool, now: Instant) {
+        if success {
+            self.consecutive_failures = 0;
+        } else {
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        }
+        self.next_auto_sync_at = now + self.backoff_delay();
+    }
+
+    /// 记录一次手动同步结果
+    fn record_manual(&mut self, success: bool, now: Instant) {
+        if success {
+            self.consecutive_failures = 0;
+            self.next_auto_sync_at = self.next_auto_sync_at.min(now + self.interval);
+        }
+    }
+
+    /// 调整正常同步间隔，已调度的下次自动同步不晚于按新间隔计算的时间
+    fn set_interval(&mut self, interval: Duration, now: Instant) {
+        self.interval = interval;
+        self.next_auto_sync_at = self.next_auto_sync_at.min(now + self.backoff_delay());
+    }
+
+    /// 跳过一次自动同步（定时同步被临时禁用时），按正常间隔调度下一次
+    fn skip(&mut self, now: Instant) {
+        self.next_auto_sync_at = now + self.interval;
+    }
+
+    /// 距离下次自动同步的时长
+    fn next_in(&self, now: Instant) -> Duration {
+        self.next_auto_sync_at.saturating_duration_since(now)
+    }
+}
+
 /// 凭据同步管理器
 ///
 /// 定时检查存储后端的凭据变更，并通知监听器
 pub struct CredentialSyncManager {
     /// 存储后端
     storage: Arc<dyn CredentialStorage>,
-    /// 同步间隔
-    sync_interval: Duration,
+    /// 同步间隔（秒，可通过 `set_interval` 在运行中调整）
+    sync_interval_secs: AtomicU64,
     /// 是否启用定时同步
     enabled: AtomicBool,
     /// 上次同步时间戳
     last_sync: AtomicI64,
+    /// 上次加载的凭据内容指纹（用于跳过内容未变化的重新加载）
+    last_fingerprint: Mutex<Option<u64>>,
     /// 变更回调
     callbacks: Mutex<Vec<CredentialChangeCallback>>,
+    /// 自动同步退避状态
+    backoff: Mutex<SyncBackoff>,
+    /// 调度变更通知（手动同步提前了下次自动同步时唤醒定时任务）
+    schedule_changed: Notify,
 }
 
 impl CredentialSyncManager {
@@ -48,10 +162,16 @@ impl CredentialSyncManager {
     pub fn new(storage: Arc<dyn CredentialStorage>, sync_interval_secs: u64) -> Self {
         Self {
             storage,
-            sync_interval: Duration::from_secs(sync_interval_secs),
+            sync_interval_secs: AtomicU64::new(sync_interval_secs),
             enabled: AtomicBool::new(sync_interval_secs > 0),
             last_sync: AtomicI64::new(0),
+            last_fingerprint: Mutex::new(None),
             callbacks: Mutex::new(Vec::new()),
+            backoff: Mutex::new(SyncBackoff::new(
+                Duration::from_secs(sync_interval_secs),
+                Instant::now(),
+            )),
+            schedule_changed: Notify::new(),
         }
     }
 
@@ -70,21 +190,91 @@ impl CredentialSyncManager {
         self.enabled.load(Ordering::Relaxed)
     }
 
+    /// 当前同步间隔
+    fn sync_interval(&self) -> Duration {
+        Duration::from_secs(self.sync_interval_secs.load(Ordering::Relaxed))
+    }
+
+    /// 调整定时同步间隔，返回是否已生效
+    ///
+    /// 只能在非零间隔之间调整：启用或禁用定时同步（间隔变为 0 或从 0 变为非零）需要重启。
+    /// 缩短间隔时下次自动同步提前到不晚于新间隔
+    pub fn set_interval(&self, secs: u64) -> bool {
+        let current = self.sync_interval_secs.load(Ordering::Relaxed);
+        if current == secs {
+            return true;
+        }
+        if current == 0 || secs == 0 {
+            return false;
+        }
+
+        self.sync_interval_secs.store(secs, Ordering::Relaxed);
+        self.backoff
+            .lock()
+            .set_interval(Duration::from_secs(secs), Instant::now());
+        self.schedule_changed.notify_one();
+        true
+    }
+
     /// 获取存储后端
     pub fn storage(&self) -> &Arc<dyn CredentialStorage> {
         &self.storage
     }
 
+    /// 当前同步状态
+    pub fn status(&self) -> SyncStatus {
+        let last_sync = self.last_sync.load(Ordering::Relaxed);
+        let backoff = self.backoff.lock();
+        SyncStatus {
+            enabled: self.is_enabled(),
+            interval_secs: self.sync_interval().as_secs(),
+            storage_type: self.storage.storage_type(),
+            last_sync_at: (last_sync > 0).then_some(last_sync),
+            consecutive_failures: backoff.consecutive_failures,
+            next_auto_sync_in_secs: (!self.sync_interval().is_zero() && self.is_enabled())
+                .then(|| backoff.next_in(Instant::now()).as_secs()),
+        }
+    }
+
     /// 手动触发同步
-    pub async fn sync_now(&self) -> anyhow::Result<bool> {
-        self.check_and_sync().await
+    ///
+    /// 独立于自动同步的退避计时：成功时重置退避，失败时不延长自动同步的退避
+    pub async fn sync_now(&self) -> SyncOutcome {
+        let result = self.check_and_sync().await;
+
+        let next_auto_sync_in = {
+            let mut backoff = self.backoff.lock();
+            let before = backoff.next_auto_sync_at;
+            let now = Instant::now();
+            backoff.record_manual(result.is_ok(), now);
+            if backoff.next_auto_sync_at < before {
+                self.schedule_changed.notify_one();
+            }
+            backoff.next_in(now)
+        };
+
+        let (changed, error) = match result {
+            Ok(changed) => (changed, None),
+            Err(e) => {
+                let error = SanitizedError(&e).to_string();
+                tracing::warn!("手动凭据同步失败: {}", error);
+                (false, Some(error))
+            }
+        };
+
+        SyncOutcome {
+            changed,
+            error,
+            next_auto_sync_in: (!self.sync_interval().is_zero() && self.is_enabled())
+                .then_some(next_auto_sync_in),
+        }
     }
 
     /// 启动定时同步任务
     ///
     /// 返回任务句柄，可用于取消任务
     pub fn start_sync_task(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
-        let sync_interval = self.sync_interval;
+        let sync_interval = self.sync_interval();
 
         tokio::spawn(async move {
             if sync_interval.is_zero() {
@@ -97,16 +287,45 @@ impl CredentialSyncManager {
                 sync_interval.as_secs()
             );
 
-            let mut ticker = interval(sync_interval);
+            let mut changes = self.subscribe_changes().await;
+            let push_supported = changes.is_some();
 
             loop {
-                ticker.tick().await;
+                let deadline = self.backoff.lock().next_auto_sync_at;
+                let mut timer_fired = false;
+                tokio::select! {
+                    _ = tokio::time::sleep_until(deadline.into()) => timer_fired = true,
+                    // 手动同步调整了调度时间，重新计算
+                    _ = self.schedule_changed.notified() => continue,
+                    // 存储后端推送了变更通知，立即同步
+                    change = next_change(&mut changes) => {
+                        if change.is_none() {
+                            tracing::warn!("存储变更订阅已断开，回退到定时轮询");
+                            changes = None;
+                            continue;
+                        }
+                    }
+                }
+
+                // 订阅断开后，在定时同步时尝试重新订阅
+                if timer_fired && push_supported && changes.is_none() {
+                    changes = self.subscribe_changes().await;
+                }
 
                 if !self.enabled.load(Ordering::Relaxed) {
+                    self.backoff.lock().skip(Instant::now());
                     continue;
                 }
 
-                match self.check_and_sync().await {
+                let result = self.check_and_sync().await;
+                let next_in = {
+                    let mut backoff = self.backoff.lock();
+                    let now = Instant::now();
+                    backoff.record_auto(result.is_ok(), now);
+                    backoff.next_in(now)
+                };
+
+                match result {
                     Ok(changed) => {
                         if changed {
                             tracing::info!("凭据同步完成，检测到变更");
@@ -115,13 +334,32 @@ impl CredentialSyncManager {
                         }
                     }
                     Err(e) => {
-                        tracing::error!("凭据同步失败: {}", e);
+                        tracing::error!(
+                            "凭据同步失败，{} 秒后重试: {}",
+                            next_in.as_secs(),
+                            SanitizedError(&e)
+                        );
                     }
                 }
             }
         })
     }
 
+    /// 订阅存储后端的变更通知（不支持或订阅失败时返回 None，仅依赖定时轮询）
+    async fn subscribe_changes(&self) -> Option<BoxStream<'static, ()>> {
+        match self.storage.subscribe_changes().await {
+            Ok(Some(changes)) => {
+                tracing::info!("已订阅存储变更通知，变更将立即同步");
+                Some(changes)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!("订阅存储变更通知失败，使用定时轮询: {}", SanitizedError(&e));
+                None
+            }
+        }
+    }
+
     /// 检查并同步变更
     async fn check_and_sync(&self) -> anyhow::Result<bool> {
         let last_sync = self.last_sync.load(Ordering::Relaxed);
@@ -133,22 +371,74 @@ impl CredentialSyncManager {
             return Ok(false);
         }
 
-        // 重新加载所有凭据
+        // 先流式计算凭据指纹，内容未变化时无需把整个凭据池加载到内存
+        let fingerprint = self.stream_fingerprint().await?;
+        let now = chrono::Utc::now().timestamp();
+        if *self.last_fingerprint.lock() == Some(fingerprint) {
+            self.last_sync.store(now, Ordering::Relaxed);
+            return Ok(false);
+        }
+
+        // 重新加载所有凭据（指纹以实际加载的内容为准）
         let credentials = self.storage.load_all().await?;
+        let mut hasher = DefaultHasher::new();
+        for credential in &credentials {
+            hash_credential(&mut hasher, credential)?;
+        }
+        *self.last_fingerprint.lock() = Some(hasher.finish());
 
         // 更新同步时间
-        let now = chrono::Utc::now().timestamp();
         self.last_sync.store(now, Ordering::Relaxed);
 
-        // 通知所有回调
+        // 通知所有回调（单个回调 panic 不影响其他回调和同步任务）
         let event = CredentialChangeEvent::Reloaded(credentials);
         let callbacks = self.callbacks.lock();
-        for callback in callbacks.iter() {
-            callback(event.clone());
+        for (index, callback) in callbacks.iter().enumerate() {
+            let event = event.clone();
+            if let Err(panic) = catch_unwind(AssertUnwindSafe(|| callback(event))) {
+                tracing::error!(
+                    "凭据变更回调 #{} 发生 panic，已跳过: {}",
+                    index,
+                    panic_message(panic.as_ref())
+                );
+            }
         }
 
         Ok(true)
     }
+
+    /// 以流的方式逐个读取凭据并计算内容指纹
+    async fn stream_fingerprint(&self) -> anyhow::Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        let mut credentials = self.storage.stream_all();
+        while let Some(credential) = credentials.next().await {
+            hash_credential(&mut hasher, &credential?)?;
+        }
+        Ok(hasher.finish())
+    }
+}
+
+/// 提取 panic 携带的消息
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<非字符串 panic>")
+}
+
+/// 将单个凭据写入指纹
+fn hash_credential(hasher: &mut DefaultHasher, credential: &KiroCredentials) -> anyhow::Result<()> {
+    hasher.write(&serde_json::to_vec(credential)?);
+    Ok(())
+}
+
+/// 等待下一条变更通知（未订阅时永不返回，订阅断开时返回 None）
+async fn next_change(changes: &mut Option<BoxStream<'static, ()>>) -> Option<()> {
+    match changes {
+        Some(changes) => changes.next().await,
+        None => std::future::pending().await,
+    }
 }
 
 #[cfg(test)]
@@ -201,8 +491,221 @@ mod tests {
         }));
 
         // 首次同步应该触发回调
-        let changed = manager.sync_now().await.unwrap();
-        assert!(changed);
+        let outcome = manager.sync_now().await;
+        assert!(outcome.changed);
+        assert!(outcome.error.is_none());
+        assert!(outcome.next_auto_sync_in.is_some());
         assert_eq!(callback_count.load(Ordering::Relaxed), 1);
     }
+
+    #[tokio::test]
+    async fn test_sync_now_failure_reports_error() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "not json").unwrap();
+
+        let storage = Arc::new(FileCredentialStorage::new(file.path(), true));
+        let manager = CredentialSyncManager::new(storage, 0);
+
+        let outcome = manager.sync_now().await;
+        assert!(!outcome.changed);
+        assert!(outcome.error.is_some());
+        assert!(outcome.next_auto_sync_in.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sync_skips_reload_when_content_unchanged() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"[{{"refreshToken": "t1", "id": 1}}]"#).unwrap();
+
+        let storage = Arc::new(FileCredentialStorage::new(file.path(), true));
+        let manager = CredentialSyncManager::new(storage, 30);
+
+        let callback_count = Arc::new(AtomicUsize::new(0));
+        let count_clone = callback_count.clone();
+        manager.add_callback(Box::new(move |_event| {
+            count_clone.fetch_add(1, Ordering::Relaxed);
+        }));
+
+        assert!(manager.sync_now().await.changed);
+        // 内容未变化：不重新加载
+        assert!(!manager.sync_now().await.changed);
+        assert_eq!(callback_count.load(Ordering::Relaxed), 1);
+
+        std::fs::write(file.path(), r#"[{"refreshToken": "t2", "id": 1}]"#).unwrap();
+        assert!(manager.sync_now().await.changed);
+        assert_eq!(callback_count.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_panicking_callback_does_not_stop_others_or_sync_task() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"[{{"refreshToken": "t1", "id": 1}}]"#).unwrap();
+
+        let storage = Arc::new(FileCredentialStorage::new(file.path(), true));
+        let manager = Arc::new(CredentialSyncManager::new(storage, 1));
+
+        fn failing_callback(_event: CredentialChangeEvent) {
+            panic!("回调故障");
+        }
+        manager.add_callback(Box::new(failing_callback));
+        let callback_count = Arc::new(AtomicUsize::new(0));
+        let count_clone = callback_count.clone();
+        manager.add_callback(Box::new(move |_event| {
+            count_clone.fetch_add(1, Ordering::Relaxed);
+        }));
+
+        let wait_for = |expected: usize| {
+            let callback_count = callback_count.clone();
+            async move {
+                tokio::time::timeout(Duration::from_secs(5), async {
+                    while callback_count.load(Ordering::Relaxed) < expected {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                    }
+                })
+                .await
+                .expect("正常回调未被调用");
+            }
+        };
+
+        let handle = manager.clone().start_sync_task();
+        wait_for(1).await;
+
+        // 回调 panic 后定时同步任务仍在运行，下一次变更照常通知
+        std::fs::write(file.path(), r#"[{"refreshToken": "t2", "id": 1}]"#).unwrap();
+        wait_for(2).await;
+        assert!(!handle.is_finished());
+        assert_eq!(manager.status().consecutive_failures, 0);
+        handle.abort();
+    }
+
+    /// 支持变更推送的文件存储（测试通过发送端模拟其他实例的写入通知）
+    struct PushingStorage {
+        inner: FileCredentialStorage,
+        changes: Mutex<Option<futures::channel::mpsc::UnboundedReceiver<()>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl CredentialStorage for PushingStorage {
+        async fn load_all(&self) -> anyhow::Result<Vec<KiroCredentials>> {
+            self.inner.load_all().await
+        }
+        async fn save(&self, credential: &KiroCredentials) -> anyhow::Result<()> {
+            self.inner.save(credential).await
+        }
+        async fn save_all(&self, credentials: &[KiroCredentials]) -> anyhow::Result<()> {
+            self.inner.save_all(credentials).await
+        }
+        async fn delete(&self, id: u64) -> anyhow::Result<()> {
+            self.inner.delete(id).await
+        }
+        fn storage_type(&self) -> &'static str {
+            "pushing"
+        }
+        async fn subscribe_changes(&self) -> anyhow::Result<Option<BoxStream<'static, ()>>> {
+            Ok(self.changes.lock().take().map(|changes| changes.boxed()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pushed_change_syncs_before_interval() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"[{{"refreshToken": "t1", "id": 1}}]"#).unwrap();
+
+        let (notify, changes) = futures::channel::mpsc::unbounded();
+        let storage = Arc::new(PushingStorage {
+            inner: FileCredentialStorage::new(file.path(), true),
+            changes: Mutex::new(Some(changes)),
+        });
+        // 同步间隔远大于测试时长，只有推送能触发同步
+        let manager = Arc::new(CredentialSyncManager::new(storage, 3600));
+        let callback_count = Arc::new(AtomicUsize::new(0));
+        let count_clone = callback_count.clone();
+        manager.add_callback(Box::new(move |_event| {
+            count_clone.fetch_add(1, Ordering::Relaxed);
+        }));
+
+        let handle = manager.clone().start_sync_task();
+        notify.unbounded_send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while callback_count.load(Ordering::Relaxed) < 1 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("推送的变更未触发同步");
+
+        // 订阅断开后任务继续运行（回退到定时轮询）
+        drop(notify);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+        handle.abort();
+    }
+
+    #[test]
+    fn test_backoff_interleaved_manual_and_auto_syncs() {
+        let interval = Duration::from_secs(60);
+        let t0 = Instant::now();
+        let mut backoff = SyncBackoff::new(interval, t0);
+
+        // 两次自动同步失败：指数退避
+        backoff.record_auto(false, t0);
+        assert_eq!(backoff.next_in(t0), Duration::from_secs(120));
+        backoff.record_auto(false, t0);
+        assert_eq!(backoff.next_in(t0), Duration::from_secs(240));
+
+        // 手动同步失败：不改变退避状态
+        let t1 = t0 + Duration::from_secs(10);
+        backoff.record_manual(false, t1);
+        assert_eq!(backoff.consecutive_failures, 2);
+        assert_eq!(backoff.next_in(t1), Duration::from_secs(230));
+
+        // 手动同步成功：重置退避，下次自动同步不晚于一个正常间隔
+        backoff.record_manual(true, t1);
+        assert_eq!(backoff.consecutive_failures, 0);
+        assert_eq!(backoff.next_in(t1), interval);
+
+        // 随后的自动同步失败从第一级退避重新开始
+        let t2 = t1 + interval;
+        backoff.record_auto(false, t2);
+        assert_eq!(backoff.next_in(t2), Duration::from_secs(120));
+
+        // 手动同步成功不会把已更早的自动同步推迟
+        let mut fresh = SyncBackoff::new(interval, t0);
+        fresh.record_manual(true, t0 + Duration::from_secs(5));
+        assert_eq!(fresh.next_auto_sync_at, t0);
+    }
+
+    #[test]
+    fn test_interval_adjusted_only_between_nonzero_values() {
+        let file = NamedTempFile::new().unwrap();
+        let storage: Arc<dyn CredentialStorage> =
+            Arc::new(FileCredentialStorage::new(file.path(), true));
+        let manager = CredentialSyncManager::new(storage.clone(), 600);
+        assert!(manager.set_interval(60));
+        assert_eq!(manager.status().interval_secs, 60);
+        assert!(!manager.set_interval(0));
+        assert!(!CredentialSyncManager::new(storage, 0).set_interval(60));
+
+        // 缩短间隔时提前下次同步，延长间隔时不推迟已调度的同步
+        let t0 = Instant::now();
+        let mut backoff = SyncBackoff::new(Duration::from_secs(600), t0);
+        backoff.record_auto(true, t0);
+        let t1 = t0 + Duration::from_secs(10);
+        backoff.set_interval(Duration::from_secs(60), t1);
+        assert_eq!(backoff.next_in(t1), Duration::from_secs(60));
+        backoff.set_interval(Duration::from_secs(300), t1);
+        assert_eq!(backoff.next_in(t1), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let interval = Duration::from_secs(60);
+        let t0 = Instant::now();
+        let mut backoff = SyncBackoff::new(interval, t0);
+
+        for _ in 0..20 {
+            backoff.record_auto(false, t0);
+        }
+        assert_eq!(backoff.next_in(t0), MAX_SYNC_BACKOFF);
+    }
 }
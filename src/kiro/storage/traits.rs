@@ -1,9 +1,48 @@
 //! 凭据存储 trait 定义
 
+use std::collections::HashSet;
+use std::fmt;
+
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::Serialize;
 
 use crate::kiro::model::credentials::KiroCredentials;
 
+use super::sanitize::SanitizedError;
+
+/// 存储后端健康详情（用于 `/readyz?verbose=true`）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageHealth {
+    /// 存储类型名称
+    pub backend: &'static str,
+    /// 后端是否可访问
+    pub reachable: bool,
+    /// 是否支持写操作
+    pub writable: bool,
+    /// 凭据数量（后端不可访问时为 None）
+    pub credential_count: Option<usize>,
+    /// 距上次成功同步的秒数（未同步或后端不记录时为 None）
+    pub last_sync_age_secs: Option<i64>,
+    /// 连接池状态（仅 PostgreSQL）
+    pub pool: Option<PoolStats>,
+    /// 访问失败时的错误信息
+    pub error: Option<String>,
+}
+
+/// 连接池状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolStats {
+    /// 当前连接数
+    pub size: u32,
+    /// 空闲连接数
+    pub idle: usize,
+    /// 最大连接数
+    pub max_connections: u32,
+}
+
 /// 凭据存储后端抽象
 ///
 /// 支持多种存储实现：文件、PostgreSQL 等
@@ -14,14 +53,31 @@ pub trait CredentialStorage: Send + Sync {
     /// 返回按优先级排序的凭据列表
     async fn load_all(&self) -> anyhow::Result<Vec<KiroCredentials>>;
 
+    /// 以流的形式逐个读取所有凭据
+    ///
+    /// 顺序与 `load_all` 一致，适合大规模凭据池的增量处理。
+    /// 默认实现包装 `load_all`；PostgreSQL 实现通过游标逐行读取
+    fn stream_all(&self) -> BoxStream<'_, anyhow::Result<KiroCredentials>> {
+        stream::once(self.load_all())
+            .flat_map(|result| match result {
+                Ok(credentials) => stream::iter(credentials).map(Ok).left_stream(),
+                Err(e) => stream::once(async move { Err(e) }).right_stream(),
+            })
+            .boxed()
+    }
+
     /// 保存单个凭据（更新或插入）
     ///
-    /// 如果凭据已存在（根据 id），则更新；否则插入新凭据
+    /// 如果凭据已存在（根据 id），则更新；否则插入新凭据。
+    /// 支持软删除的实现在此恢复已删除的同 ID 凭据（显式添加或重新添加）
     async fn save(&self, credential: &KiroCredentials) -> anyhow::Result<()>;
 
     /// 批量保存凭据
     ///
-    /// 替换所有现有凭据
+    /// 替换所有现有凭据。实现须保证全有或全无：写入前先用 `validate_batch` 校验整批凭据，
+    /// 任一凭据无效时返回 `BatchValidationError` 且不写入任何数据；
+    /// 写入中途失败时原有数据保持不变。
+    /// 支持软删除的实现不恢复已删除的凭据：后台回写的快照可能早于删除，恢复会让已删除的凭据复活
     async fn save_all(&self, credentials: &[KiroCredentials]) -> anyhow::Result<()>;
 
     /// 删除凭据
@@ -38,8 +94,171 @@ pub trait CredentialStorage: Send + Sync {
     /// 检查是否有变更（用于定时同步）
     ///
     /// 默认实现返回 true，表示总是需要重新加载
-    /// PostgreSQL 实现可以通过 updated_at 字段优化
+    /// PostgreSQL 实现可以通过 updated_at 字段优化，文件实现比较文件修改时间
     async fn has_changes_since(&self, _since_timestamp: i64) -> anyhow::Result<bool> {
         Ok(true)
     }
+
+    /// 订阅存储变更通知（用于亚秒级同步）
+    ///
+    /// 默认实现返回 None，表示不支持推送，仅依赖定时轮询；
+    /// Redis 实现通过 pub/sub 在其他实例写入后立即通知
+    async fn subscribe_changes(&self) -> anyhow::Result<Option<BoxStream<'static, ()>>> {
+        Ok(None)
+    }
+
+    /// 获取存储健康详情
+    ///
+    /// 默认实现通过 `load_all` 统计凭据数量并判断可访问性；
+    /// PostgreSQL 实现额外返回连接池状态和上次同步时间
+    async fn health_detail(&self) -> StorageHealth {
+        let (credential_count, error) = match self.load_all().await {
+            Ok(credentials) => (Some(credentials.len()), None),
+            Err(e) => (None, Some(SanitizedError(&e).to_string())),
+        };
+        StorageHealth {
+            backend: self.storage_type(),
+            reachable: error.is_none(),
+            writable: self.is_writable(),
+            credential_count,
+            last_sync_age_secs: None,
+            pool: None,
+            error,
+        }
+    }
+}
+
+/// 批量保存中未通过校验的单个凭据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCredential {
+    /// 在批次中的下标
+    pub index: usize,
+    /// 凭据 ID
+    pub id: Option<u64>,
+    /// 校验失败原因
+    pub reason: String,
+}
+
+/// 批量保存被拒绝：存在未通过校验的凭据，未写入任何数据
+#[derive(Debug, Clone)]
+pub struct BatchValidationError {
+    pub invalid: Vec<InvalidCredential>,
+}
+
+impl fmt::Display for BatchValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "批量保存被拒绝，{} 个凭据未通过校验", self.invalid.len())?;
+        for (i, item) in self.invalid.iter().enumerate() {
+            let sep = if i == 0 { ": " } else { "; " };
+            match item.id {
+                Some(id) => write!(f, "{}#{}（id={}）{}", sep, item.index, id, item.reason)?,
+                None => write!(f, "{}#{} {}", sep, item.index, item.reason)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BatchValidationError {}
+
+/// 批量保存前校验整批凭据
+///
+/// 收集所有无效凭据一并返回，而不是遇到第一个就停止，便于调用方一次修正。
+/// `require_id` 为 true 时要求每个凭据都有 ID（以 ID 为主键的后端）
+pub fn validate_batch(
+    credentials: &[KiroCredentials],
+    require_id: bool,
+) -> Result<(), BatchValidationError> {
+    let mut seen_ids = HashSet::new();
+    let mut invalid = Vec::new();
+
+    for (index, credential) in credentials.iter().enumerate() {
+        let reason = if require_id && credential.id.is_none() {
+            Some("缺少 id".to_string())
+        } else if credential.id.is_some_and(|id| !seen_ids.insert(id)) {
+            Some("id 重复".to_string())
+        } else {
+            validate_credential(credential).err()
+        };
+
+        if let Some(reason) = reason {
+            invalid.push(InvalidCredential {
+                index,
+                id: credential.id,
+                reason,
+            });
+        }
+    }
+
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(BatchValidationError { invalid })
+    }
+}
+
+/// 校验单个凭据的字段，返回失败原因
+///
+/// 与 `validate_batch` 使用相同的规则，供添加、导入凭据等入口在写入前校验
+pub fn validate_credential(credential: &KiroCredentials) -> Result<(), String> {
+    if credential.refresh_token.is_none() && credential.access_token.is_none() {
+        Err("缺少 refreshToken 和 accessToken".to_string())
+    } else if credential
+        .expires_at
+        .as_deref()
+        .is_some_and(|s| chrono::DateTime::parse_from_rfc3339(s).is_err())
+    {
+        Err("expiresAt 不是合法的 RFC3339 时间".to_string())
+    } else if credential
+        .plan_cost
+        .is_some_and(|cost| !cost.is_finite() || cost < 0.0)
+    {
+        Err("planCost 必须为非负数".to_string())
+    } else if credential.weight == Some(0) {
+        Err("weight 必须大于 0".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// 按上限截断凭据列表
+///
+/// 输入需已按优先级排序，截断后保留优先级最高的前 `max` 个。
+/// 发生截断时输出警告日志，便于发现误配置或失控的批量导入。
+pub fn apply_max_credentials(
+    mut credentials: Vec<KiroCredentials>,
+    max: Option<usize>,
+) -> Vec<KiroCredentials> {
+    if let Some(max) = max
+        && credentials.len() > max
+    {
+        tracing::warn!(
+            "凭据数量 {} 超过上限 max_credentials={}，仅保留优先级最高的 {} 个",
+            credentials.len(),
+            max,
+            max
+        );
+        credentials.truncate(max);
+    }
+    credentials
+}
+
+/// 超出上限而未被加载的凭据
+///
+/// `stored` 为存储中的完整凭据列表，`loaded_ids` 为本实例加载或写入过的凭据 ID，
+/// 返回 ID 不在其中且不在 `batch` 中的凭据。整批替换的 `save_all` 需原样保留这些凭据，
+/// 否则上限截断的凭据会在下一次回写时被删除。按 ID 而非位置判断，
+/// 删除或调整优先级后存储中的顺序变化不会影响结果；没有 ID 的凭据视为已加载
+pub fn unloaded_credentials(
+    stored: Vec<KiroCredentials>,
+    batch: &[KiroCredentials],
+    loaded_ids: &HashSet<u64>,
+) -> Vec<KiroCredentials> {
+    let saved: HashSet<u64> = batch.iter().filter_map(|c| c.id).collect();
+    stored
+        .into_iter()
+        .filter(|c| {
+            c.id.is_some_and(|id| !loaded_ids.contains(&id) && !saved.contains(&id))
+        })
+        .collect()
 }
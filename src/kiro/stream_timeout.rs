@@ -0,0 +1,169 @@
+//! 上游流式响应超时
+//!
+//! 按 `config.stream_first_byte_timeout_secs` 限制收到首个数据块的等待时间，
+//! 按 `config.stream_idle_timeout_secs` 限制相邻数据块之间的间隔；
+//! 只要数据持续到达，不限制流的总时长
+
+use std::fmt;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt, stream};
+
+use crate::model::config::Config;
+
+/// 流式超时类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamTimeoutKind {
+    /// 等待首个数据块超时
+    FirstByte,
+    /// 数据块间隔超时
+    Idle,
+}
+
+/// 上游流式响应超时错误
+#[derive(Debug, Clone)]
+pub struct StreamTimeoutError {
+    pub kind: StreamTimeoutKind,
+    /// 触发的超时时长
+    pub timeout: Duration,
+}
+
+impl fmt::Display for StreamTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            StreamTimeoutKind::FirstByte => write!(
+                f,
+                "上游流式响应在 {} 秒内未返回首个数据块",
+                self.timeout.as_secs_f32()
+            ),
+            StreamTimeoutKind::Idle => write!(
+                f,
+                "上游流式响应超过 {} 秒未返回新数据",
+                self.timeout.as_secs_f32()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StreamTimeoutError {}
+
+/// 流式超时配置（None 表示不限制）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StreamTimeouts {
+    pub first_byte: Option<Duration>,
+    pub idle: Option<Duration>,
+}
+
+impl StreamTimeouts {
+    pub fn from_config(config: &Config) -> Self {
+        let secs = |s: u64| (s > 0).then(|| Duration::from_secs(s));
+        Self {
+            first_byte: secs(config.stream_first_byte_timeout_secs),
+            idle: secs(config.stream_idle_timeout_secs),
+        }
+    }
+}
+
+/// 为上游数据流加上首字节/空闲超时
+///
+/// 超时时产出一个 `StreamTimeoutError` 并结束流；上游错误原样转换为 `anyhow::Error`
+pub fn with_timeouts<S, E>(
+    body: S,
+    timeouts: StreamTimeouts,
+) -> impl Stream<Item = anyhow::Result<Bytes>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<anyhow::Error>,
+{
+    stream::unfold(
+        (body, timeouts, true, false),
+        |(mut body, timeouts, first, finished)| async move {
+            if finished {
+                return None;
+            }
+
+            let (kind, limit) = if first {
+                (StreamTimeoutKind::FirstByte, timeouts.first_byte)
+            } else {
+                (StreamTimeoutKind::Idle, timeouts.idle)
+            };
+            let next = match limit {
+                Some(timeout) => match tokio::time::timeout(timeout, body.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        let error = StreamTimeoutError { kind, timeout };
+                        return Some((Err(error.into()), (body, timeouts, first, true)));
+                    }
+                },
+                None => body.next().await,
+            };
+
+            match next? {
+                Ok(chunk) => Some((Ok(chunk), (body, timeouts, false, false))),
+                Err(e) => Some((Err(e.into()), (body, timeouts, false, true))),
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    fn delayed_chunks(delays_ms: &[u64]) -> impl Stream<Item = Result<Bytes, Infallible>> + Unpin {
+        let delays = delays_ms.to_vec();
+        stream::iter(delays)
+            .then(|ms| async move {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Ok(Bytes::from_static(b"x"))
+            })
+            .boxed()
+    }
+
+    fn timeout_kind(item: &anyhow::Result<Bytes>) -> Option<StreamTimeoutKind> {
+        item.as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<StreamTimeoutError>())
+            .map(|e| e.kind)
+    }
+
+    #[tokio::test]
+    async fn test_slow_first_byte_aborts() {
+        let timeouts = StreamTimeouts {
+            first_byte: Some(Duration::from_millis(100)),
+            idle: Some(Duration::from_secs(30)),
+        };
+        let started = std::time::Instant::now();
+        let items: Vec<_> = with_timeouts(delayed_chunks(&[5_000, 0]), timeouts)
+            .collect()
+            .await;
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(items.len(), 1);
+        assert_eq!(timeout_kind(&items[0]), Some(StreamTimeoutKind::FirstByte));
+    }
+
+    #[tokio::test]
+    async fn test_idle_gap_aborts_but_total_duration_is_unbounded() {
+        let timeouts = StreamTimeouts {
+            first_byte: Some(Duration::from_millis(100)),
+            idle: Some(Duration::from_millis(100)),
+        };
+
+        // 总时长远超各项超时，但每个间隔都在限制内
+        let items: Vec<_> = with_timeouts(delayed_chunks(&[20; 20]), timeouts)
+            .collect()
+            .await;
+        assert_eq!(items.len(), 20);
+        assert!(items.iter().all(|item| item.is_ok()));
+
+        let items: Vec<_> = with_timeouts(delayed_chunks(&[10, 10, 500, 10]), timeouts)
+            .collect()
+            .await;
+        assert_eq!(items.len(), 3);
+        assert!(items[0].is_ok() && items[1].is_ok());
+        assert_eq!(timeout_kind(&items[2]), Some(StreamTimeoutKind::Idle));
+    }
+}
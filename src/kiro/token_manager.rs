@@ -4,21 +4,35 @@
 //! 支持单凭据 (TokenManager) 和多凭据 (MultiTokenManager) 管理
 
 use anyhow::bail;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use chrono::{DateTime, Duration, Utc};
 use parking_lot::Mutex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex as TokioMutex;
+use tokio::sync::broadcast;
 
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use crate::http_client::{ProxyConfig, build_client};
+use crate::kiro::credential_events::{CredentialEvent, EVENT_CHANNEL_CAPACITY, StatsSnapshot};
+use crate::kiro::credential_ref::credential_label;
+use crate::kiro::error_code::{KiroError, KiroErrorCode};
 use crate::kiro::machine_id;
-use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::model::credentials::{KiroCredentials, MonthlyUsage};
+use crate::kiro::monthly_budget::{self, MonthlyBudgetExhaustedError};
 use crate::kiro::model::token_refresh::{
     IdcRefreshRequest, IdcRefreshResponse, RefreshRequest, RefreshResponse,
 };
 use crate::kiro::model::usage_limits::UsageLimitsResponse;
-use crate::model::config::Config;
+use crate::kiro::recent_errors::{RecentError, RecentErrors};
+use crate::kiro::storage::SanitizedError;
+use crate::model::config::{
+    Config, CredentialSelectionMode, MissingProfileArnPolicy, SelectionStrategy,
+};
 
 /// Token 管理器
 ///
@@ -53,7 +67,9 @@ impl TokenManager {
     ///
     /// 如果 Token 过期或即将过期，会自动刷新
     pub async fn ensure_valid_token(&mut self) -> anyhow::Result<String> {
-        if is_token_expired(&self.credentials) || is_token_expiring_soon(&self.credentials) {
+        if is_token_expired(&self.credentials)
+            || is_token_expiring_soon(&self.credentials, self.config.token_refresh_margin_secs)
+        {
             self.credentials =
                 refresh_token(&self.credentials, &self.config, self.proxy.as_ref()).await?;
 
@@ -74,30 +90,217 @@ impl TokenManager {
     /// 调用 getUsageLimits API 查询当前账户的使用额度
     pub async fn get_usage_limits(&mut self) -> anyhow::Result<UsageLimitsResponse> {
         let token = self.ensure_valid_token().await?;
-        get_usage_limits(&self.credentials, &self.config, &token, self.proxy.as_ref()).await
+        get_usage_limits(
+            &self.credentials,
+            &self.config,
+            &token,
+            self.proxy.as_ref(),
+            None,
+        )
+        .await
     }
 }
 
 /// 检查 Token 是否在指定时间内过期
 pub(crate) fn is_token_expiring_within(
     credentials: &KiroCredentials,
-    minutes: i64,
+    window: Duration,
 ) -> Option<bool> {
     credentials
         .expires_at
         .as_ref()
         .and_then(|expires_at| DateTime::parse_from_rfc3339(expires_at).ok())
-        .map(|expires| expires <= Utc::now() + Duration::minutes(minutes))
+        .map(|expires| expires <= Utc::now() + window)
 }
 
 /// 检查 Token 是否已过期（提前 5 分钟判断）
 pub(crate) fn is_token_expired(credentials: &KiroCredentials) -> bool {
-    is_token_expiring_within(credentials, 5).unwrap_or(true)
+    is_token_expiring_within(credentials, Duration::minutes(5)).unwrap_or(true)
+}
+
+/// 检查 Token 是否即将过期（`margin_secs` 秒内，即 `token_refresh_margin_secs`）
+pub(crate) fn is_token_expiring_soon(credentials: &KiroCredentials, margin_secs: u64) -> bool {
+    is_token_expiring_within(credentials, Duration::seconds(margin_secs as i64)).unwrap_or(false)
+}
+
+/// 凭据的有效选择顺序（越小越优先）
+///
+/// 配置 `demote_near_expiry_secs` 后，距 `expires_at` 不足该时长的凭据
+/// 排在其他凭据之后（仍可使用）；`cheapest` 模式下同一组内先按 plan_cost
+/// （未配置的排在最后）排序；启用 `auto_reorder` 后再按最近一次重算的健康分档位排序，
+/// 最后按 priority 排序
+fn effective_priority(entry: &CredentialEntry, config: &Config) -> (bool, u64, u32, u32) {
+    let credentials = &entry.credentials;
+    let near_expiry = config.demote_near_expiry_secs > 0
+        && credentials
+            .expires_at
+            .as_ref()
+            .and_then(|expires_at| DateTime::parse_from_rfc3339(expires_at).ok())
+            .is_some_and(|expires| {
+                expires <= Utc::now() + Duration::seconds(config.demote_near_expiry_secs as i64)
+            });
+    let cost = match config.credential_selection_mode {
+        CredentialSelectionMode::Priority => 0,
+        CredentialSelectionMode::Cheapest => cost_order_key(credentials.plan_cost),
+    };
+    let health_rank = if config.auto_reorder {
+        entry.health.rank
+    } else {
+        0
+    };
+    (near_expiry, cost, health_rank, credentials.priority)
+}
+
+/// 将 plan_cost 映射为可排序的整数键
+///
+/// 非负有限浮点数的位模式与数值大小顺序一致；负数按 0 处理，未配置或 NaN 排在最后
+fn cost_order_key(plan_cost: Option<f64>) -> u64 {
+    match plan_cost {
+        Some(cost) if !cost.is_nan() => cost.max(0.0).to_bits(),
+        _ => u64::MAX,
+    }
+}
+
+/// 健康指标滑动平均的平滑系数（越大越偏向最近的请求）
+const HEALTH_EWMA_ALPHA: f64 = 0.3;
+
+/// 健康分分档数：同一档内按 priority 排序，避免分数小幅波动导致频繁切换凭据
+const HEALTH_SCORE_BUCKETS: f64 = 20.0;
+
+/// 凭据的滚动健康指标（仅运行期统计，不持久化）
+#[derive(Debug, Clone, Copy)]
+struct HealthStats {
+    /// 请求成功率的指数滑动平均（0.0 ~ 1.0）
+    success_rate: f64,
+    /// 成功请求响应延迟的指数滑动平均（毫秒），无样本时为 None
+    latency_ms: Option<f64>,
+    /// 最近一次重算得到的健康分档位（越小越健康）
+    rank: u32,
+}
+
+/// 凭据的累计服务量（仅运行期统计，不持久化，用于指标输出和 Admin API 统计）
+#[derive(Debug, Clone, Copy, Default)]
+struct UsageCounters {
+    /// 被选中的次数（每次成功获取调用上下文计一次）
+    selections: u64,
+    /// 成功完成的请求数
+    requests: u64,
+    /// 失败的上游请求数（每次失败的尝试计一次）
+    failures: u64,
+    /// 消耗的 token 数
+    tokens: u64,
+    /// 输入 token 数
+    input_tokens: u64,
+    /// 输出 token 数
+    output_tokens: u64,
+    /// 最近一次被选中的时间
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl Default for HealthStats {
+    fn default() -> Self {
+        Self {
+            success_rate: 1.0,
+            latency_ms: None,
+            rank: 0,
+        }
+    }
+}
+
+impl HealthStats {
+    /// 记录一次请求结果
+    fn record_outcome(&mut self, success: bool) {
+        let sample = if success { 1.0 } else { 0.0 };
+        self.success_rate += HEALTH_EWMA_ALPHA * (sample - self.success_rate);
+    }
+
+    /// 记录一次成功请求的响应延迟
+    fn record_latency(&mut self, latency: std::time::Duration) {
+        let sample = latency.as_secs_f64() * 1000.0;
+        self.latency_ms = Some(match self.latency_ms {
+            Some(avg) => avg + HEALTH_EWMA_ALPHA * (sample - avg),
+            None => sample,
+        });
+    }
+}
+
+/// 主动刷新的提前量：距过期不足该分钟数的 Token 在后台提前刷新
+/// （`token_refresh_margin_secs` 更大时以其为准）
+const PROACTIVE_REFRESH_WINDOW_MINUTES: i64 = 30;
+
+/// Token 刷新熔断器（跨凭据统计连续刷新失败）
+#[derive(Debug, Default)]
+struct RefreshBreaker {
+    /// 连续刷新失败次数（任一次成功即清零）
+    consecutive_failures: u32,
+    /// 熔断打开期间暂停主动刷新，直到该时刻
+    open_until: Option<std::time::Instant>,
+}
+
+/// 单个凭据的累计使用统计（Admin API，自启动或上次重置以来）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialStats {
+    /// 凭据 ID
+    pub id: u64,
+    /// 上游请求总数（成功 + 失败）
+    pub requests: u64,
+    /// 成功的请求数
+    pub successes: u64,
+    /// 失败的上游请求数（重试中每次失败的尝试各计一次）
+    pub failures: u64,
+    /// 被选中的次数
+    pub selections: u64,
+    /// 输入 token 数
+    pub input_tokens: u64,
+    /// 输出 token 数
+    pub output_tokens: u64,
+    /// 最近一次被选中的时间（RFC3339，从未使用时为 None）
+    pub last_used_at: Option<String>,
+}
+
+/// Token 刷新熔断器状态（Admin API）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshBreakerStatus {
+    /// 熔断是否打开（打开期间暂停主动刷新，按需刷新不受影响）
+    pub open: bool,
+    /// 连续刷新失败次数
+    pub consecutive_failures: u32,
+    /// 熔断阈值（0 表示禁用熔断）
+    pub threshold: u32,
+    /// 距熔断恢复的剩余秒数（未打开时为 None）
+    pub remaining_secs: Option<u64>,
+}
+
+/// 计算凭据健康分（0.0 ~ 1.0，越大越健康）
+///
+/// 成功率占 60%；延迟占 20%（1 秒以内满分，超过后按比例递减）；
+/// 本月剩余 token 额度占 20%（未配置 `monthly_token_limit` 时满分）
+fn health_score(health: &HealthStats, credentials: &KiroCredentials, period: &str) -> f64 {
+    let latency = health
+        .latency_ms
+        .map(|ms| 1000.0 / ms.max(1000.0))
+        .unwrap_or(1.0);
+    let quota = match credentials.monthly_token_limit {
+        Some(0) => 0.0,
+        Some(limit) => {
+            let used = credentials
+                .monthly_usage
+                .as_ref()
+                .filter(|usage| usage.period == period)
+                .map(|usage| usage.tokens)
+                .unwrap_or(0);
+            1.0 - (used as f64 / limit as f64).min(1.0)
+        }
+        None => 1.0,
+    };
+    0.6 * health.success_rate + 0.2 * latency + 0.2 * quota
 }
 
-/// 检查 Token 是否即将过期（10分钟内）
-pub(crate) fn is_token_expiring_soon(credentials: &KiroCredentials) -> bool {
-    is_token_expiring_within(credentials, 10).unwrap_or(false)
+/// 将健康分映射为排序档位（越小越健康）
+fn health_rank(score: f64) -> u32 {
+    ((1.0 - score.clamp(0.0, 1.0)) * HEALTH_SCORE_BUCKETS).round() as u32
 }
 
 /// 验证 refreshToken 的基本有效性
@@ -131,19 +334,22 @@ pub(crate) async fn refresh_token(
 ) -> anyhow::Result<KiroCredentials> {
     validate_refresh_token(credentials)?;
 
-    // 根据 auth_method 选择刷新方式
-    // 如果未指定 auth_method，根据是否有 clientId/clientSecret 自动判断
-    let auth_method = credentials.auth_method.as_deref().unwrap_or_else(|| {
-        if credentials.client_id.is_some() && credentials.client_secret.is_some() {
-            "idc"
-        } else {
-            "social"
-        }
-    });
+    // 凭据级代理优先于全局代理
+    let proxy = credentials.effective_proxy(proxy);
+    if uses_idc_auth(credentials) {
+        refresh_idc_token(credentials, config, proxy.as_ref()).await
+    } else {
+        refresh_social_token(credentials, config, proxy.as_ref()).await
+    }
+}
 
-    match auth_method.to_lowercase().as_str() {
-        "idc" | "builder-id" => refresh_idc_token(credentials, config, proxy).await,
-        _ => refresh_social_token(credentials, config, proxy).await,
+/// 凭据是否使用 IdC 认证（含 Builder ID）
+///
+/// 未指定 auth_method 时，根据是否有 clientId/clientSecret 自动判断
+fn uses_idc_auth(credentials: &KiroCredentials) -> bool {
+    match credentials.auth_method.as_deref() {
+        Some(auth_method) => matches!(auth_method.to_lowercase().as_str(), "idc" | "builder-id"),
+        None => credentials.client_id.is_some() && credentials.client_secret.is_some(),
     }
 }
 
@@ -301,28 +507,45 @@ async fn refresh_idc_token(
 const USAGE_LIMITS_AMZ_USER_AGENT_PREFIX: &str = "aws-sdk-js/1.0.0";
 
 /// 获取使用额度信息
+///
+/// 按认证方式选择查询端点：
+/// - Social：额度挂在 profileArn 所属的 Kiro profile 上，按 API 调用所用的 `config.region` 查询并携带 profileArn
+/// - IdC / Builder ID：额度属于 IdC 账号，按凭据级 region（未配置时回退到 `config.region`）查询，不携带 profileArn；
+///   响应按 resourceType 返回多条使用量明细，重置时间位于明细中
+///
+/// 两种响应结构由 `UsageLimitsResponse` 统一归一化。`base_url` 覆盖请求地址（测试使用），
+/// `host` 请求头仍按所选 region 生成
 pub(crate) async fn get_usage_limits(
     credentials: &KiroCredentials,
     config: &Config,
     token: &str,
     proxy: Option<&ProxyConfig>,
+    base_url: Option<&str>,
 ) -> anyhow::Result<UsageLimitsResponse> {
     tracing::debug!("正在获取使用额度信息...");
 
-    let region = &config.region;
+    let idc = uses_idc_auth(credentials);
+    let region = if idc {
+        credentials.region.as_ref().unwrap_or(&config.region)
+    } else {
+        &config.region
+    };
     let host = format!("q.{}.amazonaws.com", region);
     let machine_id = machine_id::generate_from_credentials(credentials, config)
         .ok_or_else(|| anyhow::anyhow!("无法生成 machineId"))?;
     let kiro_version = &config.kiro_version;
 
     // 构建 URL
+    let base_url = base_url
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("https://{}", host));
     let mut url = format!(
-        "https://{}/getUsageLimits?origin=AI_EDITOR&resourceType=AGENTIC_REQUEST",
-        host
+        "{}/getUsageLimits?origin=AI_EDITOR&resourceType=AGENTIC_REQUEST",
+        base_url
     );
 
-    // profileArn 是可选的
-    if let Some(profile_arn) = &credentials.profile_arn {
+    // profileArn 仅 Social 凭据携带（可选）
+    if let Some(profile_arn) = credentials.profile_arn.as_ref().filter(|_| !idc) {
         url.push_str(&format!("&profileArn={}", urlencoding::encode(profile_arn)));
     }
 
@@ -337,7 +560,7 @@ pub(crate) async fn get_usage_limits(
         USAGE_LIMITS_AMZ_USER_AGENT_PREFIX, kiro_version, machine_id
     );
 
-    let client = build_client(proxy, 60)?;
+    let client = build_client(credentials.effective_proxy(proxy).as_ref(), 60)?;
 
     let response = client
         .get(&url)
@@ -384,6 +607,166 @@ struct CredentialEntry {
     disabled: bool,
     /// 禁用原因（用于区分手动禁用 vs 自动禁用，便于自愈）
     disabled_reason: Option<DisabledReason>,
+    /// 滚动健康指标（用于 `auto_reorder`）
+    health: HealthStats,
+    /// 累计服务量（用于指标输出）
+    usage: UsageCounters,
+    /// 熔断器状态（`breaker_cooldown_secs` 大于 0 时生效）
+    breaker: CredentialBreaker,
+    /// 选择策略状态（`selection_strategy` 非 priority 时使用）
+    selection: SelectionState,
+}
+
+impl CredentialEntry {
+    /// 替换凭据内容，月度用量保留两者中较新的一份（避免覆盖运行期间累计的用量）
+    fn replace_credentials(&mut self, mut credentials: KiroCredentials) {
+        credentials.monthly_usage = MonthlyUsage::latest(
+            self.credentials.monthly_usage.take(),
+            credentials.monthly_usage.take(),
+        );
+        // 尚未持久化的额度用尽窗口不因同步而丢失
+        if credentials.quota_exhausted_until.is_none() {
+            credentials.quota_exhausted_until = self.credentials.quota_exhausted_until.take();
+        }
+        self.credentials = credentials;
+    }
+}
+
+/// 凭据的选择策略状态（仅运行期，不持久化）
+#[derive(Debug, Default)]
+struct SelectionState {
+    /// 最近一次被选中时的选择序号（least-recently-used 按此选择，None 表示从未被选中）
+    last_used: Option<u64>,
+    /// 平滑加权轮询的当前权重（weighted）
+    current_weight: i64,
+}
+
+/// 凭据的选择权重（未配置时为 1）
+fn selection_weight(entry: &CredentialEntry) -> i64 {
+    entry.credentials.weight.unwrap_or(1).max(1) as i64
+}
+
+/// 按选择策略从候选凭据中选出一个，不修改状态（用于选择模拟）
+///
+/// `candidates` 为可选凭据在 `entries` 中的下标，已按有效选择顺序排列；`seq` 为本次选择序号
+fn choose_by_strategy(
+    entries: &[CredentialEntry],
+    candidates: &[usize],
+    strategy: SelectionStrategy,
+    seq: u64,
+) -> Option<usize> {
+    if candidates.is_empty() {
+        return None;
+    }
+    match strategy {
+        SelectionStrategy::Priority => candidates.first().copied(),
+        SelectionStrategy::RoundRobin => Some(candidates[(seq % candidates.len() as u64) as usize]),
+        // 平滑加权轮询：各凭据累加自身权重后选择累计值最大者（相同时取顺序靠前的）
+        SelectionStrategy::Weighted => candidates.iter().copied().min_by_key(|&i| {
+            std::cmp::Reverse(entries[i].selection.current_weight + selection_weight(&entries[i]))
+        }),
+        // 从未被选中的凭据（None）最先被选择
+        SelectionStrategy::LeastRecentlyUsed => candidates
+            .iter()
+            .copied()
+            .min_by_key(|&i| entries[i].selection.last_used),
+    }
+}
+
+/// 按选择策略从可选凭据中选出一个并更新策略状态，返回其在 `entries` 中的下标
+fn pick_by_strategy(
+    entries: &mut [CredentialEntry],
+    config: &Config,
+    is_eligible: impl Fn(&CredentialEntry) -> bool,
+    seq: u64,
+) -> Option<usize> {
+    let mut candidates: Vec<usize> = (0..entries.len())
+        .filter(|&i| is_eligible(&entries[i]))
+        .collect();
+    candidates.sort_by_key(|&i| effective_priority(&entries[i], config));
+
+    let picked = choose_by_strategy(entries, &candidates, config.selection_strategy, seq)?;
+    if config.selection_strategy == SelectionStrategy::Weighted {
+        let total: i64 = candidates
+            .iter()
+            .map(|&i| selection_weight(&entries[i]))
+            .sum();
+        for &i in &candidates {
+            entries[i].selection.current_weight += selection_weight(&entries[i]);
+        }
+        entries[picked].selection.current_weight -= total;
+    }
+    entries[picked].selection.last_used = Some(seq);
+    Some(picked)
+}
+
+/// 凭据熔断器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// 正常参与选择
+    Closed,
+    /// 冷却中，选择时跳过
+    Open,
+    /// 冷却结束，允许一次试探请求（成功后关闭，失败后重新打开）
+    HalfOpen,
+}
+
+/// 单个凭据的熔断器
+///
+/// 连续失败达到 `breaker_failure_threshold` 后打开，冷却 `breaker_cooldown_secs` 后进入半开状态，
+/// 半开时只放行一个试探请求；试探请求长时间未报告结果（如客户端断开）时，冷却时长后允许再次试探
+#[derive(Debug, Default)]
+struct CredentialBreaker {
+    /// 冷却结束时刻（None 表示关闭）
+    open_until: Option<std::time::Instant>,
+    /// 半开试探请求的开始时刻
+    probe_started: Option<std::time::Instant>,
+    /// 连续被上游限流（429）的次数
+    consecutive_rate_limits: u32,
+}
+
+impl CredentialBreaker {
+    /// 当前状态
+    fn state(&self, now: std::time::Instant) -> BreakerState {
+        match self.open_until {
+            None => BreakerState::Closed,
+            Some(until) if now < until => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// 选择时是否应跳过（冷却中，或半开且已有进行中的试探请求）
+    fn blocks(&self, now: std::time::Instant, cooldown: std::time::Duration) -> bool {
+        match self.state(now) {
+            BreakerState::Closed => false,
+            BreakerState::Open => true,
+            BreakerState::HalfOpen => self
+                .probe_started
+                .is_some_and(|started| now < started + cooldown),
+        }
+    }
+
+    /// 打开熔断器
+    fn open(&mut self, now: std::time::Instant, cooldown: std::time::Duration) {
+        self.open_until = Some(now + cooldown);
+        self.probe_started = None;
+    }
+
+    /// 半开状态下被选中时记录试探请求
+    fn claim_probe(&mut self, now: std::time::Instant) {
+        if self.state(now) == BreakerState::HalfOpen {
+            self.probe_started = Some(now);
+        }
+    }
+
+    /// 冷却剩余秒数（仅打开状态）
+    fn remaining_secs(&self, now: std::time::Instant) -> Option<u64> {
+        self.open_until
+            .and_then(|until| until.checked_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+            .map(|remaining| remaining.as_secs_f64().ceil() as u64)
+    }
 }
 
 /// 禁用原因
@@ -397,6 +780,17 @@ enum DisabledReason {
     QuotaExceeded,
 }
 
+impl DisabledReason {
+    /// 事件中使用的原因名称
+    fn as_str(&self) -> &'static str {
+        match self {
+            DisabledReason::Manual => "manual",
+            DisabledReason::TooManyFailures => "too_many_failures",
+            DisabledReason::QuotaExceeded => "quota_exceeded",
+        }
+    }
+}
+
 // ============================================================================
 // Admin API 公开结构
 // ============================================================================
@@ -419,6 +813,14 @@ pub struct CredentialEntrySnapshot {
     pub has_profile_arn: bool,
     /// Token 过期时间
     pub expires_at: Option<String>,
+    /// 健康分（0.0 ~ 1.0，越大越健康）
+    pub health_score: f64,
+    /// 熔断器状态
+    pub breaker_state: BreakerState,
+    /// 熔断冷却剩余秒数（仅打开状态）
+    pub breaker_remaining_secs: Option<u64>,
+    /// 最近一次被选中的时间（从未使用时为 None）
+    pub last_used_at: Option<DateTime<Utc>>,
 }
 
 /// 凭据管理器状态快照
@@ -429,23 +831,293 @@ pub struct ManagerSnapshot {
     pub entries: Vec<CredentialEntrySnapshot>,
     /// 当前活跃凭据 ID
     pub current_id: u64,
+    /// 固定使用的凭据 ID（未固定时为 None）
+    pub pinned_id: Option<u64>,
     /// 总凭据数量
     pub total: usize,
     /// 可用凭据数量
     pub available: usize,
 }
 
+/// 单次请求的凭据选择选项
+///
+/// 仅影响本次 `acquire_context_with` 调用，不修改全局的当前凭据
+#[derive(Debug, Clone, Default)]
+pub struct AcquireOptions {
+    /// 本次请求需要排除的凭据 ID
+    pub excluded_ids: HashSet<u64>,
+    /// 本次请求的截止时间（provider 据此停止重试和故障转移），None 表示不限制
+    pub deadline: Option<tokio::time::Instant>,
+}
+
+impl AcquireOptions {
+    /// 创建排除指定凭据的选项
+    pub fn excluding(ids: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            excluded_ids: ids.into_iter().collect(),
+            deadline: None,
+        }
+    }
+
+    /// 是否为默认选项（无任何排除）
+    pub fn is_default(&self) -> bool {
+        self.excluded_ids.is_empty()
+    }
+}
+
+/// 排除指定凭据后没有可用凭据
+///
+/// 调用方可通过 `anyhow::Error::downcast_ref` 识别此错误并返回 503
+#[derive(Debug)]
+pub struct NoEligibleCredentialError {
+    /// 本次请求排除的凭据 ID（升序）
+    pub excluded_ids: Vec<u64>,
+}
+
+impl fmt::Display for NoEligibleCredentialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "排除凭据 {:?} 后没有可用凭据", self.excluded_ids)
+    }
+}
+
+impl std::error::Error for NoEligibleCredentialError {}
+
+/// 存储后端尚未完成首次连接（lazy_storage_connect 模式）
+///
+/// 调用方可通过 `anyhow::Error::is` 识别此错误并返回 503
+#[derive(Debug)]
+pub struct StorageUnavailableError;
+
+impl fmt::Display for StorageUnavailableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "凭据存储后端尚未连接，请稍后重试")
+    }
+}
+
+impl std::error::Error for StorageUnavailableError {}
+
+/// 固定凭据（`pinned_credential_id`）当前不可用
+///
+/// 固定凭据期间不会切换到其他凭据，调用方可通过 `anyhow::Error::is` 识别此错误并返回 503
+#[derive(Debug)]
+pub struct PinnedCredentialUnavailableError {
+    /// 固定的凭据 ID
+    pub id: u64,
+    /// 不可用的原因（凭据不存在时为 None）
+    pub reason: Option<SkipReason>,
+}
+
+impl fmt::Display for PinnedCredentialUnavailableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            Some(reason) => write!(f, "固定凭据 #{} 当前不可用（{}）", self.id, reason.as_str()),
+            None => write!(f, "固定凭据 #{} 不存在", self.id),
+        }
+    }
+}
+
+impl std::error::Error for PinnedCredentialUnavailableError {}
+
+/// 凭据选择时跳过凭据的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// 凭据已禁用
+    Disabled,
+    /// 被本次请求排除（`x-kiro-exclude-credentials`）
+    Excluded,
+    /// 本次请求中 Token 刷新失败
+    RefreshFailed,
+    /// 月度 token 预算已用尽
+    QuotaExceeded,
+    /// 缺少 profileArn（`missing_profile_arn_policy` 为 `skip` 时）
+    MissingProfileArn,
+    /// 熔断器打开（冷却中或半开试探进行中）
+    BreakerOpen,
+}
+
+impl SkipReason {
+    /// 所有跳过原因（与计数数组下标一致）
+    pub const ALL: [SkipReason; 6] = [
+        SkipReason::Disabled,
+        SkipReason::Excluded,
+        SkipReason::RefreshFailed,
+        SkipReason::QuotaExceeded,
+        SkipReason::MissingProfileArn,
+        SkipReason::BreakerOpen,
+    ];
+
+    /// 指标标签值
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SkipReason::Disabled => "disabled",
+            SkipReason::Excluded => "excluded",
+            SkipReason::RefreshFailed => "refresh_failed",
+            SkipReason::QuotaExceeded => "quota_exceeded",
+            SkipReason::MissingProfileArn => "missing_profile_arn",
+            SkipReason::BreakerOpen => "breaker_open",
+        }
+    }
+}
+
+/// 凭据的累计服务量指标
+#[derive(Debug, Clone)]
+pub struct CredentialUsageMetric {
+    /// 凭据 ID
+    pub id: u64,
+    /// 凭据在日志和指标中的标识（受 `log_credential_refs` 控制）
+    pub label: String,
+    /// `metrics_tag_label` 对应的标签值（凭据没有匹配的标签时为 None）
+    pub tag: Option<String>,
+    /// 被选中的次数
+    pub selections: u64,
+    /// 成功完成的请求数
+    pub requests: u64,
+    /// 消耗的 token 数
+    pub tokens: u64,
+}
+
+/// 凭据的第一个在 `metrics_tag_label.values` 中的标签
+fn metrics_tag_of(credentials: &KiroCredentials, config: &Config) -> Option<String> {
+    let label = config.metrics_tag_label.as_ref()?;
+    credentials
+        .tags
+        .iter()
+        .find(|tag| label.values.contains(tag))
+        .cloned()
+}
+
+/// 凭据在本次选择中被跳过的原因，可选时返回 None
+fn skip_reason_of(
+    entry: &CredentialEntry,
+    config: &Config,
+    options: &AcquireOptions,
+    failed_ids: &HashSet<u64>,
+    period: &str,
+) -> Option<SkipReason> {
+    if entry.disabled {
+        Some(SkipReason::Disabled)
+    } else if options.excluded_ids.contains(&entry.id) {
+        Some(SkipReason::Excluded)
+    } else if failed_ids.contains(&entry.id) {
+        Some(SkipReason::RefreshFailed)
+    } else if monthly_budget::is_exhausted(&entry.credentials, period)
+        || monthly_budget::is_quota_window_active(&entry.credentials, Utc::now())
+    {
+        Some(SkipReason::QuotaExceeded)
+    } else if config.missing_profile_arn_policy == MissingProfileArnPolicy::Skip
+        && entry.credentials.profile_arn.is_none()
+    {
+        Some(SkipReason::MissingProfileArn)
+    } else if entry.breaker.blocks(std::time::Instant::now(), breaker_cooldown(config)) {
+        Some(SkipReason::BreakerOpen)
+    } else {
+        None
+    }
+}
+
+/// 熔断冷却时长
+fn breaker_cooldown(config: &Config) -> std::time::Duration {
+    std::time::Duration::from_secs(config.breaker_cooldown_secs)
+}
+
+/// 被选中的凭据处于半开状态时记录试探请求
+fn claim_breaker_probe(entries: &mut [CredentialEntry], id: u64) {
+    if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+        entry.breaker.claim_probe(std::time::Instant::now());
+    }
+}
+
+/// 凭据选择模拟结果（Admin API）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionDecision {
+    /// 将被选中的凭据 ID（没有可用凭据时为 None）
+    pub chosen_id: Option<u64>,
+    /// 固定使用的凭据 ID（未固定时为 None，固定时只会选中该凭据）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_id: Option<u64>,
+    /// 是否沿用当前凭据（否则按有效选择顺序重新选择）
+    pub kept_current: bool,
+    /// 各凭据的选择情况（按有效选择顺序）
+    pub candidates: Vec<SelectionCandidate>,
+}
+
+/// 单个凭据的选择情况
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionCandidate {
+    pub id: u64,
+    pub priority: u32,
+    /// 被跳过的原因（可选时为 None）
+    pub skip_reason: Option<SkipReason>,
+}
+
+/// 导入凭据时用于识别重复的字段
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupKey {
+    /// 按 refreshToken 去重（默认）
+    #[default]
+    RefreshToken,
+    /// 按 profileArn 去重
+    ProfileArn,
+    /// 按凭据 ID 去重
+    Id,
+}
+
+impl DedupKey {
+    /// 提取凭据在该去重字段上的值（缺失或为空时返回 None，视为不重复）
+    fn value_of(self, credentials: &KiroCredentials) -> Option<String> {
+        match self {
+            DedupKey::RefreshToken => credentials.refresh_token.clone(),
+            DedupKey::ProfileArn => credentials.profile_arn.clone(),
+            DedupKey::Id => credentials.id.map(|id| id.to_string()),
+        }
+        .filter(|v| !v.is_empty())
+    }
+}
+
+/// 凭据导入结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    /// 新增的凭据数量
+    pub imported: usize,
+    /// 因优先级更高而替换已有凭据的数量
+    pub replaced: usize,
+    /// 因重复而跳过的数量
+    pub skipped: usize,
+}
+
+/// 凭据可变字段的部分更新（Admin API），为 None 的字段保持不变
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CredentialUpdate {
+    /// 新优先级
+    pub priority: Option<u32>,
+    /// 禁用/启用
+    pub disabled: Option<bool>,
+    /// 选择权重（`Some(None)` 表示清除）
+    pub weight: Option<Option<u32>>,
+    /// 凭据级代理地址（`Some(None)` 表示清除并同时清除代理认证信息，回退到全局代理）
+    pub proxy_url: Option<Option<String>>,
+}
+
 /// 多凭据 Token 管理器
 ///
 /// 支持多个凭据的管理，实现固定优先级 + 故障转移策略
 /// 故障统计基于 API 调用结果，而非 Token 刷新结果
 pub struct MultiTokenManager {
-    config: Config,
-    proxy: Option<ProxyConfig>,
+    /// 当前配置（可被配置热加载整体替换）
+    config: ArcSwap<Config>,
+    /// 全局代理配置（可被配置热加载替换）
+    proxy: ArcSwapOption<ProxyConfig>,
     /// 凭据条目列表
     entries: Mutex<Vec<CredentialEntry>>,
     /// 当前活动凭据 ID
     current_id: Mutex<u64>,
+    /// 固定使用的凭据 ID（初始值取自 `pinned_credential_id`，可通过 Admin API 切换）
+    pinned_id: Mutex<Option<u64>>,
     /// Token 刷新锁，确保同一时间只有一个刷新操作
     refresh_lock: TokioMutex<()>,
     /// 凭据文件路径（用于回写，仅文件存储模式使用）
@@ -454,11 +1126,32 @@ pub struct MultiTokenManager {
     is_multiple_format: bool,
     /// 存储后端（可选，用于异步持久化）
     storage: Option<std::sync::Arc<dyn crate::kiro::storage::CredentialStorage>>,
+    /// 存储后端是否已完成首次加载（lazy_storage_connect 模式下启动时为 false）
+    storage_ready: AtomicBool,
+    /// 凭据选择时按原因累计的跳过次数（下标与 `SkipReason::ALL` 一致）
+    selection_skips: [AtomicU64; SkipReason::ALL.len()],
+    /// 选择序号（round-robin 轮转位置和 least-recently-used 的使用先后）
+    selection_seq: AtomicU64,
+    /// Token 刷新熔断器
+    refresh_breaker: Mutex<RefreshBreaker>,
+    /// 各凭据最近一次 Token 刷新尝试的时间（`min_refresh_interval_secs` 启用时记录）
+    last_refresh_attempts: Mutex<HashMap<u64, std::time::Instant>>,
+    /// 运行时状态（月度用量、额度用尽窗口）是否有待持久化的变更
+    runtime_state_dirty: AtomicBool,
+    /// 最近失败的请求（`recent_errors_capacity`）
+    recent_errors: RecentErrors,
+    /// Token 刷新结果计数（成功、失败）
+    refresh_counts: [AtomicU64; 2],
+    /// 按客户端模型、再按响应状态码统计的请求数（外层按模型分组，便于按模型数封顶）
+    request_counts: Mutex<HashMap<String, HashMap<u16, u64>>>,
+    /// 尚未完成的后台存储写入（退出前由 `flush_pending_writes` 等待）
+    pending_saves: Mutex<Vec<tokio::task::JoinHandle<()>>>,
+    /// 凭据运行时事件广播（Admin API WebSocket 订阅）
+    events: broadcast::Sender<CredentialEvent>,
+    /// 使用额度查询地址（None 时按凭据认证方式和 region 生成）
+    usage_limits_url: Option<String>,
 }
 
-/// 每个凭据最大 API 调用失败次数
-const MAX_FAILURES_PER_CREDENTIAL: u32 = 3;
-
 /// API 调用上下文
 ///
 /// 绑定特定凭据的调用上下文，确保 token、credentials 和 id 的一致性
@@ -520,6 +1213,10 @@ impl MultiTokenManager {
                     failure_count: 0,
                     disabled: false,
                     disabled_reason: None,
+                    health: HealthStats::default(),
+                    usage: UsageCounters::default(),
+                    breaker: CredentialBreaker::default(),
+                    selection: SelectionState::default(),
                 }
             })
             .collect();
@@ -536,22 +1233,60 @@ impl MultiTokenManager {
             anyhow::bail!("检测到重复的凭据 ID: {:?}", duplicate_ids);
         }
 
+        if config.missing_profile_arn_policy == MissingProfileArnPolicy::Skip {
+            for entry in entries.iter().filter(|e| e.credentials.profile_arn.is_none()) {
+                tracing::warn!(
+                    "凭据 {} 缺少 profileArn，按 missingProfileArnPolicy=skip 将不会被选择",
+                    credential_label(config_ref, entry.id)
+                );
+            }
+        }
+
         // 选择初始凭据：优先级最高（priority 最小）的凭据，无凭据时为 0
         let initial_id = entries
             .iter()
-            .min_by_key(|e| e.credentials.priority)
+            .min_by_key(|e| effective_priority(e, config_ref))
             .map(|e| e.id)
             .unwrap_or(0);
 
+        if let Some(pinned_id) = config.pinned_credential_id {
+            if entries.iter().any(|e| e.id == pinned_id) {
+                tracing::warn!(
+                    "凭据固定已启用：所有请求只使用凭据 {}，不可用时返回 503",
+                    credential_label(config_ref, pinned_id)
+                );
+            } else {
+                tracing::warn!(
+                    "凭据固定已启用，但凭据 #{} 不存在，所有请求将返回 503",
+                    pinned_id
+                );
+            }
+        }
+
+        let pinned_id = config.pinned_credential_id;
+        let recent_errors = RecentErrors::new(config.recent_errors_capacity);
         let manager = Self {
-            config,
-            proxy,
+            config: ArcSwap::from_pointee(config),
+            proxy: ArcSwapOption::from_pointee(proxy),
             entries: Mutex::new(entries),
             current_id: Mutex::new(initial_id),
+            pinned_id: Mutex::new(pinned_id),
             refresh_lock: TokioMutex::new(()),
             credentials_path,
             is_multiple_format,
             storage: None,
+            storage_ready: AtomicBool::new(true),
+            selection_skips: Default::default(),
+            selection_seq: AtomicU64::new(0),
+            refresh_breaker: Mutex::new(RefreshBreaker::default()),
+            last_refresh_attempts: Mutex::new(HashMap::new()),
+            runtime_state_dirty: AtomicBool::new(false),
+            recent_errors,
+            refresh_counts: Default::default(),
+            request_counts: Mutex::new(HashMap::new()),
+            pending_saves: Mutex::new(Vec::new()),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            usage_limits_url: None,
         };
 
         // 如果有新分配的 ID 或新生成的 machineId，立即持久化到配置文件
@@ -566,6 +1301,13 @@ impl MultiTokenManager {
         Ok(manager)
     }
 
+    /// 覆盖使用额度查询地址（测试用）
+    #[cfg(test)]
+    pub(crate) fn with_usage_limits_url(mut self, url: impl Into<String>) -> Self {
+        self.usage_limits_url = Some(url.into());
+        self
+    }
+
     /// 设置存储后端
     ///
     /// 设置后，持久化操作将使用存储后端而非直接写文件
@@ -573,6 +1315,57 @@ impl MultiTokenManager {
         self.storage = Some(storage);
     }
 
+    /// 标记存储后端尚未就绪
+    ///
+    /// 用于 lazy_storage_connect 模式：在 `connect_storage_lazily` 首次加载成功前，
+    /// 所有选择凭据的调用返回 `StorageUnavailableError`
+    pub fn mark_storage_pending(&self) {
+        self.storage_ready.store(false, Ordering::Release);
+    }
+
+    /// 存储后端是否已就绪
+    pub fn is_storage_ready(&self) -> bool {
+        self.storage_ready.load(Ordering::Acquire)
+    }
+
+    /// 反复尝试从存储后端加载凭据，直到首次成功
+    ///
+    /// 成功后热更新凭据并标记存储就绪；重试间隔从 `retry_delay` 开始指数增长，最长 30 秒
+    pub async fn connect_storage_lazily(&self, retry_delay: std::time::Duration) {
+        const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+        let Some(storage) = self.storage.clone() else {
+            self.storage_ready.store(true, Ordering::Release);
+            return;
+        };
+
+        let mut delay = retry_delay;
+        loop {
+            match storage.load_all().await {
+                Ok(credentials) => {
+                    tracing::info!(
+                        "存储后端（{}）已连接，加载 {} 个凭据",
+                        storage.storage_type(),
+                        credentials.len()
+                    );
+                    self.reload_credentials(credentials);
+                    self.storage_ready.store(true, Ordering::Release);
+                    return;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "存储后端（{}）暂不可用，{} 秒后重试: {}",
+                        storage.storage_type(),
+                        delay.as_secs_f32(),
+                        SanitizedError(&e)
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                }
+            }
+        }
+    }
+
     /// 获取存储后端
     pub fn storage(&self) -> Option<&std::sync::Arc<dyn crate::kiro::storage::CredentialStorage>> {
         self.storage.as_ref()
@@ -585,6 +1378,7 @@ impl MultiTokenManager {
     pub fn reload_credentials(&self, new_credentials: Vec<KiroCredentials>) {
         use std::collections::HashSet;
 
+        let config = self.config();
         let mut entries = self.entries.lock();
         let mut current_id = self.current_id.lock();
 
@@ -608,7 +1402,7 @@ impl MultiTokenManager {
             if let Some(id) = cred.id {
                 if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
                     // 更新现有凭据（保留运行时状态）
-                    entry.credentials = cred;
+                    entry.replace_credentials(cred);
                 } else {
                     // 添加新凭据，尝试恢复之前的状态（如果有）
                     let (failure_count, disabled, disabled_reason) = existing_states
@@ -622,6 +1416,10 @@ impl MultiTokenManager {
                         failure_count,
                         disabled,
                         disabled_reason,
+                        health: HealthStats::default(),
+                        usage: UsageCounters::default(),
+                        breaker: CredentialBreaker::default(),
+                        selection: SelectionState::default(),
                     });
                 }
             }
@@ -632,9 +1430,17 @@ impl MultiTokenManager {
 
         // 如果当前凭据被删除，切换到优先级最高的可用凭据
         if !entries.iter().any(|e| e.id == *current_id && !e.disabled) {
-            if let Some(best) = entries.iter().filter(|e| !e.disabled).min_by_key(|e| e.credentials.priority) {
+            if let Some(best) = entries
+                .iter()
+                .filter(|e| !e.disabled)
+                .min_by_key(|e| effective_priority(e, &config))
+            {
                 *current_id = best.id;
-                tracing::info!("热更新后切换到凭据 #{}（优先级 {}）", best.id, best.credentials.priority);
+                tracing::info!(
+                    "热更新后切换到凭据 {}（优先级 {}）",
+                    credential_label(&config, best.id),
+                    best.credentials.priority
+                );
             } else if let Some(first) = entries.first() {
                 *current_id = first.id;
             } else {
@@ -645,9 +1451,36 @@ impl MultiTokenManager {
         tracing::info!("凭据已热更新，当前共 {} 个", entries.len());
     }
 
-    /// 获取配置的引用
-    pub fn config(&self) -> &Config {
-        &self.config
+    /// 获取当前配置
+    ///
+    /// 配置可能被热加载替换，同一次操作内应只获取一次并复用返回值
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    /// 获取当前全局代理配置
+    pub fn proxy(&self) -> Option<Arc<ProxyConfig>> {
+        self.proxy.load_full()
+    }
+
+    /// 替换配置和全局代理（配置热加载使用）
+    ///
+    /// 进行中的操作继续使用替换前获取的配置，之后的操作读取新配置
+    pub fn update_config(&self, config: Config, proxy: Option<ProxyConfig>) {
+        self.config.store(Arc::new(config));
+        self.proxy.store(proxy.map(Arc::new));
+    }
+
+    /// 订阅凭据运行时事件
+    ///
+    /// 订阅者处理过慢时最旧的事件被丢弃，接收端收到 `RecvError::Lagged`
+    pub fn subscribe_events(&self) -> broadcast::Receiver<CredentialEvent> {
+        self.events.subscribe()
+    }
+
+    /// 发布凭据运行时事件（无订阅者时直接丢弃）
+    pub fn publish_event(&self, event: CredentialEvent) {
+        let _ = self.events.send(event);
     }
 
     /// 获取当前活动凭据的克隆
@@ -666,6 +1499,11 @@ impl MultiTokenManager {
         self.entries.lock().len()
     }
 
+    /// 凭据是否仍存在（未被删除）
+    pub fn has_credential(&self, id: u64) -> bool {
+        self.entries.lock().iter().any(|e| e.id == id)
+    }
+
     /// 获取可用凭据数量
     pub fn available_count(&self) -> usize {
         self.entries.lock().iter().filter(|e| !e.disabled).count()
@@ -679,34 +1517,130 @@ impl MultiTokenManager {
     /// 如果 Token 过期或即将过期，会自动刷新
     /// Token 刷新失败时会尝试下一个可用凭据（不计入失败次数）
     pub async fn acquire_context(&self) -> anyhow::Result<CallContext> {
-        let total = self.total_count();
-        let mut tried_count = 0;
-
-        loop {
-            if tried_count >= total {
-                anyhow::bail!(
-                    "所有凭据均无法获取有效 Token（可用: {}/{}）",
-                    self.available_count(),
-                    total
-                );
-            }
+        self.acquire_context_with(&AcquireOptions::default()).await
+    }
 
-            let (id, credentials) = {
-                let mut entries = self.entries.lock();
-                let current_id = *self.current_id.lock();
+    /// 模拟一次凭据选择
+    ///
+    /// 与 `acquire_context_with` 使用相同的选择规则，但不刷新 Token、不修改当前凭据，
+    /// 也不计入跳过统计。实际请求中 Token 刷新失败的凭据会被继续跳过，模拟结果无法反映这一点
+    pub fn select_dry_run(&self, options: &AcquireOptions) -> SelectionDecision {
+        let config = self.config();
+        let period = self.current_usage_period();
+        let no_failures = HashSet::new();
+        let entries = self.entries.lock();
+        let current_id = *self.current_id.lock();
 
-                // 找到当前凭据
-                if let Some(entry) = entries.iter().find(|e| e.id == current_id && !e.disabled) {
-                    (entry.id, entry.credentials.clone())
-                } else {
-                    // 当前凭据不可用，选择优先级最高的可用凭据
-                    let mut best = entries
-                        .iter()
-                        .filter(|e| !e.disabled)
-                        .min_by_key(|e| e.credentials.priority);
+        let mut ordered: Vec<&CredentialEntry> = entries.iter().collect();
+        ordered.sort_by_key(|e| effective_priority(e, &config));
+        let candidates: Vec<SelectionCandidate> = ordered
+            .iter()
+            .map(|e| SelectionCandidate {
+                id: e.id,
+                priority: e.credentials.priority,
+                skip_reason: skip_reason_of(e, &config, options, &no_failures, &period),
+            })
+            .collect();
 
-                    // 没有可用凭据：如果是“自动禁用导致全灭”，做一次类似重启的自愈
-                    if best.is_none()
+        let pinned_id = self.pinned_credential_id();
+        let strategy = config.selection_strategy;
+        let kept_current = strategy == SelectionStrategy::Priority
+            && candidates
+                .iter()
+                .any(|c| c.id == current_id && c.skip_reason.is_none())
+            && pinned_id.is_none_or(|pinned_id| pinned_id == current_id);
+        let chosen_id = if let Some(pinned_id) = pinned_id {
+            candidates
+                .iter()
+                .find(|c| c.id == pinned_id && c.skip_reason.is_none())
+                .map(|c| c.id)
+        } else if kept_current {
+            Some(current_id)
+        } else {
+            let eligible: Vec<usize> = candidates
+                .iter()
+                .filter(|c| c.skip_reason.is_none())
+                .filter_map(|c| entries.iter().position(|e| e.id == c.id))
+                .collect();
+            let seq = self.selection_seq.load(Ordering::Relaxed);
+            choose_by_strategy(&entries, &eligible, strategy, seq).map(|i| entries[i].id)
+        };
+
+        SelectionDecision {
+            chosen_id,
+            pinned_id,
+            kept_current,
+            candidates,
+        }
+    }
+
+    /// 按指定选项获取 API 调用上下文
+    ///
+    /// `options.excluded_ids` 中的凭据在本次调用中不会被选中，
+    /// 且排除生效时不会修改全局的当前凭据，其他请求不受影响。
+    /// 排除后没有可用凭据时返回 `NoEligibleCredentialError`；
+    /// 剩余凭据均已达到月度 token 上限时返回 `MonthlyBudgetExhaustedError`；
+    /// 固定凭据期间只使用该凭据，不可用时返回 `PinnedCredentialUnavailableError`
+    pub async fn acquire_context_with(
+        &self,
+        options: &AcquireOptions,
+    ) -> anyhow::Result<CallContext> {
+        let config = self.config();
+        if !self.is_storage_ready() {
+            return Err(StorageUnavailableError.into());
+        }
+
+        if let Some(pinned_id) = self.pinned_credential_id() {
+            return self.acquire_pinned_context(pinned_id, options).await;
+        }
+
+        let total = self.total_count();
+        let mut tried_count = 0;
+        // 本次调用中 Token 刷新失败的凭据
+        let mut failed_ids: HashSet<u64> = HashSet::new();
+        let period = self.current_usage_period();
+
+        loop {
+            if tried_count >= total {
+                return Err(KiroError::new(
+                    KiroErrorCode::NoCredentialsAvailable,
+                    format!(
+                        "所有凭据均无法获取有效 Token（可用: {}/{}）",
+                        self.available_count(),
+                        total
+                    ),
+                )
+                .into());
+            }
+
+            let (id, credentials) = {
+                let mut entries = self.entries.lock();
+                let current_id = *self.current_id.lock();
+                let skip_reason = |e: &CredentialEntry| {
+                    skip_reason_of(e, &config, options, &failed_ids, &period)
+                };
+                let is_eligible = |e: &CredentialEntry| skip_reason(e).is_none();
+
+                // 找到当前凭据（仅 priority 策略沿用当前凭据）
+                let sticky = config.selection_strategy == SelectionStrategy::Priority;
+                if let Some((id, credentials)) = entries
+                    .iter()
+                    .find(|e| sticky && e.id == current_id && is_eligible(e))
+                    .map(|entry| (entry.id, entry.credentials.clone()))
+                {
+                    claim_breaker_probe(&mut entries, id);
+                    (id, credentials)
+                } else {
+                    // 当前凭据不可用（或非 priority 策略），按选择策略从可用凭据中选择
+                    for reason in entries.iter().filter_map(&skip_reason) {
+                        self.selection_skips[reason as usize].fetch_add(1, Ordering::Relaxed);
+                    }
+                    let seq = self.selection_seq.fetch_add(1, Ordering::Relaxed);
+                    let mut best = pick_by_strategy(&mut entries, &config, is_eligible, seq);
+
+                    // 没有可用凭据：如果是“自动禁用导致全灭”，做一次类似重启的自愈
+                    if best.is_none()
+                        && entries.iter().all(|e| e.disabled)
                         && entries.iter().any(|e| {
                             e.disabled && e.disabled_reason == Some(DisabledReason::TooManyFailures)
                         })
@@ -719,29 +1653,78 @@ impl MultiTokenManager {
                                 e.disabled = false;
                                 e.disabled_reason = None;
                                 e.failure_count = 0;
+                                e.breaker = CredentialBreaker::default();
+                                let event = CredentialEvent::CredentialEnabled { id: e.id };
+                                self.publish_event(event);
                             }
                         }
-                        best = entries
-                            .iter()
-                            .filter(|e| !e.disabled)
-                            .min_by_key(|e| e.credentials.priority);
+                        best = pick_by_strategy(&mut entries, &config, is_eligible, seq);
                     }
 
-                    if let Some(entry) = best {
+                    if let Some(index) = best {
                         // 先提取数据
-                        let new_id = entry.id;
-                        let new_creds = entry.credentials.clone();
+                        let new_id = entries[index].id;
+                        let new_creds = entries[index].credentials.clone();
+                        claim_breaker_probe(&mut entries, new_id);
                         drop(entries);
-                        // 更新 current_id
-                        let mut current_id = self.current_id.lock();
-                        *current_id = new_id;
+                        // 仅在未指定排除时更新 current_id，避免单次请求影响全局选择
+                        if options.is_default() {
+                            let mut current_id = self.current_id.lock();
+                            *current_id = new_id;
+                        }
                         (new_id, new_creds)
+                    } else if entries
+                        .iter()
+                        .any(|e| !e.disabled && options.excluded_ids.contains(&e.id))
+                    {
+                        // 仍有启用的凭据，但都被本次请求排除
+                        let mut excluded_ids: Vec<u64> =
+                            options.excluded_ids.iter().copied().collect();
+                        excluded_ids.sort_unstable();
+                        return Err(NoEligibleCredentialError { excluded_ids }.into());
+                    } else if entries
+                        .iter()
+                        .any(|e| !e.disabled && !options.excluded_ids.contains(&e.id))
+                        && entries
+                            .iter()
+                            .filter(|e| !e.disabled && !options.excluded_ids.contains(&e.id))
+                            .all(|e| monthly_budget::is_exhausted(&e.credentials, &period))
+                    {
+                        // 剩余凭据均已达到月度 token 上限
+                        return Err(MonthlyBudgetExhaustedError {
+                            period: period.clone(),
+                        }
+                        .into());
+                    } else if entries.iter().any(|e| {
+                        let cooldown = breaker_cooldown(&config);
+                        !e.disabled && e.breaker.blocks(std::time::Instant::now(), cooldown)
+                    }) {
+                        // 剩余启用的凭据均处于熔断中
+                        return Err(KiroError::new(
+                            KiroErrorCode::NoCredentialsAvailable,
+                            format!("所有可用凭据均处于熔断冷却中（共 {} 个）", total),
+                        )
+                        .into());
+                    } else if !failed_ids.is_empty() {
+                        let available = entries.iter().filter(|e| !e.disabled).count();
+                        return Err(KiroError::new(
+                            KiroErrorCode::NoCredentialsAvailable,
+                            format!(
+                                "所有凭据均无法获取有效 Token（可用: {}/{}）",
+                                available, total
+                            ),
+                        )
+                        .into());
                     } else {
-                        // 注意：必须在 bail! 之前计算 available_count，
+                        // 注意：必须在返回错误之前计算 available_count，
                         // 因为 available_count() 会尝试获取 entries 锁，
                         // 而此时我们已经持有该锁，会导致死锁
                         let available = entries.iter().filter(|e| !e.disabled).count();
-                        anyhow::bail!("所有凭据均已禁用（{}/{}）", available, total);
+                        return Err(KiroError::new(
+                            KiroErrorCode::NoCredentialsAvailable,
+                            format!("所有凭据均已禁用（{}/{}）", available, total),
+                        )
+                        .into());
                     }
                 }
             };
@@ -749,21 +1732,111 @@ impl MultiTokenManager {
             // 尝试获取/刷新 Token
             match self.try_ensure_token(id, &credentials).await {
                 Ok(ctx) => {
+                    self.record_selection(ctx.id);
                     return Ok(ctx);
                 }
                 Err(e) => {
-                    tracing::warn!("凭据 #{} Token 刷新失败，尝试下一个凭据: {}", id, e);
+                    tracing::warn!(
+                        "凭据 {} Token 刷新失败，尝试下一个凭据: {}",
+                        credential_label(&config, id),
+                        e
+                    );
 
                     // Token 刷新失败，切换到下一个优先级的凭据（不计入失败次数）
-                    self.switch_to_next_by_priority();
+                    failed_ids.insert(id);
+                    if options.is_default() {
+                        self.switch_to_next_by_priority();
+                    }
                     tried_count += 1;
                 }
             }
         }
     }
 
+    /// 获取固定凭据的调用上下文（内部方法）
+    ///
+    /// 不考虑选择模式和优先级，也不修改当前凭据；
+    /// 固定凭据不存在、被跳过或 Token 刷新失败时返回 `PinnedCredentialUnavailableError`
+    async fn acquire_pinned_context(
+        &self,
+        pinned_id: u64,
+        options: &AcquireOptions,
+    ) -> anyhow::Result<CallContext> {
+        let config = self.config();
+        let period = self.current_usage_period();
+        let credentials = {
+            let mut entries = self.entries.lock();
+            let Some(entry) = entries.iter().find(|e| e.id == pinned_id) else {
+                return Err(PinnedCredentialUnavailableError {
+                    id: pinned_id,
+                    reason: None,
+                }
+                .into());
+            };
+            if let Some(reason) =
+                skip_reason_of(entry, &config, options, &HashSet::new(), &period)
+            {
+                return Err(PinnedCredentialUnavailableError {
+                    id: pinned_id,
+                    reason: Some(reason),
+                }
+                .into());
+            }
+            let credentials = entry.credentials.clone();
+            claim_breaker_probe(&mut entries, pinned_id);
+            credentials
+        };
+
+        self.try_ensure_token(pinned_id, &credentials)
+            .await
+            .inspect(|ctx| self.record_selection(ctx.id))
+            .map_err(|e| {
+                tracing::warn!(
+                    "固定凭据 {} Token 刷新失败: {}",
+                    credential_label(&config, pinned_id),
+                    e
+                );
+                PinnedCredentialUnavailableError {
+                    id: pinned_id,
+                    reason: Some(SkipReason::RefreshFailed),
+                }
+                .into()
+            })
+    }
+
+    /// 当前固定使用的凭据 ID（未固定时为 None）
+    pub fn pinned_credential_id(&self) -> Option<u64> {
+        *self.pinned_id.lock()
+    }
+
+    /// 固定使用指定凭据，之后所有请求只使用该凭据
+    pub fn pin_credential(&self, id: u64) -> anyhow::Result<()> {
+        if !self.entries.lock().iter().any(|e| e.id == id) {
+            anyhow::bail!("凭据不存在: {}", id);
+        }
+        *self.pinned_id.lock() = Some(id);
+        tracing::warn!(
+            "凭据固定已启用：所有请求只使用凭据 {}，不可用时返回 503",
+            credential_label(&self.config(), id)
+        );
+        Ok(())
+    }
+
+    /// 取消凭据固定，恢复正常的凭据选择，返回之前固定的凭据 ID
+    pub fn unpin_credential(&self) -> Option<u64> {
+        let previous = self.pinned_id.lock().take();
+        if let Some(id) = previous {
+            tracing::info!(
+                "已取消固定凭据 {}，恢复正常凭据选择",
+                credential_label(&self.config(), id)
+            );
+        }
+        previous
+    }
+
     /// 切换到下一个优先级最高的可用凭据（内部方法）
     fn switch_to_next_by_priority(&self) {
+        let config = self.config();
         let entries = self.entries.lock();
         let mut current_id = self.current_id.lock();
 
@@ -771,12 +1844,12 @@ impl MultiTokenManager {
         if let Some(entry) = entries
             .iter()
             .filter(|e| !e.disabled && e.id != *current_id)
-            .min_by_key(|e| e.credentials.priority)
+            .min_by_key(|e| effective_priority(e, &config))
         {
             *current_id = entry.id;
             tracing::info!(
-                "已切换到凭据 #{}（优先级 {}）",
-                entry.id,
+                "已切换到凭据 {}（优先级 {}）",
+                credential_label(&config, entry.id),
                 entry.credentials.priority
             );
         }
@@ -787,6 +1860,7 @@ impl MultiTokenManager {
     /// 与 `switch_to_next_by_priority` 不同，此方法不排除当前凭据，
     /// 纯粹按优先级选择，用于优先级变更后立即生效
     fn select_highest_priority(&self) {
+        let config = self.config();
         let entries = self.entries.lock();
         let mut current_id = self.current_id.lock();
 
@@ -794,13 +1868,13 @@ impl MultiTokenManager {
         if let Some(best) = entries
             .iter()
             .filter(|e| !e.disabled)
-            .min_by_key(|e| e.credentials.priority)
+            .min_by_key(|e| effective_priority(e, &config))
         {
             if best.id != *current_id {
                 tracing::info!(
-                    "优先级变更后切换凭据: #{} -> #{}（优先级 {}）",
-                    *current_id,
-                    best.id,
+                    "优先级变更后切换凭据: {} -> {}（优先级 {}）",
+                    credential_label(&config, *current_id),
+                    credential_label(&config, best.id),
                     best.credentials.priority
                 );
                 *current_id = best.id;
@@ -820,8 +1894,9 @@ impl MultiTokenManager {
         id: u64,
         credentials: &KiroCredentials,
     ) -> anyhow::Result<CallContext> {
+        let config = self.config();
         // 第一次检查（无锁）：快速判断是否需要刷新
-        let needs_refresh = is_token_expired(credentials) || is_token_expiring_soon(credentials);
+        let needs_refresh = self.needs_refresh(credentials);
 
         let creds = if needs_refresh {
             // 获取刷新锁，确保同一时间只有一个刷新操作
@@ -837,10 +1912,27 @@ impl MultiTokenManager {
                     .ok_or_else(|| anyhow::anyhow!("凭据 #{} 不存在", id))?
             };
 
-            if is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds) {
-                // 确实需要刷新
-                let new_creds =
-                    refresh_token(&current_creds, &self.config, self.proxy.as_ref()).await?;
+            if !is_token_expired(&current_creds) && self.refresh_throttled_for(id).is_some() {
+                // 最小刷新间隔内，原 Token 尚未过期，继续使用
+                tracing::debug!(
+                    "凭据 {} 处于最小刷新间隔内，继续使用即将过期的 Token",
+                    credential_label(&config, id)
+                );
+                current_creds
+            } else if self.needs_refresh(&current_creds) {
+                // 确实需要刷新；提前刷新失败时原 Token 尚未过期则继续使用
+                let new_creds = match self.refresh_with_breaker(&current_creds).await {
+                    Ok(new_creds) => new_creds,
+                    Err(e) if !is_token_expired(&current_creds) => {
+                        tracing::warn!(
+                            "凭据 {} Token 提前刷新失败，继续使用未过期的 Token: {}",
+                            credential_label(&config, id),
+                            e
+                        );
+                        return Self::call_context(id, current_creds);
+                    }
+                    Err(e) => return Err(e),
+                };
 
                 if is_token_expired(&new_creds) {
                     anyhow::bail!("刷新后的 Token 仍然无效或已过期");
@@ -850,7 +1942,7 @@ impl MultiTokenManager {
                 {
                     let mut entries = self.entries.lock();
                     if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
-                        entry.credentials = new_creds.clone();
+                        entry.replace_credentials(new_creds.clone());
                     }
                 }
 
@@ -869,18 +1961,36 @@ impl MultiTokenManager {
             credentials.clone()
         };
 
-        let token = creds
+        Self::call_context(id, creds)
+    }
+
+    /// 由凭据构建调用上下文
+    fn call_context(id: u64, credentials: KiroCredentials) -> anyhow::Result<CallContext> {
+        let token = credentials
             .access_token
             .clone()
             .ok_or_else(|| anyhow::anyhow!("没有可用的 accessToken"))?;
 
         Ok(CallContext {
             id,
-            credentials: creds,
+            credentials,
             token,
         })
     }
 
+    /// Token 是否需要在使用前刷新：已过期，或在 `token_refresh_margin_secs` 内即将过期
+    fn needs_refresh(&self, credentials: &KiroCredentials) -> bool {
+        let config = self.config();
+        is_token_expired(credentials)
+            || is_token_expiring_soon(credentials, config.token_refresh_margin_secs)
+    }
+
+    /// 后台主动刷新的提前量
+    fn proactive_refresh_window(&self) -> Duration {
+        Duration::minutes(PROACTIVE_REFRESH_WINDOW_MINUTES)
+            .max(Duration::seconds(self.config().token_refresh_margin_secs as i64))
+    }
+
     /// 将凭据列表回写到源文件
     ///
     /// 仅在以下条件满足时回写：
@@ -892,6 +2002,14 @@ impl MultiTokenManager {
     /// - `Ok(false)` - 跳过写入（非多凭据格式或无路径配置）
     /// - `Err(_)` - 写入失败
     fn persist_credentials(&self) -> anyhow::Result<bool> {
+        self.persist_added_credentials(Vec::new())
+    }
+
+    /// 回写凭据列表，`added` 为本次新增的凭据
+    ///
+    /// 存储后端的 `save_all` 不恢复已软删除的凭据（删除前排队的回写可能晚于删除执行），
+    /// 新增的凭据先逐个 `save` 显式写入，ID 与已删除的凭据相同时恢复该凭据
+    fn persist_added_credentials(&self, added: Vec<KiroCredentials>) -> anyhow::Result<bool> {
         use anyhow::Context;
 
         // 收集所有凭据
@@ -900,19 +2018,32 @@ impl MultiTokenManager {
             entries.iter().map(|e| e.credentials.clone()).collect()
         };
 
+        // 先同步校验，存在无效凭据时直接返回错误，而不是在后台回写时被整批拒绝
+        crate::kiro::storage::validate_batch(&credentials, false)?;
+
         // 如果有存储后端，使用存储后端持久化
         if let Some(storage) = &self.storage {
             let storage = storage.clone();
             let creds = credentials.clone();
 
             // 在后台异步保存，不阻塞当前操作
-            tokio::spawn(async move {
-                if let Err(e) = storage.save_all(&creds).await {
-                    tracing::warn!("存储后端持久化失败: {}", e);
+            let task = tokio::spawn(async move {
+                let result = async {
+                    for credential in &added {
+                        storage.save(credential).await?;
+                    }
+                    storage.save_all(&creds).await
+                }
+                .await;
+                if let Err(e) = result {
+                    tracing::warn!("存储后端持久化失败: {}", SanitizedError(&e));
                 } else {
                     tracing::debug!("已通过存储后端持久化凭据");
                 }
             });
+            let mut pending = self.pending_saves.lock();
+            pending.retain(|task| !task.is_finished());
+            pending.push(task);
 
             return Ok(true);
         }
@@ -928,8 +2059,10 @@ impl MultiTokenManager {
             None => return Ok(false),
         };
 
-        // 序列化为 pretty JSON
+        // 序列化为 pretty JSON（`.gz` 路径压缩后写入）
         let json = serde_json::to_string_pretty(&credentials).context("序列化凭据失败")?;
+        let json = crate::kiro::storage::compression::encode_for_path(path, json.as_bytes())
+            .context("压缩凭据失败")?;
 
         // 写入文件（在 Tokio runtime 内使用 block_in_place 避免阻塞 worker）
         if tokio::runtime::Handle::try_current().is_ok() {
@@ -950,439 +2083,1319 @@ impl MultiTokenManager {
     /// # Arguments
     /// * `id` - 凭据 ID（来自 CallContext）
     pub fn report_success(&self, id: u64) {
+        let config = self.config();
         let mut entries = self.entries.lock();
         if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
             entry.failure_count = 0;
-            tracing::debug!("凭据 #{} API 调用成功", id);
-        }
-    }
-
-    /// 报告指定凭据 API 调用失败
-    ///
-    /// 增加失败计数，达到阈值时禁用凭据并切换到优先级最高的可用凭据
-    /// 返回是否还有可用凭据可以重试
-    ///
-    /// # Arguments
-    /// * `id` - 凭据 ID（来自 CallContext）
-    pub fn report_failure(&self, id: u64) -> bool {
-        let mut entries = self.entries.lock();
-        let mut current_id = self.current_id.lock();
-
-        let entry = match entries.iter_mut().find(|e| e.id == id) {
-            Some(e) => e,
-            None => return entries.iter().any(|e| !e.disabled),
-        };
-
-        entry.failure_count += 1;
-        let failure_count = entry.failure_count;
-
-        tracing::warn!(
-            "凭据 #{} API 调用失败（{}/{}）",
-            id,
-            failure_count,
-            MAX_FAILURES_PER_CREDENTIAL
-        );
-
-        if failure_count >= MAX_FAILURES_PER_CREDENTIAL {
-            entry.disabled = true;
-            entry.disabled_reason = Some(DisabledReason::TooManyFailures);
-            tracing::error!("凭据 #{} 已连续失败 {} 次，已被禁用", id, failure_count);
-
-            // 切换到优先级最高的可用凭据
-            if let Some(next) = entries
-                .iter()
-                .filter(|e| !e.disabled)
-                .min_by_key(|e| e.credentials.priority)
-            {
-                *current_id = next.id;
+            entry.breaker.consecutive_rate_limits = 0;
+            if entry.breaker.open_until.is_some() {
                 tracing::info!(
-                    "已切换到凭据 #{}（优先级 {}）",
-                    next.id,
-                    next.credentials.priority
+                    "凭据 {} 试探请求成功，熔断器关闭",
+                    credential_label(&config, id)
                 );
-            } else {
-                tracing::error!("所有凭据均已禁用！");
-                return false;
+                entry.breaker = CredentialBreaker::default();
+                self.publish_event(CredentialEvent::BreakerStateChanged {
+                    id,
+                    state: BreakerState::Closed,
+                });
             }
+            entry.health.record_outcome(true);
+            entry.usage.requests += 1;
+            tracing::debug!("凭据 {} API 调用成功", credential_label(&config, id));
         }
+    }
 
-        // 检查是否还有可用凭据
-        entries.iter().any(|e| !e.disabled)
+    /// 记录指定凭据一次成功请求的响应延迟（用于 `auto_reorder` 健康分）
+    pub fn report_latency(&self, id: u64, latency: std::time::Duration) {
+        let mut entries = self.entries.lock();
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            entry.health.record_latency(latency);
+        }
     }
 
-    /// 报告指定凭据额度已用尽
+    /// 按当前健康指标重算各凭据的健康分档位
     ///
-    /// 用于处理 402 Payment Required 且 reason 为 `MONTHLY_REQUEST_COUNT` 的场景：
-    /// - 立即禁用该凭据（不等待连续失败阈值）
-    /// - 切换到下一个可用凭据继续重试
-    /// - 返回是否还有可用凭据
-    pub fn report_quota_exhausted(&self, id: u64) -> bool {
+    /// 仅在启用 `auto_reorder` 时生效：重算后若当前凭据不再是有效顺序中最优的可用凭据，
+    /// 切换到最优凭据。持久化的 priority 不受影响
+    pub fn recompute_health_ranks(&self) {
+        let config = self.config();
+        if !config.auto_reorder {
+            return;
+        }
+
+        let period = self.current_usage_period();
         let mut entries = self.entries.lock();
         let mut current_id = self.current_id.lock();
 
-        let entry = match entries.iter_mut().find(|e| e.id == id) {
-            Some(e) => e,
-            None => return entries.iter().any(|e| !e.disabled),
-        };
-
-        if entry.disabled {
-            return entries.iter().any(|e| !e.disabled);
+        for entry in entries.iter_mut() {
+            let score = health_score(&entry.health, &entry.credentials, &period);
+            entry.health.rank = health_rank(score);
         }
 
-        entry.disabled = true;
-        entry.disabled_reason = Some(DisabledReason::QuotaExceeded);
-        // 设为阈值，便于在管理面板中直观看到该凭据已不可用
-        entry.failure_count = MAX_FAILURES_PER_CREDENTIAL;
-
-        tracing::error!(
-            "凭据 #{} 额度已用尽（MONTHLY_REQUEST_COUNT），已被禁用",
-            id
-        );
-
-        // 切换到优先级最高的可用凭据
-        if let Some(next) = entries
+        if let Some(best) = entries
             .iter()
             .filter(|e| !e.disabled)
-            .min_by_key(|e| e.credentials.priority)
+            .min_by_key(|e| effective_priority(e, &config))
+            && best.id != *current_id
         {
-            *current_id = next.id;
             tracing::info!(
-                "已切换到凭据 #{}（优先级 {}）",
-                next.id,
-                next.credentials.priority
+                "按健康分调整凭据顺序：{} -> {}（优先级 {}）",
+                credential_label(&config, *current_id),
+                credential_label(&config, best.id),
+                best.credentials.priority
             );
-            return true;
+            *current_id = best.id;
         }
+    }
 
-        tracing::error!("所有凭据均已禁用！");
-        false
+    /// 启动健康分定时重算任务（`auto_reorder`）
+    pub fn start_auto_reorder_task(
+        self: std::sync::Arc<Self>,
+        interval: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // 跳过立即触发的第一次 tick
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                self.recompute_health_ranks();
+            }
+        })
     }
 
-    /// 切换到优先级最高的可用凭据
+    /// 刷新 Token 并记录结果到熔断器
     ///
-    /// 返回是否成功切换
-    pub fn switch_to_next(&self) -> bool {
-        let entries = self.entries.lock();
-        let mut current_id = self.current_id.lock();
+    /// refreshToken 本地校验失败（缺失、被截断）与 token 端点无关，不计入熔断
+    async fn refresh_with_breaker(
+        &self,
+        credentials: &KiroCredentials,
+    ) -> anyhow::Result<KiroCredentials> {
+        validate_refresh_token(credentials)?;
+        if let Some(id) = credentials.id {
+            self.begin_refresh_attempt(id)?;
+        }
+        let result = refresh_token(credentials, &self.config(), self.proxy().as_deref()).await;
+        self.record_refresh_result(result.is_ok());
+        result
+    }
 
-        // 选择优先级最高的未禁用凭据（排除当前凭据）
-        if let Some(next) = entries
-            .iter()
-            .filter(|e| !e.disabled && e.id != *current_id)
-            .min_by_key(|e| e.credentials.priority)
-        {
-            *current_id = next.id;
-            tracing::info!(
-                "已切换到凭据 #{}（优先级 {}）",
-                next.id,
-                next.credentials.priority
-            );
-            true
-        } else {
-            // 没有其他可用凭据，检查当前凭据是否可用
-            entries.iter().any(|e| e.id == *current_id && !e.disabled)
+    /// 记录一次刷新尝试；距上次尝试不足 `min_refresh_interval_secs` 时返回错误，不发起刷新
+    fn begin_refresh_attempt(&self, id: u64) -> anyhow::Result<()> {
+        let config = self.config();
+        let min_interval = std::time::Duration::from_secs(config.min_refresh_interval_secs);
+        if min_interval.is_zero() {
+            return Ok(());
+        }
+
+        let now = std::time::Instant::now();
+        let mut attempts = self.last_refresh_attempts.lock();
+        if let Some(last) = attempts.get(&id) {
+            let elapsed = now.duration_since(*last);
+            if elapsed < min_interval {
+                bail!(
+                    "凭据 {} 距上次 Token 刷新尝试不足 {} 秒，{} 秒内不再刷新",
+                    credential_label(&config, id),
+                    min_interval.as_secs(),
+                    (min_interval - elapsed).as_secs_f32().ceil()
+                );
+            }
         }
+        attempts.insert(id, now);
+        Ok(())
     }
 
-    /// 获取使用额度信息
-    pub async fn get_usage_limits(&self) -> anyhow::Result<UsageLimitsResponse> {
-        let ctx = self.acquire_context().await?;
-        get_usage_limits(
-            &ctx.credentials,
-            &self.config,
-            &ctx.token,
-            self.proxy.as_ref(),
-        )
-        .await
+    /// 指定凭据距离允许再次刷新的剩余时长（不在最小刷新间隔内时为 None）
+    fn refresh_throttled_for(&self, id: u64) -> Option<std::time::Duration> {
+        let min_interval = std::time::Duration::from_secs(self.config().min_refresh_interval_secs);
+        let last = *self.last_refresh_attempts.lock().get(&id)?;
+        min_interval
+            .checked_sub(last.elapsed())
+            .filter(|remaining| !remaining.is_zero())
     }
 
-    // ========================================================================
-    // Admin API 方法
-    // ========================================================================
+    /// 记录一次 Token 刷新结果，连续失败达到阈值时打开熔断
+    fn record_refresh_result(&self, success: bool) {
+        let config = self.config();
+        self.refresh_counts[usize::from(!success)].fetch_add(1, Ordering::Relaxed);
+        let mut breaker = self.refresh_breaker.lock();
+        if success {
+            if breaker.open_until.take().is_some() {
+                tracing::info!("Token 刷新已恢复，关闭刷新熔断");
+            }
+            breaker.consecutive_failures = 0;
+            return;
+        }
 
-    /// 获取管理器状态快照（用于 Admin API）
-    pub fn snapshot(&self) -> ManagerSnapshot {
-        let entries = self.entries.lock();
-        let current_id = *self.current_id.lock();
-        let available = entries.iter().filter(|e| !e.disabled).count();
+        breaker.consecutive_failures = breaker.consecutive_failures.saturating_add(1);
+        let threshold = config.refresh_breaker_threshold;
+        if threshold > 0 && breaker.consecutive_failures >= threshold {
+            let cooldown = std::time::Duration::from_secs(config.refresh_breaker_cooldown_secs);
+            if breaker.open_until.is_none() {
+                tracing::warn!(
+                    "Token 连续刷新失败 {} 次，暂停主动刷新 {} 秒",
+                    breaker.consecutive_failures,
+                    cooldown.as_secs()
+                );
+            }
+            breaker.open_until = Some(std::time::Instant::now() + cooldown);
+        }
+    }
 
-        ManagerSnapshot {
-            entries: entries
-                .iter()
-                .map(|e| CredentialEntrySnapshot {
-                    id: e.id,
-                    priority: e.credentials.priority,
-                    disabled: e.disabled,
-                    failure_count: e.failure_count,
-                    auth_method: e.credentials.auth_method.clone(),
-                    has_profile_arn: e.credentials.profile_arn.is_some(),
-                    expires_at: e.credentials.expires_at.clone(),
-                })
-                .collect(),
-            current_id,
-            total: entries.len(),
-            available,
+    /// 刷新熔断是否打开（冷却结束后自动半开，允许下一次主动刷新试探）
+    fn refresh_breaker_open(&self) -> bool {
+        self.refresh_breaker
+            .lock()
+            .open_until
+            .is_some_and(|until| std::time::Instant::now() < until)
+    }
+
+    /// 记录凭据被选中一次（用于指标输出）
+    fn record_selection(&self, id: u64) {
+        let mut entries = self.entries.lock();
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            entry.usage.selections += 1;
+            entry.usage.last_used_at = Some(Utc::now());
         }
     }
 
-    /// 设置凭据禁用状态（Admin API）
-    pub fn set_disabled(&self, id: u64, disabled: bool) -> anyhow::Result<()> {
-        {
-            let mut entries = self.entries.lock();
-            let entry = entries
-                .iter_mut()
-                .find(|e| e.id == id)
-                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
-            entry.disabled = disabled;
-            if !disabled {
-                // 启用时重置失败计数
-                entry.failure_count = 0;
-                entry.disabled_reason = None;
-            } else {
-                entry.disabled_reason = Some(DisabledReason::Manual);
-            }
+    /// 记录指定凭据一次失败的上游请求（用于使用统计，不影响凭据选择）
+    pub fn record_upstream_failure(&self, id: u64) {
+        let mut entries = self.entries.lock();
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            entry.usage.failures += 1;
         }
-        // 持久化更改
-        self.persist_credentials()?;
-        Ok(())
     }
 
-    /// 设置凭据优先级（Admin API）
+    /// 记录一次客户端请求的结果（用于指标输出）
     ///
-    /// 修改优先级后会立即按新优先级重新选择当前凭据。
-    /// 即使持久化失败，内存中的优先级和当前凭据选择也会生效。
-    pub fn set_priority(&self, id: u64, priority: u32) -> anyhow::Result<()> {
-        {
-            let mut entries = self.entries.lock();
-            let entry = entries
-                .iter_mut()
-                .find(|e| e.id == id)
-                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
-            entry.credentials.priority = priority;
-        }
-        // 立即按新优先级重新选择当前凭据（无论持久化是否成功）
-        self.select_highest_priority();
-        // 持久化更改
-        self.persist_credentials()?;
-        Ok(())
+    /// 不同模型数达到 `metrics_max_series` 后，新出现的模型计入 `other`
+    pub fn record_request(&self, model: &str, status: u16) {
+        let max_series = self.config().metrics_max_series;
+        let mut counts = self.request_counts.lock();
+        let by_status = if let Some(by_status) = counts.get_mut(model) {
+            by_status
+        } else if max_series == 0 || counts.len() < max_series {
+            counts.entry(model.to_string()).or_default()
+        } else {
+            counts.entry("other".to_string()).or_default()
+        };
+        *by_status.entry(status).or_insert(0) += 1;
     }
 
-    /// 重置凭据失败计数并重新启用（Admin API）
-    pub fn reset_and_enable(&self, id: u64) -> anyhow::Result<()> {
-        {
-            let mut entries = self.entries.lock();
-            let entry = entries
-                .iter_mut()
-                .find(|e| e.id == id)
-                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
-            entry.failure_count = 0;
-            entry.disabled = false;
-            entry.disabled_reason = None;
+    /// 按（模型, 状态码）排序的请求数
+    pub fn request_counts(&self) -> Vec<(String, u16, u64)> {
+        let mut counts: Vec<_> = self
+            .request_counts
+            .lock()
+            .iter()
+            .flat_map(|(model, by_status)| {
+                by_status
+                    .iter()
+                    .map(move |(status, count)| (model.clone(), *status, *count))
+            })
+            .collect();
+        counts.sort();
+        counts
+    }
+
+    /// Token 刷新结果计数（成功, 失败）
+    pub fn refresh_counts(&self) -> (u64, u64) {
+        (
+            self.refresh_counts[0].load(Ordering::Relaxed),
+            self.refresh_counts[1].load(Ordering::Relaxed),
+        )
+    }
+
+    /// 记录一次失败的请求
+    ///
+    /// `status` 为最后一次上游响应的状态码，`credential_id` 为最后一次尝试使用的凭据
+    pub fn record_request_error(
+        &self,
+        error: &anyhow::Error,
+        status: Option<u16>,
+        credential_id: Option<u64>,
+    ) {
+        self.recent_errors.push(RecentError::new(error, status, credential_id));
+    }
+
+    /// 最近失败的请求（最新的在前），`limit` 为 None 时返回全部
+    pub fn recent_errors(&self, limit: Option<usize>) -> Vec<RecentError> {
+        self.recent_errors.latest(limit)
+    }
+
+    /// 最近错误的保留容量
+    pub fn recent_errors_capacity(&self) -> usize {
+        self.recent_errors.capacity()
+    }
+
+    /// 获取 Token 刷新熔断器状态
+    pub fn refresh_breaker_status(&self) -> RefreshBreakerStatus {
+        let breaker = self.refresh_breaker.lock();
+        let remaining = breaker
+            .open_until
+            .and_then(|until| until.checked_duration_since(std::time::Instant::now()))
+            .filter(|remaining| !remaining.is_zero());
+        RefreshBreakerStatus {
+            open: remaining.is_some(),
+            consecutive_failures: breaker.consecutive_failures,
+            threshold: self.config().refresh_breaker_threshold,
+            remaining_secs: remaining.map(|d| d.as_secs_f64().ceil() as u64),
         }
-        // 持久化更改
-        self.persist_credentials()?;
-        Ok(())
     }
 
-    /// 获取指定凭据的使用额度（Admin API）
-    pub async fn get_usage_limits_for(&self, id: u64) -> anyhow::Result<UsageLimitsResponse> {
-        let credentials = {
+    /// 主动刷新即将过期的 Token
+    ///
+    /// 刷新熔断打开时跳过（本轮中途打开时停止剩余刷新）。返回本轮实际发起的刷新次数
+    pub async fn proactive_refresh(&self) -> usize {
+        if self.refresh_breaker_open() {
+            tracing::debug!("Token 刷新熔断中，跳过主动刷新");
+            return 0;
+        }
+
+        let window = self.proactive_refresh_window();
+        let candidates: Vec<u64> = {
             let entries = self.entries.lock();
             entries
                 .iter()
-                .find(|e| e.id == id)
-                .map(|e| e.credentials.clone())
-                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
+                .filter(|e| !e.disabled && e.credentials.refresh_token.is_some())
+                .filter(|e| is_token_expiring_within(&e.credentials, window).unwrap_or(true))
+                .map(|e| e.id)
+                .collect()
         };
 
-        // 检查是否需要刷新 token
-        let needs_refresh = is_token_expired(&credentials) || is_token_expiring_soon(&credentials);
+        let mut attempted = 0;
+        for id in candidates {
+            if self.refresh_breaker_open() {
+                break;
+            }
 
-        let token = if needs_refresh {
             let _guard = self.refresh_lock.lock().await;
-            let current_creds = {
-                let entries = self.entries.lock();
-                entries
-                    .iter()
-                    .find(|e| e.id == id)
-                    .map(|e| e.credentials.clone())
-                    .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
+            let Some(current_creds) = self
+                .entries
+                .lock()
+                .iter()
+                .find(|e| e.id == id && !e.disabled)
+                .map(|e| e.credentials.clone())
+            else {
+                continue;
             };
+            // 获取锁期间可能已被按需刷新；处于最小刷新间隔内的凭据留待下一轮
+            if is_token_expiring_within(&current_creds, window) == Some(false)
+                || self.refresh_throttled_for(id).is_some()
+            {
+                continue;
+            }
 
-            if is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds) {
-                let new_creds =
-                    refresh_token(&current_creds, &self.config, self.proxy.as_ref()).await?;
-                {
-                    let mut entries = self.entries.lock();
-                    if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
-                        entry.credentials = new_creds.clone();
+            attempted += 1;
+            match self.refresh_with_breaker(&current_creds).await {
+                Ok(new_creds) => {
+                    {
+                        let mut entries = self.entries.lock();
+                        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+                            entry.replace_credentials(new_creds);
+                        }
+                    }
+                    if let Err(e) = self.persist_credentials() {
+                        tracing::warn!("Token 主动刷新后持久化失败: {}", e);
                     }
                 }
-                // 持久化失败只记录警告，不影响本次请求
-                if let Err(e) = self.persist_credentials() {
-                    tracing::warn!("Token 刷新后持久化失败（不影响本次请求）: {}", e);
+                Err(e) => {
+                    tracing::warn!(
+                        "凭据 {} Token 主动刷新失败: {}",
+                        credential_label(&self.config(), id),
+                        e
+                    );
                 }
-                new_creds
-                    .access_token
-                    .ok_or_else(|| anyhow::anyhow!("刷新后无 access_token"))?
-            } else {
-                current_creds
-                    .access_token
-                    .ok_or_else(|| anyhow::anyhow!("凭据无 access_token"))?
             }
-        } else {
-            credentials
-                .access_token
-                .ok_or_else(|| anyhow::anyhow!("凭据无 access_token"))?
-        };
+        }
+        attempted
+    }
 
-        let credentials = {
-            let entries = self.entries.lock();
-            entries
-                .iter()
-                .find(|e| e.id == id)
-                .map(|e| e.credentials.clone())
-                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
-        };
+    /// 启动 Token 主动刷新任务
+    pub fn start_proactive_refresh_task(
+        self: std::sync::Arc<Self>,
+        interval: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // 跳过立即触发的第一次 tick
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                self.proactive_refresh().await;
+            }
+        })
+    }
 
-        get_usage_limits(&credentials, &self.config, &token, self.proxy.as_ref()).await
+    /// 当前月度用量所属月份（按 `usage_reset_timezone` 计算）
+    fn current_usage_period(&self) -> String {
+        let offset = monthly_budget::parse_utc_offset(&self.config().usage_reset_timezone)
+            .unwrap_or_else(|_| chrono::FixedOffset::east_opt(0).unwrap());
+        monthly_budget::usage_period(Utc::now(), offset)
     }
 
-    /// 添加新凭据（Admin API）
-    ///
-    /// # 流程
-    /// 1. 验证凭据基本字段（refresh_token 不为空）
-    /// 2. 尝试刷新 Token 验证凭据有效性
-    /// 3. 分配新 ID（当前最大 ID + 1）
-    /// 4. 添加到 entries 列表
-    /// 5. 持久化到配置文件
+    /// 记录指定凭据本次请求消耗的 token 数
     ///
-    /// # 返回
-    /// - `Ok(u64)` - 新凭据 ID
-    /// - `Err(_)` - 验证失败或添加失败
-    pub async fn add_credential(&self, new_cred: KiroCredentials) -> anyhow::Result<u64> {
-        // 1. 基本验证
-        validate_refresh_token(&new_cred)?;
-
-        // 2. 尝试刷新 Token 验证凭据有效性
-        let mut validated_cred =
-            refresh_token(&new_cred, &self.config, self.proxy.as_ref()).await?;
+    /// 所有凭据均计入指标；仅对配置了 `monthly_token_limit` 的凭据计数并持久化，
+    /// 达到上限后该凭据在本月内不再被选中
+    pub fn record_token_usage(&self, id: u64, tokens: u64) {
+        let period = self.current_usage_period();
+        {
+            let mut entries = self.entries.lock();
+            let Some(entry) = entries.iter_mut().find(|e| e.id == id) else {
+                return;
+            };
+            entry.usage.tokens = entry.usage.tokens.saturating_add(tokens);
+            let Some(limit) = entry.credentials.monthly_token_limit else {
+                return;
+            };
 
-        // 3. 分配新 ID
-        let new_id = {
-            let entries = self.entries.lock();
-            entries.iter().map(|e| e.id).max().unwrap_or(0) + 1
-        };
+            let used = monthly_budget::add_usage(&mut entry.credentials, &period, tokens);
+            if used >= limit && used - tokens.min(used) < limit {
+                tracing::warn!(
+                    "凭据 {} 已达到月度 token 上限（{}/{}，{}），本月内不再选择",
+                    credential_label(&self.config(), id),
+                    used,
+                    limit,
+                    period
+                );
+            }
+        }
 
-        // 4. 设置 ID 并保留用户输入的元数据
-        validated_cred.id = Some(new_id);
-        validated_cred.priority = new_cred.priority;
-        validated_cred.auth_method = new_cred.auth_method;
-        validated_cred.client_id = new_cred.client_id;
-        validated_cred.client_secret = new_cred.client_secret;
+        self.persist_runtime_state();
+    }
 
+    /// 记录指定凭据本次请求的输入/输出 token 数
+    ///
+    /// 分别计入使用统计，合计通过 [`Self::record_token_usage`] 计入指标和月度用量
+    pub fn record_request_tokens(&self, id: u64, input_tokens: u64, output_tokens: u64) {
         {
             let mut entries = self.entries.lock();
-            entries.push(CredentialEntry {
-                id: new_id,
-                credentials: validated_cred,
-                failure_count: 0,
-                disabled: false,
-                disabled_reason: None,
-            });
+            if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+                entry.usage.input_tokens = entry.usage.input_tokens.saturating_add(input_tokens);
+                entry.usage.output_tokens =
+                    entry.usage.output_tokens.saturating_add(output_tokens);
+            }
         }
+        self.record_token_usage(id, input_tokens.saturating_add(output_tokens));
+    }
 
-        // 5. 持久化
-        self.persist_credentials()?;
+    /// 持久化运行时状态（月度用量、额度用尽窗口）
+    ///
+    /// `runtime_state_persist_interval_secs` 为 0 时立即回写；
+    /// 否则仅标记为待持久化，由后台任务按间隔合并写入
+    fn persist_runtime_state(&self) {
+        if self.config().runtime_state_persist_interval_secs > 0 {
+            self.runtime_state_dirty.store(true, Ordering::Relaxed);
+            return;
+        }
+        if let Err(e) = self.persist_credentials() {
+            tracing::warn!("运行时状态持久化失败: {}", e);
+        }
+    }
 
-        tracing::info!("成功添加凭据 #{}", new_id);
-        Ok(new_id)
+    /// 回写待持久化的运行时状态，返回是否执行了写入
+    pub fn flush_runtime_state(&self) -> bool {
+        if !self.runtime_state_dirty.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        if let Err(e) = self.persist_credentials() {
+            tracing::warn!("运行时状态持久化失败: {}", e);
+            // 保留标记，下次重试
+            self.runtime_state_dirty.store(true, Ordering::Relaxed);
+        }
+        true
     }
 
-    /// 删除凭据（Admin API）
-    ///
-    /// # 前置条件
-    /// - 凭据必须已禁用（disabled = true）
-    ///
-    /// # 行为
-    /// 1. 验证凭据存在
-    /// 2. 验证凭据已禁用
-    /// 3. 从 entries 移除
-    /// 4. 如果删除的是当前凭据，切换到优先级最高的可用凭据
-    /// 5. 如果删除后没有凭据，将 current_id 重置为 0
-    /// 6. 持久化到文件
+    /// 退出前回写所有待持久化的变更
     ///
-    /// # 返回
-    /// - `Ok(())` - 删除成功
-    /// - `Err(_)` - 凭据不存在、未禁用或持久化失败
-    pub fn delete_credential(&self, id: u64) -> anyhow::Result<()> {
-        let was_current = {
-            let mut entries = self.entries.lock();
-
-            // 查找凭据
-            let entry = entries
-                .iter()
-                .find(|e| e.id == id)
-                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+    /// 先回写按间隔合并中的运行时状态，再等待所有后台存储写入完成
+    pub async fn flush_pending_writes(&self) {
+        self.flush_runtime_state();
+        let pending = std::mem::take(&mut *self.pending_saves.lock());
+        for task in pending {
+            let _ = task.await;
+        }
+    }
 
-            // 检查是否已禁用
-            if !entry.disabled {
-                anyhow::bail!("只能删除已禁用的凭据（请先禁用凭据 #{}）", id);
+    /// 启动运行时状态定期持久化任务
+    pub fn start_runtime_state_persist_task(
+        self: std::sync::Arc<Self>,
+        interval: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // 跳过立即触发的第一次 tick
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                self.flush_runtime_state();
             }
+        })
+    }
 
-            // 记录是否是当前凭据
-            let current_id = *self.current_id.lock();
-            let was_current = current_id == id;
-
-            // 删除凭据
-            entries.retain(|e| e.id != id);
+    /// 报告指定凭据 API 调用失败
+    ///
+    /// 增加失败计数，达到 `breaker_failure_threshold` 时：
+    /// - `breaker_cooldown_secs` 为 0：禁用凭据
+    /// - 否则打开熔断器，冷却期间跳过该凭据（半开试探失败时直接重新打开）
+    ///
+    /// 随后切换到优先级最高的可用凭据，返回是否还有可用凭据可以重试
+    ///
+    /// # Arguments
+    /// * `id` - 凭据 ID（来自 CallContext）
+    pub fn report_failure(&self, id: u64) -> bool {
+        let config = self.config();
+        let mut entries = self.entries.lock();
+        let mut current_id = self.current_id.lock();
+        let now = std::time::Instant::now();
+        let cooldown = breaker_cooldown(&config);
+        let threshold = config.breaker_failure_threshold.max(1);
+        let is_available = |e: &CredentialEntry| !e.disabled && !e.breaker.blocks(now, cooldown);
 
-            was_current
+        let entry = match entries.iter_mut().find(|e| e.id == id) {
+            Some(e) => e,
+            None => return entries.iter().any(is_available),
         };
 
-        // 如果删除的是当前凭据，切换到优先级最高的可用凭据
-        if was_current {
-            self.select_highest_priority();
+        entry.failure_count += 1;
+        entry.health.record_outcome(false);
+        let failure_count = entry.failure_count;
+
+        tracing::warn!(
+            "凭据 {} API 调用失败（{}/{}）",
+            credential_label(&config, id),
+            failure_count,
+            threshold
+        );
+
+        let probe_failed = entry.breaker.state(now) == BreakerState::HalfOpen;
+        if !cooldown.is_zero() && (probe_failed || failure_count >= threshold) {
+            entry.breaker.open(now, cooldown);
+            self.publish_event(CredentialEvent::BreakerStateChanged {
+                id,
+                state: BreakerState::Open,
+            });
+            tracing::error!(
+                "凭据 {} {}，熔断 {} 秒",
+                credential_label(&config, id),
+                if probe_failed {
+                    "试探请求失败".to_string()
+                } else {
+                    format!("已连续失败 {} 次", failure_count)
+                },
+                cooldown.as_secs()
+            );
+        } else if failure_count >= threshold {
+            entry.disabled = true;
+            entry.disabled_reason = Some(DisabledReason::TooManyFailures);
+            self.publish_event(CredentialEvent::CredentialDisabled {
+                id,
+                reason: DisabledReason::TooManyFailures.as_str(),
+            });
+            tracing::error!(
+                "凭据 {} 已连续失败 {} 次，已被禁用",
+                credential_label(&config, id),
+                failure_count
+            );
+        } else {
+            return entries.iter().any(is_available);
         }
 
-        // 如果删除后没有任何凭据，将 current_id 重置为 0（与初始化行为保持一致）
+        // 切换到优先级最高的可用凭据
+        if let Some(next) = entries
+            .iter()
+            .filter(|e| is_available(e))
+            .min_by_key(|e| effective_priority(e, &config))
         {
-            let entries = self.entries.lock();
-            if entries.is_empty() {
-                let mut current_id = self.current_id.lock();
-                *current_id = 0;
-                tracing::info!("所有凭据已删除，current_id 已重置为 0");
-            }
+            *current_id = next.id;
+            tracing::info!(
+                "已切换到凭据 {}（优先级 {}）",
+                credential_label(&config, next.id),
+                next.credentials.priority
+            );
+            true
+        } else {
+            tracing::error!("所有凭据均已禁用或处于熔断中！");
+            false
         }
-
-        // 持久化更改
-        self.persist_credentials()?;
-
-        tracing::info!("已删除凭据 #{}", id);
-        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// 报告指定凭据被上游限流（429）
+    ///
+    /// 429 属于瞬态错误，不计入失败次数，也不会禁用凭据；仅在启用熔断冷却（`breaker_cooldown_secs`
+    /// 大于 0）时，同一凭据连续限流达到 `breaker_failure_threshold` 次后熔断，冷却期间选择其他凭据
+    pub fn report_rate_limited(&self, id: u64) {
+        let config = self.config();
+        let cooldown = breaker_cooldown(&config);
+        if cooldown.is_zero() {
+            return;
+        }
+        let threshold = config.breaker_failure_threshold.max(1);
 
-    #[test]
-    fn test_token_manager_new() {
-        let config = Config::default();
-        let credentials = KiroCredentials::default();
-        let tm = TokenManager::new(config, credentials, None);
-        assert!(tm.credentials().access_token.is_none());
+        let mut entries = self.entries.lock();
+        let Some(entry) = entries.iter_mut().find(|e| e.id == id) else {
+            return;
+        };
+        entry.breaker.consecutive_rate_limits += 1;
+        if entry.breaker.consecutive_rate_limits >= threshold {
+            entry.breaker.open(std::time::Instant::now(), cooldown);
+            entry.breaker.consecutive_rate_limits = 0;
+            self.publish_event(CredentialEvent::BreakerStateChanged {
+                id,
+                state: BreakerState::Open,
+            });
+            tracing::warn!(
+                "凭据 {} 已连续 {} 次被上游限流，熔断 {} 秒",
+                credential_label(&config, id),
+                threshold,
+                cooldown.as_secs()
+            );
+        }
     }
 
-    #[test]
-    fn test_is_token_expired_with_expired_token() {
-        let mut credentials = KiroCredentials::default();
-        credentials.expires_at = Some("2020-01-01T00:00:00Z".to_string());
-        assert!(is_token_expired(&credentials));
-    }
+    /// 报告指定凭据额度已用尽
+    ///
+    /// 用于处理 402 Payment Required 且 reason 为 `MONTHLY_REQUEST_COUNT` 的场景：
+    /// - 立即禁用该凭据（不等待连续失败阈值）
+    /// - 记录额度恢复时间（下一个用量月份起点）并持久化，重启后在此之前仍跳过该凭据
+    /// - 切换到下一个可用凭据继续重试
+    /// - 返回是否还有可用凭据
+    pub fn report_quota_exhausted(&self, id: u64) -> bool {
+        let config = self.config();
+        let quota_exhausted_until = {
+            let offset = monthly_budget::parse_utc_offset(&config.usage_reset_timezone)
+                .unwrap_or_else(|_| chrono::FixedOffset::east_opt(0).unwrap());
+            monthly_budget::next_period_start(Utc::now(), offset).to_rfc3339()
+        };
 
-    #[test]
+        let has_available = {
+            let mut entries = self.entries.lock();
+            let mut current_id = self.current_id.lock();
+
+            let entry = match entries.iter_mut().find(|e| e.id == id) {
+                Some(e) => e,
+                None => return entries.iter().any(|e| !e.disabled),
+            };
+
+            if entry.disabled {
+                return entries.iter().any(|e| !e.disabled);
+            }
+
+            entry.disabled = true;
+            entry.disabled_reason = Some(DisabledReason::QuotaExceeded);
+            self.publish_event(CredentialEvent::CredentialDisabled {
+                id,
+                reason: DisabledReason::QuotaExceeded.as_str(),
+            });
+            entry.health.record_outcome(false);
+            // 设为阈值，便于在管理面板中直观看到该凭据已不可用
+            entry.failure_count = config.breaker_failure_threshold;
+            // 记录额度恢复时间并持久化，重启后在此之前仍跳过该凭据
+            entry.credentials.quota_exhausted_until = Some(quota_exhausted_until.clone());
+
+            tracing::error!(
+                "凭据 {} 额度已用尽（MONTHLY_REQUEST_COUNT），已被禁用，{} 前不再选择",
+                credential_label(&config, id),
+                quota_exhausted_until
+            );
+
+            // 切换到优先级最高的可用凭据
+            if let Some(next) = entries
+                .iter()
+                .filter(|e| !e.disabled)
+                .min_by_key(|e| effective_priority(e, &config))
+            {
+                *current_id = next.id;
+                tracing::info!(
+                    "已切换到凭据 {}（优先级 {}）",
+                    credential_label(&config, next.id),
+                    next.credentials.priority
+                );
+                true
+            } else {
+                tracing::error!("所有凭据均已禁用！");
+                false
+            }
+        };
+
+        self.persist_runtime_state();
+        has_available
+    }
+
+    /// 切换到优先级最高的可用凭据
+    ///
+    /// 返回是否成功切换
+    pub fn switch_to_next(&self) -> bool {
+        let config = self.config();
+        let entries = self.entries.lock();
+        let mut current_id = self.current_id.lock();
+
+        // 选择优先级最高的未禁用凭据（排除当前凭据）
+        if let Some(next) = entries
+            .iter()
+            .filter(|e| !e.disabled && e.id != *current_id)
+            .min_by_key(|e| effective_priority(e, &config))
+        {
+            *current_id = next.id;
+            tracing::info!(
+                "已切换到凭据 {}（优先级 {}）",
+                credential_label(&config, next.id),
+                next.credentials.priority
+            );
+            true
+        } else {
+            // 没有其他可用凭据，检查当前凭据是否可用
+            entries.iter().any(|e| e.id == *current_id && !e.disabled)
+        }
+    }
+
+    /// 获取使用额度信息
+    pub async fn get_usage_limits(&self) -> anyhow::Result<UsageLimitsResponse> {
+        let ctx = self.acquire_context().await?;
+        get_usage_limits(
+            &ctx.credentials,
+            &self.config(),
+            &ctx.token,
+            self.proxy().as_deref(),
+            self.usage_limits_url.as_deref(),
+        )
+        .await
+    }
+
+    // ========================================================================
+    // Admin API 方法
+    // ========================================================================
+
+    /// 获取管理器状态快照（用于 Admin API）
+    pub fn snapshot(&self) -> ManagerSnapshot {
+        let entries = self.entries.lock();
+        let current_id = *self.current_id.lock();
+        let available = entries.iter().filter(|e| !e.disabled).count();
+        let period = self.current_usage_period();
+        let now = std::time::Instant::now();
+
+        ManagerSnapshot {
+            entries: entries
+                .iter()
+                .map(|e| CredentialEntrySnapshot {
+                    id: e.id,
+                    priority: e.credentials.priority,
+                    disabled: e.disabled,
+                    failure_count: e.failure_count,
+                    auth_method: e.credentials.auth_method.clone(),
+                    has_profile_arn: e.credentials.profile_arn.is_some(),
+                    expires_at: e.credentials.expires_at.clone(),
+                    health_score: health_score(&e.health, &e.credentials, &period),
+                    breaker_state: e.breaker.state(now),
+                    breaker_remaining_secs: e.breaker.remaining_secs(now),
+                    last_used_at: e.usage.last_used_at,
+                })
+                .collect(),
+            current_id,
+            pinned_id: self.pinned_credential_id(),
+            total: entries.len(),
+            available,
+        }
+    }
+
+    /// 凭据统计快照（Admin API 事件推送）
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        let entries = self.entries.lock();
+        let now = std::time::Instant::now();
+        StatsSnapshot {
+            total: entries.len(),
+            available: entries.iter().filter(|e| !e.disabled).count(),
+            breaker_open: entries
+                .iter()
+                .filter(|e| e.breaker.state(now) != BreakerState::Closed)
+                .count(),
+            current_id: *self.current_id.lock(),
+            pinned_id: self.pinned_credential_id(),
+        }
+    }
+
+    /// 按 ID 索引的凭据副本（含 token 等敏感字段，Admin API 按配置脱敏后返回）
+    pub fn credentials_by_id(&self) -> HashMap<u64, KiroCredentials> {
+        let entries = self.entries.lock();
+        entries.iter().map(|e| (e.id, e.credentials.clone())).collect()
+    }
+
+    /// 凭据选择时按原因累计的跳过次数
+    ///
+    /// 当前凭据不可用、需要在凭据池中重新选择时，每个被跳过的凭据按原因计数一次
+    pub fn selection_skip_counts(&self) -> Vec<(SkipReason, u64)> {
+        SkipReason::ALL
+            .iter()
+            .map(|reason| {
+                let count = self.selection_skips[*reason as usize].load(Ordering::Relaxed);
+                (*reason, count)
+            })
+            .collect()
+    }
+
+    /// 各凭据的累计服务量（按 ID 排序，用于 `/metrics`）
+    pub fn credential_usage_metrics(&self) -> Vec<CredentialUsageMetric> {
+        let config = self.config();
+        let entries = self.entries.lock();
+        let mut metrics: Vec<CredentialUsageMetric> = entries
+            .iter()
+            .map(|e| CredentialUsageMetric {
+                id: e.id,
+                label: credential_label(&config, e.id),
+                tag: metrics_tag_of(&e.credentials, &config),
+                selections: e.usage.selections,
+                requests: e.usage.requests,
+                tokens: e.usage.tokens,
+            })
+            .collect();
+        metrics.sort_by_key(|m| m.id);
+        metrics
+    }
+
+    /// 指定凭据的累计使用统计（Admin API）
+    pub fn credential_stats(&self, id: u64) -> anyhow::Result<CredentialStats> {
+        let entries = self.entries.lock();
+        let entry = entries
+            .iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+        let usage = &entry.usage;
+        Ok(CredentialStats {
+            id,
+            requests: usage.requests + usage.failures,
+            successes: usage.requests,
+            failures: usage.failures,
+            selections: usage.selections,
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            last_used_at: usage.last_used_at.map(|at| at.to_rfc3339()),
+        })
+    }
+
+    /// 清零指定凭据的累计使用统计（Admin API）
+    ///
+    /// `/metrics` 中该凭据的计数同时归零；不影响失败计数、熔断和月度用量
+    pub fn reset_credential_stats(&self, id: u64) -> anyhow::Result<()> {
+        let mut entries = self.entries.lock();
+        let entry = entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+        entry.usage = UsageCounters::default();
+        Ok(())
+    }
+
+    /// 设置凭据禁用状态（Admin API）
+    pub fn set_disabled(&self, id: u64, disabled: bool) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.disabled = disabled;
+            if !disabled {
+                // 启用时重置失败计数、熔断器和额度用尽窗口
+                entry.failure_count = 0;
+                entry.breaker = CredentialBreaker::default();
+                entry.disabled_reason = None;
+                entry.credentials.quota_exhausted_until = None;
+            } else {
+                entry.disabled_reason = Some(DisabledReason::Manual);
+            }
+        }
+        self.publish_disabled_change(id, disabled);
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 发布 Admin API 手动禁用/启用事件
+    fn publish_disabled_change(&self, id: u64, disabled: bool) {
+        self.publish_event(if disabled {
+            CredentialEvent::CredentialDisabled {
+                id,
+                reason: DisabledReason::Manual.as_str(),
+            }
+        } else {
+            CredentialEvent::CredentialEnabled { id }
+        });
+    }
+
+    /// 批量设置凭据禁用状态（Admin API）
+    ///
+    /// 在同一次加锁内更新所有匹配 `matches` 的凭据，最后只持久化一次。
+    /// 禁用了当前凭据时按优先级重新选择。返回匹配的凭据 ID 列表
+    pub fn set_disabled_where(
+        &self,
+        matches: impl Fn(&KiroCredentials) -> bool,
+        disabled: bool,
+    ) -> anyhow::Result<Vec<u64>> {
+        let affected: Vec<u64> = {
+            let mut entries = self.entries.lock();
+            entries
+                .iter_mut()
+                .filter(|e| matches(&e.credentials))
+                .map(|entry| {
+                    entry.disabled = disabled;
+                    if disabled {
+                        entry.disabled_reason = Some(DisabledReason::Manual);
+                    } else {
+                        // 启用时重置失败计数、熔断器和额度用尽窗口
+                        entry.failure_count = 0;
+                        entry.breaker = CredentialBreaker::default();
+                        entry.disabled_reason = None;
+                        entry.credentials.quota_exhausted_until = None;
+                    }
+                    entry.id
+                })
+                .collect()
+        };
+
+        if affected.is_empty() {
+            return Ok(affected);
+        }
+        for &id in &affected {
+            self.publish_disabled_change(id, disabled);
+        }
+
+        let current_id = *self.current_id.lock();
+        if disabled && affected.contains(&current_id) {
+            self.select_highest_priority();
+        }
+
+        self.persist_credentials()?;
+        Ok(affected)
+    }
+
+    /// 设置凭据优先级（Admin API）
+    ///
+    /// 修改优先级后会立即按新优先级重新选择当前凭据。
+    /// 即使持久化失败，内存中的优先级和当前凭据选择也会生效。
+    pub fn set_priority(&self, id: u64, priority: u32) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.credentials.priority = priority;
+        }
+        // 立即按新优先级重新选择当前凭据（无论持久化是否成功）
+        self.select_highest_priority();
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 一次更新凭据的多个可变字段（Admin API）
+    ///
+    /// 所有字段在同一次加锁内生效，最后只持久化一次；禁用/启用的语义与 `set_disabled` 一致。
+    /// 修改了优先级或禁用了当前凭据时按优先级重新选择当前凭据
+    pub fn update_credential(&self, id: u64, update: CredentialUpdate) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            if let Some(priority) = update.priority {
+                entry.credentials.priority = priority;
+            }
+            if let Some(weight) = update.weight {
+                entry.credentials.weight = weight;
+            }
+            if let Some(proxy_url) = update.proxy_url {
+                // 清除代理地址时一并清除代理认证信息，避免残留的凭据被之后设置的代理沿用
+                if proxy_url.is_none() {
+                    entry.credentials.proxy_username = None;
+                    entry.credentials.proxy_password = None;
+                }
+                entry.credentials.proxy_url = proxy_url;
+            }
+            if let Some(disabled) = update.disabled {
+                entry.disabled = disabled;
+                if disabled {
+                    entry.disabled_reason = Some(DisabledReason::Manual);
+                } else {
+                    // 启用时重置失败计数、熔断器和额度用尽窗口
+                    entry.failure_count = 0;
+                    entry.breaker = CredentialBreaker::default();
+                    entry.disabled_reason = None;
+                    entry.credentials.quota_exhausted_until = None;
+                }
+            }
+        }
+
+        if let Some(disabled) = update.disabled {
+            self.publish_disabled_change(id, disabled);
+        }
+        let current_id = *self.current_id.lock();
+        if update.priority.is_some() || (update.disabled == Some(true) && id == current_id) {
+            self.select_highest_priority();
+        }
+
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 重置凭据失败计数并重新启用（Admin API）
+    pub fn reset_and_enable(&self, id: u64) -> anyhow::Result<()> {
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            entry.failure_count = 0;
+            entry.breaker = CredentialBreaker::default();
+            entry.disabled = false;
+            entry.disabled_reason = None;
+            entry.credentials.quota_exhausted_until = None;
+        }
+        self.publish_disabled_change(id, false);
+        // 持久化更改
+        self.persist_credentials()?;
+        Ok(())
+    }
+
+    /// 获取指定凭据的使用额度（Admin API）
+    pub async fn get_usage_limits_for(&self, id: u64) -> anyhow::Result<UsageLimitsResponse> {
+        let credentials = {
+            let entries = self.entries.lock();
+            entries
+                .iter()
+                .find(|e| e.id == id)
+                .map(|e| e.credentials.clone())
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
+        };
+
+        // 检查是否需要刷新 token
+        let needs_refresh = self.needs_refresh(&credentials);
+
+        let token = if needs_refresh {
+            let _guard = self.refresh_lock.lock().await;
+            let current_creds = {
+                let entries = self.entries.lock();
+                entries
+                    .iter()
+                    .find(|e| e.id == id)
+                    .map(|e| e.credentials.clone())
+                    .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
+            };
+
+            if self.needs_refresh(&current_creds) {
+                let new_creds = self.refresh_with_breaker(&current_creds).await?;
+                {
+                    let mut entries = self.entries.lock();
+                    if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+                        entry.replace_credentials(new_creds.clone());
+                    }
+                }
+                // 持久化失败只记录警告，不影响本次请求
+                if let Err(e) = self.persist_credentials() {
+                    tracing::warn!("Token 刷新后持久化失败（不影响本次请求）: {}", e);
+                }
+                new_creds
+                    .access_token
+                    .ok_or_else(|| anyhow::anyhow!("刷新后无 access_token"))?
+            } else {
+                current_creds
+                    .access_token
+                    .ok_or_else(|| anyhow::anyhow!("凭据无 access_token"))?
+            }
+        } else {
+            credentials
+                .access_token
+                .ok_or_else(|| anyhow::anyhow!("凭据无 access_token"))?
+        };
+
+        let credentials = {
+            let entries = self.entries.lock();
+            entries
+                .iter()
+                .find(|e| e.id == id)
+                .map(|e| e.credentials.clone())
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
+        };
+
+        get_usage_limits(
+            &credentials,
+            &self.config(),
+            &token,
+            self.proxy().as_deref(),
+            self.usage_limits_url.as_deref(),
+        )
+        .await
+    }
+
+    /// 添加新凭据（Admin API）
+    ///
+    /// # 流程
+    /// 1. 验证凭据基本字段（refresh_token 不为空，指定的 ID 未被占用）
+    /// 2. 验证凭据有效性：携带仍在有效期内的 accessToken 时直接使用，否则刷新 Token 验证
+    /// 3. 保留用户输入的元数据
+    /// 4. 分配 ID（未指定时为当前最大 ID + 1）并添加到 entries 列表（立即参与凭据选择）
+    /// 5. 持久化（存储后端或配置文件）
+    ///
+    /// # 返回
+    /// - `Ok(u64)` - 新凭据 ID
+    /// - `Err(_)` - 验证失败或添加失败
+    pub async fn add_credential(&self, new_cred: KiroCredentials) -> anyhow::Result<u64> {
+        // 1. 基本验证
+        validate_refresh_token(&new_cred)?;
+        if new_cred.id == Some(0) {
+            bail!("凭据 ID 必须大于 0");
+        }
+        if let Some(id) = new_cred.id
+            && self.entries.lock().iter().any(|e| e.id == id)
+        {
+            bail!("凭据 ID {} 已存在", id);
+        }
+
+        // 2. 验证凭据有效性
+        let mut validated_cred =
+            if new_cred.access_token.is_some() && !self.needs_refresh(&new_cred) {
+                new_cred.clone()
+            } else {
+                refresh_token(&new_cred, &self.config(), self.proxy().as_deref()).await?
+            };
+
+        // 3. 保留用户输入的元数据
+        validated_cred.priority = new_cred.priority;
+        validated_cred.auth_method = new_cred.auth_method;
+        validated_cred.client_id = new_cred.client_id;
+        validated_cred.client_secret = new_cred.client_secret;
+        validated_cred.monthly_token_limit = new_cred.monthly_token_limit;
+        validated_cred.plan_cost = new_cred.plan_cost;
+
+        // 4. 分配 ID 并加入 entries（刷新期间指定的 ID 可能已被占用，持锁再次检查）
+        let new_id = {
+            let mut entries = self.entries.lock();
+            let new_id = match new_cred.id {
+                Some(id) if entries.iter().any(|e| e.id == id) => {
+                    bail!("凭据 ID {} 已存在", id)
+                }
+                Some(id) => id,
+                None => entries.iter().map(|e| e.id).max().unwrap_or(0) + 1,
+            };
+            validated_cred.id = Some(new_id);
+            entries.push(CredentialEntry {
+                id: new_id,
+                credentials: validated_cred.clone(),
+                failure_count: 0,
+                disabled: false,
+                disabled_reason: None,
+                health: HealthStats::default(),
+                usage: UsageCounters::default(),
+                breaker: CredentialBreaker::default(),
+                selection: SelectionState::default(),
+            });
+            new_id
+        };
+
+        // 5. 持久化
+        self.persist_added_credentials(vec![validated_cred])?;
+
+        tracing::info!("成功添加凭据 #{}", new_id);
+        Ok(new_id)
+    }
+
+    /// 删除凭据（Admin API）
+    ///
+    /// # 前置条件
+    /// - 凭据必须已禁用（disabled = true）
+    /// - 存储后端支持写操作（单凭据格式的凭据文件不支持删除）
+    ///
+    /// # 行为
+    /// 1. 验证凭据存在且已禁用，存储后端可写
+    /// 2. 从存储后端删除
+    /// 3. 从 entries 移除
+    /// 4. 如果删除的是当前凭据，切换到优先级最高的可用凭据
+    /// 5. 如果删除后没有凭据，将 current_id 重置为 0
+    /// 6. 回写剩余凭据
+    ///
+    /// 正在使用该凭据的请求持有独立的 `CallContext`，不受删除影响，可以正常完成；
+    /// 其后对该 ID 的 `report_*` 调用被忽略
+    ///
+    /// # 返回
+    /// - `Ok(())` - 删除成功
+    /// - `Err(_)` - 凭据不存在、未禁用、存储只读或持久化失败
+    pub async fn delete_credential(&self, id: u64) -> anyhow::Result<()> {
+        {
+            let entries = self.entries.lock();
+
+            // 查找凭据
+            let entry = entries
+                .iter()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+
+            // 检查是否已禁用
+            if !entry.disabled {
+                anyhow::bail!("只能删除已禁用的凭据（请先禁用凭据 #{}）", id);
+            }
+        }
+
+        // 检查存储是否可写（单凭据格式的文件不回写，删除会在重启后失效）
+        let writable = match &self.storage {
+            Some(storage) => storage.is_writable(),
+            None => self.credentials_path.is_none() || self.is_multiple_format,
+        };
+        if !writable {
+            anyhow::bail!("当前凭据存储不支持删除凭据（单凭据格式的凭据文件为只读，请改用数组格式）");
+        }
+
+        // 先从存储后端删除，失败时保留内存中的凭据
+        if let Some(storage) = &self.storage {
+            storage.delete(id).await?;
+        }
+
+        let was_current = {
+            let mut entries = self.entries.lock();
+            entries.retain(|e| e.id != id);
+            *self.current_id.lock() == id
+        };
+
+        // 如果删除的是当前凭据，切换到优先级最高的可用凭据
+        if was_current {
+            self.select_highest_priority();
+        }
+
+        // 如果删除后没有任何凭据，将 current_id 重置为 0（与初始化行为保持一致）
+        {
+            let entries = self.entries.lock();
+            if entries.is_empty() {
+                let mut current_id = self.current_id.lock();
+                *current_id = 0;
+                tracing::info!("所有凭据已删除，current_id 已重置为 0");
+            }
+        }
+
+        // 回写剩余凭据：覆盖删除前已排队的后台回写（如禁用时触发的回写仍包含该凭据）
+        self.persist_credentials()?;
+
+        tracing::info!("已删除凭据 #{}", id);
+        Ok(())
+    }
+
+    /// 批量导入凭据（Admin API）
+    ///
+    /// 按 `dedup_by` 指定的字段识别重复凭据（包括已有凭据和本批次内的凭据）：
+    /// - 新凭据优先级更高（priority 更小）时替换已有凭据内容，沿用已有 ID
+    /// - 否则保留已有凭据，跳过新凭据
+    ///
+    /// 导入不做 Token 刷新验证，Token 会在首次使用时按需刷新
+    pub fn import_credentials(
+        &self,
+        credentials: Vec<KiroCredentials>,
+        dedup_by: DedupKey,
+    ) -> anyhow::Result<ImportSummary> {
+        let config = self.config();
+        let mut summary = ImportSummary::default();
+        let mut added = Vec::new();
+
+        {
+            let mut entries = self.entries.lock();
+
+            for mut cred in credentials {
+                let key = dedup_by.value_of(&cred);
+                let existing = match &key {
+                    Some(key) => entries
+                        .iter_mut()
+                        .find(|e| dedup_by.value_of(&e.credentials).as_ref() == Some(key)),
+                    None => None,
+                };
+
+                if let Some(entry) = existing {
+                    if cred.priority < entry.credentials.priority {
+                        cred.id = Some(entry.id);
+                        if cred.machine_id.is_none() {
+                            cred.machine_id = entry.credentials.machine_id.clone();
+                        }
+                        entry.replace_credentials(cred);
+                        entry.failure_count = 0;
+                        summary.replaced += 1;
+                    } else {
+                        summary.skipped += 1;
+                    }
+                    continue;
+                }
+
+                // 保留未被占用的原始 ID，否则分配新 ID
+                let id = match cred.id {
+                    Some(id) if !entries.iter().any(|e| e.id == id) => id,
+                    _ => entries.iter().map(|e| e.id).max().unwrap_or(0) + 1,
+                };
+                cred.id = Some(id);
+                if cred.machine_id.is_none() {
+                    cred.machine_id = machine_id::generate_from_credentials(&cred, &config);
+                }
+
+                added.push(cred.clone());
+                entries.push(CredentialEntry {
+                    id,
+                    credentials: cred,
+                    failure_count: 0,
+                    disabled: false,
+                    disabled_reason: None,
+                    health: HealthStats::default(),
+                    usage: UsageCounters::default(),
+                    breaker: CredentialBreaker::default(),
+                    selection: SelectionState::default(),
+                });
+                summary.imported += 1;
+            }
+        }
+
+        // 导入前没有任何凭据时，选择优先级最高的凭据作为当前凭据
+        if *self.current_id.lock() == 0 {
+            self.select_highest_priority();
+        }
+
+        if summary.imported > 0 || summary.replaced > 0 {
+            self.persist_added_credentials(added)?;
+        }
+
+        tracing::info!(
+            "凭据导入完成（去重字段: {:?}）：新增 {}，替换 {}，跳过 {}",
+            dedup_by,
+            summary.imported,
+            summary.replaced,
+            summary.skipped
+        );
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_manager_new() {
+        let config = Config::default();
+        let credentials = KiroCredentials::default();
+        let tm = TokenManager::new(config, credentials, None);
+        assert!(tm.credentials().access_token.is_none());
+    }
+
+    #[test]
+    fn test_is_token_expired_with_expired_token() {
+        let mut credentials = KiroCredentials::default();
+        credentials.expires_at = Some("2020-01-01T00:00:00Z".to_string());
+        assert!(is_token_expired(&credentials));
+    }
+
+    #[test]
     fn test_is_token_expired_with_valid_token() {
         let mut credentials = KiroCredentials::default();
         let future = Utc::now() + Duration::hours(1);
@@ -1391,164 +3404,991 @@ mod tests {
     }
 
     #[test]
-    fn test_is_token_expired_within_5_minutes() {
-        let mut credentials = KiroCredentials::default();
-        let expires = Utc::now() + Duration::minutes(3);
-        credentials.expires_at = Some(expires.to_rfc3339());
-        assert!(is_token_expired(&credentials));
+    fn test_is_token_expired_within_5_minutes() {
+        let mut credentials = KiroCredentials::default();
+        let expires = Utc::now() + Duration::minutes(3);
+        credentials.expires_at = Some(expires.to_rfc3339());
+        assert!(is_token_expired(&credentials));
+    }
+
+    #[test]
+    fn test_is_token_expired_no_expires_at() {
+        let credentials = KiroCredentials::default();
+        assert!(is_token_expired(&credentials));
+    }
+
+    #[test]
+    fn test_is_token_expiring_soon_within_10_minutes() {
+        let mut credentials = KiroCredentials::default();
+        let expires = Utc::now() + Duration::minutes(8);
+        credentials.expires_at = Some(expires.to_rfc3339());
+        assert!(is_token_expiring_soon(&credentials, 600));
+    }
+
+    #[test]
+    fn test_is_token_expiring_soon_beyond_10_minutes() {
+        let mut credentials = KiroCredentials::default();
+        let expires = Utc::now() + Duration::minutes(15);
+        credentials.expires_at = Some(expires.to_rfc3339());
+        assert!(!is_token_expiring_soon(&credentials, 600));
+    }
+
+    #[test]
+    fn test_validate_refresh_token_missing() {
+        let credentials = KiroCredentials::default();
+        let result = validate_refresh_token(&credentials);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validate_refresh_token_valid() {
+        let mut credentials = KiroCredentials::default();
+        credentials.refresh_token = Some("a".repeat(150));
+        let result = validate_refresh_token(&credentials);
+        assert!(result.is_ok());
+    }
+
+    // MultiTokenManager 测试
+
+    #[test]
+    fn test_multi_token_manager_new() {
+        let config = Config::default();
+        let mut cred1 = KiroCredentials::default();
+        cred1.priority = 0;
+        let mut cred2 = KiroCredentials::default();
+        cred2.priority = 1;
+
+        let manager =
+            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
+        assert_eq!(manager.total_count(), 2);
+        assert_eq!(manager.available_count(), 2);
+    }
+
+    #[test]
+    fn test_multi_token_manager_empty_credentials() {
+        let config = Config::default();
+        let result = MultiTokenManager::new(config, vec![], None, None, false);
+        // 支持 0 个凭据启动（可通过管理面板添加）
+        assert!(result.is_ok());
+        let manager = result.unwrap();
+        assert_eq!(manager.total_count(), 0);
+        assert_eq!(manager.available_count(), 0);
+    }
+
+    #[test]
+    fn test_multi_token_manager_duplicate_ids() {
+        let config = Config::default();
+        let mut cred1 = KiroCredentials::default();
+        cred1.id = Some(1);
+        let mut cred2 = KiroCredentials::default();
+        cred2.id = Some(1); // 重复 ID
+
+        let result = MultiTokenManager::new(config, vec![cred1, cred2], None, None, false);
+        assert!(result.is_err());
+        let err_msg = result.err().unwrap().to_string();
+        assert!(
+            err_msg.contains("重复的凭据 ID"),
+            "错误消息应包含 '重复的凭据 ID'，实际: {}",
+            err_msg
+        );
+    }
+
+    #[test]
+    fn test_multi_token_manager_report_failure() {
+        let config = Config::default();
+        let cred1 = KiroCredentials::default();
+        let cred2 = KiroCredentials::default();
+
+        let manager =
+            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
+
+        // 凭据会自动分配 ID（从 1 开始）
+        // 前两次失败不会禁用（使用 ID 1）
+        assert!(manager.report_failure(1));
+        assert!(manager.report_failure(1));
+        assert_eq!(manager.available_count(), 2);
+
+        // 第三次失败会禁用第一个凭据
+        assert!(manager.report_failure(1));
+        assert_eq!(manager.available_count(), 1);
+
+        // 继续失败第二个凭据（使用 ID 2）
+        assert!(manager.report_failure(2));
+        assert!(manager.report_failure(2));
+        assert!(!manager.report_failure(2)); // 所有凭据都禁用了
+        assert_eq!(manager.available_count(), 0);
+    }
+
+    #[test]
+    fn test_multi_token_manager_report_success() {
+        let config = Config::default();
+        let cred = KiroCredentials::default();
+
+        let manager = MultiTokenManager::new(config, vec![cred], None, None, false).unwrap();
+
+        // 失败两次（使用 ID 1）
+        manager.report_failure(1);
+        manager.report_failure(1);
+
+        // 成功后重置计数（使用 ID 1）
+        manager.report_success(1);
+
+        // 再失败两次不会禁用
+        manager.report_failure(1);
+        manager.report_failure(1);
+        assert_eq!(manager.available_count(), 1);
+    }
+
+    #[test]
+    fn test_multi_token_manager_switch_to_next() {
+        let config = Config::default();
+        let mut cred1 = KiroCredentials::default();
+        cred1.refresh_token = Some("token1".to_string());
+        let mut cred2 = KiroCredentials::default();
+        cred2.refresh_token = Some("token2".to_string());
+
+        let manager =
+            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
+
+        // 初始是第一个凭据
+        assert_eq!(
+            manager.credentials().refresh_token,
+            Some("token1".to_string())
+        );
+
+        // 切换到下一个
+        assert!(manager.switch_to_next());
+        assert_eq!(
+            manager.credentials().refresh_token,
+            Some("token2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_near_expiry_credential_is_demoted() {
+        let config = Config {
+            demote_near_expiry_secs: 3600,
+            ..Default::default()
+        };
+
+        let near = KiroCredentials {
+            refresh_token: Some("near".to_string()),
+            expires_at: Some((Utc::now() + Duration::minutes(30)).to_rfc3339()),
+            ..Default::default()
+        };
+        let fresh = KiroCredentials {
+            refresh_token: Some("fresh".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(2)).to_rfc3339()),
+            ..Default::default()
+        };
+
+        let manager =
+            MultiTokenManager::new(config, vec![near, fresh], None, None, false).unwrap();
+
+        // 优先级相同：较新的凭据优先，临近过期的凭据其次
+        assert_eq!(
+            manager.credentials().refresh_token,
+            Some("fresh".to_string())
+        );
+        assert!(manager.switch_to_next());
+        assert_eq!(
+            manager.credentials().refresh_token,
+            Some("near".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lazy_storage_unavailable_until_first_load() {
+        use crate::kiro::storage::FileCredentialStorage;
+        use std::sync::Arc;
+
+        // 内容无法解析，模拟存储后端暂不可达
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "unreachable").unwrap();
+
+        let mut manager =
+            MultiTokenManager::new(Config::default(), vec![], None, None, false).unwrap();
+        manager.set_storage(Arc::new(FileCredentialStorage::new(file.path(), true)));
+        let manager = Arc::new(manager);
+        manager.mark_storage_pending();
+
+        let err = manager.acquire_context().await.err().unwrap();
+        assert!(err.is::<StorageUnavailableError>());
+
+        let connect = tokio::spawn({
+            let manager = manager.clone();
+            async move {
+                manager
+                    .connect_storage_lazily(std::time::Duration::from_millis(10))
+                    .await
+            }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!manager.is_storage_ready());
+
+        // 存储后端恢复
+        let expires_at = (Utc::now() + Duration::hours(1)).to_rfc3339();
+        std::fs::write(
+            file.path(),
+            format!(r#"[{{"id": 1, "accessToken": "t1", "expiresAt": "{}"}}]"#, expires_at),
+        )
+        .unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), connect)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(manager.is_storage_ready());
+        assert_eq!(manager.acquire_context().await.unwrap().token, "t1");
+    }
+
+    #[tokio::test]
+    async fn test_cheapest_mode_prefers_lower_plan_cost() {
+        let build = |mode| {
+            let config = Config {
+                credential_selection_mode: mode,
+                ..Default::default()
+            };
+
+            let expensive = KiroCredentials {
+                access_token: Some("expensive".to_string()),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                plan_cost: Some(20.0),
+                ..Default::default()
+            };
+            let cheap = KiroCredentials {
+                access_token: Some("cheap".to_string()),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                priority: 5,
+                plan_cost: Some(2.5),
+                ..Default::default()
+            };
+            let unpriced = KiroCredentials {
+                access_token: Some("unpriced".to_string()),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            };
+
+            MultiTokenManager::new(config, vec![expensive, cheap, unpriced], None, None, false)
+                .unwrap()
+        };
+
+        let manager = build(CredentialSelectionMode::Cheapest);
+        let ctx = manager.acquire_context().await.unwrap();
+        assert_eq!(ctx.token, "cheap");
+
+        // 排除最便宜的凭据后选择次便宜的，未配置成本的排在最后
+        let ctx = manager
+            .acquire_context_with(&AcquireOptions::excluding(vec![2]))
+            .await
+            .unwrap();
+        assert_eq!(ctx.token, "expensive");
+
+        // priority 模式下忽略 plan_cost
+        let manager = build(CredentialSelectionMode::Priority);
+        let ctx = manager.acquire_context().await.unwrap();
+        assert_eq!(ctx.token, "expensive");
+    }
+
+    #[tokio::test]
+    async fn test_auto_reorder_demotes_failing_credential() {
+        let config = Config {
+            auto_reorder: true,
+            ..Default::default()
+        };
+
+        let flaky = KiroCredentials {
+            access_token: Some("flaky".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let healthy = KiroCredentials {
+            access_token: Some("healthy".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            priority: 1,
+            ..Default::default()
+        };
+
+        let manager =
+            MultiTokenManager::new(config, vec![flaky, healthy], None, None, false).unwrap();
+        assert_eq!(manager.acquire_context().await.unwrap().token, "flaky");
+
+        // 凭据 #1 持续失败（未达到禁用阈值），凭据 #2 持续成功
+        for _ in 0..2 {
+            manager.report_failure(1);
+            manager.report_success(2);
+        }
+        manager.recompute_health_ranks();
+
+        let snapshot = manager.snapshot();
+        let score = |id| snapshot.entries.iter().find(|e| e.id == id).unwrap().health_score;
+        assert!(score(1) < score(2));
+        assert_eq!(snapshot.current_id, 2);
+        assert_eq!(manager.acquire_context().await.unwrap().token, "healthy");
+
+        // 持久化的 priority 不变
+        assert_eq!(snapshot.entries.iter().find(|e| e.id == 1).unwrap().priority, 0);
+    }
+
+    #[test]
+    fn test_log_credential_refs_hide_raw_ids() {
+        use crate::kiro::credential_ref::credential_ref;
+        use std::io::Write;
+        use std::sync::{Arc, Mutex as StdMutex};
+
+        #[derive(Clone, Default)]
+        struct Capture(Arc<StdMutex<Vec<u8>>>);
+        impl Write for Capture {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .without_time()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let config = Config {
+            log_credential_refs: true,
+            ..Default::default()
+        };
+        let mut creds = Vec::new();
+        for token in ["a", "b"] {
+            let cred = KiroCredentials {
+                access_token: Some(token.to_string()),
+                ..Default::default()
+            };
+            creds.push(cred);
+        }
+        let manager = MultiTokenManager::new(config, creds, None, None, false).unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            manager.report_success(1);
+            for _ in 0..Config::default().breaker_failure_threshold {
+                manager.report_failure(1);
+            }
+            manager.report_success(2);
+        });
+
+        let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains(&format!("凭据 {} API 调用成功", credential_ref(1))));
+        assert!(logs.contains(&format!("已切换到凭据 {}", credential_ref(2))));
+        assert_eq!(logs.matches(&credential_ref(2)).count(), 2);
+        assert!(!logs.contains("#1"));
+        assert!(!logs.contains("#2"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_breaker_pauses_proactive_refresh() {
+        // 通过无法连接的代理让每次刷新都立即失败
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = ProxyConfig::new(format!("http://{}", listener.local_addr().unwrap()));
+        drop(listener);
+
+        let config = Config {
+            refresh_breaker_threshold: 2,
+            refresh_breaker_cooldown_secs: 300,
+            ..Default::default()
+        };
+        let mut creds = Vec::new();
+        for _ in 0..2 {
+            let cred = KiroCredentials {
+                refresh_token: Some("r".repeat(120)),
+                expires_at: Some((Utc::now() - Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            };
+            creds.push(cred);
+        }
+        let manager = MultiTokenManager::new(config, creds, Some(proxy), None, false).unwrap();
+
+        assert_eq!(manager.proactive_refresh().await, 2);
+        let status = manager.refresh_breaker_status();
+        assert!(status.open);
+        assert_eq!(status.consecutive_failures, 2);
+        assert!(status.remaining_secs.unwrap() > 0);
+
+        // 熔断期间暂停主动刷新
+        assert_eq!(manager.proactive_refresh().await, 0);
+        assert_eq!(manager.refresh_breaker_status().consecutive_failures, 2);
+
+        // 请求时的按需刷新仍会尝试
+        assert!(manager.acquire_context().await.is_err());
+        assert!(manager.refresh_breaker_status().consecutive_failures > 2);
+    }
+
+    #[tokio::test]
+    async fn test_min_refresh_interval_limits_refresh_attempts() {
+        // 通过无法连接的代理让每次刷新都立即失败，刷新次数由熔断器的连续失败计数体现
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = ProxyConfig::new(format!("http://{}", listener.local_addr().unwrap()));
+        drop(listener);
+
+        let config = Config {
+            refresh_breaker_threshold: 100,
+            min_refresh_interval_secs: 60,
+            ..Default::default()
+        };
+        let cred = KiroCredentials {
+            refresh_token: Some("r".repeat(120)),
+            expires_at: Some((Utc::now() - Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(config, vec![cred], Some(proxy), None, false).unwrap();
+
+        assert!(manager.acquire_context().await.is_err());
+        assert_eq!(manager.refresh_breaker_status().consecutive_failures, 1);
+
+        // 窗口内再次过期：不再发起刷新，直接失败
+        let err = manager.acquire_context().await.err().unwrap();
+        assert_eq!(manager.refresh_breaker_status().consecutive_failures, 1);
+        assert!(err.to_string().contains("无法获取有效 Token"));
+        assert!(manager.refresh_throttled_for(1).is_some());
+
+        // 主动刷新同样遵守最小间隔
+        assert_eq!(manager.proactive_refresh().await, 0);
+        assert_eq!(manager.refresh_breaker_status().consecutive_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_token_within_refresh_margin_is_refreshed_before_use() {
+        // 通过无法连接的代理让每次刷新都立即失败，刷新次数由熔断器的连续失败计数体现
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = ProxyConfig::new(format!("http://{}", listener.local_addr().unwrap()));
+        drop(listener);
+
+        let new_manager = |margin_secs| {
+            let config = Config {
+                refresh_breaker_threshold: 100,
+                token_refresh_margin_secs: margin_secs,
+                ..Default::default()
+            };
+            let cred = KiroCredentials {
+                access_token: Some("still-valid".to_string()),
+                refresh_token: Some("r".repeat(120)),
+                expires_at: Some((Utc::now() + Duration::minutes(20)).to_rfc3339()),
+                ..Default::default()
+            };
+            MultiTokenManager::new(config, vec![cred], Some(proxy.clone()), None, false).unwrap()
+        };
+
+        // 默认余量（10 分钟）：20 分钟后过期的 Token 不刷新
+        let manager = new_manager(Config::default().token_refresh_margin_secs);
+        assert_eq!(manager.acquire_context().await.unwrap().token, "still-valid");
+        assert_eq!(manager.refresh_breaker_status().consecutive_failures, 0);
+
+        // 余量 30 分钟：使用前提前刷新；刷新失败时继续使用未过期的 Token
+        let manager = new_manager(1800);
+        assert_eq!(manager.acquire_context().await.unwrap().token, "still-valid");
+        assert_eq!(manager.refresh_breaker_status().consecutive_failures, 1);
+    }
+
+    fn strategy_manager(strategy: SelectionStrategy, weights: &[Option<u32>]) -> MultiTokenManager {
+        let config = Config {
+            selection_strategy: strategy,
+            ..Default::default()
+        };
+        let creds = weights
+            .iter()
+            .enumerate()
+            .map(|(i, weight)| KiroCredentials {
+                access_token: Some(format!("token-{}", i)),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                weight: *weight,
+                ..Default::default()
+            })
+            .collect();
+        MultiTokenManager::new(config, creds, None, None, false).unwrap()
+    }
+
+    /// 连续选择 `calls` 次，返回选中的凭据 ID 序列
+    async fn selection_sequence(manager: &MultiTokenManager, calls: usize) -> Vec<u64> {
+        let mut ids = Vec::with_capacity(calls);
+        for _ in 0..calls {
+            ids.push(manager.acquire_context().await.unwrap().id);
+        }
+        ids
+    }
+
+    fn selection_counts(ids: &[u64]) -> HashMap<u64, usize> {
+        let mut counts = HashMap::new();
+        for id in ids {
+            *counts.entry(*id).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[tokio::test]
+    async fn test_priority_strategy_sticks_to_current_credential() {
+        let manager = strategy_manager(SelectionStrategy::Priority, &[None, None, None]);
+
+        let ids = selection_sequence(&manager, 30).await;
+
+        assert_eq!(selection_counts(&ids), HashMap::from([(1, 30)]));
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_strategy_rotates_evenly() {
+        let manager = strategy_manager(SelectionStrategy::RoundRobin, &[None, None, None]);
+
+        let ids = selection_sequence(&manager, 30).await;
+
+        assert_eq!(&ids[..6], &[1, 2, 3, 1, 2, 3]);
+        assert_eq!(
+            selection_counts(&ids),
+            HashMap::from([(1, 10), (2, 10), (3, 10)])
+        );
+        // 选择模拟预测下一次选择，且不推进轮转位置
+        let decision = manager.select_dry_run(&AcquireOptions::default());
+        assert_eq!(decision.chosen_id, Some(1));
+        assert!(!decision.kept_current);
+        assert_eq!(manager.acquire_context().await.unwrap().id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_weighted_strategy_follows_weights() {
+        let manager =
+            strategy_manager(SelectionStrategy::Weighted, &[Some(1), Some(2), Some(3)]);
+
+        let ids = selection_sequence(&manager, 60).await;
+
+        assert_eq!(
+            selection_counts(&ids),
+            HashMap::from([(1, 10), (2, 20), (3, 30)])
+        );
+        // 平滑加权：同一凭据不会被连续选中超过其权重份额
+        assert!(ids.windows(4).all(|w| w.iter().any(|id| *id != 3)));
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_strategy_prefers_idle_credential() {
+        let manager =
+            strategy_manager(SelectionStrategy::LeastRecentlyUsed, &[None, None, None]);
+
+        let ids = selection_sequence(&manager, 30).await;
+        assert_eq!(
+            selection_counts(&ids),
+            HashMap::from([(1, 10), (2, 10), (3, 10)])
+        );
+
+        // 凭据 1 被排除期间其余凭据被使用，之后凭据 1 成为最久未使用的凭据
+        let without_first = AcquireOptions::excluding([1]);
+        for _ in 0..4 {
+            let ctx = manager.acquire_context_with(&without_first).await.unwrap();
+            assert_ne!(ctx.id, 1);
+        }
+        assert_eq!(manager.acquire_context().await.unwrap().id, 1);
     }
 
-    #[test]
-    fn test_is_token_expired_no_expires_at() {
-        let credentials = KiroCredentials::default();
-        assert!(is_token_expired(&credentials));
+    #[tokio::test]
+    async fn test_credential_breaker_closed_open_half_open_closed() {
+        let config = Config {
+            breaker_failure_threshold: 2,
+            breaker_cooldown_secs: 60,
+            ..Default::default()
+        };
+        let mut creds = Vec::new();
+        for token in ["a", "b"] {
+            let cred = KiroCredentials {
+                access_token: Some(token.to_string()),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            };
+            creds.push(cred);
+        }
+        let manager = MultiTokenManager::new(config, creds, None, None, false).unwrap();
+        let state_of = |id: u64| {
+            let snapshot = manager.snapshot();
+            let entry = snapshot.entries.iter().find(|e| e.id == id).unwrap();
+            (entry.breaker_state, entry.disabled)
+        };
+        // 模拟冷却结束
+        let expire_cooldown = || {
+            let mut entries = manager.entries.lock();
+            let entry = entries.iter_mut().find(|e| e.id == 1).unwrap();
+            entry.breaker.open_until = std::time::Instant::now()
+                .checked_sub(std::time::Duration::from_secs(1));
+        };
+        let only_first = AcquireOptions::excluding([2]);
+
+        // closed → open：连续失败达到阈值后熔断（不禁用），选择时跳过
+        assert!(manager.report_failure(1));
+        assert_eq!(state_of(1), (BreakerState::Closed, false));
+        assert!(manager.report_failure(1));
+        assert_eq!(state_of(1), (BreakerState::Open, false));
+        assert_eq!(manager.acquire_context().await.unwrap().id, 2);
+        assert!(manager.acquire_context_with(&only_first).await.is_err());
+
+        // open → half-open：冷却结束后只放行一个试探请求
+        expire_cooldown();
+        assert_eq!(state_of(1), (BreakerState::HalfOpen, false));
+        assert_eq!(manager.acquire_context_with(&only_first).await.unwrap().id, 1);
+        assert!(manager.acquire_context_with(&only_first).await.is_err());
+
+        // 试探失败：立即重新打开
+        manager.report_failure(1);
+        assert_eq!(state_of(1), (BreakerState::Open, false));
+
+        // 再次试探成功：关闭并清零失败计数
+        expire_cooldown();
+        assert_eq!(manager.acquire_context_with(&only_first).await.unwrap().id, 1);
+        manager.report_success(1);
+        assert_eq!(state_of(1), (BreakerState::Closed, false));
+        assert_eq!(manager.snapshot().entries[0].failure_count, 0);
+        assert_eq!(manager.acquire_context_with(&only_first).await.unwrap().id, 1);
     }
 
-    #[test]
-    fn test_is_token_expiring_soon_within_10_minutes() {
-        let mut credentials = KiroCredentials::default();
-        let expires = Utc::now() + Duration::minutes(8);
-        credentials.expires_at = Some(expires.to_rfc3339());
-        assert!(is_token_expiring_soon(&credentials));
-    }
+    #[tokio::test]
+    async fn test_repeated_rate_limits_open_breaker_only_with_cooldown() {
+        let manager_with = |cooldown_secs: u64| {
+            let config = Config {
+                breaker_failure_threshold: 2,
+                breaker_cooldown_secs: cooldown_secs,
+                ..Default::default()
+            };
+            let cred = KiroCredentials {
+                access_token: Some("a".to_string()),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            };
+            MultiTokenManager::new(config, vec![cred], None, None, false).unwrap()
+        };
+        let state_of = |manager: &MultiTokenManager| {
+            let entry = &manager.snapshot().entries[0];
+            (entry.breaker_state, entry.disabled, entry.failure_count)
+        };
 
-    #[test]
-    fn test_is_token_expiring_soon_beyond_10_minutes() {
-        let mut credentials = KiroCredentials::default();
-        let expires = Utc::now() + Duration::minutes(15);
-        credentials.expires_at = Some(expires.to_rfc3339());
-        assert!(!is_token_expiring_soon(&credentials));
-    }
+        // 未启用冷却：429 不影响凭据
+        let manager = manager_with(0);
+        for _ in 0..5 {
+            manager.report_rate_limited(1);
+        }
+        assert_eq!(state_of(&manager), (BreakerState::Closed, false, 0));
 
-    #[test]
-    fn test_validate_refresh_token_missing() {
-        let credentials = KiroCredentials::default();
-        let result = validate_refresh_token(&credentials);
-        assert!(result.is_err());
+        // 启用冷却：成功请求清零计数，连续限流达到阈值后熔断（不计入失败次数）
+        let manager = manager_with(60);
+        manager.report_rate_limited(1);
+        manager.report_success(1);
+        manager.report_rate_limited(1);
+        assert_eq!(state_of(&manager), (BreakerState::Closed, false, 0));
+        manager.report_rate_limited(1);
+        assert_eq!(state_of(&manager), (BreakerState::Open, false, 0));
+        assert!(manager.acquire_context().await.is_err());
     }
 
     #[test]
-    fn test_validate_refresh_token_valid() {
-        let mut credentials = KiroCredentials::default();
-        credentials.refresh_token = Some("a".repeat(150));
-        let result = validate_refresh_token(&credentials);
-        assert!(result.is_ok());
-    }
+    fn test_select_dry_run_skips_disabled_credential() {
+        let mut creds = Vec::new();
+        for priority in [0, 1, 2] {
+            let cred = KiroCredentials {
+                access_token: Some(format!("token-{}", priority)),
+                priority,
+                ..Default::default()
+            };
+            creds.push(cred);
+        }
+        let manager = MultiTokenManager::new(Config::default(), creds, None, None, false).unwrap();
+        manager.set_disabled(1, true).unwrap();
+
+        let decision = manager.select_dry_run(&AcquireOptions::excluding([2]));
+        assert_eq!(decision.chosen_id, Some(3));
+        assert!(!decision.kept_current);
+        let reasons: Vec<_> = decision
+            .candidates
+            .iter()
+            .map(|c| (c.id, c.skip_reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (1, Some(SkipReason::Disabled)),
+                (2, Some(SkipReason::Excluded)),
+                (3, None),
+            ]
+        );
 
-    // MultiTokenManager 测试
+        // 模拟不修改当前凭据，也不计入跳过统计
+        assert_eq!(manager.snapshot().current_id, 1);
+        assert!(manager.selection_skip_counts().iter().all(|(_, n)| *n == 0));
+    }
 
     #[test]
-    fn test_multi_token_manager_new() {
-        let config = Config::default();
-        let mut cred1 = KiroCredentials::default();
-        cred1.priority = 0;
-        let mut cred2 = KiroCredentials::default();
-        cred2.priority = 1;
+    fn test_missing_profile_arn_skip_policy() {
+        let creds: Vec<KiroCredentials> = [None, Some("arn-2")]
+            .into_iter()
+            .map(|arn| KiroCredentials {
+                access_token: Some("t".to_string()),
+                profile_arn: arn.map(str::to_string),
+                ..Default::default()
+            })
+            .collect();
 
+        // 默认 fallback：缺少 profileArn 的凭据照常选择
         let manager =
-            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
-        assert_eq!(manager.total_count(), 2);
-        assert_eq!(manager.available_count(), 2);
-    }
+            MultiTokenManager::new(Config::default(), creds.clone(), None, None, false).unwrap();
+        assert_eq!(
+            manager.select_dry_run(&AcquireOptions::default()).chosen_id,
+            Some(1)
+        );
 
-    #[test]
-    fn test_multi_token_manager_empty_credentials() {
-        let config = Config::default();
-        let result = MultiTokenManager::new(config, vec![], None, None, false);
-        // 支持 0 个凭据启动（可通过管理面板添加）
-        assert!(result.is_ok());
-        let manager = result.unwrap();
-        assert_eq!(manager.total_count(), 0);
-        assert_eq!(manager.available_count(), 0);
+        // skip：跳过缺少 profileArn 的凭据
+        let config = Config {
+            missing_profile_arn_policy: MissingProfileArnPolicy::Skip,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(config, creds, None, None, false).unwrap();
+        let decision = manager.select_dry_run(&AcquireOptions::default());
+        assert_eq!(decision.chosen_id, Some(2));
+        assert_eq!(
+            decision.candidates[0].skip_reason,
+            Some(SkipReason::MissingProfileArn)
+        );
     }
 
     #[test]
-    fn test_multi_token_manager_duplicate_ids() {
-        let config = Config::default();
-        let mut cred1 = KiroCredentials::default();
-        cred1.id = Some(1);
-        let mut cred2 = KiroCredentials::default();
-        cred2.id = Some(1); // 重复 ID
+    fn test_quota_window_survives_restart() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let expires_at = (Utc::now() + Duration::hours(1)).to_rfc3339();
+        let creds: Vec<KiroCredentials> = (1..=2)
+            .map(|id| KiroCredentials {
+                id: Some(id),
+                access_token: Some(format!("t{}", id)),
+                expires_at: Some(expires_at.clone()),
+                ..Default::default()
+            })
+            .collect();
+        let load = || -> Vec<KiroCredentials> {
+            serde_json::from_str(&std::fs::read_to_string(file.path()).unwrap()).unwrap()
+        };
 
-        let result = MultiTokenManager::new(config, vec![cred1, cred2], None, None, false);
-        assert!(result.is_err());
-        let err_msg = result.err().unwrap().to_string();
-        assert!(
-            err_msg.contains("重复的凭据 ID"),
-            "错误消息应包含 '重复的凭据 ID'，实际: {}",
-            err_msg
+        let config = Config {
+            runtime_state_persist_interval_secs: 60,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(
+            config.clone(),
+            creds,
+            None,
+            Some(file.path().to_path_buf()),
+            true,
+        )
+        .unwrap();
+        assert!(manager.report_quota_exhausted(1));
+
+        // 按间隔持久化：刷写前不写入
+        assert!(std::fs::read_to_string(file.path()).unwrap().is_empty());
+        assert!(manager.flush_runtime_state());
+        assert!(!manager.flush_runtime_state());
+
+        // 模拟重启：从持久化结果重新加载，凭据 1 仍在额度用尽窗口内
+        let restarted = MultiTokenManager::new(
+            config,
+            load(),
+            None,
+            Some(file.path().to_path_buf()),
+            true,
+        )
+        .unwrap();
+        let decision = restarted.select_dry_run(&AcquireOptions::default());
+        assert_eq!(decision.chosen_id, Some(2));
+        assert_eq!(
+            decision.candidates[0].skip_reason,
+            Some(SkipReason::QuotaExceeded)
         );
-    }
 
-    #[test]
-    fn test_multi_token_manager_report_failure() {
-        let config = Config::default();
-        let cred1 = KiroCredentials::default();
-        let cred2 = KiroCredentials::default();
+        // 手动启用后清除窗口
+        restarted.set_disabled(1, false).unwrap();
+        assert!(load()[0].quota_exhausted_until.is_none());
+        let decision = restarted.select_dry_run(&AcquireOptions::default());
+        assert_eq!(decision.candidates[0].skip_reason, None);
+    }
 
-        let manager =
-            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
+    #[tokio::test]
+    async fn test_flush_pending_writes_persists_runtime_state_to_storage() {
+        use crate::kiro::storage::{CredentialStorage, FileCredentialStorage};
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let content = r#"[{"id":1,"refreshToken":"r1"},{"id":2,"refreshToken":"r2"}]"#;
+        std::fs::write(file.path(), content).unwrap();
+        let storage = std::sync::Arc::new(FileCredentialStorage::new(file.path(), true));
+        let expires_at = (Utc::now() + Duration::hours(1)).to_rfc3339();
+        let creds: Vec<KiroCredentials> = (1..=2)
+            .map(|id| KiroCredentials {
+                id: Some(id),
+                refresh_token: Some(format!("r{}", id)),
+                access_token: Some(format!("t{}", id)),
+                expires_at: Some(expires_at.clone()),
+                ..Default::default()
+            })
+            .collect();
 
-        // 凭据会自动分配 ID（从 1 开始）
-        // 前两次失败不会禁用（使用 ID 1）
-        assert!(manager.report_failure(1));
-        assert!(manager.report_failure(1));
-        assert_eq!(manager.available_count(), 2);
+        let config = Config {
+            runtime_state_persist_interval_secs: 3600,
+            ..Default::default()
+        };
+        let mut manager = MultiTokenManager::new(config, creds, None, None, false).unwrap();
+        manager.set_storage(storage.clone());
+        assert!(manager.report_quota_exhausted(1));
 
-        // 第三次失败会禁用第一个凭据
-        assert!(manager.report_failure(1));
-        assert_eq!(manager.available_count(), 1);
+        manager.flush_pending_writes().await;
 
-        // 继续失败第二个凭据（使用 ID 2）
-        assert!(manager.report_failure(2));
-        assert!(manager.report_failure(2));
-        assert!(!manager.report_failure(2)); // 所有凭据都禁用了
-        assert_eq!(manager.available_count(), 0);
+        let stored = storage.load_all().await.unwrap();
+        assert!(stored[0].quota_exhausted_until.is_some());
+        assert!(stored[1].quota_exhausted_until.is_none());
+        assert!(manager.pending_saves.lock().is_empty());
     }
 
-    #[test]
-    fn test_multi_token_manager_report_success() {
-        let config = Config::default();
-        let cred = KiroCredentials::default();
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_delete_then_readd_credential_in_soft_delete_storage() {
+        use crate::kiro::storage::{CredentialStorage, SqliteCredentialStorage};
+        use crate::model::config::SqliteConfig;
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = std::sync::Arc::new(
+            SqliteCredentialStorage::new(&SqliteConfig {
+                database_path: dir.path().join("credentials.db").display().to_string(),
+                max_connections: 1,
+            })
+            .await
+            .unwrap(),
+        );
+        let expires_at = (Utc::now() + Duration::hours(1)).to_rfc3339();
+        let cred = |id: u64| KiroCredentials {
+            id: Some(id),
+            refresh_token: Some(format!("{}{}", id, "r".repeat(150))),
+            access_token: Some(format!("t{}", id)),
+            expires_at: Some(expires_at.clone()),
+            ..Default::default()
+        };
+        storage.save_all(&[cred(1), cred(2)]).await.unwrap();
+
+        let mut manager =
+            MultiTokenManager::new(Config::default(), vec![cred(1), cred(2)], None, None, false)
+                .unwrap();
+        manager.set_storage(storage.clone());
+        let stored_ids = || async {
+            storage
+                .load_all()
+                .await
+                .unwrap()
+                .iter()
+                .filter_map(|c| c.id)
+                .collect::<Vec<_>>()
+        };
 
-        let manager = MultiTokenManager::new(config, vec![cred], None, None, false).unwrap();
+        // 禁用时排队的回写仍包含 #2，晚于删除执行也不会让它复活
+        manager.set_disabled(2, true).unwrap();
+        manager.delete_credential(2).await.unwrap();
+        manager.flush_pending_writes().await;
+        assert_eq!(stored_ids().await, vec![1]);
 
-        // 失败两次（使用 ID 1）
-        manager.report_failure(1);
-        manager.report_failure(1);
+        // 显式重新添加同一 ID 时恢复
+        manager.add_credential(cred(2)).await.unwrap();
+        manager.flush_pending_writes().await;
+        assert_eq!(stored_ids().await, vec![1, 2]);
+    }
 
-        // 成功后重置计数（使用 ID 1）
-        manager.report_success(1);
+    #[tokio::test]
+    async fn test_persist_surfaces_invalid_credentials() {
+        use crate::kiro::storage::FileCredentialStorage;
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let creds = vec![
+            KiroCredentials {
+                id: Some(1),
+                refresh_token: Some("r1".to_string()),
+                ..Default::default()
+            },
+            KiroCredentials {
+                id: Some(2),
+                refresh_token: Some("r2".to_string()),
+                plan_cost: Some(-1.0),
+                ..Default::default()
+            },
+        ];
+        let mut manager =
+            MultiTokenManager::new(Config::default(), creds, None, None, false).unwrap();
+        manager.set_storage(std::sync::Arc::new(FileCredentialStorage::new(file.path(), true)));
+
+        // 回写前同步校验，错误返回给调用方而不是在后台被整批拒绝
+        let err = manager.set_priority(1, 3).unwrap_err();
+        assert!(err.to_string().contains("planCost"), "{}", err);
+        assert!(manager.pending_saves.lock().is_empty());
+    }
 
-        // 再失败两次不会禁用
-        manager.report_failure(1);
-        manager.report_failure(1);
-        assert_eq!(manager.available_count(), 1);
+    #[tokio::test]
+    async fn test_selection_skip_counters_by_reason() {
+        let mut creds = Vec::new();
+        for token in ["disabled", "exhausted", "ok"] {
+            let cred = KiroCredentials {
+                access_token: Some(token.to_string()),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            };
+            creds.push(cred);
+        }
+        creds[1].monthly_token_limit = Some(10);
+
+        let manager = MultiTokenManager::new(Config::default(), creds, None, None, false).unwrap();
+        manager.set_disabled(1, true).unwrap();
+        manager.record_token_usage(2, 10);
+
+        let ctx = manager.acquire_context().await.unwrap();
+        assert_eq!(ctx.token, "ok");
+
+        let count = |reason| {
+            manager
+                .selection_skip_counts()
+                .into_iter()
+                .find(|(r, _)| *r == reason)
+                .unwrap()
+                .1
+        };
+        assert_eq!(count(SkipReason::Disabled), 1);
+        assert_eq!(count(SkipReason::QuotaExceeded), 1);
+        assert_eq!(count(SkipReason::Excluded), 0);
+        assert_eq!(count(SkipReason::RefreshFailed), 0);
     }
 
-    #[test]
-    fn test_multi_token_manager_switch_to_next() {
-        let config = Config::default();
-        let mut cred1 = KiroCredentials::default();
-        cred1.refresh_token = Some("token1".to_string());
-        let mut cred2 = KiroCredentials::default();
-        cred2.refresh_token = Some("token2".to_string());
+    #[tokio::test]
+    async fn test_bulk_disable_by_region_skips_matching_credentials() {
+        let mut creds = Vec::new();
+        for (token, region) in [("t1", "us-east-1"), ("t2", "eu-west-1"), ("t3", "us-east-1")] {
+            let cred = KiroCredentials {
+                access_token: Some(token.to_string()),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                region: Some(region.to_string()),
+                ..Default::default()
+            };
+            creds.push(cred);
+        }
+        let manager = MultiTokenManager::new(Config::default(), creds, None, None, false).unwrap();
 
-        let manager =
-            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
+        let affected = manager
+            .set_disabled_where(|c| c.region.as_deref() == Some("us-east-1"), true)
+            .unwrap();
+        assert_eq!(affected, vec![1, 3]);
+        assert_eq!(manager.available_count(), 1);
 
-        // 初始是第一个凭据
-        assert_eq!(
-            manager.credentials().refresh_token,
-            Some("token1".to_string())
-        );
+        // 选择跳过被禁用区域的凭据
+        for _ in 0..3 {
+            assert_eq!(manager.acquire_context().await.unwrap().id, 2);
+        }
 
-        // 切换到下一个
-        assert!(manager.switch_to_next());
-        assert_eq!(
-            manager.credentials().refresh_token,
-            Some("token2".to_string())
-        );
+        let affected = manager
+            .set_disabled_where(|c| c.region.as_deref() == Some("us-east-1"), false)
+            .unwrap();
+        assert_eq!(affected, vec![1, 3]);
+        assert_eq!(manager.available_count(), 3);
     }
 
     #[tokio::test]
@@ -1565,10 +4405,10 @@ mod tests {
             MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
 
         // 凭据会自动分配 ID（从 1 开始）
-        for _ in 0..MAX_FAILURES_PER_CREDENTIAL {
+        for _ in 0..Config::default().breaker_failure_threshold {
             manager.report_failure(1);
         }
-        for _ in 0..MAX_FAILURES_PER_CREDENTIAL {
+        for _ in 0..Config::default().breaker_failure_threshold {
             manager.report_failure(2);
         }
 
@@ -1580,6 +4420,271 @@ mod tests {
         assert_eq!(manager.available_count(), 2);
     }
 
+    #[tokio::test]
+    async fn test_acquire_context_with_excluded_ids_is_request_scoped() {
+        let config = Config::default();
+        let cred1 = KiroCredentials {
+            priority: 0,
+            access_token: Some("t1".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let cred2 = KiroCredentials {
+            priority: 1,
+            access_token: Some("t2".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+
+        let manager =
+            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
+
+        // 排除 #1 的请求永远不会选中 #1
+        let options = AcquireOptions::excluding([1]);
+        for _ in 0..3 {
+            let ctx = manager.acquire_context_with(&options).await.unwrap();
+            assert_eq!(ctx.id, 2);
+        }
+
+        // 其他请求仍然使用 #1，且全局当前凭据未被修改
+        let ctx = manager.acquire_context().await.unwrap();
+        assert_eq!(ctx.id, 1);
+        assert_eq!(manager.snapshot().current_id, 1);
+        assert_eq!(manager.available_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_credential_bypasses_priority() {
+        let config = Config {
+            pinned_credential_id: Some(2),
+            ..Default::default()
+        };
+        let cred1 = KiroCredentials {
+            priority: 0,
+            access_token: Some("t1".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let cred2 = KiroCredentials {
+            priority: 5,
+            access_token: Some("t2".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+
+        let manager =
+            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
+
+        // 固定凭据优先于优先级更高的 #1
+        let ctx = manager.acquire_context().await.unwrap();
+        assert_eq!(ctx.id, 2);
+        assert_eq!(manager.select_dry_run(&AcquireOptions::default()).chosen_id, Some(2));
+
+        // 固定凭据被禁用时不切换到 #1，而是返回固定凭据不可用
+        manager.set_disabled(2, true).unwrap();
+        let err = manager.acquire_context().await.err().unwrap();
+        let pinned = err.downcast_ref::<PinnedCredentialUnavailableError>().unwrap();
+        assert_eq!(pinned.id, 2);
+        assert_eq!(pinned.reason, Some(SkipReason::Disabled));
+        assert_eq!(manager.select_dry_run(&AcquireOptions::default()).chosen_id, None);
+
+        // 取消固定后恢复正常选择
+        assert_eq!(manager.unpin_credential(), Some(2));
+        let ctx = manager.acquire_context().await.unwrap();
+        assert_eq!(ctx.id, 1);
+
+        assert!(manager.pin_credential(9).is_err());
+        manager.pin_credential(1).unwrap();
+        assert_eq!(manager.snapshot().pinned_id, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_context_with_all_excluded_returns_no_eligible_error() {
+        let config = Config::default();
+        let cred1 = KiroCredentials {
+            access_token: Some("t1".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let cred2 = KiroCredentials {
+            access_token: Some("t2".to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+
+        let manager =
+            MultiTokenManager::new(config, vec![cred1, cred2], None, None, false).unwrap();
+
+        let err = manager
+            .acquire_context_with(&AcquireOptions::excluding([2, 1]))
+            .await
+            .err()
+            .unwrap();
+        let no_eligible = err.downcast_ref::<NoEligibleCredentialError>().unwrap();
+        assert_eq!(no_eligible.excluded_ids, vec![1, 2]);
+
+        // 排除不影响凭据状态
+        assert_eq!(manager.available_count(), 2);
+        assert!(manager.acquire_context().await.is_ok());
+    }
+
+    fn budget_cred(token: &str, priority: u32, limit: u64) -> KiroCredentials {
+        KiroCredentials {
+            priority,
+            access_token: Some(token.to_string()),
+            expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+            monthly_token_limit: Some(limit),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_monthly_capped_credential_skipped_until_next_month() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![budget_cred("t1", 0, 1000), budget_cred("t2", 1, 1000)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        manager.entries.lock()[0].credentials.monthly_usage = Some(MonthlyUsage {
+            period: manager.current_usage_period(),
+            tokens: 1000,
+        });
+
+        // 本月已达上限：跳过优先级更高的 #1
+        let ctx = manager.acquire_context().await.unwrap();
+        assert_eq!(ctx.id, 2);
+
+        // 用量属于上个月（已跨过月份边界）：#1 恢复可用
+        manager.entries.lock()[0]
+            .credentials
+            .monthly_usage
+            .as_mut()
+            .unwrap()
+            .period = "2000-01".to_string();
+        let ctx = manager
+            .acquire_context_with(&AcquireOptions::excluding([2]))
+            .await
+            .unwrap();
+        assert_eq!(ctx.id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_all_credentials_at_monthly_cap_returns_budget_error() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![budget_cred("t1", 0, 100), budget_cred("t2", 1, 100)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        manager.record_token_usage(1, 60);
+        assert_eq!(manager.acquire_context().await.unwrap().id, 1);
+        manager.record_token_usage(1, 40);
+        manager.record_token_usage(2, 150);
+
+        let err = manager.acquire_context().await.err().unwrap();
+        assert!(err.is::<MonthlyBudgetExhaustedError>());
+    }
+
+    fn import_cred(refresh_token: &str, profile_arn: &str, priority: u32) -> KiroCredentials {
+        KiroCredentials {
+            refresh_token: Some(refresh_token.to_string()),
+            profile_arn: Some(profile_arn.to_string()),
+            priority,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_import_dedup_by_refresh_token() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![import_cred("rt-a", "arn-a", 1)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // 同一 refreshToken 且优先级不更高：保留已有凭据
+        let summary = manager
+            .import_credentials(vec![import_cred("rt-a", "arn-x", 5)], DedupKey::RefreshToken)
+            .unwrap();
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(manager.total_count(), 1);
+        assert_eq!(manager.credentials().profile_arn, Some("arn-a".to_string()));
+
+        // 同一 refreshToken 且优先级更高：替换并沿用 ID
+        let summary = manager
+            .import_credentials(vec![import_cred("rt-a", "arn-y", 0)], DedupKey::RefreshToken)
+            .unwrap();
+        assert_eq!(summary.replaced, 1);
+        assert_eq!(manager.total_count(), 1);
+        let snapshot = manager.snapshot();
+        assert_eq!(snapshot.entries[0].id, 1);
+        assert_eq!(snapshot.entries[0].priority, 0);
+    }
+
+    #[test]
+    fn test_import_dedup_by_profile_arn() {
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![import_cred("rt-a", "arn-a", 0)],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // 本批次内与已有凭据的 profileArn 相同，refreshToken 不同
+        let summary = manager
+            .import_credentials(
+                vec![import_cred("rt-b", "arn-a", 0), import_cred("rt-c", "arn-a", 3)],
+                DedupKey::ProfileArn,
+            )
+            .unwrap();
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(manager.total_count(), 1);
+
+        // 按 refreshToken 去重时则视为不同凭据
+        let summary = manager
+            .import_credentials(vec![import_cred("rt-b", "arn-a", 0)], DedupKey::RefreshToken)
+            .unwrap();
+        assert_eq!(summary.imported, 1);
+        assert_eq!(manager.total_count(), 2);
+    }
+
+    #[test]
+    fn test_import_dedup_by_id() {
+        let mut existing = import_cred("rt-a", "arn-a", 0);
+        existing.id = Some(7);
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![existing], None, None, false).unwrap();
+
+        let mut dup = import_cred("rt-b", "arn-b", 2);
+        dup.id = Some(7);
+        let mut dup_in_batch = import_cred("rt-c", "arn-c", 1);
+        dup_in_batch.id = Some(8);
+        let mut dup_in_batch_2 = import_cred("rt-d", "arn-d", 4);
+        dup_in_batch_2.id = Some(8);
+
+        let summary = manager
+            .import_credentials(vec![dup, dup_in_batch, dup_in_batch_2], DedupKey::Id)
+            .unwrap();
+        assert_eq!(summary.imported, 1);
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(manager.total_count(), 2);
+
+        let ids: Vec<u64> = manager.snapshot().entries.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![7, 8]);
+    }
+
     #[test]
     fn test_multi_token_manager_report_quota_exhausted() {
         let config = Config::default();
@@ -1744,4 +4849,31 @@ mod tests {
         // 空字符串被视为已设置，不会回退到 config
         assert_eq!(region, "");
     }
+
+    #[test]
+    fn test_record_request_caps_distinct_models_into_other() {
+        let config = Config {
+            metrics_max_series: 2,
+            ..Default::default()
+        };
+        let manager = MultiTokenManager::new(config, vec![], None, None, false).unwrap();
+        manager.record_request("model-a", 200);
+        manager.record_request("model-b", 200);
+        manager.record_request("model-a", 429);
+        // 达到上限后新模型计入 other，已记录的模型不受影响
+        manager.record_request("model-c", 200);
+        manager.record_request("model-d", 500);
+        manager.record_request("model-b", 200);
+
+        assert_eq!(
+            manager.request_counts(),
+            vec![
+                ("model-a".to_string(), 200, 1),
+                ("model-a".to_string(), 429, 1),
+                ("model-b".to_string(), 200, 2),
+                ("other".to_string(), 200, 1),
+                ("other".to_string(), 500, 1),
+            ]
+        );
+    }
 }
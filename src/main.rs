@@ -1,34 +1,47 @@
 mod admin;
 mod admin_ui;
 mod anthropic;
+mod cli;
 mod common;
+mod config_reload;
+mod diagnostics;
+mod health;
 mod http_client;
 mod kiro;
 mod model;
+mod openai;
+mod server;
 pub mod token;
 
 use std::sync::Arc;
 
 use clap::Parser;
+use kiro::credential_events::CredentialEvent;
 use kiro::model::credentials::{CredentialsConfig, KiroCredentials};
 use kiro::provider::KiroProvider;
-use kiro::storage::{CredentialChangeEvent, CredentialSyncManager, CredentialStorage, FileCredentialStorage};
+use kiro::storage::{
+    CredentialChangeEvent, CredentialStorage, CredentialSyncManager, FileCredentialStorage,
+    SanitizedError, StorageType,
+};
 use kiro::token_manager::MultiTokenManager;
-use model::arg::Args;
-use model::config::Config;
+use model::arg::{Args, Command};
+use model::config::{Config, ConfigError};
 
 #[tokio::main]
 async fn main() {
     // 解析命令行参数
     let args = Args::parse();
 
-    // 初始化日志
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
-        )
-        .init();
+    // 初始化日志（子命令模式下日志输出到 stderr，避免污染 stdout 结果）
+    let subscriber = tracing_subscriber::fmt().with_env_filter(
+        tracing_subscriber::EnvFilter::from_default_env()
+            .add_directive(tracing::Level::INFO.into()),
+    );
+    if args.command.is_some() {
+        subscriber.with_writer(std::io::stderr).init();
+    } else {
+        subscriber.init();
+    }
 
     // 加载配置
     let config_path = args
@@ -39,33 +52,109 @@ async fn main() {
         std::process::exit(1);
     });
 
-    // 获取 API Key
-    let api_key = config.api_key.clone().unwrap_or_else(|| {
-        tracing::error!("配置文件中未设置 apiKey");
-        std::process::exit(1);
-    });
+    // 校验配置（一次输出所有问题；子命令不启动服务，无需 apiKey）
+    if let Err(errors) = config.validate() {
+        let errors: Vec<_> = errors
+            .into_iter()
+            .filter(|e| args.command.is_none() || *e != ConfigError::MissingApiKey)
+            .collect();
+        if !errors.is_empty() {
+            tracing::error!("配置文件 {} 存在 {} 个问题:", config_path, errors.len());
+            for e in &errors {
+                tracing::error!("  - {}", e);
+            }
+            std::process::exit(1);
+        }
+    }
 
     // 构建代理配置
-    let proxy_config = config.proxy_url.as_ref().map(|url| {
-        let mut proxy = http_client::ProxyConfig::new(url);
-        if let (Some(username), Some(password)) = (&config.proxy_username, &config.proxy_password) {
-            proxy = proxy.with_auth(username, password);
+    let proxy_config = config.proxy_config();
+
+    if let Some(proxy) = &proxy_config {
+        // 启动时校验代理协议，避免构建 HTTP 客户端时才失败
+        match proxy.scheme() {
+            Ok(scheme) => tracing::info!("已配置 {} 代理: {}", scheme, proxy.redacted_url()),
+            Err(e) => {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }
         }
-        proxy
+    }
+
+    // 校验月度用量重置时区
+    if let Err(e) = kiro::monthly_budget::parse_utc_offset(&config.usage_reset_timezone) {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    }
+
+    // 解析存储类型（无法识别时直接退出，避免拼写错误静默回退到文件存储）
+    let storage_type = StorageType::parse(&config.credential_storage_type).unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        std::process::exit(1);
     });
+    tracing::debug!("凭据存储类型: {}", storage_type.as_str());
+
+    // 凭据文件加密密钥（仅文件存储生效）
+    let encryption_key =
+        kiro::storage::EncryptionKey::from_config(config.credential_encryption_key.as_deref());
+    if encryption_key.is_some() && !matches!(storage_type, StorageType::File) {
+        tracing::warn!(
+            "credentialEncryptionKey 仅对文件存储生效，当前存储类型: {}",
+            storage_type.as_str()
+        );
+    }
+
+    // 加密迁移子命令需在加载凭据前执行（配置密钥后明文文件无法加载）
+    if let Some(Command::EncryptCredentials) = &args.command {
+        let credentials_path = args
+            .credentials
+            .as_deref()
+            .unwrap_or(KiroCredentials::default_credentials_path());
+        match cli::encrypt::run(credentials_path, encryption_key.clone()).await {
+            Ok(()) => std::process::exit(0),
+            Err(e) => {
+                tracing::error!("加密凭据文件失败: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // 存储迁移子命令直接读写源/目标存储，无需加载当前配置的存储后端
+    if let Some(Command::Migrate(migrate_args)) = &args.command {
+        let credentials_path = args
+            .credentials
+            .as_deref()
+            .unwrap_or(KiroCredentials::default_credentials_path());
+        match cli::migrate::run(migrate_args, &config, credentials_path, encryption_key.clone())
+            .await
+        {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                tracing::error!("迁移凭据失败: {}", SanitizedError(&e));
+                std::process::exit(1);
+            }
+        }
+    }
 
-    if proxy_config.is_some() {
-        tracing::info!("已配置 HTTP 代理: {}", config.proxy_url.as_ref().unwrap());
+    // 启动延迟（等待容器网络等依赖就绪后再连接存储后端）
+    if config.startup_delay_secs > 0 {
+        tracing::info!("启动延迟 {} 秒后连接存储后端", config.startup_delay_secs);
+        tokio::time::sleep(std::time::Duration::from_secs(config.startup_delay_secs)).await;
     }
 
+    // 延迟连接模式下存储后端尚未加载凭据，需在后台完成首次加载
+    let storage_pending =
+        config.lazy_storage_connect && matches!(storage_type, StorageType::Postgres);
+
     // 根据配置创建存储后端
-    let (storage, credentials_list, is_multiple_format): (
+    let (storage, mut credentials_list, is_multiple_format): (
         Arc<dyn CredentialStorage>,
         Vec<KiroCredentials>,
         bool,
-    ) = match config.credential_storage_type.as_str() {
+    ) = match storage_type {
         #[cfg(feature = "postgres")]
-        "postgres" => {
+        StorageType::Postgres => {
             let pg_config = config.postgres.as_ref().unwrap_or_else(|| {
                 tracing::error!("credential_storage_type 为 postgres，但未配置 postgres 连接信息");
                 std::process::exit(1);
@@ -73,42 +162,164 @@ async fn main() {
 
             tracing::info!("使用 PostgreSQL 存储后端: {}", pg_config.table_name);
 
-            let storage = kiro::storage::PostgresCredentialStorage::new(
-                &pg_config.database_url,
-                &pg_config.table_name,
-                pg_config.max_connections,
-            )
-            .await
-            .unwrap_or_else(|e| {
-                tracing::error!("连接 PostgreSQL 失败: {}", e);
+            if config.lazy_storage_connect {
+                let storage = kiro::storage::PostgresCredentialStorage::new_lazy(pg_config)
+                    .unwrap_or_else(|e| {
+                        tracing::error!("创建 PostgreSQL 连接池失败: {}", SanitizedError(&e));
+                        std::process::exit(1);
+                    })
+                    .with_max_credentials(config.max_credentials);
+                let storage = Arc::new(storage);
+                start_postgres_health_check(&storage, pg_config);
+
+                tracing::info!("已启用延迟连接，PostgreSQL 将在后台首次连接成功后加载凭据");
+                (storage as Arc<dyn CredentialStorage>, Vec::new(), true)
+            } else {
+                let storage = kiro::storage::PostgresCredentialStorage::new(pg_config)
+                    .await
+                    .unwrap_or_else(|e| {
+                        tracing::error!("连接 PostgreSQL 失败: {}", SanitizedError(&e));
+                        std::process::exit(1);
+                    })
+                    .with_max_credentials(config.max_credentials);
+
+                let storage = Arc::new(storage);
+                start_postgres_health_check(&storage, pg_config);
+                let credentials = storage.load_all().await.unwrap_or_else(|e| {
+                    tracing::error!("从 PostgreSQL 加载凭据失败: {}", SanitizedError(&e));
+                    std::process::exit(1);
+                });
+
+                (storage as Arc<dyn CredentialStorage>, credentials, true)
+            }
+        }
+        #[cfg(not(feature = "postgres"))]
+        StorageType::Postgres => {
+            tracing::error!("credential_storage_type 为 postgres，但当前构建未启用 postgres feature");
+            std::process::exit(1);
+        }
+        #[cfg(feature = "redis")]
+        StorageType::Redis => {
+            let redis_config = config.redis.as_ref().unwrap_or_else(|| {
+                tracing::error!("credential_storage_type 为 redis，但未配置 redis 连接信息");
+                std::process::exit(1);
+            });
+
+            tracing::info!("使用 Redis 存储后端，键前缀: {}", redis_config.key_prefix);
+
+            let storage = kiro::storage::RedisCredentialStorage::new(redis_config)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("连接 Redis 失败: {}", SanitizedError(&e));
+                    std::process::exit(1);
+                })
+                .with_max_credentials(config.max_credentials);
+            let credentials = storage.load_all().await.unwrap_or_else(|e| {
+                tracing::error!("从 Redis 加载凭据失败: {}", SanitizedError(&e));
+                std::process::exit(1);
+            });
+
+            (Arc::new(storage) as Arc<dyn CredentialStorage>, credentials, true)
+        }
+        #[cfg(not(feature = "redis"))]
+        StorageType::Redis => {
+            tracing::error!("credential_storage_type 为 redis，但当前构建未启用 redis feature");
+            std::process::exit(1);
+        }
+        #[cfg(feature = "sqlite")]
+        StorageType::Sqlite => {
+            let sqlite_config = config.sqlite.as_ref().unwrap_or_else(|| {
+                tracing::error!("credential_storage_type 为 sqlite，但未配置 sqlite 数据库路径");
                 std::process::exit(1);
             });
 
-            let storage = Arc::new(storage);
+            tracing::info!("使用 SQLite 存储后端: {}", sqlite_config.database_path);
+
+            let storage = kiro::storage::SqliteCredentialStorage::new(sqlite_config)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("打开 SQLite 数据库失败: {}", SanitizedError(&e));
+                    std::process::exit(1);
+                })
+                .with_max_credentials(config.max_credentials);
             let credentials = storage.load_all().await.unwrap_or_else(|e| {
-                tracing::error!("从 PostgreSQL 加载凭据失败: {}", e);
+                tracing::error!("从 SQLite 加载凭据失败: {}", SanitizedError(&e));
+                std::process::exit(1);
+            });
+
+            (Arc::new(storage) as Arc<dyn CredentialStorage>, credentials, true)
+        }
+        #[cfg(not(feature = "sqlite"))]
+        StorageType::Sqlite => {
+            tracing::error!("credential_storage_type 为 sqlite，但当前构建未启用 sqlite feature");
+            std::process::exit(1);
+        }
+        #[cfg(feature = "mysql")]
+        StorageType::Mysql => {
+            let mysql_config = config.mysql.as_ref().unwrap_or_else(|| {
+                tracing::error!("credential_storage_type 为 mysql，但未配置 mysql 连接信息");
                 std::process::exit(1);
             });
 
-            (storage as Arc<dyn CredentialStorage>, credentials, true)
+            tracing::info!("使用 MySQL 存储后端: {}", mysql_config.table_name);
+
+            let storage = kiro::storage::MysqlCredentialStorage::new(mysql_config)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("连接 MySQL 失败: {}", SanitizedError(&e));
+                    std::process::exit(1);
+                })
+                .with_max_credentials(config.max_credentials);
+            let credentials = storage.load_all().await.unwrap_or_else(|e| {
+                tracing::error!("从 MySQL 加载凭据失败: {}", SanitizedError(&e));
+                std::process::exit(1);
+            });
+
+            (Arc::new(storage) as Arc<dyn CredentialStorage>, credentials, true)
+        }
+        #[cfg(not(feature = "mysql"))]
+        StorageType::Mysql => {
+            tracing::error!("credential_storage_type 为 mysql，但当前构建未启用 mysql feature");
+            std::process::exit(1);
         }
-        _ => {
+        StorageType::File => {
             // 默认使用文件存储（向后兼容）
             let credentials_path = args
                 .credentials
                 .unwrap_or_else(|| KiroCredentials::default_credentials_path().to_string());
 
-            let credentials_config = CredentialsConfig::load(&credentials_path).unwrap_or_else(|e| {
+            let credentials_config =
+                CredentialsConfig::load_with_key(&credentials_path, encryption_key.as_ref())
+                    .unwrap_or_else(|e| {
+                        tracing::error!("加载凭证失败: {}", e);
+                        std::process::exit(1);
+                    });
+
+            let is_multiple_format = credentials_config.is_multiple();
+            let storage = Arc::new(
+                FileCredentialStorage::new(&credentials_path, is_multiple_format)
+                    .with_max_credentials(config.max_credentials)
+                    .with_encryption_key(encryption_key.clone()),
+            );
+            // 通过存储后端加载，缺少 ID 的凭据分配的 ID 会在此时写回文件
+            let credentials_list = storage.load_all().await.unwrap_or_else(|e| {
                 tracing::error!("加载凭证失败: {}", e);
                 std::process::exit(1);
             });
 
-            let is_multiple_format = credentials_config.is_multiple();
-            let credentials_list = credentials_config.into_sorted_credentials();
-
-            let storage = Arc::new(FileCredentialStorage::new(&credentials_path, is_multiple_format));
+            if encryption_key.is_some() {
+                tracing::info!("使用文件存储后端（AES-256-GCM 加密）: {}", credentials_path);
+            } else {
+                tracing::info!("使用文件存储后端: {}", credentials_path);
+            }
 
-            tracing::info!("使用文件存储后端: {}", credentials_path);
+            let compaction_interval = config.file_compaction_interval_secs;
+            if compaction_interval > 0 && is_multiple_format {
+                let _compaction_handle = storage
+                    .clone()
+                    .start_compaction_task(std::time::Duration::from_secs(compaction_interval));
+                tracing::info!("凭据文件定时压缩已启动，间隔: {} 秒", compaction_interval);
+            }
 
             (storage as Arc<dyn CredentialStorage>, credentials_list, is_multiple_format)
         }
@@ -116,8 +327,21 @@ async fn main() {
 
     tracing::info!("已加载 {} 个凭据配置", credentials_list.len());
 
+    // 将稀疏的 priority 归一化为连续的 0..n
+    if config.normalize_priorities_on_load
+        && kiro::model::credentials::normalize_priorities(&mut credentials_list)
+    {
+        tracing::info!("已将 {} 个凭据的 priority 归一化为连续值", credentials_list.len());
+        if config.persist_normalized_priorities
+            && let Err(e) = storage.save_all(&credentials_list).await
+        {
+            tracing::warn!("归一化后的 priority 写回存储失败: {}", SanitizedError(&e));
+        }
+    }
+
     // 获取第一个凭据用于日志显示
     let first_credentials = credentials_list.first().cloned().unwrap_or_default();
+    // Debug 输出中的 token 已脱敏
     tracing::debug!("主凭证: {:?}", first_credentials);
 
     // 创建 MultiTokenManager
@@ -138,41 +362,121 @@ async fn main() {
 
     let token_manager = Arc::new(token_manager);
 
-    // 创建同步管理器并启动定时同步任务
-    let sync_interval = config.credential_sync_interval_secs;
-    if sync_interval > 0 {
-        let sync_manager = Arc::new(CredentialSyncManager::new(storage.clone(), sync_interval));
+    // 延迟连接：存储首次加载成功前，请求返回 503
+    if storage_pending {
+        token_manager.mark_storage_pending();
+        let tm_for_connect = token_manager.clone();
+        tokio::spawn(async move {
+            tm_for_connect
+                .connect_storage_lazily(std::time::Duration::from_secs(1))
+                .await;
+        });
+    }
 
-        // 添加变更回调，热更新 token_manager
-        let tm_for_callback = token_manager.clone();
-        sync_manager.add_callback(Box::new(move |event| {
-            let CredentialChangeEvent::Reloaded(credentials) = event;
-            tm_for_callback.reload_credentials(credentials);
-        }));
+    // 按健康分自动调整凭据选择顺序
+    if config.auto_reorder {
+        let interval = config.auto_reorder_interval_secs.max(1);
+        let _reorder_handle = token_manager
+            .clone()
+            .start_auto_reorder_task(std::time::Duration::from_secs(interval));
+        tracing::info!("凭据自动排序已启用，健康分重算间隔: {} 秒", interval);
+    }
 
-        // 启动定时同步任务
-        let _sync_handle = sync_manager.start_sync_task();
+    // 后台主动刷新即将过期的 Token（受刷新熔断保护）
+    if config.proactive_refresh_interval_secs > 0 {
+        let interval = config.proactive_refresh_interval_secs;
+        let _refresh_handle = token_manager
+            .clone()
+            .start_proactive_refresh_task(std::time::Duration::from_secs(interval));
+        tracing::info!("Token 主动刷新已启用，检查间隔: {} 秒", interval);
+    }
+
+    // 按间隔合并持久化运行时状态（月度用量、额度用尽窗口）
+    if config.runtime_state_persist_interval_secs > 0 {
+        let interval = config.runtime_state_persist_interval_secs;
+        let _persist_handle = token_manager
+            .clone()
+            .start_runtime_state_persist_task(std::time::Duration::from_secs(interval));
+        tracing::info!("运行时状态定期持久化已启用，间隔: {} 秒", interval);
+    }
+
+    // 执行子命令（不启动服务）
+    if let Some(Command::Balance(balance_args)) = &args.command {
+        let service = admin::AdminService::new(token_manager.clone());
+        match cli::balance::run(&service, balance_args).await {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                tracing::error!("查询余额失败: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // 获取 API Key
+    let api_key = config.api_key.clone().unwrap_or_else(|| {
+        tracing::error!("配置文件中未设置 apiKey");
+        std::process::exit(1);
+    });
+
+    // 创建同步管理器（定时同步禁用时仍可通过 Admin API 手动触发）
+    let sync_interval = config.credential_sync_interval_secs;
+    let sync_manager = Arc::new(CredentialSyncManager::new(storage.clone(), sync_interval));
+
+    // 添加变更回调，热更新 token_manager 并推送给 Admin 事件订阅者
+    let tm_for_callback = token_manager.clone();
+    sync_manager.add_callback(Box::new(move |event| {
+        let notification = CredentialEvent::from(&event);
+        let CredentialChangeEvent::Reloaded(credentials) = event;
+        tm_for_callback.reload_credentials(credentials);
+        tm_for_callback.publish_event(notification);
+    }));
+
+    // 启动定时同步任务
+    if sync_interval > 0 {
+        let _sync_handle = sync_manager.clone().start_sync_task();
         tracing::info!("凭据定时同步已启动，间隔: {} 秒", sync_interval);
     } else {
         tracing::info!("凭据定时同步已禁用");
     }
 
-    let kiro_provider = KiroProvider::with_proxy(token_manager.clone(), proxy_config.clone());
+    // 配置热加载：收到 SIGHUP 时重新读取配置文件，应用代理、选择策略、同步间隔和模型别名
+    let reloader = config_reload::ConfigReloader::new(&config_path, token_manager.clone())
+        .with_sync_manager(sync_manager.clone());
+    let _reload_handle = Arc::new(reloader).start_signal_task();
+
+    let kiro_provider = KiroProvider::new(token_manager.clone());
+
+    // 启动自检（required 模式下失败将终止启动）
+    if let Err(e) = anthropic::startup_gate(
+        &kiro_provider,
+        config.startup_selftest.as_ref(),
+        first_credentials.profile_arn.clone(),
+    )
+    .await
+    {
+        tracing::error!("{:#}", e);
+        std::process::exit(1);
+    }
 
     // 初始化 count_tokens 配置
     token::init_config(token::CountTokensConfig {
         api_url: config.count_tokens_api_url.clone(),
         api_key: config.count_tokens_api_key.clone(),
         auth_type: config.count_tokens_auth_type.clone(),
+        allow_local_fallback: config.count_tokens_allow_local_fallback,
+        cache_size: config.count_tokens_cache_size,
         proxy: proxy_config,
     });
 
-    // 构建 Anthropic API 路由（从第一个凭据获取 profile_arn）
-    let anthropic_app = anthropic::create_router_with_provider(
+    // 构建 Anthropic 与 OpenAI 兼容 API 路由（从第一个凭据获取 profile_arn，两者共用状态）
+    let app_state = anthropic::build_app_state(
         &api_key,
         Some(kiro_provider),
         first_credentials.profile_arn.clone(),
     );
+    let anthropic_app = anthropic::create_router(app_state.clone())
+        .merge(openai::create_openai_router(app_state));
 
     // 构建 Admin API 路由（如果配置了非空的 admin_api_key）
     // 安全检查：空字符串被视为未配置，防止空 key 绕过认证
@@ -188,13 +492,17 @@ async fn main() {
             anthropic_app
         } else {
             let admin_service = admin::AdminService::new(token_manager.clone());
-            let admin_state = admin::AdminState::new(admin_key, admin_service);
+            let admin_state = admin::AdminState::new(admin_key, admin_service)
+                .with_sync_manager(sync_manager.clone());
             let admin_app = admin::create_admin_router(admin_state);
 
             // 创建 Admin UI 路由
             let admin_ui_app = admin_ui::create_admin_ui_router();
 
             tracing::info!("Admin API 已启用");
+            if config.admin_reveal_secrets {
+                tracing::warn!("adminRevealSecrets 已开启，Admin API 凭据列表将返回完整 token");
+            }
             tracing::info!("Admin UI 已启用: /admin");
             anthropic_app
                 .nest("/api/admin", admin_app)
@@ -203,26 +511,115 @@ async fn main() {
     } else {
         anthropic_app
     };
+    let mut app = app.merge(health::create_health_router(token_manager.clone()));
+
+    // 请求活动跟踪（空闲关闭和诊断快照的进行中请求数使用）
+    let activity = (config.idle_shutdown_secs > 0 || config.diagnostics_dump_path.is_some())
+        .then(|| Arc::new(common::idle::IdleTracker::new(config.idle_ignore_health)));
+    if let Some(tracker) = &activity {
+        app = app.layer(axum::middleware::from_fn_with_state(
+            tracker.clone(),
+            common::idle::idle_activity_middleware,
+        ));
+    }
+
+    // 空闲关闭：连续无请求达到指定时长后优雅退出，由编排系统按需重启
+    let idle_shutdown = match &activity {
+        Some(tracker) if config.idle_shutdown_secs > 0 => {
+            let idle_timeout = std::time::Duration::from_secs(config.idle_shutdown_secs);
+            let tracker = tracker.clone();
+            tracing::info!("空闲关闭已启用: {} 秒无请求后退出", config.idle_shutdown_secs);
+            Some(async move {
+                tracker.wait_for_idle(idle_timeout).await;
+                tracing::info!("已连续 {} 秒无请求，开始优雅关闭", idle_timeout.as_secs());
+            })
+        }
+        _ => None,
+    };
+
+    // 诊断快照：收到 SIGQUIT / SIGUSR1 时写入当前状态，进程继续运行
+    if let Some(path) = &config.diagnostics_dump_path {
+        let mut diagnostics = diagnostics::Diagnostics::new(token_manager.clone());
+        if sync_interval > 0 {
+            diagnostics = diagnostics.with_sync_manager(sync_manager);
+        }
+        if let Some(tracker) = activity {
+            diagnostics = diagnostics.with_activity(tracker);
+        }
+        let _diagnostics_handle = Arc::new(diagnostics).start_signal_task(path.into());
+        tracing::info!("诊断快照已启用: 收到 SIGQUIT/SIGUSR1 时写入 {}", path);
+    }
 
     // 启动服务器
-    let addr = format!("{}:{}", config.host, config.port);
-    tracing::info!("启动 Anthropic API 端点: {}", addr);
+    let listen_addrs = config.listen_addresses();
+    tracing::info!("启动 Anthropic API 端点: {}", listen_addrs.join(", "));
     tracing::info!("API Key: {}***", &api_key[..(api_key.len() / 2)]);
     tracing::info!("可用 API:");
     tracing::info!("  GET  /v1/models");
     tracing::info!("  POST /v1/messages");
     tracing::info!("  POST /v1/messages/count_tokens");
+    tracing::info!("  GET  /healthz");
+    tracing::info!("  GET  /readyz");
     if admin_key_valid {
         tracing::info!("Admin API:");
         tracing::info!("  GET  /api/admin/credentials");
+        tracing::info!("  POST /api/admin/credentials/import");
+        tracing::info!("  POST /api/admin/credentials/bulk-disable");
+        tracing::info!("  POST /api/admin/credentials/bulk-enable");
         tracing::info!("  POST /api/admin/credentials/:index/disabled");
         tracing::info!("  POST /api/admin/credentials/:index/priority");
         tracing::info!("  POST /api/admin/credentials/:index/reset");
+        tracing::info!("  POST /api/admin/sync");
         tracing::info!("  GET  /api/admin/credentials/:index/balance");
         tracing::info!("Admin UI:");
         tracing::info!("  GET  /admin");
     }
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let listeners = server::bind_all(&listen_addrs).await.unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    });
+
+    // 收到 SIGTERM/SIGINT 或空闲超时后优雅关闭：停止接受新连接，等待进行中的请求完成
+    let shutdown = async move {
+        let idle = async move {
+            match idle_shutdown {
+                Some(idle) => idle.await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = server::shutdown_signal() => {}
+            _ = idle => {}
+        }
+    };
+    let drain_timeout = (config.shutdown_timeout_secs > 0)
+        .then(|| std::time::Duration::from_secs(config.shutdown_timeout_secs));
+    let served = server::serve_all_with_shutdown(listeners, app, shutdown, drain_timeout).await;
+
+    // 退出前回写待持久化的凭据变更
+    token_manager.flush_pending_writes().await;
+    if let Err(e) = served {
+        tracing::error!("HTTP 服务异常退出: {}", e);
+        std::process::exit(1);
+    }
+    tracing::info!("服务已关闭");
+}
+
+/// 按配置启动 PostgreSQL 连接健康检查任务
+#[cfg(feature = "postgres")]
+fn start_postgres_health_check(
+    storage: &Arc<kiro::storage::PostgresCredentialStorage>,
+    pg_config: &model::config::PostgresConfig,
+) {
+    if pg_config.health_check_interval_secs == 0 {
+        return;
+    }
+    let _health_check_handle = storage.clone().start_health_check_task(
+        std::time::Duration::from_secs(pg_config.health_check_interval_secs),
+    );
+    tracing::info!(
+        "PostgreSQL 连接健康检查已启动，间隔: {} 秒",
+        pg_config.health_check_interval_secs
+    );
 }
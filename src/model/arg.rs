@@ -1,14 +1,60 @@
-use clap::Parser;
+use clap::{Args as ClapArgs, Parser, Subcommand};
 
 /// Anthropic <-> Kiro API 客户端
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// 配置文件路径
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub config: Option<String>,
 
     /// 凭证文件路径
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub credentials: Option<String>,
+
+    /// 子命令（未指定时启动 API 服务）
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// 子命令
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 查询凭据余额（不启动服务）
+    Balance(BalanceArgs),
+    /// 将明文凭据文件就地加密（需配置 credentialEncryptionKey 或 KIRO_ENCRYPTION_KEY）
+    EncryptCredentials,
+    /// 在存储后端之间迁移凭据（保留 ID 和优先级）
+    Migrate(MigrateArgs),
+}
+
+/// `balance` 子命令参数
+#[derive(ClapArgs, Debug)]
+pub struct BalanceArgs {
+    /// 仅查询指定 ID 的凭据
+    #[arg(long, value_name = "N", conflicts_with = "all")]
+    pub index: Option<u64>,
+
+    /// 查询全部凭据（默认行为）
+    #[arg(long)]
+    pub all: bool,
+
+    /// 以 JSON 格式输出（便于脚本处理）
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// `migrate` 子命令参数
+///
+/// 存储规格为 `file[:<路径>]`、`postgres`、`redis`、`sqlite` 或 `mysql`；
+/// 数据库类后端使用配置文件中对应的连接配置，`file` 未指定路径时使用 `--credentials` 或默认路径
+#[derive(ClapArgs, Debug)]
+pub struct MigrateArgs {
+    /// 源存储
+    #[arg(long, value_name = "SPEC")]
+    pub from: String,
+
+    /// 目标存储
+    #[arg(long, value_name = "SPEC")]
+    pub to: String,
 }
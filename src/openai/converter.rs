@@ -0,0 +1,564 @@
+//! OpenAI ↔ Anthropic 格式转换
+//!
+//! 请求转换为 Anthropic Messages 请求后复用 `/v1/messages` 的处理链路，
+//! 非流式响应再从 Anthropic 消息转换回 OpenAI `choices`/`usage` 结构
+
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::anthropic::types::{Message, MessagesRequest, SystemMessage, Tool};
+
+use super::types::{
+    AssistantMessage, ChatCompletion, ChatCompletionRequest, ChatMessage, Choice, FunctionCall,
+    ToolCall, Usage,
+};
+
+/// 请求无法转换（返回 400）
+#[derive(Debug)]
+pub struct InvalidRequestError {
+    pub message: String,
+    /// 出错的请求字段（OpenAI 错误体的 `param`）
+    pub param: &'static str,
+}
+
+impl InvalidRequestError {
+    fn new(param: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            param,
+        }
+    }
+
+    /// `messages` 字段无法转换
+    fn messages(message: impl Into<String>) -> Self {
+        Self::new("messages", message)
+    }
+}
+
+impl std::fmt::Display for InvalidRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for InvalidRequestError {}
+
+/// 将 Anthropic stop_reason 映射为 OpenAI finish_reason
+pub fn map_finish_reason(stop_reason: Option<&str>) -> &'static str {
+    match stop_reason {
+        Some("max_tokens") | Some("model_context_window_exceeded") => "length",
+        Some("tool_use") => "tool_calls",
+        Some("refusal") => "content_filter",
+        _ => "stop",
+    }
+}
+
+/// 将 OpenAI Chat Completions 请求转换为 Anthropic Messages 请求
+pub fn to_messages_request(
+    request: ChatCompletionRequest,
+) -> Result<MessagesRequest, InvalidRequestError> {
+    let mut system = Vec::new();
+    let mut messages: Vec<Message> = Vec::new();
+
+    for message in request.messages {
+        match message.role.as_str() {
+            "system" | "developer" => {
+                let text = text_of(message.content.as_ref())?;
+                if !text.is_empty() {
+                    system.push(SystemMessage { text });
+                }
+            }
+            "user" => messages.push(Message {
+                role: "user".to_string(),
+                content: user_content(message.content)?,
+            }),
+            "assistant" => messages.push(Message {
+                role: "assistant".to_string(),
+                content: assistant_content(&message)?,
+            }),
+            "tool" => {
+                let block = json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.tool_call_id.clone().ok_or_else(|| {
+                        InvalidRequestError::messages("tool 消息缺少 tool_call_id".to_string())
+                    })?,
+                    "content": text_of(message.content.as_ref())?,
+                });
+                // 连续的工具结果合并到同一条 user 消息中（Anthropic 要求紧跟在 tool_use 之后）
+                match messages.last_mut() {
+                    Some(last) if is_tool_result_message(last) => {
+                        if let Value::Array(blocks) = &mut last.content {
+                            blocks.push(block);
+                        }
+                    }
+                    _ => messages.push(Message {
+                        role: "user".to_string(),
+                        content: Value::Array(vec![block]),
+                    }),
+                }
+            }
+            other => {
+                return Err(InvalidRequestError::messages(format!(
+                    "不支持的消息角色: {}",
+                    other
+                )));
+            }
+        }
+    }
+
+    if request.n.is_some_and(|n| n != 1) {
+        return Err(InvalidRequestError::new("n", "仅支持 n = 1"));
+    }
+    if request.stop.as_ref().is_some_and(has_stop_sequences) {
+        return Err(InvalidRequestError::new("stop", "不支持 stop 停止序列"));
+    }
+
+    if let Some(instruction) = response_format_instruction(request.response_format.as_ref())? {
+        system.push(SystemMessage { text: instruction });
+    }
+
+    let tool_choice = request.tool_choice.as_ref().map(convert_tool_choice);
+    // tool_choice 为 "none" 时不向上游提供工具
+    let tools = match tool_choice {
+        Some(None) => None,
+        _ => request.tools.map(|tools| {
+            tools
+                .into_iter()
+                .filter(|tool| tool.tool_type == "function")
+                .map(|tool| Tool {
+                    tool_type: None,
+                    name: tool.function.name,
+                    description: tool.function.description,
+                    input_schema: match tool.function.parameters {
+                        Some(Value::Object(schema)) => schema.into_iter().collect(),
+                        _ => [("type".to_string(), json!("object"))].into_iter().collect(),
+                    },
+                    max_uses: None,
+                })
+                .collect()
+        }),
+    };
+
+    Ok(MessagesRequest {
+        model: request.model,
+        max_tokens: request.max_completion_tokens.or(request.max_tokens),
+        messages,
+        stream: request.stream,
+        system: (!system.is_empty()).then_some(system),
+        tools,
+        tool_choice: tool_choice.flatten(),
+        thinking: None,
+        metadata: None,
+    })
+}
+
+/// `stop` 是否包含停止序列（null、空字符串和空数组视为未设置）
+fn has_stop_sequences(stop: &Value) -> bool {
+    match stop {
+        Value::Null => false,
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => true,
+    }
+}
+
+/// JSON 模式追加的系统指令
+const JSON_MODE_INSTRUCTION: &str = "Respond with a single valid JSON object only. \
+Do not wrap it in Markdown code fences and do not add any text before or after it.";
+
+/// 将 `response_format` 转换为追加的系统指令
+///
+/// Kiro 没有原生的结构化输出，`json_object` / `json_schema` 通过系统指令要求模型只输出 JSON，
+/// 无法严格保证输出合法；`text` 不做处理，其他类型返回 400
+fn response_format_instruction(
+    format: Option<&Value>,
+) -> Result<Option<String>, InvalidRequestError> {
+    let Some(format) = format.filter(|format| !format.is_null()) else {
+        return Ok(None);
+    };
+    match format["type"].as_str() {
+        Some("text") => Ok(None),
+        Some("json_object") => Ok(Some(JSON_MODE_INSTRUCTION.to_string())),
+        Some("json_schema") => {
+            let schema = &format["json_schema"]["schema"];
+            if !schema.is_object() {
+                return Err(InvalidRequestError::new(
+                    "response_format",
+                    "response_format.json_schema.schema 必须为 JSON 对象",
+                ));
+            }
+            Ok(Some(format!(
+                "{} The JSON object must conform to this JSON Schema:\n{}",
+                JSON_MODE_INSTRUCTION, schema
+            )))
+        }
+        other => Err(InvalidRequestError::new(
+            "response_format",
+            format!(
+                "不支持的 response_format 类型: {}（支持 text、json_object、json_schema）",
+                other.unwrap_or("unknown")
+            ),
+        )),
+    }
+}
+
+/// 是否为只包含工具结果的 user 消息
+fn is_tool_result_message(message: &Message) -> bool {
+    message.role == "user"
+        && message
+            .content
+            .as_array()
+            .is_some_and(|blocks| blocks.iter().all(|block| block["type"] == "tool_result"))
+}
+
+/// 转换 tool_choice（`None` 表示 "none"，即不使用工具）
+fn convert_tool_choice(choice: &Value) -> Option<Value> {
+    match choice {
+        Value::String(mode) => match mode.as_str() {
+            "none" => None,
+            "required" => Some(json!({ "type": "any" })),
+            _ => Some(json!({ "type": "auto" })),
+        },
+        Value::Object(_) => match choice["function"]["name"].as_str() {
+            Some(name) => Some(json!({ "type": "tool", "name": name })),
+            None => Some(json!({ "type": "auto" })),
+        },
+        _ => Some(json!({ "type": "auto" })),
+    }
+}
+
+/// 提取纯文本内容（字符串或文本片段数组）
+fn text_of(content: Option<&Value>) -> Result<String, InvalidRequestError> {
+    match content {
+        None | Some(Value::Null) => Ok(String::new()),
+        Some(Value::String(text)) => Ok(text.clone()),
+        Some(Value::Array(parts)) => Ok(parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n")),
+        Some(_) => Err(InvalidRequestError::messages("消息 content 格式无效")),
+    }
+}
+
+/// 转换 user 消息内容（文本片段和 data URL 图片）
+fn user_content(content: Option<Value>) -> Result<Value, InvalidRequestError> {
+    let parts = match content {
+        Some(Value::Array(parts)) => parts,
+        other => return Ok(Value::String(text_of(other.as_ref())?)),
+    };
+
+    parts
+        .into_iter()
+        .map(|part| match part["type"].as_str() {
+            Some("text") => Ok(json!({ "type": "text", "text": part["text"] })),
+            Some("image_url") => {
+                let url = part["image_url"]["url"].as_str().unwrap_or_default();
+                image_block(url)
+            }
+            other => Err(InvalidRequestError::messages(format!(
+                "不支持的内容片段类型: {}",
+                other.unwrap_or("unknown")
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::Array)
+}
+
+/// 将 `data:<media_type>;base64,<data>` 形式的图片转换为 Anthropic 图片块
+fn image_block(url: &str) -> Result<Value, InvalidRequestError> {
+    let (media_type, data) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+        .ok_or_else(|| InvalidRequestError::messages("image_url 仅支持 base64 data URL"))?;
+    Ok(json!({
+        "type": "image",
+        "source": { "type": "base64", "media_type": media_type, "data": data }
+    }))
+}
+
+/// 转换 assistant 消息内容（文本 + 工具调用）
+fn assistant_content(message: &ChatMessage) -> Result<Value, InvalidRequestError> {
+    let text = text_of(message.content.as_ref())?;
+    let Some(tool_calls) = message.tool_calls.as_ref().filter(|calls| !calls.is_empty()) else {
+        return Ok(Value::String(text));
+    };
+
+    let mut blocks = Vec::with_capacity(tool_calls.len() + 1);
+    if !text.is_empty() {
+        blocks.push(json!({ "type": "text", "text": text }));
+    }
+    for call in tool_calls {
+        let input: Value = if call.function.arguments.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(&call.function.arguments).map_err(|e| {
+                InvalidRequestError::messages(format!(
+                    "工具调用 {} 的 arguments 不是合法的 JSON: {}",
+                    call.id, e
+                ))
+            })?
+        };
+        blocks.push(json!({
+            "type": "tool_use",
+            "id": call.id,
+            "name": call.function.name,
+            "input": input,
+        }));
+    }
+    Ok(Value::Array(blocks))
+}
+
+/// 将 Anthropic 非流式响应转换为 OpenAI Chat Completion
+pub fn to_chat_completion(message: &Value, model: &str) -> ChatCompletion {
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in message["content"].as_array().into_iter().flatten() {
+        match block["type"].as_str() {
+            Some("text") => text.push_str(block["text"].as_str().unwrap_or_default()),
+            Some("tool_use") => tool_calls.push(ToolCall {
+                id: block["id"].as_str().unwrap_or_default().to_string(),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: block["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: block["input"].to_string(),
+                },
+            }),
+            _ => {}
+        }
+    }
+
+    let usage = &message["usage"];
+    ChatCompletion {
+        id: completion_id(message["id"].as_str()),
+        object: "chat.completion",
+        created: chrono::Utc::now().timestamp(),
+        model: model.to_string(),
+        system_fingerprint: system_fingerprint(model),
+        choices: vec![Choice {
+            index: 0,
+            message: AssistantMessage {
+                role: "assistant",
+                content: (!text.is_empty() || tool_calls.is_empty()).then_some(text),
+                tool_calls,
+            },
+            finish_reason: map_finish_reason(message["stop_reason"].as_str()),
+        }],
+        usage: Usage::new(
+            usage["input_tokens"].as_i64().unwrap_or(0),
+            usage["output_tokens"].as_i64().unwrap_or(0),
+        ),
+    }
+}
+
+/// 生成 `system_fingerprint`
+///
+/// 由服务版本和请求的模型名称派生，同一版本下同一模型的指纹保持稳定
+pub fn system_fingerprint(model: &str) -> String {
+    let digest = Sha256::digest(format!("{}:{}", env!("CARGO_PKG_VERSION"), model));
+    format!("fp_{}", &hex::encode(digest)[..10])
+}
+
+/// 由 Anthropic 消息 ID 生成 OpenAI 响应 ID
+pub fn completion_id(message_id: Option<&str>) -> String {
+    let suffix = message_id
+        .map(|id| id.trim_start_matches("msg_").to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+    format!("chatcmpl-{}", suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: Value) -> ChatCompletionRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_request_conversion_maps_roles_tools_and_results() {
+        let converted = to_messages_request(request(json!({
+            "model": "claude-sonnet-4",
+            "max_completion_tokens": 256,
+            "max_tokens": 1,
+            "messages": [
+                { "role": "system", "content": "be brief" },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "weather?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } }
+                ]},
+                { "role": "assistant", "content": null, "tool_calls": [
+                    { "id": "call_1", "type": "function",
+                      "function": { "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" } },
+                    { "id": "call_2", "type": "function",
+                      "function": { "name": "get_time", "arguments": "" } }
+                ]},
+                { "role": "tool", "tool_call_id": "call_1", "content": "sunny" },
+                { "role": "tool", "tool_call_id": "call_2", "content": "noon" }
+            ],
+            "tools": [{ "type": "function", "function": {
+                "name": "get_weather", "description": "weather",
+                "parameters": { "type": "object", "properties": { "city": { "type": "string" } } }
+            }}],
+            "tool_choice": "required"
+        })))
+        .unwrap();
+
+        assert_eq!(converted.max_tokens, Some(256));
+        assert_eq!(converted.system.unwrap()[0].text, "be brief");
+        assert_eq!(converted.messages.len(), 3);
+        assert_eq!(converted.messages[0].content[1]["source"]["media_type"], "image/png");
+        assert_eq!(converted.messages[1].content[0]["input"]["city"], "Paris");
+        assert_eq!(converted.messages[1].content[1]["input"], json!({}));
+        // 连续的工具结果合并到同一条 user 消息
+        let results = converted.messages[2].content.as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1]["tool_use_id"], "call_2");
+        assert_eq!(converted.tools.unwrap()[0].input_schema["type"], "object");
+        assert_eq!(converted.tool_choice.unwrap(), json!({ "type": "any" }));
+    }
+
+    #[test]
+    fn test_request_conversion_rejects_remote_images_and_unknown_roles() {
+        let remote_image = request(json!({
+            "model": "claude-sonnet-4",
+            "messages": [{ "role": "user", "content": [
+                { "type": "image_url", "image_url": { "url": "https://example.com/a.png" } }
+            ]}]
+        }));
+        assert!(to_messages_request(remote_image).is_err());
+
+        let unknown_role = request(json!({
+            "model": "claude-sonnet-4",
+            "messages": [{ "role": "function", "content": "x" }]
+        }));
+        assert!(to_messages_request(unknown_role).is_err());
+
+        let no_tools = to_messages_request(request(json!({
+            "model": "claude-sonnet-4",
+            "messages": [{ "role": "user", "content": "hi" }],
+            "tools": [{ "type": "function", "function": { "name": "f" } }],
+            "tool_choice": "none"
+        })))
+        .unwrap();
+        assert!(no_tools.tools.is_none());
+        assert!(no_tools.tool_choice.is_none());
+    }
+
+    #[test]
+    fn test_json_object_response_format_adds_system_instruction() {
+        let converted = to_messages_request(request(json!({
+            "model": "claude-sonnet-4",
+            "messages": [
+                { "role": "system", "content": "be brief" },
+                { "role": "user", "content": "list three colors" }
+            ],
+            "response_format": { "type": "json_object" }
+        })))
+        .unwrap();
+
+        let system = converted.system.unwrap();
+        assert_eq!(system.len(), 2);
+        assert_eq!(system[0].text, "be brief");
+        assert_eq!(system[1].text, JSON_MODE_INSTRUCTION);
+
+        let schema = to_messages_request(request(json!({
+            "model": "claude-sonnet-4",
+            "messages": [{ "role": "user", "content": "hi" }],
+            "response_format": { "type": "json_schema", "json_schema": {
+                "name": "colors",
+                "schema": { "type": "object", "required": ["colors"] }
+            }}
+        })))
+        .unwrap();
+        let text = &schema.system.unwrap()[0].text;
+        assert!(text.starts_with(JSON_MODE_INSTRUCTION));
+        assert!(text.contains(r#""required":["colors"]"#));
+
+        let plain = to_messages_request(request(json!({
+            "model": "claude-sonnet-4",
+            "messages": [{ "role": "user", "content": "hi" }],
+            "response_format": { "type": "text" }
+        })))
+        .unwrap();
+        assert!(plain.system.is_none());
+    }
+
+    #[test]
+    fn test_unsupported_response_format_rejected() {
+        for format in [
+            json!({ "type": "grammar" }),
+            json!({ "type": "json_schema", "json_schema": { "name": "x" } }),
+        ] {
+            let err = to_messages_request(request(json!({
+                "model": "claude-sonnet-4",
+                "messages": [{ "role": "user", "content": "hi" }],
+                "response_format": format
+            })))
+            .unwrap_err();
+            assert_eq!(err.param, "response_format");
+        }
+    }
+
+    #[test]
+    fn test_unsupported_n_and_stop_rejected() {
+        let base = json!({
+            "model": "claude-sonnet-4",
+            "messages": [{ "role": "user", "content": "hi" }]
+        });
+        let with = |key: &str, value: Value| {
+            let mut body = base.clone();
+            body[key] = value;
+            to_messages_request(request(body))
+        };
+
+        assert_eq!(with("n", json!(2)).unwrap_err().param, "n");
+        assert_eq!(with("stop", json!("END")).unwrap_err().param, "stop");
+        assert_eq!(with("stop", json!(["a", "b"])).unwrap_err().param, "stop");
+        assert!(with("n", json!(1)).is_ok());
+        assert!(with("stop", json!([])).is_ok());
+        assert!(with("stop", Value::Null).is_ok());
+    }
+
+    #[test]
+    fn test_finish_reason_mapping() {
+        assert_eq!(map_finish_reason(Some("end_turn")), "stop");
+        assert_eq!(map_finish_reason(Some("stop_sequence")), "stop");
+        assert_eq!(map_finish_reason(Some("max_tokens")), "length");
+        assert_eq!(map_finish_reason(Some("tool_use")), "tool_calls");
+        assert_eq!(map_finish_reason(None), "stop");
+    }
+
+    #[test]
+    fn test_response_conversion() {
+        let completion = to_chat_completion(
+            &json!({
+                "id": "msg_abc",
+                "content": [
+                    { "type": "thinking", "thinking": "hmm" },
+                    { "type": "text", "text": "Checking." },
+                    { "type": "tool_use", "id": "toolu_1", "name": "get_weather",
+                      "input": { "city": "Paris" } }
+                ],
+                "stop_reason": "tool_use",
+                "usage": { "input_tokens": 12, "output_tokens": 5 }
+            }),
+            "claude-sonnet-4",
+        );
+
+        let value = serde_json::to_value(&completion).unwrap();
+        assert_eq!(value["id"], "chatcmpl-abc");
+        assert_eq!(value["object"], "chat.completion");
+        assert_eq!(value["choices"][0]["message"]["content"], "Checking.");
+        assert_eq!(value["choices"][0]["finish_reason"], "tool_calls");
+        let call = &value["choices"][0]["message"]["tool_calls"][0];
+        assert_eq!(call["function"]["name"], "get_weather");
+        assert_eq!(
+            serde_json::from_str::<Value>(call["function"]["arguments"].as_str().unwrap()).unwrap(),
+            json!({ "city": "Paris" })
+        );
+        assert_eq!(value["usage"]["total_tokens"], 17);
+        assert_eq!(value["system_fingerprint"], system_fingerprint("claude-sonnet-4"));
+        assert!(value["system_fingerprint"].as_str().unwrap().starts_with("fp_"));
+    }
+}
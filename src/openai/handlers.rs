@@ -0,0 +1,172 @@
+//! OpenAI Chat Completions 端点处理器
+
+use axum::{
+    Json as JsonExtractor,
+    body::Body,
+    extract::{State, rejection::JsonRejection},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use futures::{StreamExt, stream};
+
+use crate::anthropic::{self, AppState};
+use crate::kiro::error_code::KiroErrorCode;
+
+use super::converter::{to_chat_completion, to_messages_request};
+use super::stream::ChunkTranslator;
+use super::types::{ChatCompletionRequest, ErrorDetail, ErrorResponse};
+
+/// POST /v1/chat/completions
+///
+/// 将请求转换为 Anthropic Messages 请求后复用 `/v1/messages` 的完整处理链路
+/// （模型映射、限流、重试、凭据故障转移），再把响应转换回 OpenAI 格式。
+/// 错误响应由 [`super::middleware::openai_error_middleware`] 统一转换；
+/// 请求体解析失败时直接返回 OpenAI 格式的 `invalid_request_error`
+pub async fn post_chat_completions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<JsonExtractor<ChatCompletionRequest>, JsonRejection>,
+) -> Response {
+    let payload = match payload {
+        Ok(JsonExtractor(payload)) => payload,
+        Err(rejection) => {
+            tracing::warn!("OpenAI 请求体解析失败: {}", rejection.body_text());
+            return invalid_request(rejection.status(), rejection.body_text(), None);
+        }
+    };
+    tracing::info!(
+        model = %payload.model,
+        stream = %payload.stream,
+        message_count = %payload.messages.len(),
+        "Received POST /v1/chat/completions request"
+    );
+
+    let model = payload.model.clone();
+    let include_usage = payload
+        .stream_options
+        .as_ref()
+        .is_some_and(|options| options.include_usage);
+    let request = match to_messages_request(payload) {
+        Ok(request) => request,
+        Err(e) => {
+            tracing::warn!("OpenAI 请求转换失败: {}", e);
+            return invalid_request(StatusCode::BAD_REQUEST, e.message, Some(e.param));
+        }
+    };
+    let is_stream = request.stream;
+
+    let response =
+        anthropic::post_messages(State(state), None, headers, JsonExtractor(request)).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    if is_stream {
+        let mut translator = ChunkTranslator::new(model, include_usage);
+        let body = body.into_data_stream().flat_map(move |chunk| {
+            let items: Vec<_> = match chunk {
+                Ok(chunk) => translator.feed(&chunk).into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(items)
+        });
+        return Response::from_parts(parts, Body::from_stream(body));
+    }
+
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("读取响应体失败: {}", e);
+            return internal_error(format!("读取响应体失败: {}", e));
+        }
+    };
+    let message = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::error!("解析 Anthropic 响应失败: {}", e);
+            return internal_error(format!("解析响应失败: {}", e));
+        }
+    };
+    let completion = to_chat_completion(&message, &model);
+    let body = serde_json::to_vec(&completion).unwrap_or_default();
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// 构建 `invalid_request_error` 错误响应
+fn invalid_request(status: StatusCode, message: String, param: Option<&str>) -> Response {
+    let error = ErrorResponse {
+        error: ErrorDetail {
+            message,
+            error_type: "invalid_request_error".to_string(),
+            param: param.map(str::to_string),
+            code: serde_json::to_value(KiroErrorCode::InvalidRequest).ok(),
+        },
+    };
+    (status, Json(error)).into_response()
+}
+
+/// 构建 500 错误响应
+fn internal_error(message: String) -> Response {
+    let error = ErrorResponse {
+        error: ErrorDetail {
+            message,
+            error_type: "api_error".to_string(),
+            param: None,
+            code: None,
+        },
+    };
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::router::create_openai_router;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_malformed_body_returns_openai_error() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_openai_router(AppState::new("test-key"));
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let send = |body: &'static str| {
+            client
+                .post(format!("http://{}/v1/chat/completions", addr))
+                .header("authorization", "Bearer test-key")
+                .header("content-type", "application/json")
+                .body(body)
+                .send()
+        };
+
+        for body in ["{not json", r#"{"model": "claude-sonnet-4"}"#] {
+            let response = send(body).await.unwrap();
+            assert!(response.status().is_client_error());
+            assert!(
+                response.headers()[header::CONTENT_TYPE]
+                    .to_str()
+                    .unwrap()
+                    .starts_with("application/json")
+            );
+            let body: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(body["error"]["type"], "invalid_request_error");
+            assert_eq!(body["error"]["code"], "invalid_request");
+            assert!(!body["error"]["message"].as_str().unwrap().is_empty());
+        }
+
+        let response = send(
+            r#"{"model": "claude-sonnet-4", "n": 2,
+                "messages": [{"role": "user", "content": "hi"}]}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 400);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["param"], "n");
+    }
+}
@@ -0,0 +1,98 @@
+//! OpenAI 兼容端点中间件
+
+use axum::{
+    body::Body,
+    http::{Request, header},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+
+use super::types::{ErrorDetail, ErrorResponse};
+
+/// 错误格式转换中间件（位于认证和并发限制之外）
+///
+/// 将 Anthropic 格式的错误响应 `{"type":"error","error":{"type","message","kiro_error_code"}}`
+/// 转换为 OpenAI 格式 `{"error":{"message","type","param","code"}}`，
+/// `kiro_error_code` 保留在 `code` 字段中。已是 OpenAI 格式（含 `param`）的错误原样返回
+pub async fn openai_error_middleware(request: Request<Body>, next: Next) -> Response {
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if response.status().is_success() || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("读取错误响应体失败: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let Some(converted) = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|body| convert_error(&body))
+    else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let body = serde_json::to_vec(&converted).unwrap_or_default();
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// 将 Anthropic 错误体转换为 OpenAI 错误体（非 Anthropic 错误格式时返回 None）
+fn convert_error(body: &Value) -> Option<ErrorResponse> {
+    let error = body.get("error")?.as_object()?;
+    if error.contains_key("param") {
+        return None;
+    }
+    Some(ErrorResponse {
+        error: ErrorDetail {
+            message: error.get("message")?.as_str()?.to_string(),
+            error_type: error.get("type")?.as_str()?.to_string(),
+            param: None,
+            code: error.get("kiro_error_code").cloned(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_convert_anthropic_error_keeps_kiro_error_code() {
+        let body = json!({"error": {
+            "type": "rate_limit_error",
+            "message": "并发请求数已达上限 1",
+            "kiro_error_code": "rate_limited"
+        }});
+
+        let converted = serde_json::to_value(convert_error(&body).unwrap()).unwrap();
+
+        assert_eq!(converted["error"]["type"], "rate_limit_error");
+        assert_eq!(converted["error"]["message"], "并发请求数已达上限 1");
+        assert_eq!(converted["error"]["code"], "rate_limited");
+        assert!(converted["error"]["param"].is_null());
+    }
+
+    #[test]
+    fn test_openai_error_passes_through() {
+        let body = json!({"error": {
+            "message": "messages 不能为空",
+            "type": "invalid_request_error",
+            "param": "messages",
+            "code": "invalid_request"
+        }});
+
+        assert!(convert_error(&body).is_none());
+        assert!(convert_error(&json!({"detail": "x"})).is_none());
+    }
+}
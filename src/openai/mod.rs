@@ -0,0 +1,22 @@
+//! OpenAI 兼容 API 模块
+//!
+//! 提供 `POST /v1/chat/completions` 端点：将 OpenAI Chat Completions 请求转换为
+//! Anthropic Messages 请求，复用 `/v1/messages` 的处理链路调用 Kiro，
+//! 再将响应（含流式 SSE）转换回 OpenAI 格式
+//!
+//! # 模块结构
+//! - `converter` - 请求/响应格式转换
+//! - `handlers` - 端点处理器
+//! - `middleware` - 错误格式转换中间件
+//! - `router` - 路由配置
+//! - `stream` - 流式响应转换
+//! - `types` - OpenAI 类型定义
+
+mod converter;
+mod handlers;
+mod middleware;
+mod router;
+mod stream;
+pub mod types;
+
+pub use router::create_openai_router;
@@ -0,0 +1,65 @@
+//! OpenAI 兼容 API 路由配置
+
+use axum::{Router, extract::DefaultBodyLimit, middleware, routing::post};
+
+use crate::anthropic::{
+    AppState, auth_middleware, body_limit_middleware, concurrency_limit_middleware, cors_layer,
+    max_request_bytes, rate_limit_middleware,
+};
+use crate::common::json_format::json_format_middleware;
+
+use super::{handlers::post_chat_completions, middleware::openai_error_middleware};
+
+/// 创建 OpenAI 兼容 API 路由
+///
+/// # 端点
+/// - `POST /v1/chat/completions` - 创建对话补全（支持流式与非流式）
+///
+/// 与 Anthropic 路由共用同一个 [`AppState`]：认证方式相同，
+/// 按模型限流、按 API Key 限流和 `maxConcurrentPerKey` 并发名额在两个端点之间共享
+pub fn create_openai_router(state: AppState) -> Router {
+    let pretty_json = state
+        .kiro_provider
+        .as_ref()
+        .map(|p| p.token_manager().config().pretty_json)
+        .unwrap_or(false);
+
+    let max_request_bytes = max_request_bytes(&state);
+
+    let mut v1_routes = Router::new()
+        .route("/chat/completions", post(post_chat_completions))
+        .layer(middleware::from_fn_with_state(
+            pretty_json,
+            json_format_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            max_request_bytes,
+            body_limit_middleware,
+        ))
+        .layer(DefaultBodyLimit::max(max_request_bytes));
+    // 并发限制位于认证之后，只对已认证的 API Key 计数
+    if let Some(limiter) = state.concurrency_limiter.clone() {
+        v1_routes = v1_routes.layer(middleware::from_fn_with_state(
+            limiter,
+            concurrency_limit_middleware,
+        ));
+    }
+    if let Some(limiter) = state.api_key_rate_limiter.clone() {
+        v1_routes = v1_routes.layer(middleware::from_fn_with_state(
+            limiter,
+            rate_limit_middleware,
+        ));
+    }
+    let v1_routes = v1_routes
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ))
+        // 错误格式转换位于最外层，覆盖认证、限流与并发限制产生的错误
+        .layer(middleware::from_fn(openai_error_middleware));
+
+    Router::new()
+        .nest("/v1", v1_routes)
+        .layer(cors_layer())
+        .with_state(state)
+}
@@ -0,0 +1,282 @@
+//! Anthropic SSE → OpenAI 流式响应转换
+//!
+//! 逐块解析 `/v1/messages` 输出的 Anthropic SSE 事件，转换为 OpenAI `chat.completion.chunk`
+//! 数据块（`data: {...}`），正常结束时以 `data: [DONE]` 收尾；
+//! 流中途的 `error` 事件转换为 OpenAI 错误对象后结束流（不发送 `[DONE]`）
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use serde_json::{Value, json};
+
+use crate::common::sse::{find_event_end, parse_event};
+
+use super::converter::{completion_id, map_finish_reason, system_fingerprint};
+use super::types::Usage;
+
+/// 流式响应转换器
+pub struct ChunkTranslator {
+    /// 响应 ID（收到 message_start 后由消息 ID 生成）
+    id: String,
+    /// 创建时间（Unix 秒）
+    created: i64,
+    /// 客户端请求的模型名称
+    model: String,
+    /// 后端配置指纹
+    system_fingerprint: String,
+    /// 是否在结束前发送用量块（`stream_options.include_usage`）
+    include_usage: bool,
+    /// Anthropic 内容块索引 → OpenAI tool_calls 索引
+    tool_indices: HashMap<i64, usize>,
+    /// 输入 token 数
+    prompt_tokens: i64,
+    /// 输出 token 数
+    completion_tokens: i64,
+    /// 未构成完整事件的剩余字节
+    buffer: Vec<u8>,
+    /// 是否已结束（收到 message_stop 或 error）
+    finished: bool,
+}
+
+impl ChunkTranslator {
+    pub fn new(model: impl Into<String>, include_usage: bool) -> Self {
+        let model = model.into();
+        Self {
+            id: completion_id(None),
+            created: chrono::Utc::now().timestamp(),
+            system_fingerprint: system_fingerprint(&model),
+            model,
+            include_usage,
+            tool_indices: HashMap::new(),
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            buffer: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// 是否已结束
+    #[cfg(test)]
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// 输入一段 Anthropic SSE 字节，返回需要发送给客户端的 OpenAI SSE 字节
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Bytes> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut output = Vec::new();
+        while let Some(end) = find_event_end(&self.buffer) {
+            let raw: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            if self.finished {
+                continue;
+            }
+            let Some((event, data)) = parse_event(&String::from_utf8_lossy(&raw)) else {
+                continue;
+            };
+            output.extend(self.translate(&event, &data));
+        }
+        output
+    }
+
+    /// 转换单个 Anthropic 事件
+    fn translate(&mut self, event: &str, data: &Value) -> Vec<Bytes> {
+        match event {
+            "message_start" => {
+                let message = &data["message"];
+                self.id = completion_id(message["id"].as_str());
+                self.prompt_tokens = message["usage"]["input_tokens"].as_i64().unwrap_or(0);
+                vec![self.chunk(json!({ "role": "assistant", "content": "" }), None)]
+            }
+            "content_block_start" if data["content_block"]["type"] == "tool_use" => {
+                let block = &data["content_block"];
+                let tool_index = self.tool_indices.len();
+                self.tool_indices
+                    .insert(data["index"].as_i64().unwrap_or(-1), tool_index);
+                vec![self.chunk(
+                    json!({ "tool_calls": [{
+                        "index": tool_index,
+                        "id": block["id"],
+                        "type": "function",
+                        "function": { "name": block["name"], "arguments": "" }
+                    }]}),
+                    None,
+                )]
+            }
+            "content_block_delta" => {
+                let delta = &data["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => {
+                        vec![self.chunk(json!({ "content": delta["text"] }), None)]
+                    }
+                    Some("input_json_delta") => {
+                        let index = data["index"].as_i64().unwrap_or(-1);
+                        let Some(&tool_index) = self.tool_indices.get(&index) else {
+                            return Vec::new();
+                        };
+                        vec![self.chunk(
+                            json!({ "tool_calls": [{
+                                "index": tool_index,
+                                "function": { "arguments": delta["partial_json"] }
+                            }]}),
+                            None,
+                        )]
+                    }
+                    // thinking 内容不属于 OpenAI 响应结构，忽略
+                    _ => Vec::new(),
+                }
+            }
+            "message_delta" => {
+                let usage = &data["usage"];
+                if let Some(input) = usage["input_tokens"].as_i64() {
+                    self.prompt_tokens = input;
+                }
+                self.completion_tokens = usage["output_tokens"].as_i64().unwrap_or(0);
+                let finish_reason = map_finish_reason(data["delta"]["stop_reason"].as_str());
+                vec![self.chunk(json!({}), Some(finish_reason))]
+            }
+            "message_stop" => {
+                self.finished = true;
+                let mut output = Vec::new();
+                if self.include_usage {
+                    let usage = Usage::new(self.prompt_tokens, self.completion_tokens);
+                    output.push(sse_data(&json!({
+                        "id": self.id,
+                        "object": "chat.completion.chunk",
+                        "created": self.created,
+                        "model": self.model,
+                        "system_fingerprint": self.system_fingerprint,
+                        "choices": [],
+                        "usage": usage,
+                    })));
+                }
+                output.push(Bytes::from_static(b"data: [DONE]\n\n"));
+                output
+            }
+            "error" => {
+                self.finished = true;
+                let error = &data["error"];
+                vec![sse_data(&json!({
+                    "error": {
+                        "message": error["message"],
+                        "type": error["type"],
+                        "param": null,
+                        "code": null,
+                    }
+                }))]
+            }
+            // 保持连接活跃（SSE 注释行，客户端会忽略）
+            "ping" => vec![Bytes::from_static(b": ping\n\n")],
+            _ => Vec::new(),
+        }
+    }
+
+    /// 构建一个 `chat.completion.chunk`
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> Bytes {
+        sse_data(&json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "system_fingerprint": self.system_fingerprint,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        }))
+    }
+}
+
+/// 格式化为 `data: <json>` SSE 行
+fn sse_data(value: &Value) -> Bytes {
+    Bytes::from(format!("data: {}\n\n", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anthropic_sse(events: &[(&str, Value)]) -> String {
+        events
+            .iter()
+            .map(|(event, data)| format!("event: {}\ndata: {}\n\n", event, data))
+            .collect()
+    }
+
+    fn data_lines(output: &[Bytes]) -> Vec<String> {
+        output
+            .iter()
+            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .filter_map(|line| line.strip_prefix("data: ").map(|d| d.trim_end().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_translates_text_tool_calls_and_usage() {
+        let sse = anthropic_sse(&[
+            ("message_start", json!({"type": "message_start", "message": {
+                "id": "msg_1", "usage": {"input_tokens": 7, "output_tokens": 0}}})),
+            ("content_block_start", json!({"type": "content_block_start", "index": 0,
+                "content_block": {"type": "text", "text": ""}})),
+            ("content_block_delta", json!({"type": "content_block_delta", "index": 0,
+                "delta": {"type": "text_delta", "text": "你好"}})),
+            ("content_block_start", json!({"type": "content_block_start", "index": 1,
+                "content_block": {"type": "tool_use", "id": "toolu_1", "name": "lookup"}})),
+            ("content_block_delta", json!({"type": "content_block_delta", "index": 1,
+                "delta": {"type": "input_json_delta", "partial_json": "{\"q\":1}"}})),
+            ("ping", json!({"type": "ping"})),
+            ("message_delta", json!({"type": "message_delta",
+                "delta": {"stop_reason": "tool_use"},
+                "usage": {"input_tokens": 9, "output_tokens": 4}})),
+            ("message_stop", json!({"type": "message_stop"})),
+        ]);
+
+        // 逐字节输入，验证跨块拆分的事件和多字节字符
+        let mut translator = ChunkTranslator::new("claude-sonnet-4", true);
+        let output: Vec<Bytes> = sse
+            .as_bytes()
+            .chunks(7)
+            .flat_map(|chunk| translator.feed(chunk))
+            .collect();
+        let lines = data_lines(&output);
+        let chunks: Vec<Value> = lines[..lines.len() - 1]
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(chunks[0]["id"], "chatcmpl-1");
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(
+            chunks[0]["system_fingerprint"],
+            system_fingerprint("claude-sonnet-4")
+        );
+        assert_eq!(chunks[1]["choices"][0]["delta"]["content"], "你好");
+        let call = &chunks[2]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(call["index"], 0);
+        assert_eq!(call["function"]["name"], "lookup");
+        assert_eq!(
+            chunks[3]["choices"][0]["delta"]["tool_calls"][0]["function"]["arguments"],
+            "{\"q\":1}"
+        );
+        assert_eq!(chunks[4]["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(chunks[5]["usage"]["prompt_tokens"], 9);
+        assert_eq!(chunks[5]["usage"]["total_tokens"], 13);
+        assert_eq!(lines.last().unwrap(), "[DONE]");
+        assert!(translator.is_finished());
+    }
+
+    #[test]
+    fn test_error_event_ends_stream_without_done() {
+        let sse = anthropic_sse(&[
+            ("message_start", json!({"type": "message_start", "message": {"id": "msg_2"}})),
+            ("error", json!({"type": "error",
+                "error": {"type": "api_error", "message": "上游返回错误"}})),
+            ("message_stop", json!({"type": "message_stop"})),
+        ]);
+
+        let mut translator = ChunkTranslator::new("claude-sonnet-4", false);
+        let lines = data_lines(&translator.feed(sse.as_bytes()));
+
+        assert_eq!(lines.len(), 2);
+        let error: Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(error["error"]["type"], "api_error");
+        assert!(!lines.iter().any(|line| line == "[DONE]"));
+    }
+}
@@ -0,0 +1,156 @@
+//! OpenAI Chat Completions API 类型定义
+
+use serde::{Deserialize, Serialize};
+
+// === 请求类型 ===
+
+/// Chat Completions 请求体
+///
+/// 仅解析转换到 Anthropic 请求所需的字段，其余字段（如 temperature）忽略
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    /// 流式选项（`include_usage` 为 true 时在 `[DONE]` 之前额外发送用量块）
+    pub stream_options: Option<StreamOptions>,
+    /// 旧版最大输出 token 字段
+    pub max_tokens: Option<i32>,
+    /// 新版最大输出 token 字段（优先于 `max_tokens`）
+    pub max_completion_tokens: Option<i32>,
+    pub tools: Option<Vec<ChatTool>>,
+    pub tool_choice: Option<serde_json::Value>,
+    /// 输出格式（`text` / `json_object` / `json_schema`），JSON 模式通过系统指令实现
+    pub response_format: Option<serde_json::Value>,
+    /// 候选数量（仅支持 1）
+    pub n: Option<u32>,
+    /// 停止序列（不支持，非空时返回 400）
+    pub stop: Option<serde_json::Value>,
+}
+
+/// 流式选项
+#[derive(Debug, Deserialize)]
+pub struct StreamOptions {
+    #[serde(default)]
+    pub include_usage: bool,
+}
+
+/// 对话消息
+#[derive(Debug, Deserialize)]
+pub struct ChatMessage {
+    /// system / developer / user / assistant / tool
+    pub role: String,
+    /// 字符串或内容片段数组（assistant 仅调用工具时可为 null）
+    #[serde(default)]
+    pub content: Option<serde_json::Value>,
+    /// assistant 消息中的工具调用
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// tool 消息对应的工具调用 ID
+    pub tool_call_id: Option<String>,
+}
+
+/// 工具调用
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub call_type: String,
+    pub function: FunctionCall,
+}
+
+fn default_tool_type() -> String {
+    "function".to_string()
+}
+
+/// 工具调用的函数名和参数（参数为 JSON 字符串）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: String,
+}
+
+/// 工具定义
+#[derive(Debug, Deserialize)]
+pub struct ChatTool {
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub tool_type: String,
+    pub function: FunctionDefinition,
+}
+
+/// 函数定义
+#[derive(Debug, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 参数 JSON Schema
+    pub parameters: Option<serde_json::Value>,
+}
+
+// === 响应类型 ===
+
+/// 非流式响应
+#[derive(Debug, Serialize)]
+pub struct ChatCompletion {
+    pub id: String,
+    pub object: &'static str,
+    pub created: i64,
+    pub model: String,
+    /// 后端配置指纹（见 [`super::converter::system_fingerprint`]）
+    pub system_fingerprint: String,
+    pub choices: Vec<Choice>,
+    pub usage: Usage,
+}
+
+/// 非流式响应的候选结果
+#[derive(Debug, Serialize)]
+pub struct Choice {
+    pub index: u32,
+    pub message: AssistantMessage,
+    pub finish_reason: &'static str,
+}
+
+/// 助手回复
+#[derive(Debug, Serialize)]
+pub struct AssistantMessage {
+    pub role: &'static str,
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// Token 用量
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Usage {
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+}
+
+impl Usage {
+    pub fn new(prompt_tokens: i64, completion_tokens: i64) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+}
+
+/// 错误响应
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
+
+/// 错误详情（`code` 为 kiro-rs 的稳定错误码）
+#[derive(Debug, Serialize)]
+pub struct ErrorDetail {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub param: Option<String>,
+    pub code: Option<serde_json::Value>,
+}
@@ -0,0 +1,290 @@
+//! HTTP 服务监听
+//!
+//! 支持同时监听多个地址（如 IPv4 + IPv6 双栈），所有地址共享同一个路由
+
+use std::time::Duration;
+
+use axum::Router;
+use tokio::net::TcpListener;
+
+/// 绑定所有监听地址
+///
+/// 任一地址绑定失败即返回错误（fail fast），避免部分地址静默不可用
+pub async fn bind_all(addrs: &[String]) -> anyhow::Result<Vec<TcpListener>> {
+    if addrs.is_empty() {
+        anyhow::bail!("未配置任何监听地址");
+    }
+
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("绑定监听地址 {} 失败: {}", addr, e))?;
+        listeners.push(listener);
+    }
+    Ok(listeners)
+}
+
+/// 在所有监听器上提供同一个路由服务，`shutdown` 完成时优雅关闭
+///
+/// 每个监听器运行独立的 `axum::serve` 任务。优雅关闭时停止接受新连接，
+/// 等待所有监听器上进行中的请求完成后返回；配置了 `drain_timeout` 时最多等待该时长，
+/// 超时后中止服务任务并返回。此前任一任务异常退出时立即返回
+pub async fn serve_all_with_shutdown(
+    listeners: Vec<TcpListener>,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+    drain_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    if listeners.is_empty() {
+        anyhow::bail!("没有可用的监听器");
+    }
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    tokio::spawn(async move {
+        shutdown.await;
+        let _ = shutdown_tx.send(true);
+    });
+
+    let tasks: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| {
+            let app = app.clone();
+            let mut shutdown_rx = shutdown_rx.clone();
+            tokio::spawn(async move {
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(async move {
+                        let _ = shutdown_rx.wait_for(|shutdown| *shutdown).await;
+                    })
+                    .await
+            })
+        })
+        .collect();
+    let abort_handles: Vec<_> = tasks.iter().map(|task| task.abort_handle()).collect();
+
+    let mut drain_rx = shutdown_rx.clone();
+    let drain_deadline = async move {
+        let _ = drain_rx.wait_for(|shutdown| *shutdown).await;
+        match drain_timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    let serving = async move {
+        let (result, _, remaining) = futures::future::select_all(tasks).await;
+        if *shutdown_rx.borrow() {
+            // 优雅关闭：等待其余监听器处理完进行中的请求
+            for task in remaining {
+                task.await??;
+            }
+        } else {
+            for task in remaining {
+                task.abort();
+            }
+        }
+
+        result??;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    tokio::select! {
+        result = serving => result,
+        _ = drain_deadline => {
+            tracing::warn!(
+                "等待进行中的请求超过 {} 秒，强制关闭",
+                drain_timeout.unwrap_or_default().as_secs()
+            );
+            for handle in abort_handles {
+                handle.abort();
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 等待进程退出信号（SIGTERM 或 SIGINT/Ctrl-C）
+///
+/// 注册信号处理失败时记录警告，并不再等待该信号
+pub async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("注册 SIGINT 信号处理失败: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("注册 SIGTERM 信号处理失败: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => tracing::info!("收到 SIGINT，开始优雅关闭"),
+        _ = terminate => tracing::info!("收到 SIGTERM，开始优雅关闭"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+
+    #[tokio::test]
+    async fn test_serve_on_multiple_addresses() {
+        let listeners = bind_all(&["127.0.0.1:0".to_string(), "127.0.0.1:0".to_string()])
+            .await
+            .unwrap();
+        let addrs: Vec<_> = listeners
+            .iter()
+            .map(|l| l.local_addr().unwrap())
+            .collect();
+        assert_ne!(addrs[0], addrs[1]);
+
+        let app = Router::new().route("/ping", get(|| async { "pong" }));
+        tokio::spawn(serve_all_with_shutdown(
+            listeners,
+            app,
+            std::future::pending(),
+            None,
+        ));
+
+        for addr in addrs {
+            let body = reqwest::get(format!("http://{}/ping", addr))
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            assert_eq!(body, "pong");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_all_returns_on_shutdown() {
+        let listeners = bind_all(&["127.0.0.1:0".to_string(), "127.0.0.1:0".to_string()])
+            .await
+            .unwrap();
+        let app = Router::new().route("/ping", get(|| async { "pong" }));
+
+        let shutdown = tokio::time::sleep(std::time::Duration::from_millis(100));
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            serve_all_with_shutdown(listeners, app, shutdown, None),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_lets_in_flight_request_finish() {
+        let listeners = bind_all(&["127.0.0.1:0".to_string()]).await.unwrap();
+        let addr = listeners[0].local_addr().unwrap();
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel::<()>();
+        let started_tx = std::sync::Arc::new(parking_lot::Mutex::new(Some(started_tx)));
+        let app = Router::new().route(
+            "/slow",
+            get(move || {
+                let started_tx = started_tx.clone();
+                async move {
+                    if let Some(tx) = started_tx.lock().take() {
+                        let _ = tx.send(());
+                    }
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    "done"
+                }
+            }),
+        );
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_all_with_shutdown(
+            listeners,
+            app,
+            async move {
+                let _ = shutdown_rx.await;
+            },
+            Some(Duration::from_secs(5)),
+        ));
+
+        let request = tokio::spawn(async move {
+            reqwest::get(format!("http://{}/slow", addr))
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap()
+        });
+        // 请求已进入处理器后再触发关闭
+        started_rx.await.unwrap();
+        shutdown_tx.send(()).unwrap();
+
+        assert_eq!(request.await.unwrap(), "done");
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        // 关闭后不再接受新连接
+        assert!(reqwest::get(format!("http://{}/slow", addr)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_gives_up_after_drain_timeout() {
+        let listeners = bind_all(&["127.0.0.1:0".to_string()]).await.unwrap();
+        let addr = listeners[0].local_addr().unwrap();
+        let app = Router::new().route(
+            "/hang",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                "never"
+            }),
+        );
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_all_with_shutdown(
+            listeners,
+            app,
+            async move {
+                let _ = shutdown_rx.await;
+            },
+            Some(Duration::from_millis(200)),
+        ));
+        let _request = tokio::spawn(reqwest::get(format!("http://{}/hang", addr)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        shutdown_tx.send(()).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bind_all_fails_fast_on_conflict() {
+        let occupied = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let occupied_addr = occupied.local_addr().unwrap().to_string();
+
+        let result = bind_all(&["127.0.0.1:0".to_string(), occupied_addr.clone()]).await;
+        let err = result.err().unwrap();
+        assert!(err.to_string().contains(&occupied_addr));
+    }
+
+    #[tokio::test]
+    async fn test_bind_all_rejects_empty() {
+        assert!(bind_all(&[]).await.is_err());
+    }
+}
@@ -11,6 +11,9 @@ use crate::anthropic::types::{
     CountTokensRequest, CountTokensResponse, Message, SystemMessage, Tool,
 };
 use crate::http_client::{ProxyConfig, build_client};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::sync::OnceLock;
 
 /// Count Tokens API 配置
@@ -24,15 +27,34 @@ pub struct CountTokensConfig {
     pub auth_type: String,
     /// 代理配置
     pub proxy: Option<ProxyConfig>,
+    /// 远程 API 调用失败时是否回退到本地估算
+    pub allow_local_fallback: bool,
+    /// 远程计数结果缓存条数（0 表示不缓存）
+    pub cache_size: usize,
+}
+
+/// 输入 token 计数结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TokenCount {
+    /// 输入 tokens
+    pub input_tokens: u64,
+    /// 是否为本地估算值
+    pub estimated: bool,
 }
 
 /// 全局配置存储
 static COUNT_TOKENS_CONFIG: OnceLock<CountTokensConfig> = OnceLock::new();
 
+/// 全局远程计数结果缓存
+static COUNT_TOKENS_CACHE: OnceLock<TokenCountCache> = OnceLock::new();
+
 /// 初始化 count_tokens 配置
 ///
 /// 应在应用启动时调用一次
 pub fn init_config(config: CountTokensConfig) {
+    if config.cache_size > 0 {
+        let _ = COUNT_TOKENS_CACHE.set(TokenCountCache::new(config.cache_size));
+    }
     let _ = COUNT_TOKENS_CONFIG.set(config);
 }
 
@@ -101,37 +123,162 @@ pub fn count_tokens(text: &str) -> u64 {
 
 /// 估算请求的输入 tokens
 ///
-/// 优先调用远程 API，失败时回退到本地计算
+/// 优先调用远程 API，失败时总是回退到本地计算（用于响应中的用量估算）
 pub(crate) fn count_all_tokens(
     model: String,
     system: Option<Vec<SystemMessage>>,
     messages: Vec<Message>,
     tools: Option<Vec<Tool>>,
 ) -> u64 {
-    // 检查是否配置了远程 API
-    if let Some(config) = get_config() {
-        if let Some(api_url) = &config.api_url {
-            // 尝试调用远程 API
-            let result = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(call_remote_count_tokens(
-                    api_url, config, model, &system, &messages, &tools,
-                ))
+    let cache = COUNT_TOKENS_CACHE.get();
+    count_with_config(get_config(), cache, true, model, &system, &messages, &tools)
+        .map(|count| count.input_tokens)
+        .unwrap_or_else(|_| count_all_tokens_local(&system, &messages, &tools))
+}
+
+/// 计算 `/v1/messages/count_tokens` 请求的输入 tokens
+///
+/// 优先调用远程 API；未配置 API 地址时本地估算，远程调用失败时按
+/// `allow_local_fallback` 回退到本地估算或返回错误
+pub(crate) fn count_request_tokens(
+    model: String,
+    system: Option<Vec<SystemMessage>>,
+    messages: Vec<Message>,
+    tools: Option<Vec<Tool>>,
+) -> Result<TokenCount, String> {
+    let config = get_config();
+    let cache = COUNT_TOKENS_CACHE.get();
+    let allow_local_fallback = config.is_none_or(|config| config.allow_local_fallback);
+    count_with_config(config, cache, allow_local_fallback, model, &system, &messages, &tools)
+}
+
+/// 远程计数结果命中缓存时直接返回，不调用远程 API；本地估算结果不缓存
+fn count_with_config(
+    config: Option<&CountTokensConfig>,
+    cache: Option<&TokenCountCache>,
+    allow_local_fallback: bool,
+    model: String,
+    system: &Option<Vec<SystemMessage>>,
+    messages: &[Message],
+    tools: &Option<Vec<Tool>>,
+) -> Result<TokenCount, String> {
+    // 检查是否配置了远程 API（空地址视为未配置）
+    if let Some(config) = config
+        && let Some(api_url) = config.api_url.as_deref().filter(|url| !url.trim().is_empty())
+    {
+        let key = cache.map(|_| cache_key(&model, system, messages, tools));
+        if let (Some(cache), Some(key)) = (cache, &key)
+            && let Some(tokens) = cache.get(key)
+        {
+            tracing::debug!("count_tokens 命中缓存: {}", tokens);
+            return Ok(TokenCount {
+                input_tokens: tokens,
+                estimated: false,
             });
+        }
 
-            match result {
-                Ok(tokens) => {
-                    tracing::debug!("远程 count_tokens API 返回: {}", tokens);
-                    return tokens;
-                }
-                Err(e) => {
-                    tracing::warn!("远程 count_tokens API 调用失败，回退到本地计算: {}", e);
+        // 尝试调用远程 API
+        let result = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(call_remote_count_tokens(
+                api_url, config, model, system, messages, tools,
+            ))
+        });
+
+        match result {
+            Ok(tokens) => {
+                tracing::debug!("远程 count_tokens API 返回: {}", tokens);
+                if let (Some(cache), Some(key)) = (cache, key) {
+                    cache.insert(key, tokens);
                 }
+                return Ok(TokenCount {
+                    input_tokens: tokens,
+                    estimated: false,
+                });
+            }
+            Err(e) if allow_local_fallback => {
+                tracing::warn!("远程 count_tokens API 调用失败，回退到本地计算: {}", e);
+            }
+            Err(e) => {
+                tracing::warn!("远程 count_tokens API 调用失败: {}", e);
+                return Err(format!("count_tokens API 调用失败: {}", e));
             }
         }
     }
 
     // 本地计算
-    count_all_tokens_local(system, messages, tools)
+    Ok(TokenCount {
+        input_tokens: count_all_tokens_local(system, messages, tools),
+        estimated: true,
+    })
+}
+
+/// 计数缓存键：模型、系统消息、消息和工具定义序列化后的 SHA-256
+///
+/// 不同模型的计数不同，模型名必须参与计算；serde_json 对象键有序，序列化结果稳定
+fn cache_key(
+    model: &str,
+    system: &Option<Vec<SystemMessage>>,
+    messages: &[Message],
+    tools: &Option<Vec<Tool>>,
+) -> [u8; 32] {
+    let normalized = serde_json::json!({
+        "model": model,
+        "system": system,
+        "messages": messages,
+        "tools": tools,
+    });
+    Sha256::digest(normalized.to_string().as_bytes()).into()
+}
+
+/// 远程计数结果的 LRU 缓存
+///
+/// 条目只有键和计数，容量通常在千级以内，淘汰时线性扫描最久未使用的条目
+pub(crate) struct TokenCountCache {
+    capacity: usize,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    /// 键 → (token 数, 最近使用序号)
+    entries: HashMap<[u8; 32], (u64, u64)>,
+    /// 单调递增的使用序号
+    tick: u64,
+}
+
+impl TokenCountCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+
+    fn get(&self, key: &[u8; 32]) -> Option<u64> {
+        let mut inner = self.inner.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+        let (tokens, used) = inner.entries.get_mut(key)?;
+        *used = tick;
+        Some(*tokens)
+    }
+
+    fn insert(&self, key: [u8; 32], tokens: u64) {
+        let mut inner = self.inner.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key) {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.entries.insert(key, (tokens, tick));
+    }
 }
 
 /// 调用远程 count_tokens API
@@ -140,7 +287,7 @@ async fn call_remote_count_tokens(
     config: &CountTokensConfig,
     model: String,
     system: &Option<Vec<SystemMessage>>,
-    messages: &Vec<Message>,
+    messages: &[Message],
     tools: &Option<Vec<Tool>>,
 ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
     let client = build_client(config.proxy.as_ref(), 300)?;
@@ -148,7 +295,7 @@ async fn call_remote_count_tokens(
     // 构建请求体
     let request = CountTokensRequest {
         model: model, // 模型名称用于 token 计算
-        messages: messages.clone(),
+        messages: messages.to_vec(),
         system: system.clone(),
         tools: tools.clone(),
     };
@@ -181,22 +328,24 @@ async fn call_remote_count_tokens(
 }
 
 /// 本地计算请求的输入 tokens
+///
+/// 基于字符数的近似，刻意偏高估算（宁多勿少），适合客户端预检上下文长度
 fn count_all_tokens_local(
-    system: Option<Vec<SystemMessage>>,
-    messages: Vec<Message>,
-    tools: Option<Vec<Tool>>,
+    system: &Option<Vec<SystemMessage>>,
+    messages: &[Message],
+    tools: &Option<Vec<Tool>>,
 ) -> u64 {
     let mut total = 0;
 
     // 系统消息
-    if let Some(ref system) = system {
+    if let Some(system) = system {
         for msg in system {
             total += count_tokens(&msg.text);
         }
     }
 
     // 用户消息
-    for msg in &messages {
+    for msg in messages {
         if let serde_json::Value::String(s) = &msg.content {
             total += count_tokens(s);
         } else if let serde_json::Value::Array(arr) = &msg.content {
@@ -209,7 +358,7 @@ fn count_all_tokens_local(
     }
 
     // 工具定义
-    if let Some(ref tools) = tools {
+    if let Some(tools) = tools {
         for tool in tools {
             total += count_tokens(&tool.name);
             total += count_tokens(&tool.description);
@@ -240,3 +389,126 @@ pub(crate) fn estimate_output_tokens(content: &[serde_json::Value]) -> i32 {
 
     total.max(1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, routing::post};
+    use serde_json::json;
+
+    fn user_message(text: &str) -> Vec<Message> {
+        vec![Message {
+            role: "user".to_string(),
+            content: json!(text),
+        }]
+    }
+
+    #[test]
+    fn test_local_count_is_conservative_against_fixtures() {
+        // (文本, cl100k 参考 token 数)
+        let fox = "The quick brown fox jumps over the lazy dog.";
+        let repeated = vec![fox; 20].join(" ");
+        let fixtures = [("Hello, world!", 4), (fox, 10), (repeated.as_str(), 200)];
+
+        for (text, reference) in fixtures {
+            let local = count_all_tokens_local(&None, &user_message(text), &None);
+            // 本地估算不应低于参考值，也不应偏高超过一倍
+            assert!(
+                (reference..=reference * 2).contains(&local),
+                "{:?}: local {} vs reference {}",
+                text,
+                local,
+                reference
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_count_tokens_fallback_follows_config() {
+        let app = Router::new().route(
+            "/count_tokens",
+            post(|| async { Json(json!({"input_tokens": 42})) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let messages = user_message("Hello, world!");
+        let count = |config: &CountTokensConfig, allow_local_fallback: bool| {
+            let model = "claude-sonnet-4".to_string();
+            let config = Some(config);
+            count_with_config(config, None, allow_local_fallback, model, &None, &messages, &None)
+        };
+
+        let mut config = CountTokensConfig {
+            api_url: Some(format!("http://{}/count_tokens", addr)),
+            ..Default::default()
+        };
+        let remote = count(&config, false).unwrap();
+        assert_eq!(remote, TokenCount { input_tokens: 42, estimated: false });
+
+        // 远程不可用：按配置回退或报错
+        config.api_url = Some(format!("http://{}/missing", addr));
+        assert!(count(&config, false).is_err());
+        assert!(count(&config, true).unwrap().estimated);
+
+        // 空地址视为未配置，直接本地估算
+        config.api_url = Some(String::new());
+        assert!(count(&config, false).unwrap().estimated);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remote_counts_cached_per_model() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/count_tokens",
+            post(move || {
+                let n = counter.fetch_add(1, Ordering::SeqCst) as u64;
+                async move { Json(json!({"input_tokens": 100 + n})) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let config = CountTokensConfig {
+            api_url: Some(format!("http://{}/count_tokens", addr)),
+            ..Default::default()
+        };
+        let cache = TokenCountCache::new(2);
+        let system = Some(vec![SystemMessage {
+            text: "You are a helpful assistant.".to_string(),
+        }]);
+        let messages = user_message("Hello, world!");
+        let count = |model: &str| {
+            let model = model.to_string();
+            count_with_config(Some(&config), Some(&cache), false, model, &system, &messages, &None)
+                .unwrap()
+                .input_tokens
+        };
+
+        assert_eq!(count("claude-sonnet-4"), 100);
+        // 相同请求命中缓存，不再调用远程 API
+        assert_eq!(count("claude-sonnet-4"), 100);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // 不同模型不命中
+        assert_eq!(count("claude-opus-4"), 101);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // 容量 2：新条目淘汰最久未使用的 sonnet 条目
+        assert_eq!(count("claude-opus-4"), 101);
+        assert_eq!(count("claude-haiku-4"), 102);
+        assert_eq!(count("claude-opus-4"), 101);
+        assert_eq!(count("claude-sonnet-4"), 103);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}